@@ -2,63 +2,376 @@ extern crate proc_macro;
 use proc_macro::TokenStream;
 use quote::quote;
 use syn::parse::Parser;
-use syn::{parse_macro_input, Field, Fields, ItemStruct};
+use syn::{
+    parse_macro_input, AttributeArgs, Field, Fields, ItemStruct, Lit, Meta, MetaNameValue,
+    NestedMeta,
+};
 
+/// Adds the `escrow_auth` and `escrow` accounts of an action to an Anchor accounts struct.
+///
+/// With `escrow_index = N`, `escrow` is also constrained to sign and to be derived from
+/// `escrow_auth` and `N`, i.e. the escrow specified in `ActionArgs`. The struct then can't
+/// declare `escrow` itself, as the constraints wouldn't be applied to it:
+///
+/// ```ignore
+/// #[action(escrow_index = 0)]
+/// #[derive(Accounts)]
+/// pub struct IncrementCounter<'info> { ... }
+/// ```
 #[proc_macro_attribute]
-pub fn action(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn action(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as AttributeArgs);
     let input = parse_macro_input!(item as ItemStruct);
 
+    let escrow_index = match parse_escrow_index(&args) {
+        Ok(escrow_index) => escrow_index,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    match action_struct(&input, escrow_index) {
+        Ok(expanded) => expanded.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn action_struct(
+    input: &ItemStruct,
+    escrow_index: Option<u8>,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let Fields::Named(fields_named) = &input.fields else {
+        return Err(syn::Error::new_spanned(
+            input,
+            "Action attribute can only be used with structs with named fields",
+        ));
+    };
+    let field = |name: &str| {
+        fields_named
+            .named
+            .iter()
+            .find(|field| field.ident.as_ref().is_some_and(|ident| ident == name))
+    };
+
+    let mut new_fields = fields_named.named.clone();
+    if field("escrow_auth").is_none() {
+        new_fields.push(Field::parse_named.parse2(quote! {
+            /// CHECK: Escrow Authority is an account used to derive `escrow` with `escrow_index`, it is used to verify that action is scheduled with expected authority
+            pub escrow_auth: UncheckedAccount<'info>
+        })?);
+    }
+    match (field("escrow"), escrow_index) {
+        (Some(escrow), Some(_)) => {
+            return Err(syn::Error::new_spanned(
+                escrow,
+                "`escrow` is added by the action attribute when `escrow_index` is set",
+            ));
+        }
+        (Some(_), None) => {}
+        (None, Some(escrow_index)) => {
+            let constraints = escrow_constraints(escrow_index);
+            new_fields.push(Field::parse_named.parse2(quote! {
+                /// CHECK: Escrow account that is a `signer` in callback, it is derived from `escrow_auth` and `escrow_index` one specified in `ActionArgs`
+                #[account(signer, #constraints)]
+                pub escrow: UncheckedAccount<'info>
+            })?);
+        }
+        (None, None) => {
+            new_fields.push(Field::parse_named.parse2(quote! {
+                /// CHECK: Escrow account that is a `signer` in callback, it is derived from `escrow_auth` and `escrow_index` one specified in `ActionArgs`
+                pub escrow: UncheckedAccount<'info>
+            })?);
+        }
+    }
+
     let name = &input.ident;
     let attrs = &input.attrs; // Capture all attributes
-    let expanded = if let Fields::Named(fields_named) = &input.fields {
-        let mut has_escrow_auth = false;
-        let mut has_escrow = false;
-
-        for field in &fields_named.named {
-            if let Some(ident) = &field.ident {
-                if ident == "escrow_auth" {
-                    has_escrow_auth = true;
-                } else if ident == "escrow" {
-                    has_escrow = true;
-                }
-            }
+    Ok(quote! {
+        #(#attrs)*
+        pub struct #name<'info> {
+            #new_fields
         }
+    })
+}
 
-        let mut new_fields = fields_named.named.clone();
-
-        if !has_escrow_auth {
-            new_fields.push(
-                Field::parse_named
-                    .parse2(quote! {
-                        /// CHECK: Escrow Authority is an account used to derive `escrow` with `escrow_index`, it is used to verify that action is scheduled with expected authority
-                        pub escrow_auth: UncheckedAccount<'info>
-                    })
-                    .unwrap(),
-            );
-        }
+/// Names of the accounts added by the `action_callback` attribute
+const ACTION_CALLBACK_ACCOUNTS: [&str; 4] = [
+    "callback_signer",
+    "escrow_auth",
+    "escrow",
+    "callback_sequence",
+];
+
+/// Verifies an action callback in an Anchor accounts struct, taking the `ActionCallbackResponse`
+/// as instruction argument:
+///
+/// - `callback_signer` is prepended, required to sign and to be `CALLBACK_SIGNER`, the callback
+///   executor of the validator, see `CallbackInstruction::ExecuteCallback` in
+///   `magicblock-magic-program-api`,
+/// - `escrow_auth` and `escrow` are appended, `escrow` being derived from `escrow_auth` and
+///   `escrow_index` (255 by default, as in `ActionArgs`),
+/// - `callback_sequence` is appended, the PDA of the program derived from
+///   `[CALLBACK_SEQUENCE_SEED, escrow_auth]`, rejecting the callbacks whose sequence has already
+///   been accepted, see `reject_callback_replay`. The program creates it beforehand with at
+///   least `CallbackSequence::LEN` zeroed bytes.
+///
+/// The accounts of the `ActionCallback` are the ones of the struct following `callback_signer`,
+/// and its payload starts with the sequence, see `ActionCallback::with_sequence`.
+///
+/// ```ignore
+/// #[action_callback(escrow_index = 0)]
+/// #[derive(Accounts)]
+/// pub struct OnCounterIncremented<'info> { ... }
+///
+/// pub fn on_counter_incremented(
+///     ctx: Context<OnCounterIncremented>,
+///     response: ActionCallbackResponse,
+/// ) -> Result<()> { ... }
+/// ```
+#[proc_macro_attribute]
+pub fn action_callback(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as AttributeArgs);
+    let input = parse_macro_input!(item as ItemStruct);
 
-        if !has_escrow {
-            new_fields.push(
-                Field::parse_named
-                    .parse2(quote! {
-                        /// CHECK: Escrow account that is a `signer` in callback, it is derived from `escrow_auth` and `escrow_index` one specified in `ActionArgs`
-                        pub escrow: UncheckedAccount<'info>
-                    })
-                    .unwrap(),
-            );
+    let escrow_index = match parse_escrow_index(&args) {
+        Ok(escrow_index) => escrow_index.unwrap_or(u8::MAX),
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    match action_callback_struct(&input, escrow_index) {
+        Ok(expanded) => expanded.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn action_callback_struct(
+    input: &ItemStruct,
+    escrow_index: u8,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let Fields::Named(fields_named) = &input.fields else {
+        return Err(syn::Error::new_spanned(
+            input,
+            "Action callback attribute can only be used with structs with named fields",
+        ));
+    };
+    if let Some(field) = fields_named.named.iter().find(|field| {
+        field
+            .ident
+            .as_ref()
+            .is_some_and(|ident| ACTION_CALLBACK_ACCOUNTS.iter().any(|name| ident == name))
+    }) {
+        return Err(syn::Error::new_spanned(
+            field,
+            "`callback_signer`, `escrow_auth`, `escrow` and `callback_sequence` are added by the \
+             action_callback attribute",
+        ));
+    }
+    if let Some(attr) = input
+        .attrs
+        .iter()
+        .find(|attr| attr.path.is_ident("instruction"))
+    {
+        return Err(syn::Error::new_spanned(
+            attr,
+            "the instruction argument is the `ActionCallbackResponse` added by the \
+             action_callback attribute",
+        ));
+    }
+
+    let mut new_fields = syn::punctuated::Punctuated::<Field, syn::Token![,]>::new();
+    new_fields.push(Field::parse_named.parse2(quote! {
+        /// Callback executor of the validator, signing the callbacks it issues
+        #[account(address = ephemeral_rollups_sdk::consts::CALLBACK_SIGNER)]
+        pub callback_signer: Signer<'info>
+    })?);
+    new_fields.extend(fields_named.named.iter().cloned());
+    new_fields.push(Field::parse_named.parse2(quote! {
+        /// CHECK: Escrow Authority is an account used to derive `escrow` with `escrow_index`, it is used to verify that the callback is issued for the expected authority
+        pub escrow_auth: UncheckedAccount<'info>
+    })?);
+    let constraints = escrow_constraints(escrow_index);
+    new_fields.push(Field::parse_named.parse2(quote! {
+        /// CHECK: Escrow of the action, derived from `escrow_auth` and `escrow_index`
+        #[account(#constraints)]
+        pub escrow: UncheckedAccount<'info>
+    })?);
+    new_fields.push(Field::parse_named.parse2(quote! {
+        /// CHECK: Callback sequence of `escrow_auth`, recording the sequence of the callback
+        #[account(
+            mut,
+            seeds = [ephemeral_rollups_sdk::ephem::CALLBACK_SEQUENCE_SEED, escrow_auth.key().as_ref()],
+            bump,
+            constraint = ephemeral_rollups_sdk::ephem::reject_callback_replay(
+                &callback_sequence,
+                &response,
+            ).is_ok() @ ephemeral_rollups_sdk::compat::ProgramError::from(
+                ephemeral_rollups_sdk::error::EphemeralRollupsSdkError::CallbackReplayed,
+            ),
+        )]
+        pub callback_sequence: UncheckedAccount<'info>
+    })?);
+
+    let name = &input.ident;
+    let attrs = &input.attrs;
+    Ok(quote! {
+        #(#attrs)*
+        #[instruction(response: ephemeral_rollups_sdk::ephem::ActionCallbackResponse)]
+        pub struct #name<'info> {
+            #new_fields
         }
+    })
+}
+
+/// Anchor constraints deriving `escrow` from `escrow_auth` and `escrow_index`, see
+/// `ephemeral_rollups_sdk::cpi::escrow_pda`.
+fn escrow_constraints(escrow_index: u8) -> proc_macro2::TokenStream {
+    quote! {
+        seeds = [
+            ephemeral_rollups_sdk::pda::EPHEMERAL_BALANCE_TAG,
+            escrow_auth.key().as_ref(),
+            &[#escrow_index],
+        ],
+        bump,
+        seeds::program = ephemeral_rollups_sdk::id(),
+    }
+}
 
-        quote! {
-            #(#attrs)*
-            pub struct #name<'info> {
-                #new_fields
+fn parse_escrow_index(args: &[NestedMeta]) -> syn::Result<Option<u8>> {
+    let mut escrow_index = None;
+    for arg in args {
+        match arg {
+            NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                path,
+                lit: Lit::Int(lit),
+                ..
+            })) if path.is_ident("escrow_index") => {
+                escrow_index = Some(lit.base10_parse::<u8>()?);
+            }
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    arg,
+                    "expected `escrow_index = <u8>`",
+                ))
             }
         }
-    } else {
-        quote! {
-            compile_error!("Action attribute can only be used with structs with named fields");
+    }
+    Ok(escrow_index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn escrow_field(output: proc_macro2::TokenStream) -> Field {
+        let output: ItemStruct = syn::parse2(output).unwrap();
+        let Fields::Named(fields) = output.fields else {
+            panic!("expected named fields");
+        };
+        fields
+            .named
+            .into_iter()
+            .find(|field| field.ident.as_ref().unwrap() == "escrow")
+            .unwrap()
+    }
+
+    #[test]
+    fn test_action_escrow_index() {
+        let input: ItemStruct = syn::parse_quote! {
+            #[derive(Accounts)]
+            pub struct IncrementCounter<'info> {
+                pub counter: UncheckedAccount<'info>,
+            }
+        };
+
+        let escrow = escrow_field(action_struct(&input, Some(3)).unwrap());
+        let expected: Field = Field::parse_named
+            .parse2(quote! {
+                /// CHECK: Escrow account that is a `signer` in callback, it is derived from `escrow_auth` and `escrow_index` one specified in `ActionArgs`
+                #[account(
+                    signer,
+                    seeds = [
+                        ephemeral_rollups_sdk::pda::EPHEMERAL_BALANCE_TAG,
+                        escrow_auth.key().as_ref(),
+                        &[3u8],
+                    ],
+                    bump,
+                    seeds::program = ephemeral_rollups_sdk::id(),
+                )]
+                pub escrow: UncheckedAccount<'info>
+            })
+            .unwrap();
+        assert_eq!(quote!(#escrow).to_string(), quote!(#expected).to_string());
+
+        let escrow = escrow_field(action_struct(&input, None).unwrap());
+        assert!(escrow
+            .attrs
+            .iter()
+            .all(|attr| !attr.path.is_ident("account")));
+    }
+
+    #[test]
+    fn test_action_escrow_index_with_declared_escrow() {
+        let input: ItemStruct = syn::parse_quote! {
+            #[derive(Accounts)]
+            pub struct IncrementCounter<'info> {
+                pub escrow: UncheckedAccount<'info>,
+            }
+        };
+        assert!(action_struct(&input, Some(0)).is_err());
+        assert!(action_struct(&input, None).is_ok());
+    }
+
+    #[test]
+    fn test_action_callback_rejects_reserved_accounts() {
+        for field in ACTION_CALLBACK_ACCOUNTS {
+            let field = syn::Ident::new(field, proc_macro2::Span::call_site());
+            let input: ItemStruct = syn::parse_quote! {
+                #[derive(Accounts)]
+                pub struct OnCallback<'info> {
+                    pub #field: UncheckedAccount<'info>,
+                }
+            };
+            assert!(action_callback_struct(&input, 0).is_err(), "{field}");
         }
-    };
 
-    TokenStream::from(expanded)
+        let input: ItemStruct = syn::parse_quote! {
+            #[derive(Accounts)]
+            #[instruction(amount: u64)]
+            pub struct OnCallback<'info> {
+                pub counter: UncheckedAccount<'info>,
+            }
+        };
+        assert!(action_callback_struct(&input, 0).is_err());
+    }
+
+    #[test]
+    fn test_action_callback_accounts() {
+        let input: ItemStruct = syn::parse_quote! {
+            #[derive(Accounts)]
+            pub struct OnCallback<'info> {
+                pub counter: UncheckedAccount<'info>,
+            }
+        };
+        let output: ItemStruct = syn::parse2(action_callback_struct(&input, 0).unwrap()).unwrap();
+        let Fields::Named(fields) = output.fields else {
+            panic!("expected named fields");
+        };
+        let names: Vec<_> = fields
+            .named
+            .iter()
+            .map(|field| field.ident.as_ref().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "callback_signer",
+                "counter",
+                "escrow_auth",
+                "escrow",
+                "callback_sequence"
+            ]
+        );
+        assert!(output
+            .attrs
+            .iter()
+            .any(|attr| attr.path.is_ident("instruction")));
+    }
 }
@@ -15,7 +15,10 @@ fn generated_unchecked_account_type() -> TokenStream2 {
 #[proc_macro_attribute]
 pub fn delegate(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let input = parse_macro_input!(item as ItemStruct);
+    TokenStream::from(expand_delegate(input))
+}
 
+fn expand_delegate(input: ItemStruct) -> TokenStream2 {
     // Extract the struct name and fields
     let struct_name = &input.ident;
     let fields = &input.fields;
@@ -39,8 +42,7 @@ pub fn delegate(_attr: TokenStream, item: TokenStream) -> TokenStream {
                     field,
                     "Unnamed fields are not supported in this macro",
                 )
-                .to_compile_error()
-                .into();
+                .to_compile_error();
             }
         };
 
@@ -121,6 +123,40 @@ pub fn delegate(_attr: TokenStream, item: TokenStream) -> TokenStream {
                     ephemeral_rollups_sdk::cpi::delegate_account(del_accounts, seeds, config)
                 }
             });
+
+            // Add re-delegate method, accepting the migration action signed by the escrow. The
+            // payer funds the delegation buffer, e.g. the escrow itself
+            let redelegate_method_name =
+                syn::Ident::new(&format!("redelegate_{field_name}"), field.span());
+            delegate_methods.push(quote! {
+                pub fn #redelegate_method_name<'a>(
+                    &'a self,
+                    payer: &'a AccountInfo<'info>,
+                    escrow_authority: &'a AccountInfo<'info>,
+                    escrow: &'a AccountInfo<'info>,
+                    escrow_index: u8,
+                    seeds: &[&[u8]],
+                    config: ephemeral_rollups_sdk::cpi::DelegateConfig,
+                ) -> anchor_lang::solana_program::entrypoint::ProgramResult {
+                    ephemeral_rollups_sdk::cpi::verify_migration_escrow(
+                        &self.#field_name.key(),
+                        escrow_authority,
+                        escrow,
+                        escrow_index,
+                    )?;
+                    let del_accounts = ephemeral_rollups_sdk::cpi::DelegateAccounts {
+                        payer,
+                        pda: &self.#field_name.to_account_info(),
+                        owner_program: &self.owner_program,
+                        buffer: &self.#buffer_field,
+                        delegation_record: &self.#delegation_record_field,
+                        delegation_metadata: &self.#delegation_metadata_field,
+                        delegation_program: &self.delegation_program,
+                        system_program: &self.system_program,
+                    };
+                    ephemeral_rollups_sdk::cpi::delegate_account(del_accounts, seeds, config)
+                }
+            });
         }
 
         // Add the original field without `del`
@@ -164,7 +200,7 @@ pub fn delegate(_attr: TokenStream, item: TokenStream) -> TokenStream {
     }
 
     // Generate the new struct definition
-    let expanded = quote! {
+    quote! {
         #(#original_attrs)*
         pub struct #struct_name<'info> {
             #(#new_fields)*
@@ -173,7 +209,41 @@ pub fn delegate(_attr: TokenStream, item: TokenStream) -> TokenStream {
         impl<'info> #struct_name<'info> {
             #(#delegate_methods)*
         }
-    };
+    }
+}
 
-    TokenStream::from(expanded)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redelegate_payer() {
+        let input: ItemStruct = syn::parse_quote! {
+            #[derive(Accounts)]
+            pub struct Redelegate<'info> {
+                /// CHECK: the migrated counter
+                #[account(mut, del)]
+                pub counter: AccountInfo<'info>,
+            }
+        };
+        let expanded = expand_delegate(input).to_string();
+
+        let signature = quote! {
+            pub fn redelegate_counter<'a>(
+                &'a self,
+                payer: &'a AccountInfo<'info>,
+                escrow_authority: &'a AccountInfo<'info>,
+                escrow: &'a AccountInfo<'info>,
+                escrow_index: u8,
+                seeds: &[&[u8]],
+                config: ephemeral_rollups_sdk::cpi::DelegateConfig,
+            )
+        };
+        assert!(expanded.contains(&signature.to_string()), "{expanded}");
+        // the delegation is paid by the given payer, not by the escrow
+        let redelegate = &expanded[expanded.find("fn redelegate_counter").unwrap()..];
+        assert!(redelegate.contains("DelegateAccounts { payer ,"));
+        assert!(!redelegate.contains("payer : escrow"));
+        assert!(expanded.contains("fn delegate_counter"));
+    }
 }
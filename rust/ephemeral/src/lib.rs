@@ -2,7 +2,10 @@ use proc_macro::TokenStream;
 
 use proc_macro2::TokenStream as TokenStream2;
 use quote::quote;
-use syn::{parse_macro_input, ItemMod};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input, Ident, ItemMod, Path, Token,
+};
 
 fn generated_unchecked_account_type() -> TokenStream2 {
     if cfg!(feature = "backward-compat") {
@@ -16,6 +19,20 @@ fn generated_unchecked_account_type() -> TokenStream2 {
 ///
 /// Components can be delegate and undelegated to allow fast udpates in the Ephemeral Rollups.
 ///
+/// The `on_undelegate = path::to::fn` argument registers a hook, which is invoked once the
+/// undelegated account has been restored, with the account and its original seeds:
+///
+/// ```ignore
+/// #[ephemeral(on_undelegate = crate::on_undelegate)]
+/// #[program]
+/// pub mod counter { /* ... */ }
+///
+/// pub fn on_undelegate(account: &AccountInfo, seeds: &[Vec<u8>]) -> Result<()> {
+///     // update the state derived from the account
+///     Ok(())
+/// }
+/// ```
+///
 /// # Example
 /// ```ignore
 ///
@@ -75,9 +92,10 @@ fn generated_unchecked_account_type() -> TokenStream2 {
 /// }
 /// ```
 #[proc_macro_attribute]
-pub fn ephemeral(_args: TokenStream, input: TokenStream) -> TokenStream {
+pub fn ephemeral(args: TokenStream, input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(args as EphemeralArgs);
     let ast = parse_macro_input!(input as syn::ItemMod);
-    let modified = modify_component_module(ast);
+    let modified = modify_component_module(ast, &args);
     TokenStream::from(quote! {
         #[allow(unused_imports)]
         use ephemeral_rollups_sdk::anchor::MagicProgram;
@@ -86,8 +104,36 @@ pub fn ephemeral(_args: TokenStream, input: TokenStream) -> TokenStream {
     })
 }
 
+/// Arguments of the `#[ephemeral]` attribute
+struct EphemeralArgs {
+    on_undelegate: Option<Path>,
+}
+
+impl Parse for EphemeralArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut on_undelegate = None;
+        while !input.is_empty() {
+            let key: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            match key.to_string().as_str() {
+                "on_undelegate" => on_undelegate = Some(input.parse()?),
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        "unknown argument, expected `on_undelegate`",
+                    ))
+                }
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(Self { on_undelegate })
+    }
+}
+
 /// Modifies the component module and adds the necessary functions and structs.
-fn modify_component_module(mut module: ItemMod) -> ItemMod {
+fn modify_component_module(mut module: ItemMod, args: &EphemeralArgs) -> ItemMod {
     // Add common imports for MagicIntentBundleBuilder for user
     let builder_imports = quote! {
         #[allow(unused_imports)]
@@ -98,7 +144,7 @@ fn modify_component_module(mut module: ItemMod) -> ItemMod {
         };
     };
 
-    let (imports, undelegate_fn, undelegate_struct) = generate_undelegate();
+    let (imports, undelegate_fn, undelegate_struct) = generate_undelegate(args);
     module.content = module.content.map(|(brace, mut items)| {
         items.extend(
             vec![builder_imports, imports, undelegate_fn, undelegate_struct]
@@ -114,11 +160,38 @@ fn modify_component_module(mut module: ItemMod) -> ItemMod {
 }
 
 /// Generates the undelegate function and struct.
-fn generate_undelegate() -> (TokenStream2, TokenStream2, TokenStream2) {
+fn generate_undelegate(args: &EphemeralArgs) -> (TokenStream2, TokenStream2, TokenStream2) {
     let unchecked_account = generated_unchecked_account_type();
+    let undelegate = match &args.on_undelegate {
+        Some(hook) => quote! {
+            undelegate_account_with_hook(
+                delegated_account,
+                &id(),
+                buffer,
+                payer,
+                system_program,
+                account_seeds,
+                |account, seeds| {
+                    #hook(account, seeds)?;
+                    Ok(())
+                },
+            )?;
+        },
+        None => quote! {
+            undelegate_account(
+                delegated_account,
+                &id(),
+                buffer,
+                payer,
+                system_program,
+                account_seeds,
+            )?;
+        },
+    };
     (
         quote! {
-            use ephemeral_rollups_sdk::cpi::undelegate_account;
+            #[allow(unused_imports)]
+            use ephemeral_rollups_sdk::cpi::{undelegate_account, undelegate_account_with_hook};
         },
         quote! {
             #[automatically_derived]
@@ -129,14 +202,7 @@ fn generate_undelegate() -> (TokenStream2, TokenStream2, TokenStream2) {
                     &ctx.accounts.payer,
                     &ctx.accounts.system_program,
                 ];
-                undelegate_account(
-                    delegated_account,
-                    &id(),
-                    buffer,
-                    payer,
-                    system_program,
-                    account_seeds,
-                )?;
+                #undelegate
                 Ok(())
             }
         },
@@ -159,3 +225,66 @@ fn generate_undelegate() -> (TokenStream2, TokenStream2, TokenStream2) {
         },
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(args: TokenStream2) -> String {
+        let args: EphemeralArgs = syn::parse2(args).unwrap();
+        let module = modify_component_module(
+            syn::parse_quote!(
+                pub mod counter {}
+            ),
+            &args,
+        );
+        quote!(#module).to_string()
+    }
+
+    #[test]
+    fn test_on_undelegate_expansion() {
+        let expanded = expand(quote!(on_undelegate = crate::hooks::on_undelegate));
+        let expected = quote! {
+            undelegate_account_with_hook(
+                delegated_account,
+                &id(),
+                buffer,
+                payer,
+                system_program,
+                account_seeds,
+                |account, seeds| {
+                    crate::hooks::on_undelegate(account, seeds)?;
+                    Ok(())
+                },
+            )?;
+        };
+        assert!(expanded.contains(&expected.to_string()), "{expanded}");
+        assert!(!expanded.contains("undelegate_account (delegated_account"));
+    }
+
+    #[test]
+    fn test_default_expansion() {
+        let expanded = expand(quote!());
+        let expected = quote! {
+            undelegate_account(
+                delegated_account,
+                &id(),
+                buffer,
+                payer,
+                system_program,
+                account_seeds,
+            )?;
+        };
+        assert!(expanded.contains(&expected.to_string()), "{expanded}");
+        assert!(!expanded.contains("undelegate_account_with_hook ("));
+    }
+
+    #[test]
+    fn test_unknown_argument_rejected() {
+        let err = syn::parse2::<EphemeralArgs>(quote!(on_delegate = crate::hook)).err();
+        assert_eq!(
+            err.map(|err| err.to_string()),
+            Some("unknown argument, expected `on_undelegate`".to_string())
+        );
+    }
+}
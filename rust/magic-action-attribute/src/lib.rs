@@ -0,0 +1,503 @@
+extern crate proc_macro;
+use heck::ToSnakeCase;
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use sha2::{Digest, Sha256};
+use syn::parse::{Parse, ParseStream};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Expr, Field, Fields, Ident, ItemStruct, LitStr, Token, Type};
+
+// ==================== Constants ====================
+
+const ATTR_MAGIC_ACTION: &str = "magic_action";
+const ATTR_ACCOUNT: &str = "account";
+/// Fields injected by `#[action]`, the validator passes them on its own.
+const ESCROW_FIELDS: [&str; 2] = ["escrow_auth", "escrow"];
+/// Constraints for which Anchor requires the account to be writable.
+const WRITABLE_MARKERS: [&str; 4] = ["mut", "init", "init_if_needed", "zero"];
+const SIGNER_MARKER: &str = "signer";
+/// Anchor types of a single account, any other field type is a nested `Accounts` struct.
+const ACCOUNT_TYPES: [&str; 11] = [
+    "Account",
+    "AccountInfo",
+    "AccountLoader",
+    "Interface",
+    "InterfaceAccount",
+    "LazyAccount",
+    "Program",
+    "Signer",
+    "SystemAccount",
+    "Sysvar",
+    "UncheckedAccount",
+];
+
+#[derive(Clone, Copy)]
+enum Flavour {
+    Sdk,
+    Pinocchio,
+}
+
+/// Derives a `<Name>MagicAction` struct holding the keys of the accounts of an Anchor
+/// instruction, with an `into_call_handler` turning them into a `CallHandler` that
+/// schedules the instruction as a base-layer action.
+///
+/// Account order and writability follow the fields and their `#[account(...)]`
+/// constraints, the `escrow_auth` and `escrow` fields injected by `#[action]` are
+/// skipped. Signer fields are rejected, actions can't request signatures, and so are
+/// optional accounts and nested `Accounts` structs, as actions pass a fixed list of accounts.
+///
+/// Optional `#[magic_action(...)]` arguments:
+/// - `program = <expr>`: destination program id, defaults to `crate::ID`.
+/// - `args = <type>`: borsh-serialized instruction args, none by default.
+/// - `instruction = "<name>"`: instruction name, defaults to the snake-cased struct name.
+/// - `discriminator = <expr>`: overrides the Anchor `global:<instruction>` discriminator.
+#[proc_macro_derive(MagicAction, attributes(magic_action, account))]
+pub fn magic_action(item: TokenStream) -> TokenStream {
+    expand(item, Flavour::Sdk)
+}
+
+/// Pinocchio flavour of [`macro@MagicAction`], re-exported as `MagicAction` by
+/// `ephemeral-rollups-pinocchio` with its `magic-action` feature.
+///
+/// `into_call_handler` takes the already serialized args and stores the instruction data
+/// and account metas in a `MagicActionBuf`, aliased as `<Name>MagicActionBuf<DATA>`.
+#[proc_macro_derive(PinocchioMagicAction, attributes(magic_action, account))]
+pub fn pinocchio_magic_action(item: TokenStream) -> TokenStream {
+    expand(item, Flavour::Pinocchio)
+}
+
+// ==================== Parsing ====================
+
+#[derive(Default)]
+struct MagicActionArgs {
+    program: Option<Expr>,
+    args: Option<Type>,
+    instruction: Option<LitStr>,
+    discriminator: Option<Expr>,
+}
+
+impl Parse for MagicActionArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut parsed = Self::default();
+        while !input.is_empty() {
+            let key: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            match key.to_string().as_str() {
+                "program" => parsed.program = Some(input.parse()?),
+                "args" => parsed.args = Some(input.parse()?),
+                "instruction" => parsed.instruction = Some(input.parse()?),
+                "discriminator" => parsed.discriminator = Some(input.parse()?),
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        format!("unknown magic_action argument `{}`", key),
+                    ))
+                }
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+fn parse_magic_action_args(input: &ItemStruct) -> syn::Result<MagicActionArgs> {
+    match input
+        .attrs
+        .iter()
+        .find(|attr| attr.path.is_ident(ATTR_MAGIC_ACTION))
+    {
+        Some(attr) => attr.parse_args(),
+        None => Ok(MagicActionArgs::default()),
+    }
+}
+
+/// Returns the leading identifiers of the top-level `#[account(...)]` constraints,
+/// e.g. `mut`, `init` or `seeds`.
+fn account_markers(field: &Field) -> Vec<String> {
+    let Some(attr) = field
+        .attrs
+        .iter()
+        .find(|attr| attr.path.is_ident(ATTR_ACCOUNT))
+    else {
+        return vec![];
+    };
+
+    let tokens = attr.tokens.to_string();
+    let trimmed = tokens.trim();
+    let Some(inner) = trimmed
+        .strip_prefix('(')
+        .and_then(|inner| inner.strip_suffix(')'))
+    else {
+        return vec![];
+    };
+
+    let mut markers = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in inner.char_indices().chain([(inner.len(), ',')]) {
+        match c {
+            '[' | '(' | '{' => depth += 1,
+            ']' | ')' | '}' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                let marker = inner[start..i]
+                    .trim()
+                    .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .next()
+                    .unwrap_or_default();
+                markers.push(marker.to_string());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    markers
+}
+
+/// Returns true if the type is `Signer<'info>` (AST-based, matches last path segment).
+fn is_signer_type(ty: &Type) -> bool {
+    if let Type::Path(type_path) = ty {
+        type_path
+            .path
+            .segments
+            .last()
+            .is_some_and(|seg| seg.ident == "Signer")
+    } else {
+        false
+    }
+}
+
+/// Returns the last path segment of the type, looking through `Box<...>`.
+fn account_type(ty: &Type) -> Option<&syn::PathSegment> {
+    let Type::Path(type_path) = ty else {
+        return None;
+    };
+    let segment = type_path.path.segments.last()?;
+    if segment.ident != "Box" {
+        return Some(segment);
+    }
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => match args.args.first() {
+            Some(syn::GenericArgument::Type(inner)) => account_type(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Rejects the fields which aren't a single, always present account: an action passes a
+/// fixed list of accounts, so optional accounts and nested `Accounts` structs can't be
+/// expanded. The fields of the pinocchio flavour aren't Anchor types, only `Option` is
+/// rejected there.
+fn check_account_type(ident: &Ident, ty: &Type, flavour: Flavour) -> syn::Result<()> {
+    match (account_type(ty), flavour) {
+        (Some(segment), _) if segment.ident == "Option" => Err(syn::Error::new(
+            ty.span(),
+            format!(
+                "`{}` can't be optional, actions pass a fixed list of accounts",
+                ident
+            ),
+        )),
+        (_, Flavour::Pinocchio) => Ok(()),
+        (Some(segment), Flavour::Sdk) if ACCOUNT_TYPES.iter().any(|name| segment.ident == name) => {
+            Ok(())
+        }
+        (_, Flavour::Sdk) => Err(syn::Error::new(
+            ty.span(),
+            format!(
+                "`{}` isn't an account, nested `Accounts` structs aren't supported",
+                ident
+            ),
+        )),
+    }
+}
+
+/// Anchor discriminator of the instruction: `sha256("global:<instruction>")[..8]`.
+fn anchor_discriminator(instruction: &str) -> [u8; 8] {
+    let hash = Sha256::digest(format!("global:{}", instruction).as_bytes());
+    let mut discriminator = [0; 8];
+    discriminator.copy_from_slice(&hash[..8]);
+    discriminator
+}
+
+// ==================== Code Generation ====================
+
+struct ActionAccount<'a> {
+    ident: &'a Ident,
+    is_writable: bool,
+}
+
+fn action_accounts(input: &ItemStruct, flavour: Flavour) -> syn::Result<Vec<ActionAccount<'_>>> {
+    let Fields::Named(fields_named) = &input.fields else {
+        return Err(syn::Error::new(
+            input.span(),
+            "MagicAction can only be derived for structs with named fields",
+        ));
+    };
+
+    let mut accounts = Vec::new();
+    for field in &fields_named.named {
+        let Some(ident) = &field.ident else {
+            continue;
+        };
+        if ESCROW_FIELDS.iter().any(|escrow| ident == escrow) {
+            continue;
+        }
+
+        let markers = account_markers(field);
+        if is_signer_type(&field.ty) || markers.iter().any(|m| m == SIGNER_MARKER) {
+            return Err(syn::Error::new(
+                field.span(),
+                format!(
+                    "`{}` can't be a signer, base-layer actions can't request signatures",
+                    ident
+                ),
+            ));
+        }
+        check_account_type(ident, &field.ty, flavour)?;
+        accounts.push(ActionAccount {
+            ident,
+            is_writable: markers
+                .iter()
+                .any(|m| WRITABLE_MARKERS.contains(&m.as_str())),
+        });
+    }
+    Ok(accounts)
+}
+
+fn expand(item: TokenStream, flavour: Flavour) -> TokenStream {
+    let input = parse_macro_input!(item as ItemStruct);
+    match expand_magic_action(&input, flavour) {
+        Ok(expanded) => TokenStream::from(expanded),
+        Err(err) => TokenStream::from(err.to_compile_error()),
+    }
+}
+
+fn expand_magic_action(input: &ItemStruct, flavour: Flavour) -> syn::Result<TokenStream2> {
+    let args = parse_magic_action_args(input)?;
+    let accounts = action_accounts(input, flavour)?;
+
+    let name = &input.ident;
+    let vis = &input.vis;
+    let action_name = format_ident!("{}MagicAction", name);
+    let instruction = args
+        .instruction
+        .map(|instruction| instruction.value())
+        .unwrap_or_else(|| name.to_string().to_snake_case());
+    let discriminator = match args.discriminator {
+        Some(discriminator) => quote! { &#discriminator },
+        None => {
+            let bytes = anchor_discriminator(&instruction);
+            quote! { &[#(#bytes),*] }
+        }
+    };
+    let program = args.program.unwrap_or_else(|| syn::parse_quote!(crate::ID));
+
+    let idents: Vec<_> = accounts.iter().map(|account| account.ident).collect();
+    let is_writable: Vec<_> = accounts.iter().map(|account| account.is_writable).collect();
+    let accounts_len = accounts.len();
+    let struct_doc = format!(
+        "Accounts of the `{}` instruction, scheduled as a base-layer action with \
+         [`{}::into_call_handler`].",
+        instruction, action_name
+    );
+
+    let expanded = match flavour {
+        Flavour::Sdk => {
+            let (args_param, serialize_args) = match &args.args {
+                Some(args_ty) => (
+                    quote! { args: #args_ty, },
+                    quote! {
+                        anchor_lang::AnchorSerialize::serialize(&args, &mut data)
+                            .expect("serializing into a Vec can't fail");
+                    },
+                ),
+                None => (quote! {}, quote! {}),
+            };
+            quote! {
+                #[doc = #struct_doc]
+                #vis struct #action_name {
+                    #(pub #idents: ::ephemeral_rollups_sdk::compat::Pubkey,)*
+                }
+
+                impl #action_name {
+                    /// Discriminator of the instruction.
+                    pub const DISCRIMINATOR: &'static [u8] = #discriminator;
+
+                    /// Builds the call handler executing the instruction on the base layer.
+                    pub fn into_call_handler<'info>(
+                        self,
+                        #args_param
+                        escrow_authority: ::ephemeral_rollups_sdk::compat::AccountInfo<'info>,
+                        compute_units: u32,
+                    ) -> ::ephemeral_rollups_sdk::ephem::CallHandler<'info> {
+                        #[allow(unused_mut)]
+                        let mut data = Self::DISCRIMINATOR.to_vec();
+                        #serialize_args
+                        ::ephemeral_rollups_sdk::ephem::CallHandler {
+                            args: ::ephemeral_rollups_sdk::ActionArgs::new(data),
+                            compute_units,
+                            escrow_authority,
+                            destination_program: ::ephemeral_rollups_sdk::compat::Pubkey::new_from_array(
+                                (#program).to_bytes(),
+                            ),
+                            accounts: vec![#(
+                                ::ephemeral_rollups_sdk::ShortAccountMeta {
+                                    pubkey: self.#idents.to_bytes().into(),
+                                    is_writable: #is_writable,
+                                },
+                            )*],
+                        }
+                    }
+                }
+            }
+        }
+        Flavour::Pinocchio => {
+            let buf_name = format_ident!("{}MagicActionBuf", name);
+            let buf_doc = format!(
+                "Storage of the call handler built by [`{}::into_call_handler`].",
+                action_name
+            );
+            quote! {
+                #[doc = #struct_doc]
+                #vis struct #action_name {
+                    #(pub #idents: ::pinocchio::Address,)*
+                }
+
+                #[doc = #buf_doc]
+                #vis type #buf_name<const DATA: usize> =
+                    ::ephemeral_rollups_pinocchio::intent_bundle::MagicActionBuf<#accounts_len, DATA>;
+
+                impl #action_name {
+                    /// Discriminator of the instruction.
+                    pub const DISCRIMINATOR: &'static [u8] = #discriminator;
+
+                    /// Builds the call handler executing the instruction on the base layer,
+                    /// `args` being the serialized instruction args.
+                    pub fn into_call_handler<'a, const DATA: usize>(
+                        self,
+                        args: &[u8],
+                        escrow_authority: ::pinocchio::AccountView,
+                        compute_units: u32,
+                        buf: &'a mut #buf_name<DATA>,
+                    ) -> Result<
+                        ::ephemeral_rollups_pinocchio::intent_bundle::CallHandler<'a>,
+                        ::pinocchio::error::ProgramError,
+                    > {
+                        buf.call_handler(
+                            ::pinocchio::Address::new_from_array((#program).to_bytes()),
+                            Self::DISCRIMINATOR,
+                            args,
+                            [#(
+                                ::ephemeral_rollups_pinocchio::intent_bundle::ShortAccountMeta {
+                                    pubkey: self.#idents,
+                                    is_writable: #is_writable,
+                                },
+                            )*],
+                            escrow_authority,
+                            compute_units,
+                        )
+                    }
+                }
+            }
+        }
+    };
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_anchor_discriminator() {
+        // sha256("global:initialize")[..8], as generated by Anchor
+        assert_eq!(
+            anchor_discriminator("initialize"),
+            [175, 175, 109, 31, 13, 152, 155, 237]
+        );
+    }
+
+    #[test]
+    fn test_action_accounts() {
+        let input: ItemStruct = syn::parse_quote! {
+            #[action]
+            pub struct UpdateCounter<'info> {
+                #[account(mut, seeds = [b"counter", authority.key().as_ref()], bump)]
+                pub counter: Account<'info, Counter>,
+                #[account(init_if_needed, payer = payer, space = 8)]
+                pub record: Account<'info, Record>,
+                /// CHECK: read only
+                pub authority: UncheckedAccount<'info>,
+                pub escrow_auth: UncheckedAccount<'info>,
+                pub escrow: UncheckedAccount<'info>,
+            }
+        };
+        let accounts: Vec<_> = action_accounts(&input, Flavour::Sdk)
+            .unwrap()
+            .into_iter()
+            .map(|account| (account.ident.to_string(), account.is_writable))
+            .collect();
+        assert_eq!(
+            accounts,
+            [
+                ("counter".to_string(), true),
+                ("record".to_string(), true),
+                ("authority".to_string(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_optional_and_nested_accounts_rejected() {
+        let optional: ItemStruct = syn::parse_quote! {
+            pub struct Update<'info> {
+                #[account(mut)]
+                pub counter: Option<Account<'info, Counter>>,
+            }
+        };
+        let err = action_accounts(&optional, Flavour::Sdk).err().unwrap();
+        assert!(err.to_string().contains("can't be optional"));
+
+        let nested: ItemStruct = syn::parse_quote! {
+            pub struct Update<'info> {
+                pub inner: Inner<'info>,
+            }
+        };
+        let err = action_accounts(&nested, Flavour::Sdk).err().unwrap();
+        assert!(err.to_string().contains("nested `Accounts` structs"));
+
+        // the pinocchio flavour only mirrors the accounts
+        assert!(action_accounts(&nested, Flavour::Pinocchio).is_ok());
+        assert!(action_accounts(&optional, Flavour::Pinocchio).is_err());
+
+        let boxed: ItemStruct = syn::parse_quote! {
+            pub struct Update<'info> {
+                #[account(mut)]
+                pub counter: Box<Account<'info, Counter>>,
+                pub system_program: Program<'info, System>,
+            }
+        };
+        assert_eq!(action_accounts(&boxed, Flavour::Sdk).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_signer_rejected() {
+        let signer_type: ItemStruct = syn::parse_quote! {
+            pub struct Update<'info> {
+                pub payer: Signer<'info>,
+            }
+        };
+        assert!(action_accounts(&signer_type, Flavour::Sdk).is_err());
+
+        let signer_constraint: ItemStruct = syn::parse_quote! {
+            pub struct Update<'info> {
+                #[account(mut, signer)]
+                pub payer: UncheckedAccount<'info>,
+            }
+        };
+        assert!(action_accounts(&signer_constraint, Flavour::Sdk).is_err());
+    }
+}
@@ -14,6 +14,10 @@ pub const MAGIC_PROGRAM_ID: Address =
 pub const MAGIC_CONTEXT_ID: Address =
     Address::new_from_array(pubkey!("MagicContext1111111111111111111111111111111"));
 
+/// The signer of the action callbacks, i.e. the PDA of the callback executor of the validator.
+pub const CALLBACK_SIGNER: Address =
+    Address::new_from_array(pubkey!("EscZwLs6xwKUgj16wHqPH42wBKJR9Ku93Y5oRheS3FNY"));
+
 /// The ephemeral vault ID (collects rent for ephemeral accounts).
 pub const EPHEMERAL_VAULT_ID: Address =
     Address::new_from_array(pubkey!("MagicVau1t999999999999999999999999999999999"));
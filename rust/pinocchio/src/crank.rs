@@ -10,7 +10,14 @@ use pinocchio::{
     AccountView, Address, ProgramResult,
 };
 
+use crate::consts::{MAGIC_CONTEXT_ID, MAGIC_PROGRAM_ID};
+use crate::error::{INVALID_COMMITTED_ACCOUNTS_ERROR, INVALID_MAGIC_CONTEXT_ERROR};
+use crate::intent_bundle::MagicIntentBundleBuilder;
+
 const SCHEDULE_CRANK_DISCRIMINANT: [u8; 4] = 6_u32.to_le_bytes();
+/// Number of accounts preceding the committed accounts in the crank instruction of a
+/// [`ScheduledCommit`]: the commit payer, the magic context and the magic program.
+const SCHEDULED_COMMIT_PREFIX_LEN: usize = 3;
 
 pub struct CrankInstruction<'a> {
     pub program_id: Address,
@@ -435,6 +442,154 @@ impl CancelCrankCpi {
     }
 }
 
+/// Registers a crank committing `committed_accounts` every `execution_interval_millis`,
+/// without undelegating them.
+///
+/// The crank invokes `program_id` with `data`, whose handler commits the accounts with
+/// [`process_scheduled_commit`]. The crank instruction accounts are the commit payer, the
+/// magic context, the magic program and the committed accounts.
+pub struct ScheduledCommit<'a> {
+    pub payer: AccountView,
+    pub task_context: AccountView,
+    pub magic_program: AccountView,
+    pub magic_context: AccountView,
+    /// Payer of the commits, a PDA of `program_id` signed for by the handler
+    pub commit_payer: AccountView,
+    pub committed_accounts: &'a [AccountView],
+    /// Program handling the crank instruction
+    pub program_id: Address,
+    /// Instruction data of the handler
+    pub data: &'a [u8],
+    pub task_id: i64,
+    pub execution_interval_millis: i64,
+    pub iterations: i64,
+}
+
+impl ScheduledCommit<'_> {
+    pub fn serialize_into<const MAX_ACCOUNT_INFOS: usize>(
+        &self,
+        data: &mut [u8],
+    ) -> Result<usize, ProgramError> {
+        self.with_schedule_cpi::<MAX_ACCOUNT_INFOS, _>(|cpi| cpi.serialize_into(data))
+    }
+
+    pub fn invoke<const MAX_ACCOUNT_INFOS: usize>(&self, data_buf: &mut [u8]) -> ProgramResult {
+        self.with_schedule_cpi::<MAX_ACCOUNT_INFOS, _>(|cpi| {
+            cpi.invoke::<MAX_ACCOUNT_INFOS>(data_buf)
+        })
+    }
+
+    pub fn invoke_signed<const MAX_ACCOUNT_INFOS: usize>(
+        &self,
+        data_buf: &mut [u8],
+        signers_seeds: &[Signer<'_, '_>],
+    ) -> ProgramResult {
+        self.with_schedule_cpi::<MAX_ACCOUNT_INFOS, _>(|cpi| {
+            cpi.invoke_signed::<MAX_ACCOUNT_INFOS>(data_buf, signers_seeds)
+        })
+    }
+
+    /// Cancels the crank registered with `task_id`.
+    pub fn cancel(
+        authority: AccountView,
+        task_context: AccountView,
+        magic_program: AccountView,
+        task_id: i64,
+    ) -> CancelCrankCpi {
+        CancelCrankCpi {
+            authority,
+            task_context,
+            magic_program,
+            crank_id: task_id,
+        }
+    }
+
+    /// Calls `f` with the `ScheduleTask` CPI of the crank, `MAX_ACCOUNT_INFOS` bounding its
+    /// accounts: the payer, the task context and the crank instruction accounts.
+    fn with_schedule_cpi<const MAX_ACCOUNT_INFOS: usize, R>(
+        &self,
+        f: impl FnOnce(&ScheduleCrankCpi<'_>) -> Result<R, ProgramError>,
+    ) -> Result<R, ProgramError> {
+        let num_crank_accounts = SCHEDULED_COMMIT_PREFIX_LEN + self.committed_accounts.len();
+        if 2 + num_crank_accounts > MAX_ACCOUNT_INFOS {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let mut crank_accounts: [InstructionAccount; MAX_ACCOUNT_INFOS] =
+            core::array::from_fn(|_| InstructionAccount::readonly(self.magic_program.address()));
+        crank_accounts[0] = InstructionAccount::writable(self.commit_payer.address());
+        crank_accounts[1] = InstructionAccount::writable(self.magic_context.address());
+        let mut instruction_accounts = [&self.task_context; MAX_ACCOUNT_INFOS];
+        instruction_accounts[1..4].copy_from_slice(&[
+            &self.commit_payer,
+            &self.magic_context,
+            &self.magic_program,
+        ]);
+        for (i, account) in self.committed_accounts.iter().enumerate() {
+            crank_accounts[SCHEDULED_COMMIT_PREFIX_LEN + i] =
+                InstructionAccount::new(account.address(), account.is_writable(), false);
+            instruction_accounts[1 + SCHEDULED_COMMIT_PREFIX_LEN + i] = account;
+        }
+
+        let instructions = [CrankInstruction::new(
+            self.program_id,
+            &crank_accounts[..num_crank_accounts],
+            self.data,
+        )];
+        f(&ScheduleCrankCpi::new(
+            self.payer.clone(),
+            self.magic_program.clone(),
+            &instruction_accounts[..1 + num_crank_accounts],
+            ScheduleCrankArgs::new(self.task_id, &instructions)
+                .execution_interval_millis(self.execution_interval_millis)
+                .iterations(self.iterations),
+        ))
+    }
+}
+
+/// Commits the accounts of a crank registered with [`ScheduledCommit`], `accounts` being the
+/// accounts of the crank instruction and `commit_payer_seeds` signing for the commit payer.
+///
+/// As anyone can invoke the handler, the magic program and context must be
+/// [`MAGIC_PROGRAM_ID`] and [`MAGIC_CONTEXT_ID`], and the committed accounts must be
+/// `committed`, the accounts the crank has been registered with, in order. The program either
+/// stores them at registration or derives them, e.g. as PDAs of the commit payer.
+pub fn process_scheduled_commit(
+    accounts: &[AccountView],
+    committed: &[Address],
+    data_buf: &mut [u8],
+    commit_payer_seeds: &[Signer<'_, '_>],
+) -> ProgramResult {
+    let [commit_payer, magic_context, magic_program, committed_accounts @ ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    if committed_accounts.is_empty() {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    if magic_program.address() != &MAGIC_PROGRAM_ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if magic_context.address() != &MAGIC_CONTEXT_ID {
+        return Err(ProgramError::Custom(INVALID_MAGIC_CONTEXT_ERROR));
+    }
+    if !committed_accounts
+        .iter()
+        .map(AccountView::address)
+        .eq(committed)
+    {
+        return Err(ProgramError::Custom(INVALID_COMMITTED_ACCOUNTS_ERROR));
+    }
+
+    MagicIntentBundleBuilder::new(
+        commit_payer.clone(),
+        magic_context.clone(),
+        magic_program.clone(),
+    )
+    .commit(committed_accounts)
+    .fold()
+    .build_and_invoke_signed(data_buf, commit_payer_seeds)
+}
+
 #[inline(always)]
 fn write_serialized_bytes(
     data: &mut [u8],
@@ -543,7 +698,7 @@ mod tests {
         let acc1 = Address::new_from_array(core::array::from_fn(|i| if i == 0 { 2 } else { 0 }));
         let instruction_accounts = [InstructionAccount::new(&acc1, true, false)];
         let crank_instructions = [CrankInstruction::new(
-            program_id.clone(),
+            program_id,
             &instruction_accounts,
             &[1, 2, 3],
         )];
@@ -617,8 +772,8 @@ mod tests {
             InstructionAccount::new(&acc2, true, false),
         ];
         let crank_instructions = [
-            CrankInstruction::new(program_id.clone(), &first_accounts, &[1, 2, 3]),
-            CrankInstruction::new(program_id.clone(), &second_accounts, &[1, 2, 3]),
+            CrankInstruction::new(program_id, &first_accounts, &[1, 2, 3]),
+            CrankInstruction::new(program_id, &second_accounts, &[1, 2, 3]),
         ];
         let this_args = ScheduleCrankArgs::new(123, &crank_instructions)
             .execution_interval_millis(123456)
@@ -877,4 +1032,122 @@ mod tests {
         assert!(writable_view.accounts[0].is_writable);
         assert!(writable_view.accounts[0].is_signer);
     }
+
+    #[test]
+    fn test_scheduled_commit() {
+        let address = |i: u8| Address::new_from_array(core::array::from_fn(|j| (j == 0) as u8 * i));
+        let mut runtime_accounts: Vec<RuntimeAccount> = (1..=7)
+            .map(|i| runtime_account(address(i), 0, (i != 3) as u8))
+            .collect();
+        let accounts: Vec<AccountView> = runtime_accounts
+            .iter_mut()
+            .map(|account| unsafe { AccountView::new_unchecked(account as *mut RuntimeAccount) })
+            .collect();
+        let [payer, task_context, magic_program, magic_context, commit_payer, committed @ ..] =
+            accounts.as_slice()
+        else {
+            unreachable!()
+        };
+        let committed = committed.to_vec();
+        let program_id = address(9);
+
+        let scheduled_commit = ScheduledCommit {
+            payer: payer.clone(),
+            task_context: task_context.clone(),
+            magic_program: magic_program.clone(),
+            magic_context: magic_context.clone(),
+            commit_payer: commit_payer.clone(),
+            committed_accounts: &committed,
+            program_id,
+            data: &[1, 2, 3],
+            task_id: 5,
+            execution_interval_millis: 1_000,
+            iterations: 10,
+        };
+        let mut data = [0; 512];
+        let len = scheduled_commit.serialize_into::<16>(&mut data).unwrap();
+
+        let meta = |i: u8, is_writable| AccountMeta {
+            pubkey: address(i).to_bytes().into(),
+            is_signer: false,
+            is_writable,
+        };
+        let api_args = ScheduleTaskArgs {
+            task_id: 5,
+            execution_interval_millis: 1_000,
+            iterations: 10,
+            instructions: vec![Instruction {
+                program_id: program_id.to_bytes().into(),
+                accounts: vec![
+                    meta(5, true),
+                    meta(4, true),
+                    meta(3, false),
+                    meta(6, true),
+                    meta(7, true),
+                ],
+                data: vec![1, 2, 3],
+            }],
+        };
+        let api_data = bincode1::serialize(&MagicBlockInstruction::ScheduleTask(api_args)).unwrap();
+        assert_eq!(&data[..len], api_data.as_slice());
+
+        // the payer, the task context and the five crank accounts
+        assert_eq!(
+            scheduled_commit.serialize_into::<6>(&mut data),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(
+            process_scheduled_commit(&committed, &[], &mut data, &[]),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+    }
+
+    #[test]
+    fn test_process_scheduled_commit_checks_accounts() {
+        let committed = [
+            Address::new_from_array([1; 32]),
+            Address::new_from_array([2; 32]),
+        ];
+        let other = Address::new_from_array([3; 32]);
+        let runtime_accounts = |keys: &[Address]| -> Vec<RuntimeAccount> {
+            keys.iter()
+                .map(|&address| runtime_account(address, 0, 1))
+                .collect()
+        };
+        let process = |keys: &[Address]| {
+            let mut runtime_accounts = runtime_accounts(keys);
+            let accounts: Vec<AccountView> = runtime_accounts
+                .iter_mut()
+                .map(|account| unsafe {
+                    AccountView::new_unchecked(account as *mut RuntimeAccount)
+                })
+                .collect();
+            process_scheduled_commit(&accounts, &committed, &mut [0; 512], &[])
+        };
+        let keys = [
+            Address::new_from_array([4; 32]),
+            MAGIC_CONTEXT_ID,
+            MAGIC_PROGRAM_ID,
+            committed[0],
+            committed[1],
+        ];
+        let with_key = |i: usize| {
+            let mut keys = keys;
+            keys[i] = other;
+            keys
+        };
+
+        assert_eq!(process(&with_key(2)), Err(ProgramError::IncorrectProgramId));
+        assert_eq!(
+            process(&with_key(1)),
+            Err(ProgramError::Custom(INVALID_MAGIC_CONTEXT_ERROR))
+        );
+        let swapped = [keys[0], keys[1], keys[2], keys[4], keys[3]];
+        for keys in [&with_key(3)[..], &with_key(4), &keys[..4], &swapped] {
+            assert_eq!(
+                process(keys),
+                Err(ProgramError::Custom(INVALID_COMMITTED_ACCOUNTS_ERROR))
+            );
+        }
+    }
 }
@@ -0,0 +1,316 @@
+//! Introspection of the delegation state, see the `delegation` module of the sdk crate.
+//!
+//! The readers borrow the delegation record and metadata accounts without copying them, after
+//! verifying that they're owned by the delegation program and derived from the delegated account.
+
+use pinocchio::{account::Ref, error::ProgramError, AccountView, Address};
+
+use crate::consts::DELEGATION_PROGRAM_ID;
+use crate::instruction::{INVALID_DELEGATION_METADATA_ERROR, INVALID_DELEGATION_RECORD_ERROR};
+use crate::pda::{
+    delegation_metadata_pda_from_delegated_account, delegation_record_pda_from_delegated_account,
+};
+
+/// Account discriminator of the delegation record
+const DELEGATION_RECORD_DISCRIMINATOR: [u8; 8] = [100, 0, 0, 0, 0, 0, 0, 0];
+/// Account discriminator of the delegation metadata
+const DELEGATION_METADATA_DISCRIMINATOR: [u8; 8] = [102, 0, 0, 0, 0, 0, 0, 0];
+
+const RECORD_AUTHORITY_OFFSET: usize = 8;
+const RECORD_OWNER_OFFSET: usize = RECORD_AUTHORITY_OFFSET + 32;
+const RECORD_SLOT_OFFSET: usize = RECORD_OWNER_OFFSET + 32;
+const RECORD_LAMPORTS_OFFSET: usize = RECORD_SLOT_OFFSET + 8;
+const RECORD_COMMIT_FREQUENCY_OFFSET: usize = RECORD_LAMPORTS_OFFSET + 8;
+const RECORD_LEN: usize = RECORD_COMMIT_FREQUENCY_OFFSET + 8;
+
+const METADATA_NONCE_OFFSET: usize = 8;
+const METADATA_UNDELEGATABLE_OFFSET: usize = METADATA_NONCE_OFFSET + 8;
+const METADATA_SEEDS_OFFSET: usize = METADATA_UNDELEGATABLE_OFFSET + 1;
+
+/// Whether the account is currently delegated, i.e. owned by the delegation program
+pub fn is_delegated(account: &AccountView) -> bool {
+    account.owned_by(&DELEGATION_PROGRAM_ID)
+}
+
+fn borrow_delegation_account<'a>(
+    account: &'a AccountView,
+    expected_address: &Address,
+    invalid_address_error: u32,
+) -> Result<Ref<'a, [u8]>, ProgramError> {
+    if account.address() != expected_address {
+        return Err(ProgramError::Custom(invalid_address_error));
+    }
+    if !account.owned_by(&DELEGATION_PROGRAM_ID) {
+        return Err(ProgramError::IllegalOwner);
+    }
+    account.try_borrow()
+}
+
+fn read_address(data: &[u8], offset: usize) -> Address {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&data[offset..offset + 32]);
+    Address::new_from_array(bytes)
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// Zero-copy reader of the delegation record of a delegated account
+pub struct DelegationRecordView<'a> {
+    data: Ref<'a, [u8]>,
+}
+
+impl<'a> DelegationRecordView<'a> {
+    /// Borrows the delegation record of `delegated_account`, verifying its owner and address
+    pub fn from_account(
+        delegation_record: &'a AccountView,
+        delegated_account: &Address,
+    ) -> Result<Self, ProgramError> {
+        let data = borrow_delegation_account(
+            delegation_record,
+            &delegation_record_pda_from_delegated_account(delegated_account),
+            INVALID_DELEGATION_RECORD_ERROR,
+        )?;
+        if data.len() < RECORD_LEN || data[..8] != DELEGATION_RECORD_DISCRIMINATOR {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(Self { data })
+    }
+
+    /// Validator the account is delegated to
+    pub fn authority(&self) -> Address {
+        read_address(&self.data, RECORD_AUTHORITY_OFFSET)
+    }
+
+    /// Program which owned the account before the delegation
+    pub fn owner(&self) -> Address {
+        read_address(&self.data, RECORD_OWNER_OFFSET)
+    }
+
+    /// Slot at which the account was delegated
+    pub fn delegation_slot(&self) -> u64 {
+        read_u64(&self.data, RECORD_SLOT_OFFSET)
+    }
+
+    /// Lamports of the account at the delegation or at the last finalized commit
+    pub fn lamports(&self) -> u64 {
+        read_u64(&self.data, RECORD_LAMPORTS_OFFSET)
+    }
+
+    pub fn commit_frequency_ms(&self) -> u64 {
+        read_u64(&self.data, RECORD_COMMIT_FREQUENCY_OFFSET)
+    }
+}
+
+/// Zero-copy reader of the delegation metadata of a delegated account
+pub struct DelegationMetadataView<'a> {
+    data: Ref<'a, [u8]>,
+    rent_payer_offset: usize,
+}
+
+impl<'a> DelegationMetadataView<'a> {
+    /// Borrows the delegation metadata of `delegated_account`, verifying its owner and address
+    pub fn from_account(
+        delegation_metadata: &'a AccountView,
+        delegated_account: &Address,
+    ) -> Result<Self, ProgramError> {
+        let data = borrow_delegation_account(
+            delegation_metadata,
+            &delegation_metadata_pda_from_delegated_account(delegated_account),
+            INVALID_DELEGATION_METADATA_ERROR,
+        )?;
+        if data.get(..8) != Some(&DELEGATION_METADATA_DISCRIMINATOR[..]) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let rent_payer_offset = seeds_end(&data)
+            .filter(|offset| data.len() >= offset + 32)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        Ok(Self {
+            data,
+            rent_payer_offset,
+        })
+    }
+
+    pub fn last_update_nonce(&self) -> u64 {
+        read_u64(&self.data, METADATA_NONCE_OFFSET)
+    }
+
+    /// Whether the account can be undelegated
+    pub fn is_undelegatable(&self) -> bool {
+        self.data[METADATA_UNDELEGATABLE_OFFSET] != 0
+    }
+
+    /// Seeds of the delegated account, without the bump
+    pub fn seeds(&self) -> SeedsIter<'_> {
+        SeedsIter {
+            data: &self.data[..self.rent_payer_offset],
+            offset: METADATA_SEEDS_OFFSET + 4,
+            remaining: read_u32(&self.data, METADATA_SEEDS_OFFSET).unwrap_or_default(),
+        }
+    }
+
+    /// Account which paid the rent of the delegation accounts, refunded on undelegation
+    pub fn rent_payer(&self) -> Address {
+        read_address(&self.data, self.rent_payer_offset)
+    }
+}
+
+/// Walks the borsh encoded seeds, returns the offset right after them
+fn seeds_end(data: &[u8]) -> Option<usize> {
+    let count = read_u32(data, METADATA_SEEDS_OFFSET)?;
+    let mut offset = METADATA_SEEDS_OFFSET + 4;
+    for _ in 0..count {
+        let len = read_u32(data, offset)? as usize;
+        offset = offset.checked_add(4 + len)?;
+    }
+    (offset <= data.len()).then_some(offset)
+}
+
+/// Iterator over the seeds of the delegation metadata, see [`DelegationMetadataView::seeds`]
+pub struct SeedsIter<'a> {
+    data: &'a [u8],
+    offset: usize,
+    remaining: u32,
+}
+
+impl<'a> Iterator for SeedsIter<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        // the layout has been validated when the view was created
+        let len = read_u32(self.data, self.offset)? as usize;
+        let seed = &self.data[self.offset + 4..self.offset + 4 + len];
+        self.offset += 4 + len;
+        self.remaining -= 1;
+        Some(seed)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining as usize, Some(self.remaining as usize))
+    }
+}
+
+impl ExactSizeIterator for SeedsIter<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use pinocchio::account::RuntimeAccount;
+
+    use super::*;
+
+    /// Runtime account followed by its data, as laid out in the input buffer
+    #[repr(C)]
+    struct AccountWithData {
+        account: RuntimeAccount,
+        data: [u8; 256],
+    }
+
+    impl AccountWithData {
+        fn new(address: Address, owner: Address, data: &[u8]) -> Self {
+            let mut account = Self {
+                account: RuntimeAccount {
+                    borrow_state: 0xFF,
+                    is_signer: 0,
+                    is_writable: 0,
+                    executable: 0,
+                    resize_delta: 0,
+                    address,
+                    owner,
+                    lamports: 0,
+                    data_len: data.len() as u64,
+                },
+                data: [0; 256],
+            };
+            account.data[..data.len()].copy_from_slice(data);
+            account
+        }
+
+        fn as_account_view(&mut self) -> AccountView {
+            unsafe { AccountView::new_unchecked(&mut self.account) }
+        }
+    }
+
+    #[test]
+    fn test_read_delegation_record() {
+        let delegated = Address::new_from_array([1; 32]);
+        let mut data = DELEGATION_RECORD_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&[2; 32]);
+        data.extend_from_slice(&[3; 32]);
+        data.extend_from_slice(&42u64.to_le_bytes());
+        data.extend_from_slice(&1_000_000u64.to_le_bytes());
+        data.extend_from_slice(&30_000u64.to_le_bytes());
+        let mut account = AccountWithData::new(
+            delegation_record_pda_from_delegated_account(&delegated),
+            DELEGATION_PROGRAM_ID,
+            &data,
+        );
+        let record = account.as_account_view();
+
+        assert!(is_delegated(&record));
+        let view = DelegationRecordView::from_account(&record, &delegated).unwrap();
+        assert_eq!(view.authority(), Address::new_from_array([2; 32]));
+        assert_eq!(view.owner(), Address::new_from_array([3; 32]));
+        assert_eq!(view.delegation_slot(), 42);
+        assert_eq!(view.lamports(), 1_000_000);
+        assert_eq!(view.commit_frequency_ms(), 30_000);
+        drop(view);
+
+        assert_eq!(
+            DelegationRecordView::from_account(&record, &Address::new_from_array([9; 32])).err(),
+            Some(ProgramError::Custom(INVALID_DELEGATION_RECORD_ERROR))
+        );
+    }
+
+    #[test]
+    fn test_read_delegation_metadata() {
+        let delegated = Address::new_from_array([1; 32]);
+        let seeds: [&[u8]; 2] = [b"counter", &[]];
+        let mut data = DELEGATION_METADATA_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&7u64.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(&(seeds.len() as u32).to_le_bytes());
+        for seed in seeds {
+            data.extend_from_slice(&(seed.len() as u32).to_le_bytes());
+            data.extend_from_slice(seed);
+        }
+        data.extend_from_slice(&[4; 32]);
+        let address = delegation_metadata_pda_from_delegated_account(&delegated);
+
+        let mut account = AccountWithData::new(address, Address::new_from_array([5; 32]), &data);
+        let metadata = account.as_account_view();
+        assert!(!is_delegated(&metadata));
+        assert_eq!(
+            DelegationMetadataView::from_account(&metadata, &delegated).err(),
+            Some(ProgramError::IllegalOwner)
+        );
+
+        let mut account = AccountWithData::new(address, DELEGATION_PROGRAM_ID, &data);
+        let metadata = account.as_account_view();
+        let view = DelegationMetadataView::from_account(&metadata, &delegated).unwrap();
+        assert_eq!(view.last_update_nonce(), 7);
+        assert!(view.is_undelegatable());
+        assert_eq!(view.seeds().collect::<Vec<_>>(), seeds);
+        assert_eq!(view.rent_payer(), Address::new_from_array([4; 32]));
+        drop(view);
+
+        // the rent payer is missing
+        let mut account =
+            AccountWithData::new(address, DELEGATION_PROGRAM_ID, &data[..data.len() - 1]);
+        assert_eq!(
+            DelegationMetadataView::from_account(&account.as_account_view(), &delegated).err(),
+            Some(ProgramError::InvalidAccountData)
+        );
+    }
+}
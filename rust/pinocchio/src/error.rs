@@ -0,0 +1,124 @@
+//! Custom error codes shared with the sdk crate.
+//!
+//! Every code below is surfaced as `ProgramError::Custom(code)` and matches the code of the
+//! `EphemeralRollupsSdkError` variant of the same name, so that clients can decode the
+//! failures of pinocchio programs with the sdk.
+
+/// First custom error code reserved for the SDK errors
+pub const SDK_ERROR_CODE_START: u32 = 0xEB_01_00_00;
+
+/// The intent references more unique accounts than can be indexed in a single instruction
+pub const TOO_MANY_ACCOUNTS_ERROR: u32 = SDK_ERROR_CODE_START + 0x05;
+/// The delegated account doesn't match the PDA derived from the provided seeds and bump
+pub const INVALID_DELEGATED_ACCOUNT_ERROR: u32 = SDK_ERROR_CODE_START + 0x09;
+/// The buffer doesn't match the PDA derived from the delegated account
+pub const INVALID_BUFFER_ERROR: u32 = SDK_ERROR_CODE_START + 0x0A;
+/// The delegation record doesn't match the PDA derived from the delegated account
+pub const INVALID_DELEGATION_RECORD_ERROR: u32 = SDK_ERROR_CODE_START + 0x0B;
+/// The delegation metadata doesn't match the PDA derived from the delegated account
+pub const INVALID_DELEGATION_METADATA_ERROR: u32 = SDK_ERROR_CODE_START + 0x0C;
+/// The same account is listed more than once in the delegation batch
+pub const DUPLICATE_DELEGATED_ACCOUNT_ERROR: u32 = SDK_ERROR_CODE_START + 0x0E;
+/// The escrow hasn't signed the action
+pub const ESCROW_NOT_SIGNER_ERROR: u32 = SDK_ERROR_CODE_START + 0x10;
+/// The escrow doesn't match the PDA derived from the escrow authority and index
+pub const INVALID_ESCROW_ERROR: u32 = SDK_ERROR_CODE_START + 0x12;
+/// The payer is delegated, but the magic fee vault hasn't been provided
+pub const MISSING_MAGIC_FEE_VAULT_ERROR: u32 = SDK_ERROR_CODE_START + 0x13;
+/// The intent bundle has more actions than `MAX_INTENT_BUNDLE_ACTIONS`
+pub const TOO_MANY_ACTIONS_ERROR: u32 = SDK_ERROR_CODE_START + 0x16;
+/// The serialized intent bundle exceeds the CPI instruction data limit
+pub const INTENT_DATA_TOO_LARGE_ERROR: u32 = SDK_ERROR_CODE_START + 0x17;
+/// The action callback data isn't a valid response of the magic program
+pub const INVALID_MAGIC_RESPONSE_ERROR: u32 = SDK_ERROR_CODE_START + 0x18;
+/// The action callback has already been processed
+pub const CALLBACK_REPLAYED_ERROR: u32 = SDK_ERROR_CODE_START + 0x19;
+/// An account to commit and undelegate hasn't been passed as writable
+pub const COMMITTED_ACCOUNT_NOT_WRITABLE_ERROR: u32 = SDK_ERROR_CODE_START + 0x1A;
+/// An account to commit isn't owned by the program committing it, as delegated accounts are on
+/// the ER
+pub const COMMITTED_ACCOUNT_NOT_DELEGATED_ERROR: u32 = SDK_ERROR_CODE_START + 0x1B;
+/// More seeds have been provided than a PDA can be derived from
+pub const TOO_MANY_SEEDS_ERROR: u32 = SDK_ERROR_CODE_START + 0x22;
+/// The delegated or undelegated account is larger than can be re-created within the
+/// undelegation callback
+pub const UNDELEGATED_ACCOUNT_TOO_LARGE_ERROR: u32 = SDK_ERROR_CODE_START + 0x23;
+/// The action callback hasn't been signed by the callback signer of the validator
+pub const INVALID_CALLBACK_SIGNER_ERROR: u32 = SDK_ERROR_CODE_START + 0x24;
+/// The callback sequence account is too small or already borrowed
+pub const INVALID_CALLBACK_SEQUENCE_ERROR: u32 = SDK_ERROR_CODE_START + 0x25;
+/// The magic context isn't `MAGIC_CONTEXT_ID`
+pub const INVALID_MAGIC_CONTEXT_ERROR: u32 = SDK_ERROR_CODE_START + 0x26;
+/// The committed accounts of a scheduled commit aren't the ones it has been registered with
+pub const INVALID_COMMITTED_ACCOUNTS_ERROR: u32 = SDK_ERROR_CODE_START + 0x27;
+
+#[cfg(test)]
+mod tests {
+    use ephemeral_rollups_sdk::error::{self, EphemeralRollupsSdkError as SdkError};
+
+    use super::*;
+
+    #[test]
+    fn test_codes_match_the_sdk() {
+        assert_eq!(SDK_ERROR_CODE_START, error::SDK_ERROR_CODE_START);
+        let pairs = [
+            (TOO_MANY_ACCOUNTS_ERROR, SdkError::TooManyAccounts),
+            (
+                INVALID_DELEGATED_ACCOUNT_ERROR,
+                SdkError::InvalidDelegatedAccount,
+            ),
+            (INVALID_BUFFER_ERROR, SdkError::InvalidBuffer),
+            (
+                INVALID_DELEGATION_RECORD_ERROR,
+                SdkError::InvalidDelegationRecord,
+            ),
+            (
+                INVALID_DELEGATION_METADATA_ERROR,
+                SdkError::InvalidDelegationMetadata,
+            ),
+            (
+                DUPLICATE_DELEGATED_ACCOUNT_ERROR,
+                SdkError::DuplicateDelegatedAccount,
+            ),
+            (ESCROW_NOT_SIGNER_ERROR, SdkError::EscrowNotSigner),
+            (INVALID_ESCROW_ERROR, SdkError::InvalidEscrow),
+            (
+                MISSING_MAGIC_FEE_VAULT_ERROR,
+                SdkError::MissingMagicFeeVault,
+            ),
+            (TOO_MANY_ACTIONS_ERROR, SdkError::TooManyActions),
+            (INTENT_DATA_TOO_LARGE_ERROR, SdkError::IntentDataTooLarge),
+            (INVALID_MAGIC_RESPONSE_ERROR, SdkError::InvalidMagicResponse),
+            (CALLBACK_REPLAYED_ERROR, SdkError::CallbackReplayed),
+            (
+                COMMITTED_ACCOUNT_NOT_WRITABLE_ERROR,
+                SdkError::CommittedAccountNotWritable,
+            ),
+            (
+                COMMITTED_ACCOUNT_NOT_DELEGATED_ERROR,
+                SdkError::CommittedAccountNotDelegated,
+            ),
+            (TOO_MANY_SEEDS_ERROR, SdkError::TooManySeeds),
+            (
+                UNDELEGATED_ACCOUNT_TOO_LARGE_ERROR,
+                SdkError::UndelegatedAccountTooLarge,
+            ),
+            (
+                INVALID_CALLBACK_SIGNER_ERROR,
+                SdkError::InvalidCallbackSigner,
+            ),
+            (
+                INVALID_CALLBACK_SEQUENCE_ERROR,
+                SdkError::InvalidCallbackSequence,
+            ),
+            (INVALID_MAGIC_CONTEXT_ERROR, SdkError::InvalidMagicContext),
+            (
+                INVALID_COMMITTED_ACCOUNTS_ERROR,
+                SdkError::InvalidCommittedAccounts,
+            ),
+        ];
+        for (code, err) in pairs {
+            assert_eq!(code, err.code(), "{err:?}");
+        }
+    }
+}
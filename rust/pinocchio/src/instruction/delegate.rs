@@ -1,4 +1,5 @@
 use pinocchio::{
+    account::MAX_PERMITTED_DATA_INCREASE,
     cpi::{Seed, Signer},
     error::ProgramError,
     AccountView, Address, ProgramResult,
@@ -6,15 +7,23 @@ use pinocchio::{
 use pinocchio_system::instructions::{Assign, CreateAccount};
 
 use crate::consts::DELEGATION_PROGRAM_ID;
-use crate::pda::find_program_address;
+use crate::pda::{
+    create_program_address, delegation_metadata_pda_from_delegated_account,
+    delegation_record_pda_from_delegated_account, find_program_address,
+};
 use crate::types::DelegateAccountArgs;
 use crate::utils::{cpi_delegate, cpi_delegate_with_any_validator, make_seed_buf};
 use crate::{consts::BUFFER, types::DelegateConfig, utils::close_pda_acc};
 
-/// Find the bump for a buffer PDA using the pinocchio PDA derivation.
-fn find_buffer_pda_bump(pda_key: &[u8], owner_program: &Address) -> u8 {
-    let (_, bump) = find_program_address(&[BUFFER, pda_key], owner_program);
-    bump
+pub use crate::error::{
+    DUPLICATE_DELEGATED_ACCOUNT_ERROR, INVALID_BUFFER_ERROR, INVALID_DELEGATED_ACCOUNT_ERROR,
+    INVALID_DELEGATION_METADATA_ERROR, INVALID_DELEGATION_RECORD_ERROR, TOO_MANY_SEEDS_ERROR,
+    UNDELEGATED_ACCOUNT_TOO_LARGE_ERROR,
+};
+
+/// Find the buffer PDA and its bump using the pinocchio PDA derivation.
+fn find_buffer_pda(pda_key: &[u8], owner_program: &Address) -> (Address, u8) {
+    find_program_address(&[BUFFER, pda_key], owner_program)
 }
 
 #[allow(unknown_lints, clippy::cloned_ref_to_slice_refs)]
@@ -24,7 +33,7 @@ pub fn delegate_account(
     bump: u8,
     config: DelegateConfig,
 ) -> ProgramResult {
-    delegate_account_inner(accounts, seeds, bump, config, false)
+    delegate_account_inner(accounts, seeds, bump, None, config, false, false)
 }
 
 #[allow(unknown_lints, clippy::cloned_ref_to_slice_refs)]
@@ -34,7 +43,7 @@ pub fn delegate_account_with_any_validator(
     bump: u8,
     config: DelegateConfig,
 ) -> ProgramResult {
-    delegate_account_inner(accounts, seeds, bump, config, true)
+    delegate_account_inner(accounts, seeds, bump, None, config, true, false)
 }
 
 #[allow(unknown_lints, clippy::cloned_ref_to_slice_refs)]
@@ -42,8 +51,10 @@ fn delegate_account_inner(
     accounts: &[&AccountView],
     seeds: &[&[u8]],
     bump: u8,
+    buffer_bump: Option<u8>,
     config: DelegateConfig,
     any_validator: bool,
+    verify: bool,
 ) -> ProgramResult {
     let [payer, pda_acc, owner_program, buffer_acc, delegation_record, delegation_metadata, system_program] =
         accounts
@@ -58,8 +69,27 @@ fn delegate_account_inner(
     // Buffer PDA seeds
     let pda_key_bytes: &[u8; 32] = pda_acc.address().as_array();
 
-    // Find buffer PDA bump
-    let buffer_pda_bump = find_buffer_pda_bump(pda_key_bytes.as_ref(), owner_program.address());
+    // Find buffer PDA bump, a known one implies the accounts have been verified by the caller
+    let buffer_pda_bump = match buffer_bump {
+        Some(bump) => bump,
+        None => {
+            let (buffer_pda, buffer_pda_bump) =
+                find_buffer_pda(pda_key_bytes.as_ref(), owner_program.address());
+            if verify {
+                verify_delegate_accounts(
+                    pda_acc,
+                    owner_program.address(),
+                    buffer_acc,
+                    &buffer_pda,
+                    delegation_record,
+                    delegation_metadata,
+                    seeds,
+                    bump,
+                )?;
+            }
+            buffer_pda_bump
+        }
+    };
 
     // Buffer signer seeds
     let buffer_bump_slice = [buffer_pda_bump];
@@ -71,6 +101,7 @@ fn delegate_account_inner(
     let buffer_signer_seeds = Signer::from(&buffer_seed_binding);
 
     // Single data_len and rent lookup
+    verify_restorable(pda_acc)?;
     let data_len = pda_acc.data_len();
 
     // Create Buffer PDA
@@ -164,6 +195,7 @@ pub struct DelegateAccountCpiBuilder<'a> {
     seeds: Option<&'a [&'a [u8]]>,
     bump: Option<u8>,
     config: Option<DelegateConfig>,
+    skip_address_checks: bool,
 }
 
 impl<'a> DelegateAccountCpiBuilder<'a> {
@@ -187,6 +219,7 @@ impl<'a> DelegateAccountCpiBuilder<'a> {
             seeds: None,
             bump: None,
             config: None,
+            skip_address_checks: false,
         }
     }
 
@@ -205,6 +238,13 @@ impl<'a> DelegateAccountCpiBuilder<'a> {
         self
     }
 
+    /// By default the PDA, buffer, delegation record and metadata addresses are verified
+    /// before delegating, skipping the checks saves the compute units spent on derivations.
+    pub fn skip_address_checks(mut self) -> Self {
+        self.skip_address_checks = true;
+        self
+    }
+
     pub fn invoke(self) -> ProgramResult {
         self.invoke_inner(false)
     }
@@ -229,12 +269,172 @@ impl<'a> DelegateAccountCpiBuilder<'a> {
             self.delegation_metadata,
             self.system_program,
         ];
-        if any_validator {
-            delegate_account_with_any_validator(&accounts, seeds, bump, config)
-        } else {
-            delegate_account(&accounts, seeds, bump, config)
+        delegate_account_inner(
+            &accounts,
+            seeds,
+            bump,
+            None,
+            config,
+            any_validator,
+            !self.skip_address_checks,
+        )
+    }
+}
+
+/// Account to delegate as part of [`delegate_accounts_batch`]
+pub struct DelegateItem<'a> {
+    pub pda_acc: &'a AccountView,
+    pub buffer_acc: &'a AccountView,
+    pub delegation_record: &'a AccountView,
+    pub delegation_metadata: &'a AccountView,
+    pub seeds: &'a [&'a [u8]],
+    pub bump: u8,
+    pub config: DelegateConfig,
+}
+
+/// Failure of [`delegate_accounts_batch`], with the result of each item, in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DelegateBatchError<const N: usize> {
+    pub results: [Result<(), ProgramError>; N],
+}
+
+impl<const N: usize> DelegateBatchError<N> {
+    /// Index and error of the items which failed
+    pub fn errors(&self) -> impl Iterator<Item = (usize, &ProgramError)> {
+        self.results
+            .iter()
+            .enumerate()
+            .filter_map(|(index, result)| result.as_ref().err().map(|error| (index, error)))
+    }
+}
+
+impl<const N: usize> From<DelegateBatchError<N>> for ProgramError {
+    fn from(value: DelegateBatchError<N>) -> Self {
+        value
+            .results
+            .into_iter()
+            .find_map(Result::err)
+            .unwrap_or(ProgramError::InvalidArgument)
+    }
+}
+
+/// Delegate multiple PDAs of the calling program at once, without allocations.
+///
+/// All of the items are validated before any account is mutated: duplicates are rejected and the
+/// addresses are verified as in [`DelegateAccountCpiBuilder`]. The buffer bumps derived during
+/// validation are reused for the delegation itself.
+///
+/// If any item is invalid, nothing is delegated and the error holds the result of every item.
+/// If the delegation of an item fails, the following items aren't delegated either, and the
+/// error holds the failure of that item along with the validation result of the others.
+pub fn delegate_accounts_batch<const N: usize>(
+    payer: &AccountView,
+    owner_program: &AccountView,
+    system_program: &AccountView,
+    items: &[DelegateItem; N],
+) -> Result<(), DelegateBatchError<N>> {
+    let mut results: [Result<(), ProgramError>; N] = core::array::from_fn(|_| Ok(()));
+    let mut buffer_bumps = [0u8; N];
+    for (index, item) in items.iter().enumerate() {
+        if items[..index]
+            .iter()
+            .any(|other| other.pda_acc.address() == item.pda_acc.address())
+        {
+            results[index] = Err(ProgramError::Custom(DUPLICATE_DELEGATED_ACCOUNT_ERROR));
+            continue;
+        }
+
+        let (buffer_pda, buffer_bump) =
+            find_buffer_pda(item.pda_acc.address().as_ref(), owner_program.address());
+        results[index] = verify_delegate_accounts(
+            item.pda_acc,
+            owner_program.address(),
+            item.buffer_acc,
+            &buffer_pda,
+            item.delegation_record,
+            item.delegation_metadata,
+            item.seeds,
+            item.bump,
+        )
+        .and_then(|()| verify_restorable(item.pda_acc));
+        buffer_bumps[index] = buffer_bump;
+    }
+    if results.iter().any(Result::is_err) {
+        return Err(DelegateBatchError { results });
+    }
+
+    for (index, (item, buffer_bump)) in items.iter().zip(buffer_bumps).enumerate() {
+        let accounts = [
+            payer,
+            item.pda_acc,
+            owner_program,
+            item.buffer_acc,
+            item.delegation_record,
+            item.delegation_metadata,
+            system_program,
+        ];
+        if let Err(error) = delegate_account_inner(
+            &accounts,
+            item.seeds,
+            item.bump,
+            Some(buffer_bump),
+            item.config.clone(),
+            false,
+            false,
+        ) {
+            results[index] = Err(error);
+            return Err(DelegateBatchError { results });
         }
     }
+    Ok(())
+}
+
+/// Rejects accounts that the undelegation callback couldn't re-create, as the runtime bounds
+/// the growth of an account within an instruction to `MAX_PERMITTED_DATA_INCREASE`.
+fn verify_restorable(pda_acc: &AccountView) -> ProgramResult {
+    if pda_acc.data_len() > MAX_PERMITTED_DATA_INCREASE {
+        return Err(ProgramError::Custom(UNDELEGATED_ACCOUNT_TOO_LARGE_ERROR));
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn verify_delegate_accounts(
+    pda_acc: &AccountView,
+    owner_program: &Address,
+    buffer_acc: &AccountView,
+    buffer_pda: &Address,
+    delegation_record: &AccountView,
+    delegation_metadata: &AccountView,
+    seeds: &[&[u8]],
+    bump: u8,
+) -> ProgramResult {
+    if seeds.len() > 15 {
+        return Err(ProgramError::Custom(TOO_MANY_SEEDS_ERROR));
+    }
+    let mut seeds_with_bump: [&[u8]; 16] = [&[]; 16];
+    seeds_with_bump[..seeds.len()].copy_from_slice(seeds);
+    let bump_slice = [bump];
+    seeds_with_bump[seeds.len()] = &bump_slice;
+
+    let pda = create_program_address(&seeds_with_bump[..=seeds.len()], owner_program);
+    if pda.as_ref() != Some(pda_acc.address()) {
+        return Err(ProgramError::Custom(INVALID_DELEGATED_ACCOUNT_ERROR));
+    }
+    if buffer_acc.address() != buffer_pda {
+        return Err(ProgramError::Custom(INVALID_BUFFER_ERROR));
+    }
+    if delegation_record.address()
+        != &delegation_record_pda_from_delegated_account(pda_acc.address())
+    {
+        return Err(ProgramError::Custom(INVALID_DELEGATION_RECORD_ERROR));
+    }
+    if delegation_metadata.address()
+        != &delegation_metadata_pda_from_delegated_account(pda_acc.address())
+    {
+        return Err(ProgramError::Custom(INVALID_DELEGATION_METADATA_ERROR));
+    }
+    Ok(())
 }
 
 pub fn fill_seeds<'a>(
@@ -255,3 +455,187 @@ pub fn fill_seeds<'a>(
 
     &out[..=i]
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use ephemeral_rollups_sdk::error::EphemeralRollupsSdkError;
+    use pinocchio::account::RuntimeAccount;
+
+    use super::*;
+
+    fn runtime_account(address: Address) -> RuntimeAccount {
+        RuntimeAccount {
+            borrow_state: 0,
+            is_signer: 0,
+            is_writable: 1,
+            executable: 0,
+            resize_delta: 0,
+            address,
+            owner: Address::new_from_array([0; 32]),
+            lamports: 0,
+            data_len: 0,
+        }
+    }
+
+    fn verify(
+        addresses: [Address; 4],
+        owner_program: &Address,
+        seeds: &[&[u8]],
+        bump: u8,
+    ) -> ProgramResult {
+        let mut accounts = addresses.map(runtime_account);
+        let [pda, buffer, record, metadata] = accounts
+            .each_mut()
+            .map(|acc| unsafe { AccountView::new_unchecked(acc) });
+        let (buffer_pda, _) = find_buffer_pda(pda.address().as_ref(), owner_program);
+        verify_delegate_accounts(
+            &pda,
+            owner_program,
+            &buffer,
+            &buffer_pda,
+            &record,
+            &metadata,
+            seeds,
+            bump,
+        )
+    }
+
+    #[test]
+    fn test_verify_delegate_accounts() {
+        let owner_program = Address::new_from_array([7; 32]);
+        let seeds: &[&[u8]] = &[b"counter", &[1, 2, 3]];
+        let (pda, bump) = find_program_address(seeds, &owner_program);
+        let mut addresses = [
+            pda,
+            find_buffer_pda(pda.as_ref(), &owner_program).0,
+            delegation_record_pda_from_delegated_account(&pda),
+            delegation_metadata_pda_from_delegated_account(&pda),
+        ];
+        assert_eq!(verify(addresses, &owner_program, seeds, bump), Ok(()));
+        assert_eq!(
+            verify(addresses, &owner_program, seeds, bump.wrapping_sub(1)),
+            Err(ProgramError::Custom(INVALID_DELEGATED_ACCOUNT_ERROR))
+        );
+
+        let expected = [
+            EphemeralRollupsSdkError::InvalidDelegatedAccount,
+            EphemeralRollupsSdkError::InvalidBuffer,
+            EphemeralRollupsSdkError::InvalidDelegationRecord,
+            EphemeralRollupsSdkError::InvalidDelegationMetadata,
+        ];
+        for (i, err) in expected.into_iter().enumerate() {
+            let valid = addresses[i];
+            addresses[i] = Address::new_from_array([9; 32]);
+            assert_eq!(
+                verify(addresses, &owner_program, seeds, bump),
+                Err(ProgramError::Custom(err.code()))
+            );
+            addresses[i] = valid;
+        }
+
+        let too_many_seeds: [&[u8]; 16] = [b"seed"; 16];
+        assert_eq!(
+            verify(addresses, &owner_program, &too_many_seeds, bump),
+            Err(ProgramError::Custom(TOO_MANY_SEEDS_ERROR))
+        );
+    }
+
+    #[test]
+    fn test_verify_restorable() {
+        let mut account = runtime_account(Address::new_from_array([1; 32]));
+        account.data_len = MAX_PERMITTED_DATA_INCREASE as u64;
+        let view = unsafe { AccountView::new_unchecked(&mut account) };
+        assert_eq!(verify_restorable(&view), Ok(()));
+
+        account.data_len += 1;
+        let view = unsafe { AccountView::new_unchecked(&mut account) };
+        assert_eq!(
+            verify_restorable(&view),
+            Err(ProgramError::Custom(UNDELEGATED_ACCOUNT_TOO_LARGE_ERROR))
+        );
+    }
+
+    #[test]
+    fn test_delegate_accounts_batch_validation() {
+        let owner_program = Address::new_from_array([7; 32]);
+        let seeds: [&[&[u8]]; 2] = [&[b"player"], &[b"stats"]];
+        let mut addresses = seeds.map(|seeds| {
+            let (pda, bump) = find_program_address(seeds, &owner_program);
+            let accounts = [
+                pda,
+                find_buffer_pda(pda.as_ref(), &owner_program).0,
+                delegation_record_pda_from_delegated_account(&pda),
+                delegation_metadata_pda_from_delegated_account(&pda),
+            ];
+            (accounts, bump)
+        });
+
+        fn batch<const N: usize>(
+            owner_program: Address,
+            addresses: [([Address; 4], u8); N],
+            seeds: [&[&[u8]]; N],
+        ) -> Result<(), DelegateBatchError<N>> {
+            let mut shared = runtime_account(owner_program);
+            let shared = unsafe { AccountView::new_unchecked(&mut shared) };
+            let mut accounts = addresses.map(|(addresses, _)| addresses.map(runtime_account));
+            let views = accounts.each_mut().map(|accounts| {
+                accounts
+                    .each_mut()
+                    .map(|acc| unsafe { AccountView::new_unchecked(acc) })
+            });
+            let mut index = 0;
+            let items = [(); N].map(|_| {
+                let item = DelegateItem {
+                    pda_acc: &views[index][0],
+                    buffer_acc: &views[index][1],
+                    delegation_record: &views[index][2],
+                    delegation_metadata: &views[index][3],
+                    seeds: seeds[index],
+                    bump: addresses[index].1,
+                    config: DelegateConfig::default(),
+                };
+                index += 1;
+                item
+            });
+            delegate_accounts_batch(&shared, &shared, &shared, &items)
+        }
+
+        assert_eq!(
+            batch(
+                owner_program,
+                [addresses[0], addresses[1], addresses[0]],
+                [seeds[0], seeds[1], seeds[0]]
+            ),
+            Err(DelegateBatchError {
+                results: [
+                    Ok(()),
+                    Ok(()),
+                    Err(ProgramError::Custom(
+                        EphemeralRollupsSdkError::DuplicateDelegatedAccount.code()
+                    )),
+                ],
+            })
+        );
+
+        // every invalid item is reported, not only the first one
+        addresses[0].0[3] = Address::new_from_array([9; 32]);
+        addresses[1].0[2] = Address::new_from_array([9; 32]);
+        let error = batch(owner_program, addresses, seeds).unwrap_err();
+        assert_eq!(
+            error.results,
+            [
+                Err(ProgramError::Custom(INVALID_DELEGATION_METADATA_ERROR)),
+                Err(ProgramError::Custom(INVALID_DELEGATION_RECORD_ERROR)),
+            ]
+        );
+        assert_eq!(
+            error.errors().map(|(index, _)| index).collect::<Vec<_>>(),
+            [0, 1]
+        );
+        assert_eq!(
+            ProgramError::from(error),
+            ProgramError::Custom(INVALID_DELEGATION_METADATA_ERROR)
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use pinocchio::{
+    cpi::{invoke_signed, Signer},
+    error::ProgramError,
+    instruction::{InstructionAccount, InstructionView},
+    AccountView, ProgramResult,
+};
+
+use crate::{
+    consts::DELEGATION_PROGRAM_ID,
+    types::{DelegateAccountArgs, DelegateConfig, MAX_DELEGATE_ACCOUNT_ARGS_SIZE},
+};
+
+const TOP_UP_EPHEMERAL_BALANCE_DISCRIMINATOR: u64 = 9;
+const DELEGATE_EPHEMERAL_BALANCE_DISCRIMINATOR: u64 = 10;
+const CLOSE_EPHEMERAL_BALANCE_DISCRIMINATOR: u64 = 11;
+
+/// Top up the ephemeral balance (escrow) of the authority at the given index.
+///
+/// Accounts: `[payer, escrow_authority, ephemeral_balance, system_program]`
+pub fn top_up_ephemeral_balance(
+    accounts: &[&AccountView],
+    amount: u64,
+    index: u8,
+    signers: &[Signer<'_, '_>],
+) -> ProgramResult {
+    let [payer, escrow_authority, ephemeral_balance, system_program] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    let ix_accounts = [
+        InstructionAccount::writable_signer(payer.address()),
+        InstructionAccount::readonly(escrow_authority.address()),
+        InstructionAccount::writable(ephemeral_balance.address()),
+        InstructionAccount::readonly(&pinocchio_system::ID),
+    ];
+    let data = top_up_ephemeral_balance_data(amount, index);
+
+    let instruction = InstructionView {
+        program_id: &DELEGATION_PROGRAM_ID,
+        accounts: &ix_accounts,
+        data: &data,
+    };
+    invoke_signed(
+        &instruction,
+        &[payer, escrow_authority, ephemeral_balance, system_program],
+        signers,
+    )
+}
+
+/// Delegate the ephemeral balance (escrow) of the authority at the given index.
+/// Both payer and escrow authority must sign, if the authority is a PDA of the
+/// calling program, pass its seeds via `signers`.
+///
+/// Accounts: `[payer, escrow_authority, ephemeral_balance, buffer, delegation_record,
+/// delegation_metadata, system_program, delegation_program]`
+pub fn delegate_ephemeral_balance(
+    accounts: &[&AccountView],
+    index: u8,
+    config: DelegateConfig,
+    signers: &[Signer<'_, '_>],
+) -> ProgramResult {
+    let [payer, escrow_authority, ephemeral_balance, buffer, delegation_record, delegation_metadata, system_program, delegation_program] =
+        accounts
+    else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    let ix_accounts = [
+        InstructionAccount::writable_signer(payer.address()),
+        InstructionAccount::readonly_signer(escrow_authority.address()),
+        InstructionAccount::writable(ephemeral_balance.address()),
+        InstructionAccount::writable(buffer.address()),
+        InstructionAccount::writable(delegation_record.address()),
+        InstructionAccount::writable(delegation_metadata.address()),
+        InstructionAccount::readonly(&pinocchio_system::ID),
+        InstructionAccount::readonly(&DELEGATION_PROGRAM_ID),
+    ];
+
+    let mut data = [0u8; 8 + MAX_DELEGATE_ACCOUNT_ARGS_SIZE + 1];
+    let data_len = delegate_ephemeral_balance_data(&mut data, index, config)?;
+
+    let instruction = InstructionView {
+        program_id: &DELEGATION_PROGRAM_ID,
+        accounts: &ix_accounts,
+        data: &data[..data_len],
+    };
+    invoke_signed(
+        &instruction,
+        &[
+            payer,
+            escrow_authority,
+            ephemeral_balance,
+            buffer,
+            delegation_record,
+            delegation_metadata,
+            system_program,
+            delegation_program,
+        ],
+        signers,
+    )
+}
+
+/// Close the (undelegated) ephemeral balance (escrow) of the authority at the given index,
+/// the remaining lamports are returned to the authority.
+///
+/// Accounts: `[escrow_authority, ephemeral_balance, system_program]`
+pub fn close_ephemeral_balance(
+    accounts: &[&AccountView],
+    index: u8,
+    signers: &[Signer<'_, '_>],
+) -> ProgramResult {
+    let [escrow_authority, ephemeral_balance, system_program] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    let ix_accounts = [
+        InstructionAccount::writable_signer(escrow_authority.address()),
+        InstructionAccount::writable(ephemeral_balance.address()),
+        InstructionAccount::readonly(&pinocchio_system::ID),
+    ];
+    let data = close_ephemeral_balance_data(index);
+
+    let instruction = InstructionView {
+        program_id: &DELEGATION_PROGRAM_ID,
+        accounts: &ix_accounts,
+        data: &data,
+    };
+    invoke_signed(
+        &instruction,
+        &[escrow_authority, ephemeral_balance, system_program],
+        signers,
+    )
+}
+
+fn top_up_ephemeral_balance_data(amount: u64, index: u8) -> [u8; 17] {
+    let mut data = [0u8; 17];
+    data[..8].copy_from_slice(&TOP_UP_EPHEMERAL_BALANCE_DISCRIMINATOR.to_le_bytes());
+    data[8..16].copy_from_slice(&amount.to_le_bytes());
+    data[16] = index;
+    data
+}
+
+fn delegate_ephemeral_balance_data(
+    data: &mut [u8; 8 + MAX_DELEGATE_ACCOUNT_ARGS_SIZE + 1],
+    index: u8,
+    config: DelegateConfig,
+) -> Result<usize, ProgramError> {
+    data[..8].copy_from_slice(&DELEGATE_EPHEMERAL_BALANCE_DISCRIMINATOR.to_le_bytes());
+    // the seeds are derived by the delegation program from authority and index
+    let args = DelegateAccountArgs {
+        commit_frequency_ms: config.commit_frequency_ms,
+        seeds: &[],
+        validator: config.validator,
+    };
+    let args_len = args
+        .try_to_slice(&mut data[8..8 + MAX_DELEGATE_ACCOUNT_ARGS_SIZE])?
+        .len();
+    data[8 + args_len] = index;
+    Ok(8 + args_len + 1)
+}
+
+fn close_ephemeral_balance_data(index: u8) -> [u8; 9] {
+    let mut data = [0u8; 9];
+    data[..8].copy_from_slice(&CLOSE_EPHEMERAL_BALANCE_DISCRIMINATOR.to_le_bytes());
+    data[8] = index;
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use ephemeral_rollups_sdk::{cpi::DelegateConfig as SdkDelegateConfig, ephemeral_balance};
+    use pinocchio::Address;
+    use solana_program::pubkey::Pubkey;
+
+    use super::*;
+
+    #[test]
+    fn test_top_up_and_close_data_match_sdk() {
+        let authority = Pubkey::new_from_array([2; 32]);
+        let sdk_ix =
+            ephemeral_balance::top_up_ephemeral_balance(Pubkey::default(), authority, 42, 7);
+        assert_eq!(top_up_ephemeral_balance_data(42, 7).as_slice(), sdk_ix.data);
+
+        let sdk_ix = ephemeral_balance::close_ephemeral_balance(authority, 7);
+        assert_eq!(close_ephemeral_balance_data(7).as_slice(), sdk_ix.data);
+    }
+
+    #[test]
+    fn test_delegate_data_matches_sdk() {
+        let validator = [3; 32];
+        let sdk_ix = ephemeral_balance::delegate_ephemeral_balance(
+            Pubkey::default(),
+            Pubkey::new_from_array([2; 32]),
+            5,
+            SdkDelegateConfig {
+                commit_frequency_ms: 1_000,
+                validator: Some(Pubkey::new_from_array(validator)),
+            },
+        );
+
+        let mut data = [0u8; 8 + MAX_DELEGATE_ACCOUNT_ARGS_SIZE + 1];
+        let len = delegate_ephemeral_balance_data(
+            &mut data,
+            5,
+            DelegateConfig {
+                commit_frequency_ms: 1_000,
+                validator: Some(Address::new_from_array(validator)),
+            },
+        )
+        .unwrap();
+        assert_eq!(&data[..len], sdk_ix.data.as_slice());
+    }
+}
@@ -3,6 +3,8 @@ pub mod commit_and_undelegate;
 pub mod delegate;
 #[cfg(feature = "delegation-actions")]
 pub mod delegate_with_actions;
+pub mod ephemeral_balance;
+pub mod schedule_commit;
 pub mod undelegate;
 
 pub use commit::*;
@@ -10,4 +12,6 @@ pub use commit_and_undelegate::*;
 pub use delegate::*;
 #[cfg(feature = "delegation-actions")]
 pub use delegate_with_actions::*;
+pub use ephemeral_balance::*;
+pub use schedule_commit::*;
 pub use undelegate::*;
@@ -0,0 +1,171 @@
+use crate::instruction::commit::commit_accounts_internal;
+use pinocchio::{cpi::Signer, error::ProgramError, AccountView, Address, ProgramResult};
+
+pub use crate::error::{
+    COMMITTED_ACCOUNT_NOT_DELEGATED_ERROR, COMMITTED_ACCOUNT_NOT_WRITABLE_ERROR,
+};
+
+/// CPI to the `ScheduleCommit` (or `ScheduleCommitAndUndelegate`) instruction of the magic
+/// program, validating the committed accounts before invoking, see [`Self::validate`].
+///
+/// The payer can be a PDA signing via [`Self::invoke_signed`].
+pub struct ScheduleCommitCpi<'a> {
+    program_id: &'a Address,
+    payer: &'a AccountView,
+    magic_context: &'a AccountView,
+    magic_program: &'a AccountView,
+    accounts: &'a [AccountView],
+    magic_fee_vault: Option<&'a AccountView>,
+    undelegate: bool,
+}
+
+impl<'a> ScheduleCommitCpi<'a> {
+    /// `program_id` is the program invoking the magic program, i.e. the owner of the
+    /// committed accounts on the ER
+    #[inline(always)]
+    pub const fn new(
+        program_id: &'a Address,
+        payer: &'a AccountView,
+        magic_context: &'a AccountView,
+        magic_program: &'a AccountView,
+        accounts: &'a [AccountView],
+    ) -> Self {
+        Self {
+            program_id,
+            payer,
+            magic_context,
+            magic_program,
+            accounts,
+            magic_fee_vault: None,
+            undelegate: false,
+        }
+    }
+
+    /// Sets the magic fee vault, required when the payer is delegated
+    #[inline(always)]
+    pub const fn magic_fee_vault(mut self, vault: &'a AccountView) -> Self {
+        self.magic_fee_vault = Some(vault);
+        self
+    }
+
+    /// Undelegates the accounts once committed
+    #[inline(always)]
+    pub const fn undelegate(mut self) -> Self {
+        self.undelegate = true;
+        self
+    }
+
+    /// Checks that every committed account is owned by `program_id`, as the magic program
+    /// only commits the accounts of the program invoking it, and that it's writable when
+    /// undelegating, as the magic program rejects read-only accounts to undelegate.
+    ///
+    /// On the ER, a delegated account is owned by its program rather than by the delegation
+    /// program, and the delegation of the payer can't be told, so it's up to the caller to
+    /// pass the magic fee vault for a delegated payer.
+    pub fn validate(&self) -> ProgramResult {
+        for account in self.accounts {
+            if !account.owned_by(self.program_id) {
+                return Err(ProgramError::Custom(COMMITTED_ACCOUNT_NOT_DELEGATED_ERROR));
+            }
+            if self.undelegate && !account.is_writable() {
+                return Err(ProgramError::Custom(COMMITTED_ACCOUNT_NOT_WRITABLE_ERROR));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn invoke(&self) -> ProgramResult {
+        self.invoke_signed(&[])
+    }
+
+    /// Validates the accounts, then invokes the magic program signed with the seeds of the
+    /// PDA payer
+    pub fn invoke_signed(&self, signers_seeds: &[Signer<'_, '_>]) -> ProgramResult {
+        self.validate()?;
+        commit_accounts_internal(
+            self.payer,
+            self.accounts,
+            self.magic_context,
+            self.magic_program,
+            self.magic_fee_vault,
+            self.undelegate,
+            Some(signers_seeds),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use alloc::vec::Vec;
+    use pinocchio::account::{RuntimeAccount, NOT_BORROWED};
+
+    use super::*;
+
+    fn runtime_account(address: u8, owner: Address, is_writable: u8) -> RuntimeAccount {
+        RuntimeAccount {
+            borrow_state: NOT_BORROWED,
+            is_signer: 0,
+            is_writable,
+            executable: 0,
+            resize_delta: 0,
+            address: Address::new_from_array([address; 32]),
+            owner,
+            lamports: 0,
+            data_len: 0,
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let program_id = Address::new_from_array([9; 32]);
+        let delegation_program = crate::consts::DELEGATION_PROGRAM_ID;
+        let mut runtime_accounts = [
+            // a delegated payer is owned by its program on the ER
+            runtime_account(1, program_id, 1),
+            runtime_account(2, Address::new_from_array([0; 32]), 1),
+            runtime_account(3, Address::new_from_array([0; 32]), 0),
+            runtime_account(4, program_id, 1),
+            runtime_account(5, program_id, 0),
+            runtime_account(6, delegation_program, 1),
+            runtime_account(7, Address::new_from_array([8; 32]), 1),
+        ];
+        let accounts: Vec<AccountView> = runtime_accounts
+            .iter_mut()
+            .map(|account| unsafe { AccountView::new_unchecked(account as *mut RuntimeAccount) })
+            .collect();
+        let [payer, magic_context, magic_program, committed @ ..] = accounts.as_slice() else {
+            unreachable!()
+        };
+        let cpi = |accounts| {
+            ScheduleCommitCpi::new(&program_id, payer, magic_context, magic_program, accounts)
+        };
+
+        // neither the vault nor writable accounts are required to commit
+        assert_eq!(cpi(&committed[..2]).validate(), Ok(()));
+        assert_eq!(cpi(&committed[..1]).undelegate().validate(), Ok(()));
+
+        // the accounts to undelegate must be writable
+        assert_eq!(
+            cpi(&committed[..2]).undelegate().validate(),
+            Err(ProgramError::Custom(COMMITTED_ACCOUNT_NOT_WRITABLE_ERROR))
+        );
+        assert_eq!(
+            cpi(&committed[1..2]).undelegate().invoke(),
+            Err(ProgramError::Custom(COMMITTED_ACCOUNT_NOT_WRITABLE_ERROR))
+        );
+
+        // the committed accounts must be owned by the program invoking the magic program
+        for i in [2, 3] {
+            assert_eq!(
+                cpi(&committed[..=i]).validate(),
+                Err(ProgramError::Custom(COMMITTED_ACCOUNT_NOT_DELEGATED_ERROR))
+            );
+            assert_eq!(
+                cpi(&committed[i..=i]).invoke(),
+                Err(ProgramError::Custom(COMMITTED_ACCOUNT_NOT_DELEGATED_ERROR))
+            );
+        }
+    }
+}
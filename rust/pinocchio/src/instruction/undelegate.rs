@@ -1,5 +1,6 @@
 use core::mem::MaybeUninit;
 use pinocchio::{
+    account::MAX_PERMITTED_DATA_INCREASE,
     address::MAX_SEEDS,
     cpi::{Seed, Signer},
     error::ProgramError,
@@ -8,6 +9,7 @@ use pinocchio::{
 };
 use pinocchio_system::instructions::CreateAccount;
 
+use crate::error::UNDELEGATED_ACCOUNT_TOO_LARGE_ERROR;
 use crate::pda::find_program_address;
 
 #[inline(always)]
@@ -16,8 +18,32 @@ pub fn undelegate(
     owner_program: &Address,
     buffer: &AccountView,
     payer: &AccountView,
-    mut callback_args: &[u8],
+    callback_args: &[u8],
 ) -> ProgramResult {
+    undelegate_with_hook(
+        delegated_account,
+        owner_program,
+        buffer,
+        payer,
+        callback_args,
+        |_, _| Ok(()),
+    )
+}
+
+/// Same as [`undelegate`], but invokes `on_undelegate` with the restored account and its
+/// seeds once the data has been copied back, e.g. to update the state derived from it.
+#[inline(always)]
+pub fn undelegate_with_hook<F>(
+    delegated_account: &AccountView,
+    owner_program: &Address,
+    buffer: &AccountView,
+    payer: &AccountView,
+    mut callback_args: &[u8],
+    on_undelegate: F,
+) -> ProgramResult
+where
+    F: FnOnce(&AccountView, &[&[u8]]) -> ProgramResult,
+{
     if !buffer.is_signer() {
         return Err(ProgramError::MissingRequiredSignature);
     }
@@ -74,7 +100,11 @@ pub fn undelegate(
     let seeds = unsafe { core::slice::from_raw_parts(seeds_ptr, count) };
     let signer = Signer::from(seeds);
 
-    // create delegated account and copy buffer data
+    // create delegated account and copy buffer data, the runtime bounds the growth of an
+    // account within the callback instruction to `MAX_PERMITTED_DATA_INCREASE`
+    if buffer.data_len() > MAX_PERMITTED_DATA_INCREASE {
+        return Err(ProgramError::Custom(UNDELEGATED_ACCOUNT_TOO_LARGE_ERROR));
+    }
     let space = buffer.data_len() as u64;
     let lamports = Rent::get()?.try_minimum_balance(space as usize)?;
 
@@ -87,9 +117,133 @@ pub fn undelegate(
     }
     .invoke_signed(&[signer])?;
 
-    let mut data = delegated_account.try_borrow_mut()?;
-    let buffer_data = buffer.try_borrow()?;
-    (*data).copy_from_slice(&buffer_data);
+    restore_with_hook(delegated_account, buffer, pda_seeds, on_undelegate)
+}
+
+/// Copies the buffer data into the re-created account, then invokes the hook. An error of
+/// the hook fails the callback, which reverts the undelegation as a whole.
+#[inline(always)]
+fn restore_with_hook<F>(
+    delegated_account: &AccountView,
+    buffer: &AccountView,
+    pda_seeds: &[&[u8]],
+    on_undelegate: F,
+) -> ProgramResult
+where
+    F: FnOnce(&AccountView, &[&[u8]]) -> ProgramResult,
+{
+    {
+        let mut data = delegated_account.try_borrow_mut()?;
+        let buffer_data = buffer.try_borrow()?;
+        (*data).copy_from_slice(&buffer_data);
+    }
+
+    on_undelegate(delegated_account, pda_seeds)
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use pinocchio::account::{RuntimeAccount, NOT_BORROWED};
+
+    use super::*;
+
+    /// Runtime account followed by its data, as laid out in the input of the program
+    #[repr(C)]
+    struct AccountWithData<const N: usize> {
+        account: RuntimeAccount,
+        data: [u8; N],
+    }
+
+    impl<const N: usize> AccountWithData<N> {
+        fn new(is_signer: bool, data: [u8; N]) -> Self {
+            Self {
+                account: RuntimeAccount {
+                    borrow_state: NOT_BORROWED,
+                    is_signer: is_signer as u8,
+                    is_writable: 1,
+                    executable: 0,
+                    resize_delta: 0,
+                    address: Address::new_from_array([1; 32]),
+                    owner: Address::new_from_array([0; 32]),
+                    lamports: 0,
+                    data_len: N as u64,
+                },
+                data,
+            }
+        }
+
+        fn view(&mut self) -> AccountView {
+            unsafe { AccountView::new_unchecked(&mut self.account) }
+        }
+    }
+
+    #[test]
+    fn test_hook_runs_after_restore() {
+        let mut account = AccountWithData::new(false, [0; 4]);
+        let mut buffer = AccountWithData::new(true, [1, 2, 3, 4]);
+        let (account, buffer) = (account.view(), buffer.view());
+        let seeds: &[&[u8]] = &[b"counter"];
+
+        let called = Cell::new(false);
+        let result = restore_with_hook(&account, &buffer, seeds, |restored, hook_seeds| {
+            assert_eq!(&*restored.try_borrow()?, &[1, 2, 3, 4]);
+            assert_eq!(hook_seeds, seeds);
+            called.set(true);
+            Ok(())
+        });
+        assert_eq!(result, Ok(()));
+        assert!(called.get());
+    }
+
+    #[test]
+    fn test_hook_error_aborts_undelegation() {
+        let mut account = AccountWithData::new(false, [0; 4]);
+        let mut buffer = AccountWithData::new(true, [1, 2, 3, 4]);
+        let (account, buffer) = (account.view(), buffer.view());
+
+        let result = restore_with_hook(&account, &buffer, &[b"counter"], |_, _| {
+            Err(ProgramError::Custom(42))
+        });
+        assert_eq!(result, Err(ProgramError::Custom(42)));
+    }
+
+    #[test]
+    fn test_hook_skipped_on_invalid_callback() {
+        let mut account = AccountWithData::new(false, [0; 4]);
+        let mut buffer = AccountWithData::new(false, [1, 2, 3, 4]);
+        let mut payer = AccountWithData::new(true, []);
+        let (account, buffer, payer) = (account.view(), buffer.view(), payer.view());
+        let owner_program = Address::new_from_array([7; 32]);
+        let mut args = 1u32.to_le_bytes().to_vec();
+        args.extend(7u32.to_le_bytes());
+        args.extend(b"counter");
+
+        let called = Cell::new(false);
+        let hook = |_: &AccountView, _: &[&[u8]]| {
+            called.set(true);
+            Ok(())
+        };
+        // the buffer must sign the callback
+        assert_eq!(
+            undelegate_with_hook(&account, &owner_program, &buffer, &payer, &args, hook),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        // trailing callback data
+        let mut buffer = AccountWithData::new(true, [1, 2, 3, 4]);
+        args.push(0);
+        assert_eq!(
+            undelegate_with_hook(
+                &account,
+                &owner_program,
+                &buffer.view(),
+                &payer,
+                &args,
+                hook
+            ),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        assert!(!called.get());
+        assert_eq!(&*account.try_borrow().unwrap(), &[0; 4]);
+    }
 }
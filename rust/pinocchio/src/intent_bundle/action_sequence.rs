@@ -0,0 +1,49 @@
+use pinocchio::error::ProgramError;
+
+use crate::intent_bundle::no_vec::NoVec;
+use crate::intent_bundle::types::CallHandler;
+use crate::intent_bundle::CAPACITY_EXCEEDED_ERROR;
+
+/// Standalone actions executed in the order of the `then_action` calls, holding up to `N`
+/// actions, see [`MagicIntentBundleBuilder::add_action_sequence`].
+///
+/// The sequence is always scheduled whole by a single `ScheduleIntentBundle` CPI, whose
+/// standalone actions are executed in order. [`build_chunked_and_invoke`] fails if it doesn't
+/// fit in a single CPI.
+///
+/// Only the order is guaranteed: no failure policy is encoded in the intent, so whether the
+/// following actions are executed once one fails is decided by the validator. An action
+/// depending on the outcome of a previous one has to check it itself, e.g. in a callback.
+///
+/// [`MagicIntentBundleBuilder::add_action_sequence`]: crate::intent_bundle::MagicIntentBundleBuilder::add_action_sequence
+/// [`build_chunked_and_invoke`]: crate::intent_bundle::MagicIntentBundleBuilder::build_chunked_and_invoke
+pub struct ActionSequence<'args, const N: usize> {
+    actions: NoVec<CallHandler<'args>, N>,
+}
+
+impl<'args, const N: usize> Default for ActionSequence<'args, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'args, const N: usize> ActionSequence<'args, N> {
+    pub fn new() -> Self {
+        Self {
+            actions: NoVec::new(),
+        }
+    }
+
+    /// Appends an action executed after the previous ones, fails with
+    /// [`CAPACITY_EXCEEDED_ERROR`] if the sequence already holds `N` actions.
+    pub fn then_action(mut self, action: CallHandler<'args>) -> Result<Self, ProgramError> {
+        self.actions
+            .try_push(action)
+            .map_err(|_| ProgramError::Custom(CAPACITY_EXCEEDED_ERROR))?;
+        Ok(self)
+    }
+
+    pub fn actions(&self) -> &[CallHandler<'args>] {
+        self.actions.as_slice()
+    }
+}
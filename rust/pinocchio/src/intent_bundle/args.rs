@@ -1,5 +1,5 @@
 use crate::intent_bundle::no_vec::NoVec;
-use crate::intent_bundle::MAX_ACTIONS_NUM;
+use crate::intent_bundle::MAX_INTENT_BUNDLE_ACTIONS;
 use bincode::enc::Encoder;
 use bincode::error::EncodeError;
 use pinocchio::cpi::MAX_STATIC_CPI_ACCOUNTS;
@@ -95,7 +95,7 @@ pub enum CommitTypeArgs<'args> {
     Standalone(NoVec<u8, MAX_STATIC_CPI_ACCOUNTS>),
     WithBaseActions {
         committed_accounts: NoVec<u8, MAX_STATIC_CPI_ACCOUNTS>,
-        base_actions: NoVec<BaseActionArgs<'args>, MAX_ACTIONS_NUM>,
+        base_actions: NoVec<BaseActionArgs<'args>, MAX_INTENT_BUNDLE_ACTIONS>,
     },
 }
 
@@ -105,7 +105,7 @@ pub enum CommitTypeArgs<'args> {
 pub enum UndelegateTypeArgs<'args> {
     Standalone,
     WithBaseActions {
-        base_actions: NoVec<BaseActionArgs<'args>, MAX_ACTIONS_NUM>,
+        base_actions: NoVec<BaseActionArgs<'args>, MAX_INTENT_BUNDLE_ACTIONS>,
     },
 }
 
@@ -122,11 +122,9 @@ pub struct CommitAndUndelegateArgs<'args> {
 pub struct MagicIntentBundleArgs<'args> {
     pub commit: Option<CommitTypeArgs<'args>>,
     pub commit_and_undelegate: Option<CommitAndUndelegateArgs<'args>>,
-    /// Not yet implemented; always `None`. Reserved for wire-format compatibility.
-    pub commit_finalize: Option<()>,
-    /// Not yet implemented; always `None`. Reserved for wire-format compatibility.
-    pub commit_finalize_and_undelegate: Option<()>,
-    pub standalone_actions: NoVec<BaseActionArgs<'args>, MAX_ACTIONS_NUM>,
+    pub commit_finalize: Option<CommitTypeArgs<'args>>,
+    pub commit_finalize_and_undelegate: Option<CommitAndUndelegateArgs<'args>>,
+    pub standalone_actions: NoVec<BaseActionArgs<'args>, MAX_INTENT_BUNDLE_ACTIONS>,
 }
 
 #[cfg(test)]
@@ -262,7 +260,7 @@ mod tests {
         for i in &indices {
             pino_indices.push(*i);
         }
-        let mut pino_base_actions = NoVec::<BaseActionArgs, MAX_ACTIONS_NUM>::new();
+        let mut pino_base_actions = NoVec::<BaseActionArgs, MAX_INTENT_BUNDLE_ACTIONS>::new();
         pino_base_actions.push(BaseActionArgs {
             args: ActionArgs {
                 escrow_index: 10,
@@ -324,7 +322,7 @@ mod tests {
         };
         let sdk_with_actions_bytes = bincode1::serialize(&sdk_with_actions).unwrap();
 
-        let mut pino_base_actions = NoVec::<BaseActionArgs, MAX_ACTIONS_NUM>::new();
+        let mut pino_base_actions = NoVec::<BaseActionArgs, MAX_INTENT_BUNDLE_ACTIONS>::new();
         pino_base_actions.push(BaseActionArgs {
             args: ActionArgs {
                 escrow_index: 255,
@@ -401,7 +399,7 @@ mod tests {
             is_writable: true,
         }];
 
-        let mut pino_standalone = NoVec::<BaseActionArgs, MAX_ACTIONS_NUM>::new();
+        let mut pino_standalone = NoVec::<BaseActionArgs, MAX_INTENT_BUNDLE_ACTIONS>::new();
         pino_standalone.push(BaseActionArgs {
             args: ActionArgs {
                 escrow_index: 0,
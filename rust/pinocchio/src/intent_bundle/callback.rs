@@ -0,0 +1,228 @@
+//! Helpers for the base-layer handlers of action callbacks.
+//!
+//! The validator invokes the callback through its callback executor, which signs with
+//! [`CALLBACK_SIGNER`], see `CallbackInstruction::ExecuteCallback` and `pda::CALLBACK_SIGNER`
+//! in `magicblock-magic-program-api`. The other accounts of the callback are the ones set
+//! in `ActionCallback::accounts` by the scheduling program.
+
+use pinocchio::error::ProgramError;
+use pinocchio::{AccountView, ProgramResult};
+
+use crate::consts::CALLBACK_SIGNER;
+use crate::error::INVALID_ESCROW_ERROR;
+use crate::intent_bundle::escrow::escrow_pda;
+
+pub use crate::error::{
+    CALLBACK_REPLAYED_ERROR, INVALID_CALLBACK_SEQUENCE_ERROR, INVALID_CALLBACK_SIGNER_ERROR,
+    INVALID_MAGIC_RESPONSE_ERROR,
+};
+
+/// Length of the sequence prefixing the payload of callbacks guarded against replays,
+/// see [`ActionCallbackResponse::reject_replay`].
+pub const CALLBACK_SEQUENCE_LEN: usize = 8;
+
+/// Seed of the callback sequence account of an escrow authority, a PDA of the program
+/// handling the callbacks derived from `[CALLBACK_SEQUENCE_SEED, escrow_authority]`, see
+/// [`reject_callback_replay`].
+pub const CALLBACK_SEQUENCE_SEED: &[u8] = b"callback-sequence";
+
+const SIGNATURE_LEN: usize = 64;
+
+/// Verifies that the callback has been signed by [`CALLBACK_SIGNER`], i.e. that it has been
+/// issued by the validator, and that `escrow` is derived from `escrow_authority` and
+/// `escrow_index`, i.e. that the callback is the one of an action paid from that escrow.
+pub fn verify_action_callback(
+    callback_signer: &AccountView,
+    escrow_authority: &AccountView,
+    escrow: &AccountView,
+    escrow_index: u8,
+) -> ProgramResult {
+    if !callback_signer.is_signer() || callback_signer.address() != &CALLBACK_SIGNER {
+        return Err(ProgramError::Custom(INVALID_CALLBACK_SIGNER_ERROR));
+    }
+    if escrow.address() != &escrow_pda(escrow_authority.address(), escrow_index) {
+        return Err(ProgramError::Custom(INVALID_ESCROW_ERROR));
+    }
+    Ok(())
+}
+
+/// Rejects the callback if its sequence has already been accepted by the callback sequence
+/// account, and records it otherwise, see [`CallbackSequence`].
+///
+/// The account must be owned by the program, for the runtime to let it write the sequence, and
+/// hold at least [`CallbackSequence::LEN`] bytes, zeroed when created.
+pub fn reject_callback_replay(
+    callback_sequence: &AccountView,
+    response: &ActionCallbackResponse,
+) -> ProgramResult {
+    let invalid_sequence = || ProgramError::Custom(INVALID_CALLBACK_SEQUENCE_ERROR);
+    let mut data = callback_sequence
+        .try_borrow_mut()
+        .map_err(|_| invalid_sequence())?;
+    let state: &mut [u8; CallbackSequence::LEN] = data
+        .get_mut(..CallbackSequence::LEN)
+        .and_then(|state| state.try_into().ok())
+        .ok_or_else(invalid_sequence)?;
+    let mut sequence = CallbackSequence::from_bytes(state);
+    response.reject_replay(&mut sequence)?;
+    *state = sequence.to_bytes();
+    Ok(())
+}
+
+/// Sequences of the callbacks accepted so far, as a sliding window: any sequence not accepted
+/// yet is accepted, whatever the order the callbacks are executed in, unless it is
+/// [`Self::WINDOW`] or more below the highest one accepted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CallbackSequence {
+    /// Highest sequence accepted
+    pub highest: u64,
+    /// Sequences accepted up to `highest`, bit `n` standing for `highest - n`
+    pub window: u64,
+}
+
+impl CallbackSequence {
+    /// Length of the encoded sequence, little-endian `highest` followed by `window`
+    pub const LEN: usize = 16;
+    /// Number of sequences up to the highest one tracked by the window
+    pub const WINDOW: u64 = u64::BITS as u64;
+
+    /// Records `sequence`, `false` if it has already been accepted or is out of the window.
+    pub fn accept(&mut self, sequence: u64) -> bool {
+        if sequence > self.highest {
+            let shift = sequence - self.highest;
+            let window = if shift < Self::WINDOW {
+                self.window << shift
+            } else {
+                0
+            };
+            self.window = window | 1;
+            self.highest = sequence;
+            return true;
+        }
+        let offset = self.highest - sequence;
+        if offset >= Self::WINDOW || self.window & (1 << offset) != 0 {
+            return false;
+        }
+        self.window |= 1 << offset;
+        true
+    }
+
+    pub fn from_bytes(bytes: &[u8; Self::LEN]) -> Self {
+        let (highest, window) = bytes.split_at(8);
+        Self {
+            highest: u64::from_le_bytes(highest.try_into().unwrap()),
+            window: u64::from_le_bytes(window.try_into().unwrap()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::LEN] {
+        let mut bytes = [0; Self::LEN];
+        bytes[..8].copy_from_slice(&self.highest.to_le_bytes());
+        bytes[8..].copy_from_slice(&self.window.to_le_bytes());
+        bytes
+    }
+}
+
+/// Outcome of an action, borrowed from the bincode-encoded `MagicResponse` following the
+/// discriminator in the callback instruction data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActionCallbackResponse<'a> {
+    /// `Ok` if the action succeeded, the reason of the failure otherwise
+    pub result: Result<(), &'a str>,
+    /// Payload of the callback, present even if the action failed
+    pub data: &'a [u8],
+    /// Signature of the action transaction, if available
+    pub signature: Option<&'a [u8; SIGNATURE_LEN]>,
+}
+
+impl<'a> ActionCallbackResponse<'a> {
+    /// Decodes the response following the discriminator in the callback instruction data.
+    pub fn decode(data: &'a [u8]) -> Result<Self, ProgramError> {
+        let mut reader = Reader(data);
+        // `MagicResponse::V1`
+        if reader.u32()? != 0 {
+            return Err(invalid_response());
+        }
+        let ok = match reader.u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err(invalid_response()),
+        };
+        let payload = reader.bytes()?;
+        let error = core::str::from_utf8(reader.bytes()?).map_err(|_| invalid_response())?;
+        let signature = match reader.u8()? {
+            0 => None,
+            1 => Some(reader.take_array::<SIGNATURE_LEN>()?),
+            _ => return Err(invalid_response()),
+        };
+        Ok(Self {
+            result: if ok { Ok(()) } else { Err(error) },
+            data: payload,
+            signature,
+        })
+    }
+
+    /// Whether the action succeeded.
+    pub fn is_ok(&self) -> bool {
+        self.result.is_ok()
+    }
+
+    /// Payload following the sequence, see [`Self::reject_replay`].
+    pub fn sequenced_payload(&self) -> Result<&'a [u8], ProgramError> {
+        self.data
+            .get(CALLBACK_SEQUENCE_LEN..)
+            .ok_or_else(invalid_response)
+    }
+
+    /// Sequence prefixing the payload, as a little-endian `u64`.
+    pub fn sequence(&self) -> Option<u64> {
+        let sequence = self.data.get(..CALLBACK_SEQUENCE_LEN)?;
+        Some(u64::from_le_bytes(sequence.try_into().ok()?))
+    }
+
+    /// Rejects a callback that has already been processed. The payload of the callback
+    /// must start with a sequence unique to the callback, `sequence` being the ones accepted
+    /// so far stored by the program, which are updated.
+    pub fn reject_replay(&self, sequence: &mut CallbackSequence) -> ProgramResult {
+        let callback_sequence = self.sequence().ok_or_else(invalid_response)?;
+        if !sequence.accept(callback_sequence) {
+            return Err(ProgramError::Custom(CALLBACK_REPLAYED_ERROR));
+        }
+        Ok(())
+    }
+}
+
+fn invalid_response() -> ProgramError {
+    ProgramError::Custom(INVALID_MAGIC_RESPONSE_ERROR)
+}
+
+/// Reader of the bincode (legacy, fixed-int) encoding used by the magic program.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ProgramError> {
+        if self.0.len() < len {
+            return Err(invalid_response());
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<&'a [u8; N], ProgramError> {
+        self.take(N)?.try_into().map_err(|_| invalid_response())
+    }
+
+    fn u8(&mut self) -> Result<u8, ProgramError> {
+        Ok(self.take_array::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, ProgramError> {
+        Ok(u32::from_le_bytes(*self.take_array()?))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], ProgramError> {
+        let len = u64::from_le_bytes(*self.take_array()?);
+        self.take(usize::try_from(len).map_err(|_| invalid_response())?)
+    }
+}
@@ -3,11 +3,17 @@ use crate::intent_bundle::types::MagicIntentBundle;
 use crate::intent_bundle::{CallHandler, CommitIntent, MagicIntentBundleBuilder};
 use pinocchio::{AccountView, ProgramResult};
 
-/// Builder of Commit Intent.
+/// Builder of Commit and CommitFinalize Intents.
 ///
-/// Created via [`MagicIntentBundleBuilder::commit()`]. Owns the parent builder
+/// Created via [`MagicIntentBundleBuilder::commit()`] or
+/// [`MagicIntentBundleBuilder::commit_finalize()`]. Owns the parent builder
 /// and returns it (or a sibling sub-builder) on every transition/terminal call.
 ///
+/// The intent only references the committed accounts, their state is read by the validator
+/// when it commits them. The encoding of the commit on the base layer, e.g. the byte-range
+/// diff of the delegation program `CommitDiff` instruction, which is signed by the validator,
+/// can't be chosen by the intent.
+///
 /// - `'acc`  – lifetime of the `&[AccountView]` slice passed to `.commit()`
 /// - `'args` – lifetime of `&[CallHandler]` action slices and their payload data
 /// - `T`     – typestate: tracks whether post-commit actions have been set
@@ -15,6 +21,7 @@ pub struct CommitIntentBuilder<'acc, 'args, T> {
     parent: MagicIntentBundleBuilder<'acc, 'args>,
     accounts: &'acc [AccountView],
     actions: T,
+    finalize: bool,
 }
 
 impl<'acc, 'args> CommitIntentBuilder<'acc, 'args, &'static [CallHandler<'static>]> {
@@ -29,6 +36,22 @@ impl<'acc, 'args> CommitIntentBuilder<'acc, 'args, &'static [CallHandler<'static
             parent,
             accounts,
             actions: &[],
+            finalize: false,
+        }
+    }
+
+    /// Same as [`Self::new`], but builds a CommitFinalize intent which commits and
+    /// finalizes the accounts on the base layer in a single step.
+    pub fn new_finalize<'new_acc>(
+        parent: MagicIntentBundleBuilder<'acc, 'args>,
+        accounts: &'new_acc [AccountView],
+    ) -> CommitIntentBuilder<'new_acc, 'args, &'static [CallHandler<'static>]>
+    where
+        'acc: 'new_acc,
+    {
+        CommitIntentBuilder {
+            finalize: true,
+            ..Self::new(parent, accounts)
         }
     }
 
@@ -40,52 +63,39 @@ impl<'acc, 'args> CommitIntentBuilder<'acc, 'args, &'static [CallHandler<'static
     where
         'args: 'new_args,
     {
-        let MagicIntentBundle {
-            standalone_actions,
-            commit_intent: _,
-            commit_and_undelegate_intent,
-        } = self.parent.intent_bundle;
-
-        MagicIntentBundleBuilder {
-            payer: self.parent.payer,
-            magic_context: self.parent.magic_context,
-            magic_program: self.parent.magic_program,
-            magic_fee_vault: self.parent.magic_fee_vault,
-            intent_bundle: MagicIntentBundle {
-                standalone_actions,
-                commit_intent: Some(CommitIntent {
-                    accounts: self.accounts,
-                    actions,
-                }),
-                commit_and_undelegate_intent,
+        fold_intent(
+            self.parent,
+            CommitIntent {
+                accounts: self.accounts,
+                actions,
             },
-        }
+            self.finalize,
+        )
     }
 }
 
 impl<'acc, 'args> CommitIntentBuilder<'acc, 'args, &'args [CallHandler<'args>]> {
     /// Finalizes this commit intent and folds it into the parent bundle.
     pub fn fold(self) -> MagicIntentBundleBuilder<'acc, 'args> {
-        let MagicIntentBundle {
-            standalone_actions,
-            commit_intent: _,
-            commit_and_undelegate_intent,
-        } = self.parent.intent_bundle;
-        let commit_intent = Some(CommitIntent {
-            accounts: self.accounts,
-            actions: self.actions,
-        });
-        MagicIntentBundleBuilder {
-            payer: self.parent.payer,
-            magic_context: self.parent.magic_context,
-            magic_program: self.parent.magic_program,
-            magic_fee_vault: self.parent.magic_fee_vault,
-            intent_bundle: MagicIntentBundle {
-                standalone_actions,
-                commit_intent,
-                commit_and_undelegate_intent,
+        fold_intent(
+            self.parent,
+            CommitIntent {
+                accounts: self.accounts,
+                actions: self.actions,
             },
-        }
+            self.finalize,
+        )
+    }
+
+    /// Transition: finalizes this commit intent and starts a commit intent.
+    pub fn commit<'new_acc>(
+        self,
+        accounts: &'new_acc [AccountView],
+    ) -> CommitIntentBuilder<'new_acc, 'args, &'static [CallHandler<'static>]>
+    where
+        'acc: 'new_acc,
+    {
+        self.fold().commit(accounts)
     }
 
     /// Transition: finalizes this commit intent and starts a commit-and-undelegate intent.
@@ -104,6 +114,34 @@ impl<'acc, 'args> CommitIntentBuilder<'acc, 'args, &'args [CallHandler<'args>]>
         self.fold().commit_and_undelegate(accounts)
     }
 
+    /// Transition: finalizes this commit intent and starts a commit-finalize intent.
+    pub fn commit_finalize<'new_acc>(
+        self,
+        accounts: &'new_acc [AccountView],
+    ) -> CommitIntentBuilder<'new_acc, 'args, &'static [CallHandler<'static>]>
+    where
+        'acc: 'new_acc,
+    {
+        self.fold().commit_finalize(accounts)
+    }
+
+    /// Transition: finalizes this commit intent and starts a commit-finalize-and-undelegate
+    /// intent.
+    pub fn commit_finalize_and_undelegate<'new_acc>(
+        self,
+        accounts: &'new_acc [AccountView],
+    ) -> CommitAndUndelegateIntentBuilder<
+        'new_acc,
+        'args,
+        &'static [CallHandler<'static>],
+        &'static [CallHandler<'static>],
+    >
+    where
+        'acc: 'new_acc,
+    {
+        self.fold().commit_finalize_and_undelegate(accounts)
+    }
+
     /// Transition: finalizes this commit intent and adds standalone base-layer actions.
     pub fn set_standalone_actions<'new_args>(
         self,
@@ -120,3 +158,48 @@ impl<'acc, 'args> CommitIntentBuilder<'acc, 'args, &'args [CallHandler<'args>]>
         self.fold().build_and_invoke(data_buf)
     }
 }
+
+/// Puts the intent into the commit or the commit-finalize slot of the parent bundle.
+fn fold_intent<'acc, 'args>(
+    parent: MagicIntentBundleBuilder<'acc, 'args>,
+    intent: CommitIntent<'acc, 'args>,
+    finalize: bool,
+) -> MagicIntentBundleBuilder<'acc, 'args> {
+    let MagicIntentBundleBuilder {
+        payer,
+        magic_context,
+        magic_program,
+        magic_fee_vault,
+        payer_is_delegated,
+        intent_bundle,
+    } = parent;
+    let MagicIntentBundle {
+        standalone_actions,
+        standalone_sequence,
+        mut commit_intent,
+        commit_and_undelegate_intent,
+        mut commit_finalize_intent,
+        commit_finalize_and_undelegate_intent,
+    } = intent_bundle;
+
+    if finalize {
+        commit_finalize_intent = Some(intent);
+    } else {
+        commit_intent = Some(intent);
+    }
+    MagicIntentBundleBuilder {
+        payer,
+        magic_context,
+        magic_program,
+        magic_fee_vault,
+        payer_is_delegated,
+        intent_bundle: MagicIntentBundle {
+            standalone_actions,
+            standalone_sequence,
+            commit_intent,
+            commit_and_undelegate_intent,
+            commit_finalize_intent,
+            commit_finalize_and_undelegate_intent,
+        },
+    }
+}
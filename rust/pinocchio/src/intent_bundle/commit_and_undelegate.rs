@@ -5,10 +5,11 @@ use crate::intent_bundle::{
     CallHandler, CommitAndUndelegateIntent, CommitIntentBuilder, MagicIntentBundleBuilder,
 };
 
-/// Builder of CommitAndUndelegate Intent.
+/// Builder of CommitAndUndelegate and CommitFinalizeAndUndelegate Intents.
 ///
-/// Created via [`MagicIntentBundleBuilder::commit_and_undelegate()`] or
-/// [`CommitIntentBuilder::commit_and_undelegate()`]. Owns the parent builder
+/// Created via [`MagicIntentBundleBuilder::commit_and_undelegate()`],
+/// [`MagicIntentBundleBuilder::commit_finalize_and_undelegate()`] or the matching
+/// [`CommitIntentBuilder`] transitions. Owns the parent builder
 /// and returns it (or a sibling sub-builder) on every transition/terminal call.
 ///
 /// - `'acc`  – lifetime of the `&[AccountView]` slice passed to `.commit_and_undelegate()`
@@ -20,6 +21,7 @@ pub struct CommitAndUndelegateIntentBuilder<'acc, 'args, S1, S2> {
     accounts: &'acc [AccountView],
     post_commit_actions: S1,
     post_undelegate_actions: S2,
+    finalize: bool,
 }
 
 /// Builder without actions
@@ -48,6 +50,27 @@ impl<'acc, 'args>
             accounts,
             post_commit_actions: &[],
             post_undelegate_actions: &[],
+            finalize: false,
+        }
+    }
+
+    /// Same as [`Self::new`], but builds a CommitFinalizeAndUndelegate intent which commits
+    /// and finalizes the accounts on the base layer in a single step before undelegating them.
+    pub fn new_finalize<'new_acc>(
+        parent: MagicIntentBundleBuilder<'acc, 'args>,
+        accounts: &'new_acc [AccountView],
+    ) -> CommitAndUndelegateIntentBuilder<
+        'new_acc,
+        'args,
+        &'static [CallHandler<'static>],
+        &'static [CallHandler<'static>],
+    >
+    where
+        'acc: 'new_acc,
+    {
+        CommitAndUndelegateIntentBuilder {
+            finalize: true,
+            ..Self::new(parent, accounts)
         }
     }
 }
@@ -69,6 +92,7 @@ impl<'acc, 'args, S2>
             accounts: self.accounts,
             post_commit_actions: actions,
             post_undelegate_actions: self.post_undelegate_actions,
+            finalize: self.finalize,
         }
     }
 }
@@ -90,6 +114,7 @@ impl<'acc, 'args, T1>
             accounts: self.accounts,
             post_commit_actions: self.post_commit_actions,
             post_undelegate_actions: actions,
+            finalize: self.finalize,
         }
     }
 }
@@ -113,6 +138,52 @@ impl<'acc, 'args>
         self.fold().commit(accounts)
     }
 
+    /// Transition: finalizes this commit-and-undelegate intent and starts a
+    /// commit-and-undelegate intent.
+    pub fn commit_and_undelegate<'new_acc>(
+        self,
+        accounts: &'new_acc [AccountView],
+    ) -> CommitAndUndelegateIntentBuilder<
+        'new_acc,
+        'args,
+        &'static [CallHandler<'static>],
+        &'static [CallHandler<'static>],
+    >
+    where
+        'acc: 'new_acc,
+    {
+        self.fold().commit_and_undelegate(accounts)
+    }
+
+    /// Transition: finalizes this commit-and-undelegate intent and starts a commit-finalize
+    /// intent.
+    pub fn commit_finalize<'new_acc>(
+        self,
+        accounts: &'new_acc [AccountView],
+    ) -> CommitIntentBuilder<'new_acc, 'args, &'static [CallHandler<'static>]>
+    where
+        'acc: 'new_acc,
+    {
+        self.fold().commit_finalize(accounts)
+    }
+
+    /// Transition: finalizes this commit-and-undelegate intent and starts a
+    /// commit-finalize-and-undelegate intent.
+    pub fn commit_finalize_and_undelegate<'new_acc>(
+        self,
+        accounts: &'new_acc [AccountView],
+    ) -> CommitAndUndelegateIntentBuilder<
+        'new_acc,
+        'args,
+        &'static [CallHandler<'static>],
+        &'static [CallHandler<'static>],
+    >
+    where
+        'acc: 'new_acc,
+    {
+        self.fold().commit_finalize_and_undelegate(accounts)
+    }
+
     /// Transition: finalizes this commit-and-undelegate intent and adds standalone base-layer actions.
     pub fn set_standalone_actions<'new_args>(
         self,
@@ -136,34 +207,48 @@ impl<'acc, 'args>
             post_commit_actions,
             post_undelegate_actions,
             parent,
+            finalize,
         } = self;
         let MagicIntentBundleBuilder {
             payer,
             magic_context,
             magic_program,
             magic_fee_vault,
+            payer_is_delegated,
             intent_bundle,
         } = parent;
         let MagicIntentBundle {
             standalone_actions,
+            standalone_sequence,
             commit_intent,
-            commit_and_undelegate_intent: _,
+            mut commit_and_undelegate_intent,
+            commit_finalize_intent,
+            mut commit_finalize_and_undelegate_intent,
         } = intent_bundle;
 
-        let commit_and_undelegate_intent = Some(CommitAndUndelegateIntent {
+        let intent = Some(CommitAndUndelegateIntent {
             accounts,
             post_commit_actions,
             post_undelegate_actions,
         });
+        if finalize {
+            commit_finalize_and_undelegate_intent = intent;
+        } else {
+            commit_and_undelegate_intent = intent;
+        }
         MagicIntentBundleBuilder {
             payer,
             magic_context,
             magic_program,
             magic_fee_vault,
+            payer_is_delegated,
             intent_bundle: MagicIntentBundle {
                 standalone_actions,
+                standalone_sequence,
                 commit_intent,
                 commit_and_undelegate_intent,
+                commit_finalize_intent,
+                commit_finalize_and_undelegate_intent,
             },
         }
     }
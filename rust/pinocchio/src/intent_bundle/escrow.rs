@@ -0,0 +1,29 @@
+use pinocchio::error::ProgramError;
+use pinocchio::{AccountView, ProgramResult};
+use solana_address::Address;
+
+use crate::pda::ephemeral_balance_pda_from_payer;
+
+pub use crate::error::{ESCROW_NOT_SIGNER_ERROR, INVALID_ESCROW_ERROR};
+
+/// Derive the escrow PDA of `escrow_authority` at `index`, signing the actions and callbacks
+/// paid from it.
+pub fn escrow_pda(escrow_authority: &Address, index: u8) -> Address {
+    ephemeral_balance_pda_from_payer(escrow_authority, index)
+}
+
+/// Verifies that `escrow` signs and is the escrow of `escrow_authority` at `escrow_index`,
+/// i.e. that the action or callback has been scheduled with the expected escrow authority.
+pub fn verify_escrow(
+    escrow: &AccountView,
+    escrow_authority: &AccountView,
+    escrow_index: u8,
+) -> ProgramResult {
+    if !escrow.is_signer() {
+        return Err(ProgramError::Custom(ESCROW_NOT_SIGNER_ERROR));
+    }
+    if escrow.address() != &escrow_pda(escrow_authority.address(), escrow_index) {
+        return Err(ProgramError::Custom(INVALID_ESCROW_ERROR));
+    }
+    Ok(())
+}
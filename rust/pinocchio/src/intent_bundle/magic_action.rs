@@ -0,0 +1,56 @@
+use crate::intent_bundle::{ActionArgs, CallHandler, ShortAccountMeta, CAPACITY_EXCEEDED_ERROR};
+use pinocchio::error::ProgramError;
+use pinocchio::AccountView;
+use solana_address::Address;
+
+/// Storage of a [`CallHandler`] built by a `#[derive(MagicAction)]` struct: the
+/// instruction data, up to `DATA` bytes, and the `ACCOUNTS` account metas.
+pub struct MagicActionBuf<const ACCOUNTS: usize, const DATA: usize> {
+    accounts: [ShortAccountMeta; ACCOUNTS],
+    data: [u8; DATA],
+}
+
+impl<const ACCOUNTS: usize, const DATA: usize> MagicActionBuf<ACCOUNTS, DATA> {
+    pub fn new() -> Self {
+        Self {
+            accounts: core::array::from_fn(|_| ShortAccountMeta::default()),
+            data: [0; DATA],
+        }
+    }
+
+    /// Writes `discriminator` followed by `args` and the account metas, then builds the
+    /// call handler borrowing them. Used by the derived `into_call_handler`.
+    #[doc(hidden)]
+    pub fn call_handler(
+        &mut self,
+        destination_program: Address,
+        discriminator: &[u8],
+        args: &[u8],
+        accounts: [ShortAccountMeta; ACCOUNTS],
+        escrow_authority: AccountView,
+        compute_units: u32,
+    ) -> Result<CallHandler<'_>, ProgramError> {
+        let len = discriminator.len() + args.len();
+        if len > DATA {
+            return Err(ProgramError::Custom(CAPACITY_EXCEEDED_ERROR));
+        }
+        self.data[..discriminator.len()].copy_from_slice(discriminator);
+        self.data[discriminator.len()..len].copy_from_slice(args);
+        self.accounts = accounts;
+
+        Ok(CallHandler {
+            destination_program,
+            escrow_authority,
+            args: ActionArgs::new(&self.data[..len]),
+            compute_units,
+            accounts: &self.accounts,
+            callback: None,
+        })
+    }
+}
+
+impl<const ACCOUNTS: usize, const DATA: usize> Default for MagicActionBuf<ACCOUNTS, DATA> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
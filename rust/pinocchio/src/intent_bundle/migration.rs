@@ -0,0 +1,147 @@
+use pinocchio::AccountView;
+use solana_address::Address;
+
+use crate::intent_bundle::{
+    ActionArgs, CallHandler, CommitAndUndelegateIntentBuilder, MagicIntentBundleBuilder,
+    ShortAccountMeta,
+};
+use crate::types::DelegateConfig;
+
+/// Max length of the re-delegate action data, see [`redelegate_action_data`]
+pub const MAX_REDELEGATE_ACTION_DATA_LEN: usize = 8 + 4 + 1 + 32;
+
+/// Migration of the delegated account to another validator, see
+/// [`MagicIntentBundleBuilder::migrate_to_validator`]. Same as in the sdk crate.
+pub struct ValidatorMigration<'a> {
+    /// Program owning the migrated account, which implements the re-delegate instruction
+    pub owner_program: Address,
+    /// Discriminator of the re-delegate instruction
+    pub discriminator: [u8; 8],
+    /// Index of the escrow of the migrated account, which signs the re-delegate action
+    pub escrow_index: u8,
+    /// Accounts of the re-delegate instruction
+    pub accounts: &'a [ShortAccountMeta],
+    /// Delegation config passed to the re-delegate instruction, with the target validator
+    pub config: DelegateConfig,
+    pub compute_units: u32,
+}
+
+/// Storage of the re-delegate action built by [`MagicIntentBundleBuilder::migrate_to_validator`]:
+/// the instruction data and the action itself, borrowed by the intent bundle.
+pub struct MigrationBuf<'a> {
+    data: [u8; MAX_REDELEGATE_ACTION_DATA_LEN],
+    action: Option<[CallHandler<'a>; 1]>,
+}
+
+impl MigrationBuf<'_> {
+    pub const fn new() -> Self {
+        Self {
+            data: [0; MAX_REDELEGATE_ACTION_DATA_LEN],
+            action: None,
+        }
+    }
+}
+
+impl Default for MigrationBuf<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Writes the instruction data of the re-delegate action into `buf`: the discriminator followed
+/// by the borsh encoded `commit_frequency_ms: u32` and `validator: Option<Pubkey>` arguments.
+/// Same as in the sdk crate.
+pub fn redelegate_action_data<'a>(
+    buf: &'a mut [u8; MAX_REDELEGATE_ACTION_DATA_LEN],
+    discriminator: &[u8; 8],
+    config: &DelegateConfig,
+) -> &'a [u8] {
+    buf[..8].copy_from_slice(discriminator);
+    buf[8..12].copy_from_slice(&config.commit_frequency_ms.to_le_bytes());
+    match config.validator {
+        Some(validator) => {
+            buf[12] = 1;
+            buf[13..].copy_from_slice(validator.as_ref());
+            &buf[..]
+        }
+        None => {
+            buf[12] = 0;
+            &buf[..13]
+        }
+    }
+}
+
+impl<'acc, 'args> MagicIntentBundleBuilder<'acc, 'args> {
+    /// Moves the delegated account to another validator in a single call: commits and
+    /// undelegates it, then re-delegates it via the post-undelegate action, which invokes the
+    /// re-delegate instruction of the owner program with the migration config. The action is
+    /// stored in `buf`.
+    ///
+    /// The migrated account is the escrow authority of the action, so the bundle has to be
+    /// invoked with its seeds via `build_and_invoke_signed`, and the re-delegate instruction must
+    /// verify the escrow signer on the base layer.
+    pub fn migrate_to_validator<'new_acc, 'new_args>(
+        self,
+        account: &'new_acc [AccountView; 1],
+        migration: ValidatorMigration<'new_args>,
+        buf: &'new_args mut MigrationBuf<'new_args>,
+    ) -> CommitAndUndelegateIntentBuilder<
+        'new_acc,
+        'new_args,
+        &'static [CallHandler<'static>],
+        &'new_args [CallHandler<'new_args>],
+    >
+    where
+        'acc: 'new_acc,
+        'args: 'new_args,
+    {
+        let MigrationBuf { data, action } = buf;
+        let data = redelegate_action_data(data, &migration.discriminator, &migration.config);
+        let redelegate = action.insert([CallHandler {
+            destination_program: migration.owner_program,
+            escrow_authority: account[0].clone(),
+            args: ActionArgs {
+                escrow_index: migration.escrow_index,
+                data,
+            },
+            compute_units: migration.compute_units,
+            accounts: migration.accounts,
+            callback: None,
+        }]);
+        self.commit_and_undelegate(account)
+            .add_post_undelegate_actions(redelegate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ephemeral_rollups_sdk::cpi::DelegateConfig as SdkDelegateConfig;
+    use ephemeral_rollups_sdk::ephem::redelegate_action_data as sdk_redelegate_action_data;
+    use pinocchio::Address;
+    use solana_program::pubkey::Pubkey;
+
+    use super::*;
+
+    #[test]
+    fn test_redelegate_action_data_matches_sdk() {
+        for validator in [None, Some([3; 32])] {
+            let mut buf = [0u8; MAX_REDELEGATE_ACTION_DATA_LEN];
+            let data = redelegate_action_data(
+                &mut buf,
+                &[7; 8],
+                &DelegateConfig {
+                    commit_frequency_ms: 1_000,
+                    validator: validator.map(Address::new_from_array),
+                },
+            );
+            let sdk_data = sdk_redelegate_action_data(
+                &[7; 8],
+                &SdkDelegateConfig {
+                    commit_frequency_ms: 1_000,
+                    validator: validator.map(Pubkey::new_from_array),
+                },
+            );
+            assert_eq!(data, sdk_data.as_slice());
+        }
+    }
+}
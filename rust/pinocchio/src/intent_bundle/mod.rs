@@ -5,9 +5,14 @@ use pinocchio::instruction::{InstructionAccount, InstructionView};
 use pinocchio::{AccountView, ProgramResult};
 use solana_address::Address;
 
+mod action_sequence;
 mod args;
+mod callback;
 mod commit;
 mod commit_and_undelegate;
+mod escrow;
+mod magic_action;
+mod migration;
 mod no_vec;
 mod serialize;
 pub mod types;
@@ -15,16 +20,48 @@ pub mod types;
 use crate::intent_bundle::commit::CommitIntentBuilder;
 use crate::intent_bundle::commit_and_undelegate::CommitAndUndelegateIntentBuilder;
 use crate::intent_bundle::serialize::{MagicIntentBundleSerialize, DISCRIMINANT_SIZE};
+pub use action_sequence::ActionSequence;
 pub use args::{ActionArgs, ShortAccountMeta};
+pub use callback::{
+    reject_callback_replay, verify_action_callback, ActionCallbackResponse, CallbackSequence,
+    CALLBACK_REPLAYED_ERROR, CALLBACK_SEQUENCE_LEN, CALLBACK_SEQUENCE_SEED,
+    INVALID_CALLBACK_SEQUENCE_ERROR, INVALID_CALLBACK_SIGNER_ERROR, INVALID_MAGIC_RESPONSE_ERROR,
+};
+#[cfg(feature = "magic-action")]
+pub use ephemeral_rollups_sdk_attribute_magic_action::PinocchioMagicAction as MagicAction;
+pub use escrow::{escrow_pda, verify_escrow, ESCROW_NOT_SIGNER_ERROR, INVALID_ESCROW_ERROR};
+pub use magic_action::MagicActionBuf;
+pub use migration::{
+    redelegate_action_data, MigrationBuf, ValidatorMigration, MAX_REDELEGATE_ACTION_DATA_LEN,
+};
 use types::MagicIntentBundle;
 pub use types::{
     ActionCallback, CallHandler, CommitAndUndelegateIntent, CommitIntent, MagicIntent,
 };
 
-const MAX_ACTIONS_NUM: usize = 10;
-const _: () = assert!(MAX_ACTIONS_NUM <= u8::MAX as usize);
+/// Maximum number of actions of a bundle, shared with the sdk crate
+pub const MAX_INTENT_BUNDLE_ACTIONS: usize = 10;
+// the callbacks reference the actions by `u8` index
+const _: () = assert!(MAX_INTENT_BUNDLE_ACTIONS <= u8::MAX as usize);
 /// Custom error code returned when a `NoVec` capacity limit is exceeded.
 pub const CAPACITY_EXCEEDED_ERROR: u32 = 0xEB_00_00_01;
+pub use crate::error::{
+    INTENT_DATA_TOO_LARGE_ERROR, MISSING_MAGIC_FEE_VAULT_ERROR, TOO_MANY_ACCOUNTS_ERROR,
+    TOO_MANY_ACTIONS_ERROR,
+};
+/// Maximum instruction data length of a CPI.
+pub const MAX_CPI_INSTRUCTION_DATA_LEN: usize = 10 * 1024;
+
+/// Pre-flight report of an intent bundle, see [`MagicIntentBundleBuilder::validate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntentBundleReport {
+    /// Number of unique accounts passed to the CPI.
+    pub accounts: usize,
+    /// Length of the `ScheduleIntentBundle` instruction data.
+    pub data_len: usize,
+    /// Number of actions of all the intents.
+    pub actions: usize,
+}
 
 /// Builds a single `MagicBlockInstruction::ScheduleIntentBundle` instruction by aggregating
 /// multiple independent intents (base actions, commits, commit+undelegate), normalizing them,
@@ -34,6 +71,7 @@ pub struct MagicIntentBundleBuilder<'acc, 'args> {
     magic_context: AccountView,
     magic_program: AccountView,
     magic_fee_vault: Option<AccountView>,
+    payer_is_delegated: bool,
     intent_bundle: MagicIntentBundle<'acc, 'args>,
 }
 
@@ -44,6 +82,7 @@ impl MagicIntentBundleBuilder<'static, 'static> {
             magic_context,
             magic_program,
             magic_fee_vault: None,
+            payer_is_delegated: false,
             intent_bundle: MagicIntentBundle::default(),
         }
     }
@@ -57,6 +96,23 @@ impl<'acc, 'args> MagicIntentBundleBuilder<'acc, 'args> {
         self
     }
 
+    /// Marks the payer as delegated, so that building fails with
+    /// [`MISSING_MAGIC_FEE_VAULT_ERROR`] without a magic fee vault, as the magic program
+    /// charges the fees of a delegated payer to the vault. The delegation of the payer can't
+    /// be told on the ER, where delegated accounts are owned by their program.
+    pub fn payer_is_delegated(mut self) -> Self {
+        self.payer_is_delegated = true;
+        self
+    }
+
+    /// Checks that the magic fee vault is passed for a delegated payer.
+    fn check_magic_fee_vault(&self) -> ProgramResult {
+        if self.payer_is_delegated && self.magic_fee_vault.is_none() {
+            return Err(ProgramError::Custom(MISSING_MAGIC_FEE_VAULT_ERROR));
+        }
+        Ok(())
+    }
+
     /// Starts building a Commit intent. Returns a [`CommitIntentBuilder`] that owns this parent.
     ///
     /// The returned builder lets you chain `.add_post_commit_actions()`, transition to other
@@ -92,18 +148,75 @@ impl<'acc, 'args> MagicIntentBundleBuilder<'acc, 'args> {
         CommitAndUndelegateIntentBuilder::new(self, accounts)
     }
 
+    /// Starts building a CommitFinalize intent, which commits and finalizes the accounts on
+    /// the base layer in a single step. Returns a [`CommitIntentBuilder`] that owns this parent.
+    pub fn commit_finalize<'new_acc>(
+        self,
+        accounts: &'new_acc [AccountView],
+    ) -> CommitIntentBuilder<'new_acc, 'args, &'static [CallHandler<'static>]>
+    where
+        'acc: 'new_acc,
+    {
+        CommitIntentBuilder::new_finalize(self, accounts)
+    }
+
+    /// Starts building a CommitFinalizeAndUndelegate intent, which commits and finalizes the
+    /// accounts on the base layer in a single step before undelegating them. Returns a
+    /// [`CommitAndUndelegateIntentBuilder`] that owns this parent.
+    pub fn commit_finalize_and_undelegate<'new_acc>(
+        self,
+        accounts: &'new_acc [AccountView],
+    ) -> CommitAndUndelegateIntentBuilder<
+        'new_acc,
+        'args,
+        &'static [CallHandler<'static>],
+        &'static [CallHandler<'static>],
+    >
+    where
+        'acc: 'new_acc,
+    {
+        CommitAndUndelegateIntentBuilder::new_finalize(self, accounts)
+    }
+
     /// Adds standalone base-layer actions to be executed without any commit/undelegate semantics.
     pub fn set_standalone_actions<'new_args>(
         self,
         actions: &'new_args [CallHandler<'new_args>],
     ) -> MagicIntentBundleBuilder<'acc, 'new_args>
+    where
+        'args: 'new_args,
+    {
+        self.set_standalone_actions_impl(actions, false)
+    }
+
+    /// Adds the actions of `sequence` as the standalone actions, executed in order, see
+    /// [`ActionSequence`]. Like [`Self::set_standalone_actions`], it replaces the standalone
+    /// actions set before.
+    pub fn add_action_sequence<'new_args, const N: usize>(
+        self,
+        sequence: &'new_args ActionSequence<'new_args, N>,
+    ) -> MagicIntentBundleBuilder<'acc, 'new_args>
+    where
+        'args: 'new_args,
+    {
+        self.set_standalone_actions_impl(sequence.actions(), true)
+    }
+
+    fn set_standalone_actions_impl<'new_args>(
+        self,
+        actions: &'new_args [CallHandler<'new_args>],
+        sequence: bool,
+    ) -> MagicIntentBundleBuilder<'acc, 'new_args>
     where
         'args: 'new_args,
     {
         let MagicIntentBundle {
             standalone_actions: _,
+            standalone_sequence: _,
             commit_intent,
             commit_and_undelegate_intent,
+            commit_finalize_intent,
+            commit_finalize_and_undelegate_intent,
         } = self.intent_bundle;
 
         MagicIntentBundleBuilder {
@@ -111,10 +224,14 @@ impl<'acc, 'args> MagicIntentBundleBuilder<'acc, 'args> {
             magic_program: self.magic_program,
             magic_context: self.magic_context,
             magic_fee_vault: self.magic_fee_vault,
+            payer_is_delegated: self.payer_is_delegated,
             intent_bundle: MagicIntentBundle {
                 standalone_actions: actions,
+                standalone_sequence: sequence,
                 commit_intent,
                 commit_and_undelegate_intent,
+                commit_finalize_intent,
+                commit_finalize_and_undelegate_intent,
             },
         }
     }
@@ -135,6 +252,34 @@ impl<'acc, 'args> MagicIntentBundleBuilder<'acc, 'args> {
         Ok(all_accounts)
     }
 
+    /// Checks the bundle against the CPI limits without invoking the magic program.
+    ///
+    /// Fails with [`TOO_MANY_ACCOUNTS_ERROR`], [`TOO_MANY_ACTIONS_ERROR`] or
+    /// [`INTENT_DATA_TOO_LARGE_ERROR`] if the bundle can't be scheduled by a single CPI, and
+    /// with [`MISSING_MAGIC_FEE_VAULT_ERROR`] per [`Self::payer_is_delegated`].
+    #[inline(never)]
+    pub fn validate(&self) -> Result<IntentBundleReport, ProgramError> {
+        self.check_magic_fee_vault()?;
+        self.intent_bundle.validate()?;
+        let actions = self.intent_bundle.actions_len()?;
+        let all_accounts = self
+            .collect_unique_account()
+            .map_err(|_| ProgramError::Custom(TOO_MANY_ACCOUNTS_ERROR))?;
+
+        let indices_map = create_indices_map(all_accounts.as_slice())?;
+        let data_len = MagicIntentBundleSerialize::new(&indices_map, self.intent_bundle)
+            .encoded_intent_len()?;
+        if data_len > MAX_CPI_INSTRUCTION_DATA_LEN {
+            return Err(ProgramError::Custom(INTENT_DATA_TOO_LARGE_ERROR));
+        }
+
+        Ok(IntentBundleReport {
+            accounts: all_accounts.len(),
+            data_len,
+            actions,
+        })
+    }
+
     /// Normalizes the bundle, serializes it with bincode into `data_buf`, builds the
     /// CPI instruction, and invokes the magic program.
     ///
@@ -155,6 +300,162 @@ impl<'acc, 'args> MagicIntentBundleBuilder<'acc, 'args> {
         self.build_and_invoke_impl(data_buf, signers_seeds)
     }
 
+    /// Equivalent to [`Self::build_and_invoke`], but splits a bundle that doesn't pass
+    /// [`Self::validate`] into several `ScheduleIntentBundle` CPIs.
+    ///
+    /// Accounts of commit intents without actions and standalone actions are spread over
+    /// the CPIs, an intent with actions or an [`ActionSequence`] is always scheduled whole.
+    /// An account is committed and undelegated by the same CPI. Intents are checked for
+    /// overlapping accounts per CPI.
+    #[inline(never)]
+    pub fn build_chunked_and_invoke(self, data_buf: &mut [u8]) -> ProgramResult {
+        self.build_chunked_and_invoke_impl(data_buf, &[])
+    }
+
+    /// Equivalent to [`Self::build_chunked_and_invoke`], but signs the CPIs with the
+    /// provided PDA seeds.
+    #[inline(never)]
+    pub fn build_chunked_and_invoke_signed(
+        self,
+        data_buf: &mut [u8],
+        signers_seeds: &[Signer<'_, '_>],
+    ) -> ProgramResult {
+        self.build_chunked_and_invoke_impl(data_buf, signers_seeds)
+    }
+
+    fn build_chunked_and_invoke_impl(
+        self,
+        data_buf: &mut [u8],
+        signers_seeds: &[Signer<'_, '_>],
+    ) -> ProgramResult {
+        self.check_magic_fee_vault()?;
+        let mut remaining = self.intent_bundle;
+        while !remaining.is_empty() {
+            let chunk = self.next_chunk(&mut remaining)?;
+            self.with_intent_bundle(chunk)
+                .build_and_invoke_impl(data_buf, signers_seeds)?;
+        }
+        Ok(())
+    }
+
+    fn with_intent_bundle(
+        &self,
+        intent_bundle: MagicIntentBundle<'acc, 'args>,
+    ) -> MagicIntentBundleBuilder<'acc, 'args> {
+        MagicIntentBundleBuilder {
+            payer: self.payer.clone(),
+            magic_context: self.magic_context.clone(),
+            magic_program: self.magic_program.clone(),
+            magic_fee_vault: self.magic_fee_vault.clone(),
+            payer_is_delegated: self.payer_is_delegated,
+            intent_bundle,
+        }
+    }
+
+    /// Moves as many parts of `remaining` into a new bundle as pass [`Self::validate`].
+    #[inline(never)]
+    fn next_chunk(
+        &self,
+        remaining: &mut MagicIntentBundle<'acc, 'args>,
+    ) -> Result<MagicIntentBundle<'acc, 'args>, ProgramError> {
+        let mut chunk = MagicIntentBundle::default();
+        let mut standalone_actions =
+            (!remaining.standalone_actions.is_empty()).then_some(remaining.standalone_actions);
+
+        self.take_parts(
+            &mut chunk,
+            &mut remaining.commit_intent,
+            CommitIntent::parts_len,
+            CommitIntent::split_at,
+            |bundle, intent| bundle.commit_intent = Some(intent),
+        )?;
+        self.take_parts(
+            &mut chunk,
+            &mut remaining.commit_and_undelegate_intent,
+            CommitAndUndelegateIntent::parts_len,
+            CommitAndUndelegateIntent::split_at,
+            |bundle, intent| bundle.commit_and_undelegate_intent = Some(intent),
+        )?;
+        self.take_parts(
+            &mut chunk,
+            &mut remaining.commit_finalize_intent,
+            CommitIntent::parts_len,
+            CommitIntent::split_at,
+            |bundle, intent| bundle.commit_finalize_intent = Some(intent),
+        )?;
+        self.take_parts(
+            &mut chunk,
+            &mut remaining.commit_finalize_and_undelegate_intent,
+            CommitAndUndelegateIntent::parts_len,
+            CommitAndUndelegateIntent::split_at,
+            |bundle, intent| bundle.commit_finalize_and_undelegate_intent = Some(intent),
+        )?;
+        chunk.standalone_sequence = remaining.standalone_sequence;
+        self.take_parts(
+            &mut chunk,
+            &mut standalone_actions,
+            |actions| match remaining.standalone_sequence {
+                false => actions.len(),
+                true => 1,
+            },
+            |actions, parts| {
+                let parts = match remaining.standalone_sequence {
+                    false => parts,
+                    true => actions.len(),
+                };
+                let (head, tail) = actions.split_at(parts);
+                (head, (!tail.is_empty()).then_some(tail))
+            },
+            |bundle, actions| bundle.standalone_actions = actions,
+        )?;
+
+        remaining.standalone_actions = standalone_actions.unwrap_or(&[]);
+        Ok(chunk)
+    }
+
+    /// Moves as many parts of `intent` into `chunk` as keep it valid.
+    fn take_parts<T: Copy>(
+        &self,
+        chunk: &mut MagicIntentBundle<'acc, 'args>,
+        intent: &mut Option<T>,
+        parts_len: impl Fn(&T) -> usize,
+        split_at: impl Fn(T, usize) -> (T, Option<T>),
+        place: impl Fn(&mut MagicIntentBundle<'acc, 'args>, T),
+    ) -> ProgramResult {
+        let Some(value) = *intent else {
+            return Ok(());
+        };
+        let with_parts = |parts: usize| {
+            let mut candidate = *chunk;
+            place(&mut candidate, split_at(value, parts).0);
+            self.with_intent_bundle(candidate).validate()
+        };
+
+        // Binary search for the largest number of parts that still fits
+        let (mut low, mut high) = (0, parts_len(&value));
+        while low < high {
+            let mid = low + (high - low).div_ceil(2);
+            if with_parts(mid).is_ok() {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+
+        if low == 0 {
+            if chunk.is_empty() {
+                // Not even a single part fits on its own
+                return Err(with_parts(1).err().unwrap_or(ProgramError::InvalidArgument));
+            }
+            return Ok(());
+        }
+
+        let (head, tail) = split_at(value, low);
+        place(chunk, head);
+        *intent = tail;
+        Ok(())
+    }
+
     fn build_and_invoke_impl(
         self,
         data_buf: &mut [u8],
@@ -164,6 +465,7 @@ impl<'acc, 'args> MagicIntentBundleBuilder<'acc, 'args> {
             return Err(ProgramError::InvalidInstructionData);
         }
 
+        self.check_magic_fee_vault()?;
         self.intent_bundle.validate()?;
 
         let all_accounts = self.collect_unique_account()?;
@@ -303,7 +605,7 @@ impl MagicIntentBundleBuilder<'_, '_> {
         let all_accounts = self.collect_unique_account().unwrap();
         let mut account_keys = NoVec::<Address, MAX_STATIC_CPI_ACCOUNTS>::new();
         for account in all_accounts.iter() {
-            account_keys.push(account.address().clone());
+            account_keys.push(*account.address());
         }
         let indices_map = create_indices_map(all_accounts.as_slice()).unwrap();
         let serializable = MagicIntentBundleSerialize::new(&indices_map, self.intent_bundle);
@@ -340,16 +642,25 @@ mod tests {
 
     // SDK builder
     use ephemeral_rollups_sdk::ephem::{
-        ActionCallback as SdkActionCallback, CallHandler as SdkCallHandler,
-        FoldableCauIntentBuilder, FoldableIntentBuilder, IntentInstructions,
-        MagicIntentBundleBuilder as SdkBuilder,
+        decode_intent_bundle, decode_intent_instructions, ActionCallback as SdkActionCallback,
+        CallHandler as SdkCallHandler, DecodedIntentBundle, FoldableCauIntentBuilder,
+        FoldableIntentBuilder, IntentInstructions, MagicIntentBundleBuilder as SdkBuilder,
+    };
+    use magicblock_magic_program_api::args::{
+        ActionArgs as SdkActionArgs, ShortAccountMeta as SdkShortAccountMeta,
     };
-    use magicblock_magic_program_api::args::ActionArgs as SdkActionArgs;
     use magicblock_magic_program_api::Pubkey as MagicPubkey;
     use solana_program::account_info::AccountInfo;
 
+    use crate::consts::CALLBACK_SIGNER;
     use crate::intent_bundle::serialize::MagicIntentBundleSerialize;
 
+    /// Resolves `anchor_lang::AnchorSerialize` in the sdk `#[derive(MagicAction)]` expansion.
+    #[cfg(feature = "magic-action")]
+    mod anchor_lang {
+        pub use ephemeral_rollups_sdk::compat::borsh::BorshSerialize as AnchorSerialize;
+    }
+
     // -----------------------------------------------------------------
     // Mock helpers
     // -----------------------------------------------------------------
@@ -439,6 +750,54 @@ mod tests {
         }
     }
 
+    /// Asserts that the pinocchio and SDK intents are byte-identical and decode to the same
+    /// bundle, returns the decoded bundle.
+    fn assert_decoded_parity(
+        pino_data: &[u8],
+        pino_accounts: &[Address],
+        sdk_ix: &solana_instruction::Instruction,
+    ) -> DecodedIntentBundle {
+        assert_eq!(pino_data, sdk_ix.data.as_slice(), "intent data mismatch");
+        assert_eq!(
+            pino_accounts.len(),
+            sdk_ix.accounts.len(),
+            "account count mismatch"
+        );
+        let pino_ix = solana_instruction::Instruction {
+            program_id: sdk_ix.program_id,
+            accounts: pino_accounts
+                .iter()
+                .zip(&sdk_ix.accounts)
+                .map(|(address, meta)| solana_instruction::AccountMeta {
+                    pubkey: *address,
+                    ..meta.clone()
+                })
+                .collect(),
+            data: pino_data.to_vec(),
+        };
+        let decoded = decode_intent_bundle(sdk_ix).expect("sdk intent should decode");
+        assert_eq!(
+            decode_intent_bundle(&pino_ix).expect("pinocchio intent should decode"),
+            decoded,
+            "decoded intent mismatch"
+        );
+        decoded
+    }
+
+    /// Mirrors `build_chunked_and_invoke`, returning the instruction data and CPI account
+    /// keys of every chunk instead of invoking CPIs.
+    fn build_chunked_serialized(builder: MagicIntentBundleBuilder) -> Vec<(Vec<u8>, Vec<Address>)> {
+        let mut chunks = Vec::new();
+        let mut remaining = builder.intent_bundle;
+        while !remaining.is_empty() {
+            let chunk = builder.next_chunk(&mut remaining).unwrap();
+            let mut buf = [0u8; MAX_CPI_INSTRUCTION_DATA_LEN];
+            let (len, account_keys) = builder.with_intent_bundle(chunk).build_serialized(&mut buf);
+            chunks.push((buf[..len].to_vec(), account_keys.as_slice().to_vec()));
+        }
+        chunks
+    }
+
     // -----------------------------------------------------------------
     // Builder compatibility tests
     // -----------------------------------------------------------------
@@ -606,6 +965,86 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_compat_migrate_to_validator() {
+        let owner = [0xCC; 32];
+        let validator = [0xDD; 32];
+        let meta = [0xEE; 32];
+
+        // --- Pinocchio ---
+        let mut p_payer = MockRuntimeAccount::new_unique();
+        let mut p_ctx = MockRuntimeAccount::new_unique();
+        let mut p_acc = MockRuntimeAccount::new_unique();
+        let mut p_prog = MockRuntimeAccount::new_unique();
+
+        let accounts = [ShortAccountMeta {
+            pubkey: Address::new_from_array(meta),
+            is_writable: true,
+        }];
+        let migrated = [p_acc.as_account_view()];
+        let mut migration_buf = MigrationBuf::new();
+        let mut buf = [0u8; CPI_DATA_BUF_SIZE];
+        let (pino_len, pino_accounts) = MagicIntentBundleBuilder::new(
+            p_payer.as_account_view(),
+            p_ctx.as_account_view(),
+            p_prog.as_account_view(),
+        )
+        .migrate_to_validator(
+            &migrated,
+            ValidatorMigration {
+                owner_program: Address::new_from_array(owner),
+                discriminator: [7; 8],
+                escrow_index: 3,
+                accounts: &accounts,
+                config: crate::types::DelegateConfig {
+                    commit_frequency_ms: 1_000,
+                    validator: Some(Address::new_from_array(validator)),
+                },
+                compute_units: 200_000,
+            },
+            &mut migration_buf,
+        )
+        .build_serialized(&mut buf);
+
+        // --- SDK ---
+        let mut s_payer = SdkTestAccount::new(p_payer.address);
+        let mut s_ctx = SdkTestAccount::new(p_ctx.address);
+        let mut s_acc = SdkTestAccount::new(p_acc.address);
+        let mut s_prog = SdkTestAccount::new(p_prog.address);
+
+        let (accounts, ix) = SdkBuilder::new(
+            s_payer.as_account_info(),
+            s_ctx.as_account_info(),
+            s_prog.as_account_info(),
+        )
+        .migrate_to_validator(
+            &s_acc.as_account_info(),
+            ephemeral_rollups_sdk::ephem::ValidatorMigration {
+                owner_program: Address::new_from_array(owner),
+                discriminator: [7; 8],
+                escrow_index: 3,
+                accounts: vec![SdkShortAccountMeta {
+                    pubkey: Address::new_from_array(meta),
+                    is_writable: true,
+                }],
+                config: ephemeral_rollups_sdk::cpi::DelegateConfig {
+                    commit_frequency_ms: 1_000,
+                    validator: Some(Address::new_from_array(validator)),
+                },
+                compute_units: 200_000,
+            },
+        )
+        .build()
+        .schedule_intent_ix;
+
+        assert_eq!(&buf[..pino_len], &ix.data, "migrate_to_validator mismatch");
+        let sdk_addrs: Vec<Address> = accounts
+            .iter()
+            .map(|a| Address::new_from_array(a.key.to_bytes()))
+            .collect();
+        assert_eq!(pino_accounts.as_slice(), sdk_addrs.as_slice());
+    }
+
     /// Full chain with actions on all intents.
     #[test]
     fn test_compat_full_chain_with_actions() {
@@ -702,6 +1141,24 @@ mod tests {
             sdk_addrs.as_slice(),
             "full chain: account list mismatch"
         );
+
+        let decoded = assert_decoded_parity(&buf[..pino_len], pino_accounts.as_slice(), &ix);
+        let commit = decoded.commit.expect("commit intent");
+        assert_eq!(commit.committed_accounts, [s_commit.key]);
+        assert_eq!(commit.actions.len(), 1);
+        assert_eq!(commit.actions[0].escrow_authority, s_escrow1.key);
+        assert_eq!(
+            commit.actions[0].destination_program,
+            Address::new_from_array(dest1_addr)
+        );
+        assert_eq!(commit.actions[0].data, commit_data);
+        let cau = decoded.commit_and_undelegate.expect("cau intent");
+        assert_eq!(cau.commit.committed_accounts, [s_cau.key]);
+        assert!(cau.commit.actions.is_empty());
+        assert_eq!(cau.undelegate_actions.len(), 1);
+        assert_eq!(cau.undelegate_actions[0].escrow_authority, s_escrow2.key);
+        assert_eq!(cau.undelegate_actions[0].compute_units, 50_000);
+        assert_eq!(cau.undelegate_actions[0].data, undelegate_data);
     }
 
     /// Demonstrates that the builder API supports conditional building:
@@ -846,6 +1303,58 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_payer_is_delegated() {
+        let mut p_payer = MockRuntimeAccount::new_unique();
+        let mut p_ctx = MockRuntimeAccount::new_unique();
+        let mut p_vault = MockRuntimeAccount::new_unique();
+        let mut p_acc1 = MockRuntimeAccount::new_unique();
+        let mut p_prog = MockRuntimeAccount::new_unique();
+
+        let commit_accs = [p_acc1.as_account_view()];
+        let (payer, ctx, prog) = (
+            p_payer.as_account_view(),
+            p_ctx.as_account_view(),
+            p_prog.as_account_view(),
+        );
+        let builder = || MagicIntentBundleBuilder::new(payer.clone(), ctx.clone(), prog.clone());
+        let mut buf = [0u8; CPI_DATA_BUF_SIZE];
+
+        // the vault is optional unless the payer is delegated
+        assert!(builder().commit(&commit_accs).fold().validate().is_ok());
+        assert_eq!(
+            builder()
+                .payer_is_delegated()
+                .commit(&commit_accs)
+                .fold()
+                .validate(),
+            Err(ProgramError::Custom(MISSING_MAGIC_FEE_VAULT_ERROR))
+        );
+        assert_eq!(
+            builder()
+                .payer_is_delegated()
+                .commit(&commit_accs)
+                .fold()
+                .build_and_invoke(&mut buf),
+            Err(ProgramError::Custom(MISSING_MAGIC_FEE_VAULT_ERROR))
+        );
+        assert_eq!(
+            builder()
+                .payer_is_delegated()
+                .commit(&commit_accs)
+                .fold()
+                .build_chunked_and_invoke(&mut buf),
+            Err(ProgramError::Custom(MISSING_MAGIC_FEE_VAULT_ERROR))
+        );
+        assert!(builder()
+            .payer_is_delegated()
+            .magic_fee_vault(p_vault.as_account_view())
+            .commit(&commit_accs)
+            .fold()
+            .validate()
+            .is_ok());
+    }
+
     // -----------------------------------------------------------------
     // Callback serialization tests
     // -----------------------------------------------------------------
@@ -931,10 +1440,7 @@ mod tests {
             compute_units: 50_000,
             accounts: vec![],
         };
-        let IntentInstructions {
-            schedule_intent_ix: (_, sdk_ix),
-            add_callback_ixs,
-        } = SdkBuilder::new(
+        let instructions = SdkBuilder::new(
             s_payer.as_account_info(),
             s_ctx.as_account_info(),
             s_prog.as_account_info(),
@@ -944,6 +1450,10 @@ mod tests {
         .then(sdk_cb)
         .fold_builder()
         .build();
+        let IntentInstructions {
+            schedule_intent_ix: (_, sdk_ix),
+            add_callback_ixs,
+        } = &instructions;
 
         assert_eq!(
             &intent_buf[..intent_len],
@@ -956,6 +1466,18 @@ mod tests {
             add_callback_ixs[0].1.data.as_slice(),
             "callback ix mismatch"
         );
+
+        let decoded = decode_intent_instructions(&instructions).unwrap();
+        let action = &decoded.commit.as_ref().expect("commit intent").actions[0];
+        assert_eq!(action.escrow_authority, s_escrow.key);
+        let callback = action.callback.as_ref().expect("callback of action 0");
+        assert_eq!(
+            callback.destination_program,
+            Address::new_from_array(cb_dest_addr)
+        );
+        assert_eq!(callback.discriminator, cb_disc);
+        assert_eq!(callback.payload, cb_payload);
+        assert_eq!(callback.compute_units, 50_000);
     }
 
     /// CAU with one post-commit callback (action 0) and one post-undelegate callback (action 1).
@@ -1183,6 +1705,209 @@ mod tests {
         assert!(cb_len > 0);
     }
 
+    /// Commit, CommitFinalize and CommitFinalizeAndUndelegate intents in one bundle.
+    #[test]
+    fn test_compat_commit_finalize_intents() {
+        use magicblock_magic_program_api::args as sdk;
+        use magicblock_magic_program_api::instruction::MagicBlockInstruction;
+
+        let dest1_addr = [0xC1; 32];
+        let dest2_addr = [0xD1; 32];
+        let finalize_data = [0xF0u8];
+        let undelegate_data = [0xD0u8];
+
+        let mut p_payer = MockRuntimeAccount::new_unique();
+        let mut p_ctx = MockRuntimeAccount::new_unique();
+        let mut p_commit = MockRuntimeAccount::new_unique();
+        let mut p_finalize = MockRuntimeAccount::new_unique();
+        let mut p_cfau = MockRuntimeAccount::new_unique();
+        let mut p_escrow1 = MockRuntimeAccount::new_unique();
+        let mut p_escrow2 = MockRuntimeAccount::new_unique();
+        let mut p_prog = MockRuntimeAccount::new_unique();
+
+        let finalize_handler = CallHandler {
+            destination_program: Address::new_from_array(dest1_addr),
+            escrow_authority: p_escrow1.as_account_view(),
+            args: ActionArgs::new(&finalize_data),
+            compute_units: 100_000,
+            accounts: &[],
+            callback: None,
+        };
+        let undelegate_handler = CallHandler {
+            destination_program: Address::new_from_array(dest2_addr),
+            escrow_authority: p_escrow2.as_account_view(),
+            args: ActionArgs::new(&undelegate_data),
+            compute_units: 50_000,
+            accounts: &[],
+            callback: None,
+        };
+        let commit_accs = [p_commit.as_account_view()];
+        let finalize_accs = [p_finalize.as_account_view()];
+        let cfau_accs = [p_cfau.as_account_view()];
+        let mut buf = [0u8; CPI_DATA_BUF_SIZE];
+        let (pino_len, pino_accounts) = MagicIntentBundleBuilder::new(
+            p_payer.as_account_view(),
+            p_ctx.as_account_view(),
+            p_prog.as_account_view(),
+        )
+        .commit(&commit_accs)
+        .commit_finalize(&finalize_accs)
+        .add_post_commit_actions(&[finalize_handler])
+        .commit_finalize_and_undelegate(&cfau_accs)
+        .add_post_undelegate_actions(&[undelegate_handler])
+        .build_serialized(&mut buf);
+
+        // payer, magic context, then the intent accounts in the wire order of the intents
+        let addresses = [
+            p_payer.address,
+            p_ctx.address,
+            p_commit.address,
+            p_finalize.address,
+            p_escrow1.address,
+            p_cfau.address,
+            p_escrow2.address,
+        ]
+        .map(Address::new_from_array);
+        assert_eq!(pino_accounts.as_slice(), addresses.as_slice());
+
+        let action = |escrow_authority: u8, dest: [u8; 32], data: &[u8], compute_units| {
+            sdk::BaseActionArgs {
+                args: sdk::ActionArgs::new(data.to_vec()),
+                compute_units,
+                escrow_authority,
+                destination_program: MagicPubkey::new_from_array(dest),
+                accounts: vec![],
+            }
+        };
+        let sdk_args = sdk::MagicIntentBundleArgs {
+            commit: Some(sdk::CommitTypeArgs::Standalone(vec![2])),
+            commit_and_undelegate: None,
+            commit_finalize: Some(sdk::CommitTypeArgs::WithBaseActions {
+                committed_accounts: vec![3],
+                base_actions: vec![action(4, dest1_addr, &finalize_data, 100_000)],
+            }),
+            commit_finalize_and_undelegate: Some(sdk::CommitAndUndelegateArgs {
+                commit_type: sdk::CommitTypeArgs::Standalone(vec![5]),
+                undelegate_type: sdk::UndelegateTypeArgs::WithBaseActions {
+                    base_actions: vec![action(6, dest2_addr, &undelegate_data, 50_000)],
+                },
+            }),
+            standalone_actions: vec![],
+        };
+        let sdk_ix = solana_instruction::Instruction::new_with_bincode(
+            Address::new_from_array(p_prog.address),
+            &MagicBlockInstruction::ScheduleIntentBundle(sdk_args),
+            addresses
+                .iter()
+                .map(|address| solana_instruction::AccountMeta::new(*address, false))
+                .collect(),
+        );
+
+        let decoded = assert_decoded_parity(&buf[..pino_len], pino_accounts.as_slice(), &sdk_ix);
+        assert_eq!(
+            decoded.commit.expect("commit intent").committed_accounts,
+            [addresses[2]]
+        );
+        assert!(decoded.commit_and_undelegate.is_none());
+        let commit_finalize = decoded.commit_finalize.expect("commit finalize intent");
+        assert_eq!(commit_finalize.committed_accounts, [addresses[3]]);
+        assert_eq!(commit_finalize.actions[0].escrow_authority, addresses[4]);
+        assert_eq!(commit_finalize.actions[0].data, finalize_data);
+        let cfau = decoded
+            .commit_finalize_and_undelegate
+            .expect("commit finalize and undelegate intent");
+        assert_eq!(cfau.commit.committed_accounts, [addresses[5]]);
+        assert_eq!(cfau.undelegate_actions[0].escrow_authority, addresses[6]);
+        assert_eq!(cfau.undelegate_actions[0].data, undelegate_data);
+    }
+
+    /// Accounts can't be committed by both a commit and a commit-finalize intent.
+    #[test]
+    fn test_commit_finalize_overlap_rejected() {
+        let mut p_payer = MockRuntimeAccount::new_unique();
+        let mut p_ctx = MockRuntimeAccount::new_unique();
+        let mut p_acc = MockRuntimeAccount::new_unique();
+        let mut p_prog = MockRuntimeAccount::new_unique();
+
+        let accs = [p_acc.as_account_view()];
+        let builder = MagicIntentBundleBuilder::new(
+            p_payer.as_account_view(),
+            p_ctx.as_account_view(),
+            p_prog.as_account_view(),
+        )
+        .commit(&accs)
+        .commit_finalize_and_undelegate(&accs)
+        .fold();
+        assert_eq!(
+            builder.intent_bundle.validate(),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    /// Finalize intents take their action indices after the commit and
+    /// commit-and-undelegate ones.
+    #[test]
+    fn test_commit_finalize_callback_action_index() {
+        let dest_addr = [0xDD; 32];
+        let cb_dest_addr = [0xCB; 32];
+        let data = [0x11u8];
+        let cb_disc = [0xFFu8];
+
+        let mut p_payer = MockRuntimeAccount::new_unique();
+        let mut p_ctx = MockRuntimeAccount::new_unique();
+        let mut p_commit = MockRuntimeAccount::new_unique();
+        let mut p_finalize = MockRuntimeAccount::new_unique();
+        let mut p_cfau = MockRuntimeAccount::new_unique();
+        let mut p_escrow = MockRuntimeAccount::new_unique();
+        let mut p_prog = MockRuntimeAccount::new_unique();
+
+        let escrow = p_escrow.as_account_view();
+        let handler = |callback| CallHandler {
+            destination_program: Address::new_from_array(dest_addr),
+            escrow_authority: escrow.clone(),
+            args: ActionArgs::new(&data),
+            compute_units: 100_000,
+            accounts: &[],
+            callback,
+        };
+        let callback = ActionCallback {
+            destination_program: Address::new_from_array(cb_dest_addr),
+            discriminator: &cb_disc,
+            payload: &[],
+            compute_units: 25_000,
+            accounts: &[],
+        };
+        let commit_handlers = [handler(None)];
+        let finalize_handlers = [handler(Some(callback.clone()))];
+        let undelegate_handlers = [handler(None), handler(Some(callback))];
+        let commit_accs = [p_commit.as_account_view()];
+        let finalize_accs = [p_finalize.as_account_view()];
+        let cfau_accs = [p_cfau.as_account_view()];
+        let builder = MagicIntentBundleBuilder::new(
+            p_payer.as_account_view(),
+            p_ctx.as_account_view(),
+            p_prog.as_account_view(),
+        )
+        .commit(&commit_accs)
+        .add_post_commit_actions(&commit_handlers)
+        .commit_finalize(&finalize_accs)
+        .add_post_commit_actions(&finalize_handlers)
+        .commit_finalize_and_undelegate(&cfau_accs)
+        .add_post_undelegate_actions(&undelegate_handlers)
+        .fold();
+
+        let all_accounts = builder.collect_unique_account().unwrap();
+        let indices_map = create_indices_map(all_accounts.as_slice()).unwrap();
+        let serializable = MagicIntentBundleSerialize::new(&indices_map, builder.intent_bundle);
+
+        assert_eq!(serializable.get_actions_len(), 4);
+        let indices: Vec<usize> = serializable
+            .action_callback_iter()
+            .map(|(index, _)| index)
+            .collect();
+        assert_eq!(indices, [1, 3]);
+    }
+
     #[test]
     fn test_instruction_accounts_force_payer_signer() {
         let mut payer = MockRuntimeAccount::new_unique();
@@ -1204,4 +1929,666 @@ mod tests {
         assert!(!instruction_accounts.as_slice()[1].is_signer);
         assert!(!instruction_accounts.as_slice()[2].is_signer);
     }
+
+    /// `validate` reports the CPI footprint and rejects bundles over the limits.
+    #[test]
+    fn test_validate_reports_limits() {
+        let dest_addr = [0xDD; 32];
+        let action_data = [0xAA, 0xBB, 0xCC];
+
+        let mut p_payer = MockRuntimeAccount::new_unique();
+        let mut p_ctx = MockRuntimeAccount::new_unique();
+        let mut p_escrow = MockRuntimeAccount::new_unique();
+        let mut p_prog = MockRuntimeAccount::new_unique();
+        let mut p_accs: Vec<MockRuntimeAccount> = (0..MAX_STATIC_CPI_ACCOUNTS)
+            .map(|_| MockRuntimeAccount::new_unique())
+            .collect();
+        let accs: Vec<AccountView> = p_accs
+            .iter_mut()
+            .map(MockRuntimeAccount::as_account_view)
+            .collect();
+
+        let escrow = p_escrow.as_account_view();
+        let handler = CallHandler {
+            destination_program: Address::new_from_array(dest_addr),
+            escrow_authority: escrow,
+            args: ActionArgs::new(&action_data),
+            compute_units: 200_000,
+            accounts: &[],
+            callback: None,
+        };
+        let handlers = vec![handler; MAX_INTENT_BUNDLE_ACTIONS + 1];
+        let (payer, ctx, prog) = (
+            p_payer.as_account_view(),
+            p_ctx.as_account_view(),
+            p_prog.as_account_view(),
+        );
+        let builder = || MagicIntentBundleBuilder::new(payer.clone(), ctx.clone(), prog.clone());
+
+        let small = builder()
+            .commit(&accs[..2])
+            .add_post_commit_actions(&handlers[..1]);
+        let report = small.validate().unwrap();
+        let mut buf = [0u8; CPI_DATA_BUF_SIZE];
+        let (len, accounts) = small.build_serialized(&mut buf);
+        assert_eq!(
+            report,
+            IntentBundleReport {
+                accounts: accounts.len(),
+                data_len: len,
+                actions: 1,
+            }
+        );
+
+        let too_many_accounts = builder().commit(&accs).fold();
+        assert_eq!(
+            too_many_accounts.validate(),
+            Err(ProgramError::Custom(TOO_MANY_ACCOUNTS_ERROR))
+        );
+
+        let too_many_actions = builder()
+            .commit(&accs[..1])
+            .add_post_commit_actions(&handlers);
+        assert_eq!(
+            too_many_actions.validate(),
+            Err(ProgramError::Custom(TOO_MANY_ACTIONS_ERROR))
+        );
+
+        // the limit applies to the actions of all the intents
+        let half = MAX_INTENT_BUNDLE_ACTIONS / 2 + 1;
+        let too_many_actions = builder()
+            .commit(&accs[..1])
+            .add_post_commit_actions(&handlers[..half])
+            .set_standalone_actions(&handlers[..half]);
+        assert_eq!(
+            too_many_actions.validate(),
+            Err(ProgramError::Custom(TOO_MANY_ACTIONS_ERROR))
+        );
+    }
+
+    #[test]
+    fn test_limits_match_the_sdk() {
+        use ephemeral_rollups_sdk::ephem::{
+            MAX_INTENT_BUNDLE_ACTIONS as SDK_MAX_ACTIONS,
+            MAX_INTENT_BUNDLE_DATA_LEN as SDK_MAX_DATA_LEN,
+        };
+
+        assert_eq!(MAX_INTENT_BUNDLE_ACTIONS, SDK_MAX_ACTIONS);
+        assert_eq!(MAX_CPI_INSTRUCTION_DATA_LEN, SDK_MAX_DATA_LEN);
+    }
+
+    /// Chunks stay within the CPI limits, keep every account's commit and undelegate
+    /// together and preserve the order of the intents.
+    #[test]
+    fn test_build_chunked() {
+        let dest_addr = [0xDD; 32];
+        let action_data = [0xAA, 0xBB, 0xCC];
+
+        let mut p_payer = MockRuntimeAccount::new_unique();
+        let mut p_ctx = MockRuntimeAccount::new_unique();
+        let mut p_escrow = MockRuntimeAccount::new_unique();
+        let mut p_prog = MockRuntimeAccount::new_unique();
+        let mut p_accs: Vec<MockRuntimeAccount> =
+            (0..140).map(|_| MockRuntimeAccount::new_unique()).collect();
+        let accs: Vec<AccountView> = p_accs
+            .iter_mut()
+            .map(MockRuntimeAccount::as_account_view)
+            .collect();
+        let (commit_accs, cau_accs) = accs.split_at(100);
+
+        let escrow = p_escrow.as_account_view();
+        let handler = CallHandler {
+            destination_program: Address::new_from_array(dest_addr),
+            escrow_authority: escrow,
+            args: ActionArgs::new(&action_data),
+            compute_units: 200_000,
+            accounts: &[],
+            callback: None,
+        };
+        let handlers = vec![handler; 3];
+
+        let chunks = build_chunked_serialized(
+            MagicIntentBundleBuilder::new(
+                p_payer.as_account_view(),
+                p_ctx.as_account_view(),
+                p_prog.as_account_view(),
+            )
+            .set_standalone_actions(&handlers)
+            .commit(commit_accs)
+            .commit_and_undelegate(cau_accs)
+            .fold(),
+        );
+        assert!(chunks.len() > 1);
+
+        let mut committed = Vec::new();
+        let mut undelegated = Vec::new();
+        let mut standalone_actions = 0;
+        for (data, accounts) in &chunks {
+            assert!(accounts.len() <= MAX_STATIC_CPI_ACCOUNTS);
+            let ix = solana_instruction::Instruction {
+                program_id: *p_prog.as_account_view().address(),
+                accounts: accounts
+                    .iter()
+                    .map(|address| solana_instruction::AccountMeta::new(*address, false))
+                    .collect(),
+                data: data.clone(),
+            };
+            let decoded = decode_intent_bundle(&ix).expect("chunk should decode");
+            if let Some(commit) = decoded.commit {
+                committed.extend(commit.committed_accounts);
+            }
+            if let Some(cau) = decoded.commit_and_undelegate {
+                undelegated.extend(cau.commit.committed_accounts);
+            }
+            standalone_actions += decoded.standalone_actions.len();
+        }
+
+        let addresses = |accs: &[AccountView]| -> Vec<Address> {
+            accs.iter().map(|acc| *acc.address()).collect()
+        };
+        assert_eq!(committed, addresses(commit_accs));
+        assert_eq!(undelegated, addresses(cau_accs));
+        assert_eq!(standalone_actions, handlers.len());
+    }
+
+    #[test]
+    fn test_action_sequence() {
+        let tags: [[u8; 1]; MAX_INTENT_BUNDLE_ACTIONS + 1] = core::array::from_fn(|i| [i as u8]);
+        let mut p_payer = MockRuntimeAccount::new_unique();
+        let mut p_ctx = MockRuntimeAccount::new_unique();
+        let mut p_prog = MockRuntimeAccount::new_unique();
+        let mut p_escrows: Vec<MockRuntimeAccount> = (0..tags.len())
+            .map(|_| MockRuntimeAccount::new_unique())
+            .collect();
+        let mut p_accs: Vec<MockRuntimeAccount> =
+            (0..120).map(|_| MockRuntimeAccount::new_unique()).collect();
+        let commit_accs: Vec<AccountView> = p_accs
+            .iter_mut()
+            .map(MockRuntimeAccount::as_account_view)
+            .collect();
+        let handlers: Vec<CallHandler> = p_escrows
+            .iter_mut()
+            .zip(&tags)
+            .map(|(escrow, tag)| CallHandler {
+                destination_program: Address::new_from_array([0xDD; 32]),
+                escrow_authority: escrow.as_account_view(),
+                args: ActionArgs::new(tag),
+                compute_units: 200_000,
+                accounts: &[],
+                callback: None,
+            })
+            .collect();
+        let sequence = |len: usize| {
+            handlers[..len]
+                .iter()
+                .try_fold(
+                    ActionSequence::<{ MAX_INTENT_BUNDLE_ACTIONS + 1 }>::new(),
+                    |sequence, handler| sequence.then_action(handler.clone()),
+                )
+                .unwrap()
+        };
+        let builder = MagicIntentBundleBuilder::new(
+            p_payer.as_account_view(),
+            p_ctx.as_account_view(),
+            p_prog.as_account_view(),
+        );
+        let program_id = *p_prog.as_account_view().address();
+        let chunk_tags = |chunks: Vec<(Vec<u8>, Vec<Address>)>| -> Vec<Vec<u8>> {
+            chunks
+                .iter()
+                .map(|(data, accounts)| {
+                    let ix = solana_instruction::Instruction {
+                        program_id,
+                        accounts: accounts
+                            .iter()
+                            .map(|address| solana_instruction::AccountMeta::new(*address, false))
+                            .collect(),
+                        data: data.clone(),
+                    };
+                    let decoded = decode_intent_bundle(&ix).expect("chunk should decode");
+                    decoded
+                        .standalone_actions
+                        .iter()
+                        .map(|action| action.data[0])
+                        .collect::<Vec<_>>()
+                })
+                .filter(|tags| !tags.is_empty())
+                .collect()
+        };
+
+        // a sequence is encoded as the same actions in order, without any failure policy
+        let six = sequence(6);
+        let mut sequence_buf = [0u8; 2048];
+        let mut actions_buf = [0u8; 2048];
+        let (sequence_len, _) = builder
+            .with_intent_bundle(MagicIntentBundle::default())
+            .add_action_sequence(&six)
+            .build_serialized(&mut sequence_buf);
+        let (actions_len, _) = builder
+            .with_intent_bundle(MagicIntentBundle::default())
+            .set_standalone_actions(&handlers[..6])
+            .build_serialized(&mut actions_buf);
+        assert_eq!(sequence_buf[..sequence_len], actions_buf[..actions_len]);
+
+        // standalone actions are spread over the CPIs
+        let chunks = chunk_tags(build_chunked_serialized(
+            builder
+                .with_intent_bundle(MagicIntentBundle::default())
+                .set_standalone_actions(&handlers[..6])
+                .commit(&commit_accs)
+                .fold(),
+        ));
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.concat(), [0, 1, 2, 3, 4, 5]);
+
+        // while a sequence is scheduled by a single CPI
+        let chunks = chunk_tags(build_chunked_serialized(
+            builder
+                .with_intent_bundle(MagicIntentBundle::default())
+                .add_action_sequence(&six)
+                .commit(&commit_accs)
+                .fold(),
+        ));
+        assert_eq!(chunks, [vec![0, 1, 2, 3, 4, 5]]);
+
+        // along with the callbacks of its actions, attached to the same actions
+        let mut with_callback = handlers[..3].to_vec();
+        with_callback[1].callback = Some(ActionCallback {
+            destination_program: Address::new_from_array([0xCB; 32]),
+            discriminator: &[0xFF],
+            payload: &[],
+            compute_units: 25_000,
+            accounts: &[],
+        });
+        let linked = with_callback
+            .iter()
+            .try_fold(ActionSequence::<3>::new(), |sequence, handler| {
+                sequence.then_action(handler.clone())
+            })
+            .unwrap();
+        let chunked = builder
+            .with_intent_bundle(MagicIntentBundle::default())
+            .add_action_sequence(&linked)
+            .commit(&commit_accs)
+            .fold();
+        let mut remaining = chunked.intent_bundle;
+        let mut chunk_callbacks = Vec::new();
+        while !remaining.is_empty() {
+            let chunk = chunked.with_intent_bundle(chunked.next_chunk(&mut remaining).unwrap());
+            let all_accounts = chunk.collect_unique_account().unwrap();
+            let indices_map = create_indices_map(all_accounts.as_slice()).unwrap();
+            let serializable = MagicIntentBundleSerialize::new(&indices_map, chunk.intent_bundle);
+            let tags: Vec<u8> = chunk
+                .intent_bundle
+                .standalone_actions
+                .iter()
+                .map(|action| action.args.data[0])
+                .collect();
+            let callbacks: Vec<usize> = serializable
+                .action_callback_iter()
+                .map(|(index, _)| index)
+                .collect();
+            chunk_callbacks.push((tags, callbacks));
+        }
+        assert!(chunk_callbacks.len() > 1);
+        chunk_callbacks.retain(|(tags, _)| !tags.is_empty());
+        assert_eq!(chunk_callbacks, [(vec![0, 1, 2], vec![1])]);
+
+        // and can't be split if it doesn't fit in a single CPI
+        let too_long = sequence(MAX_INTENT_BUNDLE_ACTIONS + 1);
+        let too_many = builder
+            .with_intent_bundle(MagicIntentBundle::default())
+            .add_action_sequence(&too_long);
+        let mut remaining = too_many.intent_bundle;
+        assert_eq!(
+            too_many.next_chunk(&mut remaining).err(),
+            Some(ProgramError::Custom(TOO_MANY_ACTIONS_ERROR))
+        );
+        let chunks = chunk_tags(build_chunked_serialized(
+            builder
+                .with_intent_bundle(MagicIntentBundle::default())
+                .set_standalone_actions(&handlers),
+        ));
+        assert_eq!(chunks.len(), 2);
+
+        assert_eq!(
+            ActionSequence::<1>::new()
+                .then_action(handlers[0].clone())
+                .unwrap()
+                .then_action(handlers[1].clone())
+                .err(),
+            Some(ProgramError::Custom(CAPACITY_EXCEEDED_ERROR))
+        );
+    }
+
+    /// The pinocchio and sdk `#[derive(MagicAction)]` build the same action.
+    #[cfg(feature = "magic-action")]
+    #[test]
+    fn test_compat_magic_action_derive() {
+        /// Stands for the Anchor type of the accounts of the sdk flavour
+        type UncheckedAccount = ();
+
+        #[allow(dead_code)]
+        #[derive(MagicAction)]
+        #[magic_action(
+            program = Address::new_from_array([0xDD; 32]),
+            instruction = "update_counter"
+        )]
+        struct PinoUpdate {
+            #[account(mut, seeds = [b"counter"], bump)]
+            counter: (),
+            authority: (),
+            escrow_auth: (),
+            escrow: (),
+        }
+
+        #[allow(dead_code)]
+        #[derive(ephemeral_rollups_sdk_attribute_magic_action::MagicAction)]
+        #[magic_action(
+            program = Address::new_from_array([0xDD; 32]),
+            args = u64,
+            instruction = "update_counter"
+        )]
+        struct SdkUpdate {
+            #[account(mut, seeds = [b"counter"], bump)]
+            counter: UncheckedAccount,
+            authority: UncheckedAccount,
+            escrow_auth: UncheckedAccount,
+            escrow: UncheckedAccount,
+        }
+
+        assert_eq!(
+            PinoUpdateMagicAction::DISCRIMINATOR,
+            SdkUpdateMagicAction::DISCRIMINATOR
+        );
+        let counter = Address::new_from_array([0x01; 32]);
+        let authority = Address::new_from_array([0x02; 32]);
+        let amount = 7u64;
+
+        // --- Pinocchio ---
+        let mut p_payer = MockRuntimeAccount::new_unique();
+        let mut p_ctx = MockRuntimeAccount::new_unique();
+        let mut p_acc = MockRuntimeAccount::new_unique();
+        let mut p_escrow = MockRuntimeAccount::new_unique();
+        let mut p_prog = MockRuntimeAccount::new_unique();
+
+        let mut action_buf = PinoUpdateMagicActionBuf::<16>::new();
+        let handler = PinoUpdateMagicAction { counter, authority }
+            .into_call_handler(
+                &amount.to_le_bytes(),
+                p_escrow.as_account_view(),
+                200_000,
+                &mut action_buf,
+            )
+            .unwrap();
+        let writable: Vec<bool> = handler.accounts.iter().map(|a| a.is_writable).collect();
+        assert_eq!(writable, [true, false]);
+
+        let commit_accs = [p_acc.as_account_view()];
+        let mut buf = [0u8; CPI_DATA_BUF_SIZE];
+        let (pino_len, _) = MagicIntentBundleBuilder::new(
+            p_payer.as_account_view(),
+            p_ctx.as_account_view(),
+            p_prog.as_account_view(),
+        )
+        .commit(&commit_accs)
+        .add_post_commit_actions(&[handler])
+        .build_serialized(&mut buf);
+
+        let mut small_buf = PinoUpdateMagicActionBuf::<8>::new();
+        assert!(matches!(
+            PinoUpdateMagicAction { counter, authority }.into_call_handler(
+                &amount.to_le_bytes(),
+                p_escrow.as_account_view(),
+                200_000,
+                &mut small_buf,
+            ),
+            Err(ProgramError::Custom(CAPACITY_EXCEEDED_ERROR))
+        ));
+
+        // --- SDK ---
+        let mut s_payer = SdkTestAccount::new(p_payer.address);
+        let mut s_ctx = SdkTestAccount::new(p_ctx.address);
+        let mut s_acc = SdkTestAccount::new(p_acc.address);
+        let mut s_escrow = SdkTestAccount::new(p_escrow.address);
+        let mut s_prog = SdkTestAccount::new(p_prog.address);
+
+        let sdk_handler = SdkUpdateMagicAction { counter, authority }.into_call_handler(
+            amount,
+            s_escrow.as_signer_info(),
+            200_000,
+        );
+        let (_, ix) = SdkBuilder::new(
+            s_payer.as_account_info(),
+            s_ctx.as_account_info(),
+            s_prog.as_account_info(),
+        )
+        .commit(&[s_acc.as_account_info()])
+        .add_post_commit_actions([sdk_handler])
+        .build()
+        .schedule_intent_ix;
+
+        assert_eq!(&buf[..pino_len], &ix.data, "magic action mismatch");
+    }
+
+    // -----------------------------------------------------------------
+    // Escrow and action callback
+    // -----------------------------------------------------------------
+
+    #[test]
+    fn test_compat_escrow_pda() {
+        let escrow_authority = MagicPubkey::new_unique();
+        for index in [0, 7, u8::MAX] {
+            assert_eq!(
+                escrow_pda(&Address::new_from_array(escrow_authority.to_bytes()), index).to_bytes(),
+                ephemeral_rollups_sdk::cpi::escrow_pda(&escrow_authority, index).to_bytes()
+            );
+        }
+    }
+
+    #[test]
+    fn test_verify_escrow() {
+        let mut escrow_authority = MockRuntimeAccount::new_unique();
+        let escrow_address = escrow_pda(&Address::new_from_array(escrow_authority.address), 7);
+        let mut escrow = MockRuntimeAccount::new(escrow_address.to_bytes());
+        escrow.is_signer = 1;
+        let escrow_authority = escrow_authority.as_account_view();
+        let escrow_view = escrow.as_account_view();
+
+        assert_eq!(verify_escrow(&escrow_view, &escrow_authority, 7), Ok(()));
+        assert_eq!(
+            verify_escrow(&escrow_view, &escrow_authority, u8::MAX),
+            Err(ProgramError::Custom(INVALID_ESCROW_ERROR))
+        );
+        assert_eq!(
+            verify_escrow(&escrow_authority, &escrow_view, 7),
+            Err(ProgramError::Custom(ESCROW_NOT_SIGNER_ERROR))
+        );
+    }
+
+    #[test]
+    fn test_verify_action_callback() {
+        assert_eq!(
+            CALLBACK_SIGNER.to_bytes(),
+            magicblock_magic_program_api::pda::CALLBACK_SIGNER.to_bytes()
+        );
+        let mut escrow_authority = MockRuntimeAccount::new_unique();
+        let escrow_address = escrow_pda(&Address::new_from_array(escrow_authority.address), 3);
+        let mut escrow = MockRuntimeAccount::new(escrow_address.to_bytes());
+        let mut callback_signer = MockRuntimeAccount::new(CALLBACK_SIGNER.to_bytes());
+        callback_signer.is_signer = 1;
+        let mut other_signer = MockRuntimeAccount::new_unique();
+        other_signer.is_signer = 1;
+        let escrow_authority = escrow_authority.as_account_view();
+        let escrow = escrow.as_account_view();
+        let signer_view = callback_signer.as_account_view();
+
+        assert_eq!(
+            verify_action_callback(&signer_view, &escrow_authority, &escrow, 3),
+            Ok(())
+        );
+        assert_eq!(
+            verify_action_callback(&signer_view, &escrow_authority, &escrow, 4),
+            Err(ProgramError::Custom(INVALID_ESCROW_ERROR))
+        );
+        assert_eq!(
+            verify_action_callback(
+                &other_signer.as_account_view(),
+                &escrow_authority,
+                &escrow,
+                3
+            ),
+            Err(ProgramError::Custom(INVALID_CALLBACK_SIGNER_ERROR))
+        );
+        callback_signer.is_signer = 0;
+        assert_eq!(
+            verify_action_callback(
+                &callback_signer.as_account_view(),
+                &escrow_authority,
+                &escrow,
+                3
+            ),
+            Err(ProgramError::Custom(INVALID_CALLBACK_SIGNER_ERROR))
+        );
+    }
+
+    #[test]
+    fn test_compat_action_callback_response() {
+        use ephemeral_rollups_sdk::ephem::ActionCallbackResponse as SdkActionCallbackResponse;
+        use magicblock_magic_program_api::response::{
+            ActionReceipt, MagicResponse, MagicResponseV1,
+        };
+
+        let cases = [
+            (true, 5u64.to_le_bytes().to_vec(), "", Some([7u8; 64])),
+            (false, vec![1, 2, 3], "TimeoutError", None),
+        ];
+        for (ok, data, error, signature) in cases {
+            let encoded = bincode1::serialize(&MagicResponse::V1(MagicResponseV1 {
+                ok,
+                data,
+                error: error.into(),
+                receipt: signature.map(|signature| ActionReceipt {
+                    signature: signature.into(),
+                }),
+            }))
+            .unwrap();
+
+            let pino = ActionCallbackResponse::decode(&encoded).unwrap();
+            let sdk = SdkActionCallbackResponse::decode(&encoded).unwrap();
+            assert_eq!(
+                pino.result,
+                sdk.result.as_ref().map_err(|err| err.as_str()).copied()
+            );
+            assert_eq!(pino.data, sdk.data.as_slice());
+            assert_eq!(pino.signature, sdk.signature.as_ref());
+            assert_eq!(pino.sequence(), sdk.sequence());
+
+            assert_eq!(
+                ActionCallbackResponse::decode(&encoded[..encoded.len() - 1]),
+                Err(ProgramError::Custom(INVALID_MAGIC_RESPONSE_ERROR))
+            );
+        }
+
+        let encoded = bincode1::serialize(&MagicResponse::V1(MagicResponseV1 {
+            ok: true,
+            data: 5u64.to_le_bytes().to_vec(),
+            error: std::string::String::new(),
+            receipt: None,
+        }))
+        .unwrap();
+        let response = ActionCallbackResponse::decode(&encoded).unwrap();
+        let mut sequence = CallbackSequence::default();
+        assert_eq!(response.reject_replay(&mut sequence), Ok(()));
+        assert_eq!(
+            response.reject_replay(&mut sequence),
+            Err(ProgramError::Custom(CALLBACK_REPLAYED_ERROR))
+        );
+    }
+
+    fn sequenced_response(sequence: u64) -> std::vec::Vec<u8> {
+        bincode1::serialize(&magicblock_magic_program_api::response::MagicResponse::V1(
+            magicblock_magic_program_api::response::MagicResponseV1 {
+                ok: true,
+                data: sequence.to_le_bytes().to_vec(),
+                error: std::string::String::new(),
+                receipt: None,
+            },
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_reject_out_of_order_replay() {
+        use ephemeral_rollups_sdk::ephem::CallbackSequence as SdkCallbackSequence;
+
+        let mut sequence = CallbackSequence::default();
+        let mut sdk_sequence = SdkCallbackSequence::default();
+        for n in [3, 1, 7, 2, 0, 5, 7 + CallbackSequence::WINDOW, 6, 8] {
+            let encoded = sequenced_response(n);
+            let response = ActionCallbackResponse::decode(&encoded).unwrap();
+            assert_eq!(
+                response.reject_replay(&mut sequence).is_ok(),
+                sdk_sequence.accept(n),
+                "{n}"
+            );
+            assert_eq!(sequence.to_bytes(), sdk_sequence.to_bytes());
+        }
+        for n in [0, 1, 2, 3, 5, 7] {
+            let encoded = sequenced_response(n);
+            assert_eq!(
+                ActionCallbackResponse::decode(&encoded)
+                    .unwrap()
+                    .reject_replay(&mut sequence),
+                Err(ProgramError::Custom(CALLBACK_REPLAYED_ERROR)),
+                "{n}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_reject_callback_replay() {
+        #[repr(C)]
+        struct AccountWithData<const N: usize> {
+            account: MockRuntimeAccount,
+            data: [u8; N],
+        }
+
+        let mut callback_sequence = AccountWithData {
+            account: MockRuntimeAccount::new_unique(),
+            data: [0u8; CallbackSequence::LEN],
+        };
+        callback_sequence.account.data_len = CallbackSequence::LEN as u64;
+        let view = callback_sequence.account.as_account_view();
+        for (n, result) in [
+            (2, Ok(())),
+            (1, Ok(())),
+            (2, Err(ProgramError::Custom(CALLBACK_REPLAYED_ERROR))),
+        ] {
+            let encoded = sequenced_response(n);
+            let response = ActionCallbackResponse::decode(&encoded).unwrap();
+            assert_eq!(reject_callback_replay(&view, &response), result, "{n}");
+        }
+        assert_eq!(
+            CallbackSequence::from_bytes(&callback_sequence.data),
+            CallbackSequence {
+                highest: 2,
+                window: 0b11
+            }
+        );
+
+        let mut too_small = AccountWithData {
+            account: MockRuntimeAccount::new_unique(),
+            data: [0u8; 8],
+        };
+        too_small.account.data_len = 8;
+        let encoded = sequenced_response(1);
+        assert_eq!(
+            reject_callback_replay(
+                &too_small.account.as_account_view(),
+                &ActionCallbackResponse::decode(&encoded).unwrap()
+            ),
+            Err(ProgramError::Custom(INVALID_CALLBACK_SEQUENCE_ERROR))
+        );
+    }
 }
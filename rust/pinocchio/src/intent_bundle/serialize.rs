@@ -14,6 +14,7 @@ use crate::intent_bundle::types::{
     get_index, CallHandler, CommitAndUndelegateIntent, CommitIntent, MagicIntentBundle,
 };
 use crate::intent_bundle::ActionCallback;
+use bincode::enc::write::SizeWriter;
 use bincode::enc::Encoder;
 use bincode::error::EncodeError;
 use bincode::Encode;
@@ -152,15 +153,14 @@ impl bincode::Encode for CommitAndUndelegateSerialize<'_, '_, '_> {
 ///
 /// `Option<CommitSerialize>` and `Option<CommitAndUndelegateSerialize>` are encoded
 /// by bincode's blanket `Option<T: Encode>` impl (`0u8` / `1u8 + value`), matching
-/// the bincode 1.x wire format.
+/// the bincode 1.x wire format. The finalize intents share the wire format of their
+/// non-finalize counterparts.
 pub(super) struct MagicIntentBundleSerialize<'i, 'acc, 'args> {
     indices_map: &'i [&'i Address],
     commit: Option<CommitSerialize<'i, 'acc, 'args>>,
     commit_and_undelegate: Option<CommitAndUndelegateSerialize<'i, 'acc, 'args>>,
-    /// Not yet implemented; always `None`. Reserved for wire-format compatibility.
-    commit_finalize: Option<()>,
-    /// Not yet implemented; always `None`. Reserved for wire-format compatibility.
-    commit_finalize_and_undelegate: Option<()>,
+    commit_finalize: Option<CommitSerialize<'i, 'acc, 'args>>,
+    commit_finalize_and_undelegate: Option<CommitAndUndelegateSerialize<'i, 'acc, 'args>>,
     standalone_actions: &'args [CallHandler<'args>],
 }
 
@@ -176,8 +176,12 @@ impl<'i, 'acc, 'args> MagicIntentBundleSerialize<'i, 'acc, 'args> {
             commit_and_undelegate: bundle
                 .commit_and_undelegate_intent
                 .map(|c| CommitAndUndelegateSerialize::new(c, indices_map)),
-            commit_finalize: None,
-            commit_finalize_and_undelegate: None,
+            commit_finalize: bundle
+                .commit_finalize_intent
+                .map(|c| CommitSerialize::new(c, indices_map)),
+            commit_finalize_and_undelegate: bundle
+                .commit_finalize_and_undelegate_intent
+                .map(|c| CommitAndUndelegateSerialize::new(c, indices_map)),
             standalone_actions: bundle.standalone_actions,
             indices_map,
         }
@@ -197,6 +201,14 @@ impl<'i, 'acc, 'args> MagicIntentBundleSerialize<'i, 'acc, 'args> {
         Ok(OFFSET + len)
     }
 
+    /// Length of the instruction data written by [`Self::encode_intent_into_slice`].
+    pub(super) fn encoded_intent_len(&self) -> Result<usize, ProgramError> {
+        let mut writer = SizeWriter::default();
+        bincode::encode_into_writer(self, &mut writer, bincode::config::legacy())
+            .map_err(|_| ProgramError::InvalidInstructionData)?;
+        Ok(SCHEDULE_INTENT_BUNDLE_DISCRIMINANT.len() + writer.bytes_written)
+    }
+
     pub(super) fn action_callback_iter(&self) -> ActionCallbackIter<'_, '_, '_, '_> {
         ActionCallbackIter::new(self)
     }
@@ -221,12 +233,20 @@ impl<'i, 'acc, 'args> MagicIntentBundleSerialize<'i, 'acc, 'args> {
             action_index -= cau_action_len;
         }
 
-        if let Some(ref _commit_finalize) = self.commit_finalize {
-            // TODO: implement once supported
+        if let Some(ref commit_finalize) = self.commit_finalize {
+            let commit_finalize_action_len = commit_finalize.get_actions_len();
+            if action_index < commit_finalize_action_len {
+                return commit_finalize.get_action_callback(action_index);
+            }
+            action_index -= commit_finalize_action_len;
         }
 
-        if let Some(ref _commit_finalize_and_undelegate) = self.commit_finalize_and_undelegate {
-            // TODO: implement once supported
+        if let Some(ref commit_finalize_and_undelegate) = self.commit_finalize_and_undelegate {
+            let cfau_action_len = commit_finalize_and_undelegate.get_actions_len();
+            if action_index < cfau_action_len {
+                return commit_finalize_and_undelegate.get_action_callback(action_index);
+            }
+            action_index -= cfau_action_len;
         }
 
         let standalone_action_len = self.standalone_actions.len();
@@ -237,7 +257,6 @@ impl<'i, 'acc, 'args> MagicIntentBundleSerialize<'i, 'acc, 'args> {
         }
     }
 
-    #[allow(clippy::identity_op)]
     pub(super) fn get_actions_len(&self) -> usize {
         self.standalone_actions.len()
             + self
@@ -250,8 +269,16 @@ impl<'i, 'acc, 'args> MagicIntentBundleSerialize<'i, 'acc, 'args> {
                 .as_ref()
                 .map(|el| el.get_actions_len())
                 .unwrap_or(0)
-            + 0 // TODO: support commit_finalize & commit_finalize_and_undelegate
-            + 0 // TODO: support commit_finalize_and_undelegate
+            + self
+                .commit_finalize
+                .as_ref()
+                .map(|el| el.get_actions_len())
+                .unwrap_or(0)
+            + self
+                .commit_finalize_and_undelegate
+                .as_ref()
+                .map(|el| el.get_actions_len())
+                .unwrap_or(0)
     }
 
     pub(super) fn get_standalone_action_callback(
@@ -334,7 +361,7 @@ impl AddActionCallbackArgs<'_> {
 /// Encodes a `&[CallHandler]` as a bincode-legacy slice (u64 length + elements).
 ///
 /// Each handler is encoded by constructing a [`BaseActionArgs`] locally (~80 bytes),
-/// avoiding the 808-byte `NoVec<BaseActionArgs, MAX_ACTIONS_NUM>` allocation.
+/// avoiding the 808-byte `NoVec<BaseActionArgs, MAX_INTENT_BUNDLE_ACTIONS>` allocation.
 #[inline(never)]
 fn encode_handler_slice<E: Encoder>(
     handlers: &[CallHandler<'_>],
@@ -8,7 +8,7 @@ use pinocchio::error::ProgramError;
 use pinocchio::{AccountView, ProgramResult};
 use solana_address::Address;
 
-use super::MAX_ACTIONS_NUM;
+use super::{MAX_INTENT_BUNDLE_ACTIONS, TOO_MANY_ACTIONS_ERROR};
 
 /// Intent to be scheduled for execution on the base layer.
 ///
@@ -22,6 +22,12 @@ pub enum MagicIntent<'acc, 'args> {
     Commit(CommitIntent<'acc, 'args>),
     /// Commit accounts and undelegate them, optionally with post-commit and post-undelegate actions.
     CommitAndUndelegate(CommitAndUndelegateIntent<'acc, 'args>),
+    /// Commit and finalize accounts on base layer in a single step, optionally with
+    /// post-commit actions.
+    CommitFinalize(CommitIntent<'acc, 'args>),
+    /// Commit and finalize accounts in a single step and undelegate them, optionally with
+    /// post-commit and post-undelegate actions.
+    CommitFinalizeAndUndelegate(CommitAndUndelegateIntent<'acc, 'args>),
 }
 
 /// Bundle of Intents
@@ -30,13 +36,23 @@ pub enum MagicIntent<'acc, 'args> {
 /// they will be undelegated
 ///
 /// Intents assumed to be independent and self-sufficient,
-/// hence order in which they were inserted doesn't matter
-#[derive(Default)]
+/// hence order in which they were inserted doesn't matter.
+/// Standalone actions are executed in order, see [`ActionSequence`]
+///
+/// [`ActionSequence`]: crate::intent_bundle::ActionSequence
+#[derive(Default, Clone, Copy)]
 pub(in crate::intent_bundle) struct MagicIntentBundle<'acc, 'args> {
     pub(in crate::intent_bundle) standalone_actions: &'args [CallHandler<'args>],
+    /// Whether the standalone actions are an [`ActionSequence`], scheduled whole
+    ///
+    /// [`ActionSequence`]: crate::intent_bundle::ActionSequence
+    pub(in crate::intent_bundle) standalone_sequence: bool,
     pub(in crate::intent_bundle) commit_intent: Option<CommitIntent<'acc, 'args>>,
     pub(in crate::intent_bundle) commit_and_undelegate_intent:
         Option<CommitAndUndelegateIntent<'acc, 'args>>,
+    pub(in crate::intent_bundle) commit_finalize_intent: Option<CommitIntent<'acc, 'args>>,
+    pub(in crate::intent_bundle) commit_finalize_and_undelegate_intent:
+        Option<CommitAndUndelegateIntent<'acc, 'args>>,
 }
 
 impl<'args> MagicIntentBundle<'_, 'args> {
@@ -56,7 +72,16 @@ impl<'args> MagicIntentBundle<'_, 'args> {
             .commit_and_undelegate_intent
             .map(|c| c.into_args(indices_map))
             .transpose()?;
-        let mut standalone_actions = NoVec::<BaseActionArgs<'args>, MAX_ACTIONS_NUM>::new();
+        let commit_finalize = self
+            .commit_finalize_intent
+            .map(|c| c.into_args(indices_map))
+            .transpose()?;
+        let commit_finalize_and_undelegate = self
+            .commit_finalize_and_undelegate_intent
+            .map(|c| c.into_args(indices_map))
+            .transpose()?;
+        let mut standalone_actions =
+            NoVec::<BaseActionArgs<'args>, MAX_INTENT_BUNDLE_ACTIONS>::new();
         for ch in self.standalone_actions {
             standalone_actions.try_push(ch.args(indices_map)?)?;
         }
@@ -64,12 +89,48 @@ impl<'args> MagicIntentBundle<'_, 'args> {
         Ok(MagicIntentBundleArgs {
             commit,
             commit_and_undelegate,
-            commit_finalize: None,
-            commit_finalize_and_undelegate: None,
+            commit_finalize,
+            commit_finalize_and_undelegate,
             standalone_actions,
         })
     }
 
+    pub(super) fn is_empty(&self) -> bool {
+        self.standalone_actions.is_empty()
+            && self.commit_intent.is_none()
+            && self.commit_and_undelegate_intent.is_none()
+            && self.commit_finalize_intent.is_none()
+            && self.commit_finalize_and_undelegate_intent.is_none()
+    }
+
+    /// Returns the number of actions of all the intents.
+    /// Fails if they exceed `MAX_INTENT_BUNDLE_ACTIONS`.
+    pub(super) fn actions_len(&self) -> Result<usize, ProgramError> {
+        let mut lists = NoVec::<&[CallHandler<'args>], 7>::new();
+        lists.push(self.standalone_actions);
+        for commit in [self.commit_intent, self.commit_finalize_intent]
+            .into_iter()
+            .flatten()
+        {
+            lists.push(commit.actions);
+        }
+        for cau in [
+            self.commit_and_undelegate_intent,
+            self.commit_finalize_and_undelegate_intent,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            lists.append([cau.post_commit_actions, cau.post_undelegate_actions]);
+        }
+
+        let len = lists.iter().map(|actions| actions.len()).sum();
+        if len > MAX_INTENT_BUNDLE_ACTIONS {
+            return Err(ProgramError::Custom(TOO_MANY_ACTIONS_ERROR));
+        }
+        Ok(len)
+    }
+
     /// Collects all accounts referenced by intents in this bundle.
     #[inline(never)]
     pub(super) fn collect_unique_accounts(
@@ -85,13 +146,20 @@ impl<'args> MagicIntentBundle<'_, 'args> {
         if let Some(cau) = &self.commit_and_undelegate_intent {
             cau.collect_unique_accounts(unique_accounts)?;
         }
+        if let Some(commit_finalize) = &self.commit_finalize_intent {
+            commit_finalize.collect_unique_accounts(unique_accounts)?;
+        }
+        if let Some(cfau) = &self.commit_finalize_and_undelegate_intent {
+            cfau.collect_unique_accounts(unique_accounts)?;
+        }
         Ok(())
     }
 
     /// Validates the bundle:
     /// - Each present intent must have at least one committed account.
     /// - No duplicate accounts within an intent.
-    /// - No account overlap between `Commit` and `CommitAndUndelegate`.
+    /// - No account overlap between the `Commit`, `CommitAndUndelegate`, `CommitFinalize`
+    ///   and `CommitFinalizeAndUndelegate` intents.
     #[inline(never)]
     pub(super) fn validate(&self) -> ProgramResult {
         let mut seen = NoVec::<Address, MAX_STATIC_CPI_ACCOUNTS>::new();
@@ -103,6 +171,14 @@ impl<'args> MagicIntentBundle<'_, 'args> {
             cau.validate()?;
             cau.try_collect_unique_addresses(&mut seen)?;
         }
+        if let Some(commit_finalize) = &self.commit_finalize_intent {
+            commit_finalize.validate()?;
+            commit_finalize.try_collect_unique_addresses(&mut seen)?;
+        }
+        if let Some(cfau) = &self.commit_finalize_and_undelegate_intent {
+            cfau.validate()?;
+            cfau.try_collect_unique_addresses(&mut seen)?;
+        }
         Ok(())
     }
 }
@@ -176,7 +252,7 @@ impl<'args> CommitIntent<'_, 'args> {
         if self.actions.is_empty() {
             Ok(CommitTypeArgs::Standalone(indices))
         } else {
-            let mut base_actions = NoVec::<_, MAX_ACTIONS_NUM>::new();
+            let mut base_actions = NoVec::<_, MAX_INTENT_BUNDLE_ACTIONS>::new();
             for handler in self.actions {
                 base_actions.try_push(handler.args(indices_map)?)?;
             }
@@ -191,6 +267,33 @@ impl<'args> CommitIntent<'_, 'args> {
         self.actions.len()
     }
 
+    /// Number of parts the intent can be split into: its accounts, unless it has actions.
+    pub(super) fn parts_len(&self) -> usize {
+        if self.actions.is_empty() {
+            self.accounts.len()
+        } else {
+            1
+        }
+    }
+
+    /// Splits the intent after `parts` of [`Self::parts_len`].
+    pub(super) fn split_at(self, parts: usize) -> (Self, Option<Self>) {
+        if !self.actions.is_empty() || parts >= self.accounts.len() {
+            return (self, None);
+        }
+        let (head, tail) = self.accounts.split_at(parts);
+        (
+            Self {
+                accounts: head,
+                ..self
+            },
+            Some(Self {
+                accounts: tail,
+                ..self
+            }),
+        )
+    }
+
     pub(crate) fn get_action_callback(&self, ind: usize) -> Option<&ActionCallback<'args>> {
         self.actions.get(ind).and_then(|el| el.callback.as_ref())
     }
@@ -267,7 +370,7 @@ impl<'args> CommitAndUndelegateIntent<'_, 'args> {
         let commit_type = if self.post_commit_actions.is_empty() {
             CommitTypeArgs::Standalone(indices)
         } else {
-            let mut base_actions = NoVec::<_, MAX_ACTIONS_NUM>::new();
+            let mut base_actions = NoVec::<_, MAX_INTENT_BUNDLE_ACTIONS>::new();
             for handler in self.post_commit_actions {
                 base_actions.try_push(handler.args(indices_map)?)?;
             }
@@ -281,7 +384,7 @@ impl<'args> CommitAndUndelegateIntent<'_, 'args> {
         let undelegate_type = if self.post_undelegate_actions.is_empty() {
             UndelegateTypeArgs::Standalone
         } else {
-            let mut base_actions = NoVec::<_, MAX_ACTIONS_NUM>::new();
+            let mut base_actions = NoVec::<_, MAX_INTENT_BUNDLE_ACTIONS>::new();
             for handler in self.post_undelegate_actions {
                 base_actions.try_push(handler.args(indices_map)?)?;
             }
@@ -298,6 +401,34 @@ impl<'args> CommitAndUndelegateIntent<'_, 'args> {
         self.post_commit_actions.len() + self.post_undelegate_actions.len()
     }
 
+    /// Number of parts the intent can be split into: its accounts, unless it has actions.
+    /// An account is always committed and undelegated by the same part.
+    pub(super) fn parts_len(&self) -> usize {
+        if self.get_actions_len() == 0 {
+            self.accounts.len()
+        } else {
+            1
+        }
+    }
+
+    /// Splits the intent after `parts` of [`Self::parts_len`].
+    pub(super) fn split_at(self, parts: usize) -> (Self, Option<Self>) {
+        if self.get_actions_len() != 0 || parts >= self.accounts.len() {
+            return (self, None);
+        }
+        let (head, tail) = self.accounts.split_at(parts);
+        (
+            Self {
+                accounts: head,
+                ..self
+            },
+            Some(Self {
+                accounts: tail,
+                ..self
+            }),
+        )
+    }
+
     pub(crate) fn get_action_callback(
         &self,
         mut action_index: usize,
@@ -1,11 +1,16 @@
 #![no_std]
 
 extern crate alloc;
+// Lets `#[derive(MagicAction)]` expansions resolve the crate in its own tests
+#[cfg(all(test, feature = "magic-action"))]
+extern crate self as ephemeral_rollups_pinocchio;
 
 pub mod acl;
 pub mod consts;
 pub mod crank;
+pub mod delegation;
 pub mod ephemeral_accounts;
+pub mod error;
 pub mod instruction;
 pub mod intent_bundle;
 pub mod pda;
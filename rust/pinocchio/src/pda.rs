@@ -5,6 +5,10 @@ pub(crate) fn find_program_address(seeds: &[&[u8]], program_id: &Address) -> (Ad
     Address::find_program_address(seeds, program_id)
 }
 
+pub(crate) fn create_program_address(seeds: &[&[u8]], program_id: &Address) -> Option<Address> {
+    Address::create_program_address(seeds, program_id).ok()
+}
+
 /// Find a PDA from a typed `Seed`
 fn find_seed_pda(seed: &Seed, program_id: &Address) -> Address {
     let mut buf: [&[u8]; 3] = [&[]; 3];
@@ -0,0 +1,348 @@
+//! Tracking of state commits, scheduled on ER, all the way to their finalization on base chain
+//!
+//! The flow mirrors what magic program and validator report via transaction logs:
+//! 1. the ER transaction, which scheduled the commit, logs the signature of the ER transaction
+//!    which will eventually send the commit (`ScheduledCommitSent signature: <sig>`)
+//! 2. once processed, the latter logs the signatures of the base chain transactions, which
+//!    perform the actual commit and finalization (`ScheduledCommitSent signature[i]: <sig>`)
+//! 3. those base chain transactions are then watched until they are finalized
+
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use futures::{
+    channel::mpsc::{unbounded, UnboundedSender},
+    future::select_ok,
+    Stream,
+};
+use rpc::nonblocking::rpc_client::RpcClient;
+use rpc_api::{
+    client_error::{Error as ClientError, ErrorKind as ClientErrorKind},
+    config::RpcTransactionConfig,
+};
+use sdk::{commitment_config::CommitmentConfig, signature::Signature};
+
+use crate::{error::Error, ResolverResult};
+
+/// Log prefix, via which the scheduling transaction reports the ER transaction sending the commit
+const SCHEDULED_COMMIT_SENT_PREFIX: &str = "ScheduledCommitSent signature: ";
+/// Log prefix, via which the sending transaction reports base chain signatures, the full
+/// format of the log line is `ScheduledCommitSent signature[<index>]: <signature>`
+const COMMIT_SIGNATURE_PREFIX: &str = "ScheduledCommitSent signature[";
+/// The delay between consecutive polls of transaction state
+const POLL_INTERVAL: Duration = Duration::from_millis(400);
+/// Max number of polls for each stage, before giving up (roughly 2 minutes)
+const MAX_POLL_ATTEMPTS: usize = 300;
+
+/// Progress of the commit, as observed by resolver, events are emitted in the listed order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommitProgress {
+    /// Commit has been scheduled on ER, and will be sent by the given ER transaction
+    Scheduled { sent_signature: Signature },
+    /// Commit has been sent to base chain, with the listed (commit and finalize) transactions
+    Sent { signatures: Vec<Signature> },
+    /// Base chain transaction has reached confirmed commitment level
+    Confirmed { signature: Signature, slot: u64 },
+    /// Base chain transaction has been finalized, once all of the transactions reported via
+    /// [`CommitProgress::Sent`] are finalized, the tracking is complete
+    Finalized { signature: Signature, slot: u64 },
+}
+
+/// Start tracking the commit scheduled by `er_signature` transaction, the `ers` are the clients
+/// to query for the ER side transactions, and `chain` is used to watch the base chain
+pub fn track(
+    er_signature: Signature,
+    ers: Vec<Arc<RpcClient>>,
+    chain: Arc<RpcClient>,
+) -> impl Stream<Item = ResolverResult<CommitProgress>> {
+    let (tx, rx) = unbounded();
+    tokio::spawn(async move {
+        if let Err(error) = track_inner(er_signature, ers, chain, &tx).await {
+            let _ = tx.unbounded_send(Err(error));
+        }
+    });
+    rx
+}
+
+async fn track_inner(
+    er_signature: Signature,
+    ers: Vec<Arc<RpcClient>>,
+    chain: Arc<RpcClient>,
+    tx: &UnboundedSender<ResolverResult<CommitProgress>>,
+) -> ResolverResult<()> {
+    if ers.is_empty() {
+        return Err(Error::Resolver("no ER routes available".into()));
+    }
+    let (logs, er) = select_ok(ers.into_iter().map(|er| {
+        Box::pin(async move { fetch_logs(&er, &er_signature).await.map(|logs| (logs, er)) })
+    }))
+    .await?
+    .0;
+    let sent_signature = parse_scheduled_commit(&logs).ok_or_else(|| {
+        Error::Resolver(format!(
+            "no scheduled commit found in transaction: {er_signature}"
+        ))
+    })?;
+    emit(tx, CommitProgress::Scheduled { sent_signature })?;
+
+    let logs = fetch_logs(&er, &sent_signature).await?;
+    let signatures = parse_commit_signatures(&logs);
+    if signatures.is_empty() {
+        return Err(Error::Resolver(format!(
+            "no commit signatures found in transaction: {sent_signature}"
+        )));
+    }
+    emit(
+        tx,
+        CommitProgress::Sent {
+            signatures: signatures.clone(),
+        },
+    )?;
+
+    let mut confirmed = vec![false; signatures.len()];
+    let mut finalized = vec![false; signatures.len()];
+    for _ in 0..MAX_POLL_ATTEMPTS {
+        let statuses = chain
+            .get_signature_statuses(&signatures)
+            .await
+            .map_err(Box::new)?
+            .value;
+        for (i, status) in statuses.into_iter().enumerate() {
+            let Some(status) = status else {
+                continue;
+            };
+            let signature = signatures[i];
+            if let Some(err) = status.err {
+                return Err(Error::Resolver(format!(
+                    "commit transaction {signature} failed: {err}"
+                )));
+            }
+            let slot = status.slot;
+            if !confirmed[i] && status.satisfies_commitment(CommitmentConfig::confirmed()) {
+                confirmed[i] = true;
+                emit(tx, CommitProgress::Confirmed { signature, slot })?;
+            }
+            if !finalized[i] && status.satisfies_commitment(CommitmentConfig::finalized()) {
+                finalized[i] = true;
+                emit(tx, CommitProgress::Finalized { signature, slot })?;
+            }
+        }
+        if finalized.iter().all(|f| *f) {
+            return Ok(());
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+    Err(Error::Resolver(format!(
+        "timed out waiting for commit finalization: {er_signature}"
+    )))
+}
+
+/// Fetch the log messages of ER transaction, retrying while it's not yet available, other
+/// errors, e.g. the rejection of the request by the ER, are returned right away
+async fn fetch_logs(er: &RpcClient, signature: &Signature) -> ResolverResult<Vec<String>> {
+    let config = RpcTransactionConfig {
+        commitment: Some(CommitmentConfig::confirmed()),
+        max_supported_transaction_version: Some(0),
+        ..Default::default()
+    };
+    let mut attempt = 0;
+    loop {
+        match er.get_transaction_with_config(signature, config).await {
+            Ok(tx) => {
+                let logs = tx
+                    .transaction
+                    .meta
+                    .and_then(|meta| Option::<Vec<String>>::from(meta.log_messages));
+                return logs.ok_or_else(|| {
+                    Error::Resolver(format!(
+                        "no log messages found for transaction: {signature}"
+                    ))
+                });
+            }
+            Err(err) if is_transient(&err) && attempt < MAX_POLL_ATTEMPTS => {
+                attempt += 1;
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+            Err(err) => return Err(Box::new(err).into()),
+        }
+    }
+}
+
+/// Whether the failed request may succeed once retried
+fn is_transient(err: &ClientError) -> bool {
+    match err.kind() {
+        // transaction is not yet available (null result), either it hasn't been processed
+        // or the ER hasn't reached the required commitment level for it
+        ClientErrorKind::SerdeJson(_) => true,
+        // the ER couldn't be reached
+        ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_) => true,
+        _ => false,
+    }
+}
+
+/// Send the progress event to the subscriber, fails if subscriber is no longer interested
+fn emit(
+    tx: &UnboundedSender<ResolverResult<CommitProgress>>,
+    progress: CommitProgress,
+) -> ResolverResult<()> {
+    tx.unbounded_send(Ok(progress))
+        .map_err(|_| Error::Resolver("commit progress subscriber has been dropped".into()))
+}
+
+/// Extract the signature of ER transaction, which sends the scheduled commit to base chain
+fn parse_scheduled_commit(logs: &[String]) -> Option<Signature> {
+    logs.iter().find_map(|log| {
+        let (_, sig) = log.split_once(SCHEDULED_COMMIT_SENT_PREFIX)?;
+        Signature::from_str(sig.trim()).ok()
+    })
+}
+
+/// Extract base chain signatures of commit (and finalize) transactions, ordered by their index
+fn parse_commit_signatures(logs: &[String]) -> Vec<Signature> {
+    let mut signatures: Vec<(usize, Signature)> = logs
+        .iter()
+        .filter_map(|log| {
+            let (_, rest) = log.split_once(COMMIT_SIGNATURE_PREFIX)?;
+            let (index, sig) = rest.split_once("]: ")?;
+            Some((index.parse().ok()?, Signature::from_str(sig.trim()).ok()?))
+        })
+        .collect();
+    signatures.sort_by_key(|(index, _)| *index);
+    signatures.dedup_by_key(|(_, sig)| *sig);
+    signatures.into_iter().map(|(_, sig)| sig).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        io,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use rpc::{
+        mock_sender::MockSender,
+        rpc_client::RpcClientConfig,
+        rpc_sender::{RpcSender, RpcTransportStats},
+    };
+    use rpc_api::request::{RpcError, RpcRequest, RpcResponseErrorData};
+    use serde_json::Value;
+
+    use super::*;
+
+    /// Response of the mocked ER to `getTransaction`
+    enum Reply {
+        /// The transaction isn't available yet
+        NotFound,
+        /// The ER can't be reached
+        Unreachable,
+        /// The request is rejected by the ER
+        Rejected,
+        /// The transaction, with the given log messages
+        Found(Vec<String>),
+    }
+
+    /// ER replying to `getTransaction` requests in order, counting them
+    struct MockEr {
+        replies: parking_lot::Mutex<VecDeque<Reply>>,
+        requests: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl RpcSender for MockEr {
+        async fn send(
+            &self,
+            request: RpcRequest,
+            params: Value,
+        ) -> rpc_api::client_error::Result<Value> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            let reply = self.replies.lock().pop_front().expect("unexpected request");
+            match reply {
+                Reply::NotFound => Ok(Value::Null),
+                Reply::Unreachable => Err(io::Error::from(io::ErrorKind::ConnectionRefused).into()),
+                Reply::Rejected => Err(RpcError::RpcResponseError {
+                    code: -32602,
+                    message: "Invalid param: invalid signature".into(),
+                    data: RpcResponseErrorData::Empty,
+                }
+                .into()),
+                Reply::Found(logs) => {
+                    let mut tx = MockSender::new("succeeds").send(request, params).await?;
+                    tx["meta"]["logMessages"] = logs.into();
+                    Ok(tx)
+                }
+            }
+        }
+
+        fn get_transport_stats(&self) -> RpcTransportStats {
+            RpcTransportStats::default()
+        }
+
+        fn url(&self) -> String {
+            "mock-er".into()
+        }
+    }
+
+    fn mock_er(replies: impl IntoIterator<Item = Reply>) -> (RpcClient, Arc<AtomicUsize>) {
+        let requests = Arc::<AtomicUsize>::default();
+        let sender = MockEr {
+            replies: parking_lot::Mutex::new(replies.into_iter().collect()),
+            requests: requests.clone(),
+        };
+        let config = RpcClientConfig::with_commitment(CommitmentConfig::confirmed());
+        (RpcClient::new_sender(sender, config), requests)
+    }
+
+    #[tokio::test]
+    async fn test_fetch_logs_retries_until_available() {
+        let logs = vec!["Program log: committed".to_string()];
+        let (er, requests) = mock_er([
+            Reply::NotFound,
+            Reply::Unreachable,
+            Reply::Found(logs.clone()),
+        ]);
+        let fetched = fetch_logs(&er, &Signature::from([1; 64])).await.unwrap();
+        assert_eq!(fetched, logs);
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_logs_fails_fast_on_rejection() {
+        let (er, requests) = mock_er([Reply::NotFound, Reply::Rejected]);
+        let result = fetch_logs(&er, &Signature::from([1; 64])).await;
+        assert!(matches!(result, Err(Error::Rpc(_))));
+        // the rejected request isn't retried
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    fn logs(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_scheduled_commit() {
+        let sig = Signature::from([7; 64]);
+        let logs = logs(&[
+            "Program Magic11111111111111111111111111111111111111 invoke [1]",
+            &format!("ScheduledCommitSent signature: {sig}"),
+            "Program Magic11111111111111111111111111111111111111 success",
+        ]);
+        assert_eq!(parse_scheduled_commit(&logs), Some(sig));
+        assert_eq!(parse_scheduled_commit(&logs[..1]), None);
+    }
+
+    #[test]
+    fn test_parse_commit_signatures() {
+        let commit = Signature::from([1; 64]);
+        let finalize = Signature::from([2; 64]);
+        let logs = logs(&[
+            "ScheduledCommitSent id: 42",
+            &format!("ScheduledCommitSent signature[1]: {finalize}"),
+            &format!("ScheduledCommitSent signature[0]: {commit}"),
+            "ScheduledCommitSent signature[2]: not-a-signature",
+        ]);
+        assert_eq!(parse_commit_signatures(&logs), vec![commit, finalize]);
+        // the scheduling log line must not be confused with the commit signatures
+        let scheduled = self::logs(&[&format!("ScheduledCommitSent signature: {commit}")]);
+        assert!(parse_commit_signatures(&scheduled).is_empty());
+    }
+}
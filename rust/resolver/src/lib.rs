@@ -10,12 +10,17 @@ use std::{
 
 use parking_lot::RwLock;
 
+pub use commit::CommitProgress;
 use config::Configuration;
 use error::Error;
+use futures::Stream;
 use http::{fetch_account_state, fetch_domain_records, update_account_state};
 use rpc::nonblocking::rpc_client::RpcClient;
 use scc::{hash_cache::Entry, HashCache};
-use sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, transaction::Transaction};
+use sdk::{
+    commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature,
+    transaction::Transaction,
+};
 use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
 use websocket::{
     connection::{delegations::WsDelegationsConnection, routes::WsRoutesConnection},
@@ -217,6 +222,19 @@ impl Resolver {
         Ok(self.chain.clone())
     }
 
+    /// Track the commit (or commit and undelegate), scheduled on ER by the transaction with given
+    /// signature. The ER transaction logs are parsed to find the corresponding base chain commit
+    /// and finalize transactions, which are then watched until finalized. The returned stream
+    /// yields [`CommitProgress`] events and terminates once all of the base chain transactions
+    /// are finalized, or after the first error encountered.
+    pub fn track_commit(
+        &self,
+        er_signature: Signature,
+    ) -> impl Stream<Item = ResolverResult<CommitProgress>> {
+        let ers = self.routes.read().values().cloned().collect();
+        commit::track(er_signature, ers, self.chain.clone())
+    }
+
     /// Get current delegation status for account, either from cache or
     /// from chain (if account is encoutered for the first time)
     async fn resolve_status(&self, pubkey: &Pubkey) -> ResolverResult<DelegationStatus> {
@@ -250,6 +268,7 @@ impl Resolver {
 }
 
 mod account;
+mod commit;
 pub mod config;
 mod error;
 mod http;
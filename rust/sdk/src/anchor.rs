@@ -1,8 +1,9 @@
-pub use ephemeral_rollups_sdk_attribute_action::action;
+pub use ephemeral_rollups_sdk_attribute_action::{action, action_callback};
 pub use ephemeral_rollups_sdk_attribute_commit::commit;
 pub use ephemeral_rollups_sdk_attribute_delegate::delegate;
 pub use ephemeral_rollups_sdk_attribute_ephemeral::ephemeral;
 pub use ephemeral_rollups_sdk_attribute_ephemeral_accounts::ephemeral_accounts;
+pub use ephemeral_rollups_sdk_attribute_magic_action::MagicAction;
 pub use ephemeral_vrf_sdk_vrf_macro::{vrf, vrf_callback};
 
 use crate::compat::anchor_lang;
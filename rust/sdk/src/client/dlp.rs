@@ -0,0 +1,408 @@
+//! Instruction builders for the delegation program.
+//!
+//! The builders derive all of the delegation program PDAs from the delegated account
+//! (and its owner), and produce instructions which are byte-for-byte identical to the
+//! ones issued by the CPI helpers in [`crate::cpi`], as both share the serialization.
+//!
+//! Delegating from a client only works for accounts which can sign the transaction,
+//! i.e. on-curve (system owned) accounts, or accounts which have been already assigned
+//! to the delegation program by their owner. PDAs should be delegated via CPI instead.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use ephemeral_rollups_sdk::client::dlp::DelegateBuilder;
+//!
+//! let ix = DelegateBuilder::new(payer, account)
+//!     .validator(validator)
+//!     .commit_frequency_ms(30_000)
+//!     .instruction();
+//! ```
+
+use dlp_api::{
+    args::{DelegateArgs, DelegateWithActionsArgs, PostDelegationActions},
+    discriminator::DlpDiscriminator,
+    pda::{
+        commit_record_pda_from_delegated_account, commit_state_pda_from_delegated_account,
+        delegation_metadata_pda_from_delegated_account,
+        delegation_record_pda_from_delegated_account, fees_vault_pda,
+        undelegate_buffer_pda_from_delegated_account, validator_fees_vault_pda_from_validator,
+    },
+};
+use solana_program::instruction::{AccountMeta, Instruction};
+
+use crate::{
+    compat::{self, AsModern, Compat},
+    cpi::DelegateConfig,
+    delegate_args::DelegateAccounts,
+    error::EphemeralRollupsSdkError,
+    types::DelegateAccountArgs,
+    utils::serialize_args,
+};
+
+pub use crate::ephemeral_balance::{
+    close_ephemeral_balance, delegate_ephemeral_balance, top_up_ephemeral_balance,
+};
+
+/// Builder for the `Delegate` and `DelegateWithAnyValidator` instructions
+pub struct DelegateBuilder {
+    payer: compat::Pubkey,
+    delegated_account: compat::Pubkey,
+    owner_program: compat::Pubkey,
+    seeds: Vec<Vec<u8>>,
+    config: DelegateConfig,
+    discriminator: DlpDiscriminator,
+}
+
+impl DelegateBuilder {
+    /// Delegate the `delegated_account`, which is assumed to be system owned,
+    /// unless a different owner is set via [`Self::owner_program`]
+    pub fn new(payer: compat::Pubkey, delegated_account: compat::Pubkey) -> Self {
+        Self {
+            payer,
+            delegated_account,
+            owner_program: solana_system_interface::program::ID.compat(),
+            seeds: Vec::new(),
+            config: DelegateConfig::default(),
+            discriminator: DlpDiscriminator::Delegate,
+        }
+    }
+
+    /// Same as [`Self::new`], but builds the `DelegateWithAnyValidator` instruction,
+    /// which accepts any validator identity set via [`Self::validator`]
+    pub fn with_any_validator(payer: compat::Pubkey, delegated_account: compat::Pubkey) -> Self {
+        Self {
+            discriminator: DlpDiscriminator::DelegateWithAnyValidator,
+            ..Self::new(payer, delegated_account)
+        }
+    }
+
+    /// Set the program owning the delegated account
+    pub fn owner_program(mut self, owner_program: compat::Pubkey) -> Self {
+        self.owner_program = owner_program;
+        self
+    }
+
+    /// Set the seeds of the delegated account (without bump), if it's a PDA of its owner
+    pub fn seeds(mut self, seeds: &[&[u8]]) -> Self {
+        self.seeds = seeds.iter().map(|s| s.to_vec()).collect();
+        self
+    }
+
+    /// Set the frequency at which the validator should commit the account
+    pub fn commit_frequency_ms(mut self, commit_frequency_ms: u32) -> Self {
+        self.config.commit_frequency_ms = commit_frequency_ms;
+        self
+    }
+
+    /// Set the validator, to which the account is delegated
+    pub fn validator(mut self, validator: compat::Pubkey) -> Self {
+        self.config.validator = Some(validator);
+        self
+    }
+
+    /// Attach post delegation actions, turning this into the `DelegateWithActions` instruction
+    pub fn with_actions(self, actions: PostDelegationActions) -> DelegateWithActionsBuilder {
+        DelegateWithActionsBuilder {
+            delegate: self,
+            actions,
+        }
+    }
+
+    /// Panics if the seeds can't be serialized
+    pub fn instruction(self) -> compat::Instruction {
+        let accounts = DelegateAccounts::new(self.delegated_account, self.owner_program);
+        let args = DelegateAccountArgs {
+            commit_frequency_ms: self.config.commit_frequency_ms,
+            seeds: self.seeds,
+            validator: self.config.validator,
+        };
+        delegate_instruction(self.discriminator, &self.payer, &accounts, &args)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+}
+
+/// Builder for the `DelegateWithActions` instruction, see [`DelegateBuilder::with_actions`]
+pub struct DelegateWithActionsBuilder {
+    delegate: DelegateBuilder,
+    actions: PostDelegationActions,
+}
+
+impl DelegateWithActionsBuilder {
+    /// Panics if the seeds or the actions can't be serialized
+    pub fn instruction(self) -> compat::Instruction {
+        let DelegateBuilder {
+            payer,
+            delegated_account,
+            owner_program,
+            seeds,
+            config,
+            ..
+        } = self.delegate;
+        let accounts = DelegateAccounts::new(delegated_account, owner_program);
+        let args = DelegateWithActionsArgs {
+            delegate: DelegateArgs {
+                commit_frequency_ms: config.commit_frequency_ms,
+                seeds,
+                validator: config.validator,
+            },
+            actions: self.actions,
+        };
+        delegate_with_actions_instruction(&payer, &accounts, &args)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+}
+
+/// Builder for the `Undelegate` instruction, which is issued by the validator,
+/// once the final state of the delegated account has been committed
+pub struct UndelegateBuilder {
+    validator: compat::Pubkey,
+    delegated_account: compat::Pubkey,
+    owner_program: compat::Pubkey,
+    rent_reimbursement: compat::Pubkey,
+}
+
+impl UndelegateBuilder {
+    /// The `rent_reimbursement` is the account, which has paid the rent for the delegation
+    pub fn new(
+        validator: compat::Pubkey,
+        delegated_account: compat::Pubkey,
+        owner_program: compat::Pubkey,
+        rent_reimbursement: compat::Pubkey,
+    ) -> Self {
+        Self {
+            validator,
+            delegated_account,
+            owner_program,
+            rent_reimbursement,
+        }
+    }
+
+    pub fn instruction(self) -> compat::Instruction {
+        let delegated = &self.delegated_account;
+        let undelegate_buffer = undelegate_buffer_pda_from_delegated_account(delegated);
+        let commit_state = commit_state_pda_from_delegated_account(delegated);
+        let commit_record = commit_record_pda_from_delegated_account(delegated);
+        let delegation_record = delegation_record_pda_from_delegated_account(delegated);
+        let delegation_metadata = delegation_metadata_pda_from_delegated_account(delegated);
+        let validator_fees_vault = validator_fees_vault_pda_from_validator(&self.validator);
+
+        Instruction {
+            program_id: *crate::id().as_modern(),
+            accounts: vec![
+                AccountMeta::new(*self.validator.as_modern(), true),
+                AccountMeta::new(*delegated.as_modern(), false),
+                AccountMeta::new_readonly(*self.owner_program.as_modern(), false),
+                AccountMeta::new(*undelegate_buffer.as_modern(), false),
+                AccountMeta::new_readonly(*commit_state.as_modern(), false),
+                AccountMeta::new_readonly(*commit_record.as_modern(), false),
+                AccountMeta::new(*delegation_record.as_modern(), false),
+                AccountMeta::new(*delegation_metadata.as_modern(), false),
+                AccountMeta::new(*self.rent_reimbursement.as_modern(), false),
+                AccountMeta::new(*fees_vault_pda().as_modern(), false),
+                AccountMeta::new(*validator_fees_vault.as_modern(), false),
+                AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+            ],
+            data: DlpDiscriminator::Undelegate.to_vec(),
+        }
+        .compat()
+    }
+}
+
+/// Build the `Delegate` (or `DelegateWithAnyValidator`) instruction, shared by
+/// the builders and the CPI helpers
+pub(crate) fn delegate_instruction(
+    discriminator: DlpDiscriminator,
+    payer: &compat::Pubkey,
+    accounts: &DelegateAccounts,
+    args: &DelegateAccountArgs,
+) -> Result<compat::Instruction, EphemeralRollupsSdkError> {
+    let mut data = discriminator.to_vec();
+    data.extend(serialize_args(args)?);
+
+    Ok(Instruction {
+        program_id: *crate::id().as_modern(),
+        accounts: delegate_account_metas(payer, accounts),
+        data,
+    }
+    .compat())
+}
+
+/// Build the `DelegateWithActions` instruction, shared by the builders and the
+/// CPI helpers, the signers of the actions are appended as readonly signers
+pub(crate) fn delegate_with_actions_instruction(
+    payer: &compat::Pubkey,
+    accounts: &DelegateAccounts,
+    args: &DelegateWithActionsArgs,
+) -> Result<compat::Instruction, EphemeralRollupsSdkError> {
+    let mut data = DlpDiscriminator::DelegateWithActions.to_vec();
+    data.extend(serialize_args(args)?);
+
+    let mut metas = delegate_account_metas(payer, accounts);
+    metas.extend(
+        args.actions
+            .signers
+            .iter()
+            .map(|signer| AccountMeta::new_readonly((*signer).into(), true)),
+    );
+
+    Ok(Instruction {
+        program_id: *crate::id().as_modern(),
+        accounts: metas,
+        data,
+    }
+    .compat())
+}
+
+fn delegate_account_metas(payer: &compat::Pubkey, accounts: &DelegateAccounts) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(*payer.as_modern(), true),
+        AccountMeta::new(*accounts.delegated_account.as_modern(), true),
+        AccountMeta::new_readonly(*accounts.owner_program.as_modern(), false),
+        AccountMeta::new(*accounts.delegate_buffer.as_modern(), false),
+        AccountMeta::new(*accounts.delegation_record.as_modern(), false),
+        AccountMeta::new(*accounts.delegation_metadata.as_modern(), false),
+        AccountMeta::new_readonly(*accounts.system_program.as_modern(), false),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAYER: compat::Pubkey = compat::Pubkey::new_from_array([1; 32]);
+    const DELEGATED: compat::Pubkey = compat::Pubkey::new_from_array([2; 32]);
+    const OWNER: compat::Pubkey = compat::Pubkey::new_from_array([3; 32]);
+    const VALIDATOR: compat::Pubkey = compat::Pubkey::new_from_array([4; 32]);
+
+    /// commit_frequency_ms = 30_000, seeds = [b"seed"], validator = Some([4; 32])
+    const DELEGATE_ARGS_BYTES: [u8; 49] = [
+        0x30, 0x75, 0, 0, 1, 0, 0, 0, 4, 0, 0, 0, b's', b'e', b'e', b'd', 1, 4, 4, 4, 4, 4, 4, 4,
+        4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
+    ];
+
+    fn delegate_builder(builder: DelegateBuilder) -> DelegateBuilder {
+        builder
+            .owner_program(OWNER)
+            .seeds(&[b"seed"])
+            .commit_frequency_ms(30_000)
+            .validator(VALIDATOR)
+    }
+
+    #[test]
+    fn test_delegate_golden_bytes() {
+        let ix = delegate_builder(DelegateBuilder::new(PAYER, DELEGATED)).instruction();
+
+        let expected = [[0u8; 8].as_slice(), &DELEGATE_ARGS_BYTES].concat();
+        assert_eq!(ix.program_id, crate::id());
+        assert_eq!(ix.data, expected);
+
+        let accounts = DelegateAccounts::new(DELEGATED, OWNER);
+        let keys: Vec<_> = ix.accounts.iter().map(|m| m.pubkey).collect();
+        assert_eq!(
+            keys,
+            vec![
+                PAYER,
+                DELEGATED,
+                OWNER,
+                accounts.delegate_buffer,
+                accounts.delegation_record,
+                accounts.delegation_metadata,
+                accounts.system_program,
+            ]
+        );
+        assert!(ix.accounts[0].is_signer && ix.accounts[1].is_signer);
+        assert!(!ix.accounts[2].is_writable);
+    }
+
+    #[test]
+    fn test_delegate_with_any_validator_golden_bytes() {
+        let ix =
+            delegate_builder(DelegateBuilder::with_any_validator(PAYER, DELEGATED)).instruction();
+
+        let expected = [[19u8, 0, 0, 0, 0, 0, 0, 0].as_slice(), &DELEGATE_ARGS_BYTES].concat();
+        assert_eq!(ix.data, expected);
+        assert_eq!(ix.accounts.len(), 7);
+    }
+
+    #[test]
+    fn test_delegate_defaults() {
+        let ix = DelegateBuilder::new(PAYER, DELEGATED).instruction();
+
+        // u32::MAX commit frequency, no seeds, no validator
+        let expected = [0u8, 0, 0, 0, 0, 0, 0, 0, 255, 255, 255, 255, 0, 0, 0, 0, 0];
+        assert_eq!(ix.data, expected);
+        assert_eq!(
+            ix.accounts[2].pubkey,
+            solana_system_interface::program::ID.compat()
+        );
+    }
+
+    #[test]
+    fn test_delegate_with_actions_golden_bytes() {
+        let signer = [9; 32];
+        let actions = PostDelegationActions {
+            inserted_signers: 0,
+            inserted_non_signers: 0,
+            signers: vec![signer],
+            non_signers: vec![],
+            instructions: vec![],
+        };
+        let ix = delegate_builder(DelegateBuilder::new(PAYER, DELEGATED))
+            .with_actions(actions)
+            .instruction();
+
+        let mut expected = vec![23, 0, 0, 0, 0, 0, 0, 0];
+        expected.extend_from_slice(&DELEGATE_ARGS_BYTES);
+        // inserted_signers, inserted_non_signers, signers
+        expected.extend_from_slice(&[0, 0, 1, 0, 0, 0]);
+        expected.extend_from_slice(&signer);
+        // non_signers, instructions
+        expected.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(ix.data, expected);
+
+        assert_eq!(ix.accounts.len(), 8);
+        assert_eq!(
+            ix.accounts[7].pubkey,
+            compat::Pubkey::new_from_array(signer)
+        );
+        assert!(ix.accounts[7].is_signer && !ix.accounts[7].is_writable);
+    }
+
+    #[test]
+    fn test_undelegate_golden_bytes() {
+        let ix = UndelegateBuilder::new(VALIDATOR, DELEGATED, OWNER, PAYER).instruction();
+
+        assert_eq!(ix.data, vec![3, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(ix.accounts.len(), 12);
+        assert!(ix.accounts[0].is_signer);
+        assert_eq!(ix.accounts[1].pubkey, DELEGATED);
+        assert_eq!(
+            ix.accounts[3].pubkey,
+            undelegate_buffer_pda_from_delegated_account(&DELEGATED)
+        );
+        assert_eq!(ix.accounts[8].pubkey, PAYER);
+        assert_eq!(
+            ix.accounts[10].pubkey,
+            validator_fees_vault_pda_from_validator(&VALIDATOR)
+        );
+    }
+
+    #[test]
+    fn test_ephemeral_balance_golden_bytes() {
+        let ix = top_up_ephemeral_balance(PAYER, DELEGATED, 1, 2);
+        assert_eq!(
+            ix.data,
+            vec![9, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 2]
+        );
+
+        let ix = delegate_ephemeral_balance(PAYER, DELEGATED, 2, DelegateConfig::default());
+        assert_eq!(
+            ix.data,
+            vec![10, 0, 0, 0, 0, 0, 0, 0, 255, 255, 255, 255, 0, 0, 0, 0, 0, 2]
+        );
+
+        let ix = close_ephemeral_balance(DELEGATED, 2);
+        assert_eq!(ix.data, vec![11, 0, 0, 0, 0, 0, 0, 0, 2]);
+    }
+}
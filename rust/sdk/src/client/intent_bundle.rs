@@ -0,0 +1,557 @@
+#![allow(deprecated)]
+
+//! Instruction builder for the magic program intents, signed by the payer of the transaction.
+//!
+//! [`ClientIntentBundleBuilder`] is the off-chain counterpart of
+//! [`MagicIntentBundleBuilder`]: the intents reference the accounts by pubkey, and are
+//! normalized and encoded by the same code, so both produce the same instructions.
+//!
+//! The committed accounts are passed as writable, the escrow authorities of the actions as
+//! signers, so they have to sign the transaction along with the payer.
+//!
+//! Actions can't have callbacks: `AddActionCallback` must be invoked via CPI by the program
+//! that scheduled the action, which a client transaction has none of.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use ephemeral_rollups_sdk::client::intent_bundle::ClientIntentBundleBuilder;
+//!
+//! let ix = ClientIntentBundleBuilder::new(payer)
+//!     .commit(&[counter])
+//!     .commit_and_undelegate(&[game])
+//!     .try_build()?;
+//! ```
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use magicblock_magic_program_api::args::{ActionArgs, ShortAccountMeta};
+
+use crate::compat;
+use crate::consts::{MAGIC_CONTEXT_ID, MAGIC_PROGRAM_ID};
+use crate::ephem::{
+    CallHandler, CommitAndUndelegate, CommitType, MagicIntent, MagicIntentBundleBuilder,
+    UndelegateType,
+};
+use crate::error::EphemeralRollupsSdkError;
+
+/// Owner of the accounts handed to [`MagicIntentBundleBuilder`], which doesn't read it
+const ACCOUNT_OWNER: compat::Pubkey = compat::Pubkey::new_from_array([0; 32]);
+
+/// Base layer action, same as [`CallHandler`] with the escrow authority referenced by pubkey
+#[derive(Clone)]
+pub struct ClientCallHandler {
+    pub args: ActionArgs,
+    pub compute_units: u32,
+    pub escrow_authority: compat::Pubkey,
+    pub destination_program: compat::Pubkey,
+    pub accounts: Vec<ShortAccountMeta>,
+}
+
+impl ClientCallHandler {
+    fn call_handler<'a>(
+        &self,
+        infos: &HashMap<compat::Pubkey, compat::AccountInfo<'a>>,
+    ) -> CallHandler<'a> {
+        CallHandler {
+            args: self.args.clone(),
+            compute_units: self.compute_units,
+            escrow_authority: infos[&self.escrow_authority].clone(),
+            destination_program: self.destination_program,
+            accounts: self.accounts.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+enum ClientIntent {
+    Commit {
+        accounts: Vec<compat::Pubkey>,
+        finalize: bool,
+        post_commit_actions: Vec<ClientCallHandler>,
+    },
+    CommitAndUndelegate {
+        accounts: Vec<compat::Pubkey>,
+        finalize: bool,
+        post_commit_actions: Vec<ClientCallHandler>,
+        post_undelegate_actions: Vec<ClientCallHandler>,
+    },
+    StandaloneActions(Vec<ClientCallHandler>),
+}
+
+/// Builds the `ScheduleIntentBundle` instruction of the magic program, to be submitted in a
+/// transaction signed by the payer and the escrow authorities of the actions.
+#[derive(Clone)]
+pub struct ClientIntentBundleBuilder {
+    payer: compat::Pubkey,
+    magic_fee_vault: Option<compat::Pubkey>,
+    intents: Vec<ClientIntent>,
+}
+
+impl ClientIntentBundleBuilder {
+    pub fn new(payer: compat::Pubkey) -> Self {
+        Self {
+            payer,
+            magic_fee_vault: None,
+            intents: vec![],
+        }
+    }
+
+    /// Sets the magic fee vault, required when the payer is delegated, see
+    /// [`MagicIntentBundleBuilder::magic_fee_vault`]
+    pub fn magic_fee_vault(mut self, vault: compat::Pubkey) -> Self {
+        self.magic_fee_vault = Some(vault);
+        self
+    }
+
+    /// Commits the accounts
+    pub fn commit(self, accounts: &[compat::Pubkey]) -> Self {
+        self.commit_with_actions(accounts, [])
+    }
+
+    /// Commits the accounts, then executes the actions
+    pub fn commit_with_actions(
+        mut self,
+        accounts: &[compat::Pubkey],
+        post_commit_actions: impl IntoIterator<Item = ClientCallHandler>,
+    ) -> Self {
+        self.intents.push(ClientIntent::Commit {
+            accounts: accounts.to_vec(),
+            finalize: false,
+            post_commit_actions: post_commit_actions.into_iter().collect(),
+        });
+        self
+    }
+
+    /// Commits and undelegates the accounts
+    pub fn commit_and_undelegate(self, accounts: &[compat::Pubkey]) -> Self {
+        self.commit_and_undelegate_with_actions(accounts, [], [])
+    }
+
+    /// Commits the accounts, executes the post-commit actions, then undelegates the accounts
+    /// and executes the post-undelegate actions
+    pub fn commit_and_undelegate_with_actions(
+        mut self,
+        accounts: &[compat::Pubkey],
+        post_commit_actions: impl IntoIterator<Item = ClientCallHandler>,
+        post_undelegate_actions: impl IntoIterator<Item = ClientCallHandler>,
+    ) -> Self {
+        self.intents.push(ClientIntent::CommitAndUndelegate {
+            accounts: accounts.to_vec(),
+            finalize: false,
+            post_commit_actions: post_commit_actions.into_iter().collect(),
+            post_undelegate_actions: post_undelegate_actions.into_iter().collect(),
+        });
+        self
+    }
+
+    /// Commits and finalizes the accounts
+    pub fn commit_finalize(self, accounts: &[compat::Pubkey]) -> Self {
+        self.commit_finalize_with_actions(accounts, [])
+    }
+
+    /// Commits and finalizes the accounts, then executes the actions
+    pub fn commit_finalize_with_actions(
+        mut self,
+        accounts: &[compat::Pubkey],
+        post_commit_actions: impl IntoIterator<Item = ClientCallHandler>,
+    ) -> Self {
+        self.intents.push(ClientIntent::Commit {
+            accounts: accounts.to_vec(),
+            finalize: true,
+            post_commit_actions: post_commit_actions.into_iter().collect(),
+        });
+        self
+    }
+
+    /// Commits, finalizes and undelegates the accounts
+    pub fn commit_finalize_and_undelegate(self, accounts: &[compat::Pubkey]) -> Self {
+        self.commit_finalize_and_undelegate_with_actions(accounts, [], [])
+    }
+
+    /// Commits and finalizes the accounts, executes the post-commit actions, then undelegates
+    /// the accounts and executes the post-undelegate actions
+    pub fn commit_finalize_and_undelegate_with_actions(
+        mut self,
+        accounts: &[compat::Pubkey],
+        post_commit_actions: impl IntoIterator<Item = ClientCallHandler>,
+        post_undelegate_actions: impl IntoIterator<Item = ClientCallHandler>,
+    ) -> Self {
+        self.intents.push(ClientIntent::CommitAndUndelegate {
+            accounts: accounts.to_vec(),
+            finalize: true,
+            post_commit_actions: post_commit_actions.into_iter().collect(),
+            post_undelegate_actions: post_undelegate_actions.into_iter().collect(),
+        });
+        self
+    }
+
+    /// Adds actions executed without any commit/undelegate semantics
+    pub fn add_standalone_actions(
+        mut self,
+        actions: impl IntoIterator<Item = ClientCallHandler>,
+    ) -> Self {
+        self.intents.push(ClientIntent::StandaloneActions(
+            actions.into_iter().collect(),
+        ));
+        self
+    }
+
+    /// Builds the `ScheduleIntentBundle` instruction.
+    ///
+    /// Panics if the bundle can't be encoded, see [`Self::try_build`]
+    pub fn build(self) -> compat::Instruction {
+        self.try_build().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Fallible variant of [`Self::build`], see [`MagicIntentBundleBuilder::try_build`]
+    pub fn try_build(self) -> Result<compat::Instruction, EphemeralRollupsSdkError> {
+        self.with_builder(|builder| Ok(builder.try_build()?.schedule_intent_ix.1))
+    }
+
+    /// Builds the `ScheduleIntentBundle` instruction of each transaction needed to schedule
+    /// the bundle, see [`MagicIntentBundleBuilder::build_chunked`]
+    pub fn build_chunked(self) -> Result<Vec<compat::Instruction>, EphemeralRollupsSdkError> {
+        self.with_builder(|builder| {
+            Ok(builder
+                .build_chunked()?
+                .into_iter()
+                .map(|instructions| instructions.schedule_intent_ix.1)
+                .collect())
+        })
+    }
+
+    /// Runs `f` on the equivalent [`MagicIntentBundleBuilder`], whose account infos only
+    /// carry the pubkeys and the signer/writable flags of the accounts.
+    fn with_builder<T>(
+        self,
+        f: impl for<'a> FnOnce(MagicIntentBundleBuilder<'a>) -> Result<T, EphemeralRollupsSdkError>,
+    ) -> Result<T, EphemeralRollupsSdkError> {
+        let mut accounts = self.accounts();
+        let infos: HashMap<_, _> = accounts
+            .iter_mut()
+            .map(|account| (account.key, account.info()))
+            .collect();
+
+        let mut builder = MagicIntentBundleBuilder::new(
+            infos[&self.payer].clone(),
+            infos[&MAGIC_CONTEXT_ID].clone(),
+            infos[&MAGIC_PROGRAM_ID].clone(),
+        );
+        if let Some(vault) = &self.magic_fee_vault {
+            builder = builder.magic_fee_vault(infos[vault].clone());
+        }
+        for intent in &self.intents {
+            builder = match intent {
+                ClientIntent::Commit {
+                    accounts,
+                    finalize,
+                    post_commit_actions,
+                } => {
+                    let commit = commit_type(accounts, post_commit_actions, &infos);
+                    builder.add_intent(if *finalize {
+                        MagicIntent::CommitFinalize(commit)
+                    } else {
+                        MagicIntent::Commit(commit)
+                    })
+                }
+                ClientIntent::CommitAndUndelegate {
+                    accounts,
+                    finalize,
+                    post_commit_actions,
+                    post_undelegate_actions,
+                } => {
+                    let cau = CommitAndUndelegate {
+                        commit_type: commit_type(accounts, post_commit_actions, &infos),
+                        undelegate_type: undelegate_type(post_undelegate_actions, &infos),
+                    };
+                    builder.add_intent(if *finalize {
+                        MagicIntent::CommitFinalizeAndUndelegate(cau)
+                    } else {
+                        MagicIntent::CommitAndUndelegate(cau)
+                    })
+                }
+                ClientIntent::StandaloneActions(actions) => builder.add_standalone_actions(
+                    actions.iter().map(|action| action.call_handler(&infos)),
+                ),
+            };
+        }
+        f(builder)
+    }
+
+    /// Accounts referenced by the bundle, with their flags merged
+    fn accounts(&self) -> Vec<ClientAccount> {
+        let mut accounts: Vec<ClientAccount> = vec![];
+        let mut add = |key: &compat::Pubkey, is_signer: bool, is_writable: bool| match accounts
+            .iter_mut()
+            .find(|account| account.key == *key)
+        {
+            Some(account) => {
+                account.is_signer |= is_signer;
+                account.is_writable |= is_writable;
+            }
+            None => accounts.push(ClientAccount {
+                key: *key,
+                is_signer,
+                is_writable,
+                lamports: 0,
+            }),
+        };
+
+        add(&self.payer, true, true);
+        add(&MAGIC_CONTEXT_ID, false, true);
+        add(&MAGIC_PROGRAM_ID, false, false);
+        if let Some(vault) = &self.magic_fee_vault {
+            add(vault, false, true);
+        }
+        for intent in &self.intents {
+            let (committed, actions): (&[_], Vec<_>) = match intent {
+                ClientIntent::Commit {
+                    accounts,
+                    post_commit_actions,
+                    ..
+                } => (accounts, post_commit_actions.iter().collect()),
+                ClientIntent::CommitAndUndelegate {
+                    accounts,
+                    post_commit_actions,
+                    post_undelegate_actions,
+                    ..
+                } => (
+                    accounts,
+                    post_commit_actions
+                        .iter()
+                        .chain(post_undelegate_actions)
+                        .collect(),
+                ),
+                ClientIntent::StandaloneActions(actions) => (&[], actions.iter().collect()),
+            };
+            committed.iter().for_each(|key| add(key, false, true));
+            actions
+                .into_iter()
+                .for_each(|action| add(&action.escrow_authority, true, false));
+        }
+        accounts
+    }
+}
+
+fn commit_type<'a>(
+    accounts: &[compat::Pubkey],
+    actions: &[ClientCallHandler],
+    infos: &HashMap<compat::Pubkey, compat::AccountInfo<'a>>,
+) -> CommitType<'a> {
+    let accounts = accounts.iter().map(|key| infos[key].clone()).collect();
+    if actions.is_empty() {
+        CommitType::Standalone(accounts)
+    } else {
+        CommitType::WithHandler {
+            commited_accounts: accounts,
+            call_handlers: actions.iter().map(|a| a.call_handler(infos)).collect(),
+            callbacks: vec![],
+        }
+    }
+}
+
+fn undelegate_type<'a>(
+    actions: &[ClientCallHandler],
+    infos: &HashMap<compat::Pubkey, compat::AccountInfo<'a>>,
+) -> UndelegateType<'a> {
+    if actions.is_empty() {
+        UndelegateType::Standalone
+    } else {
+        UndelegateType::WithHandler {
+            call_handlers: actions.iter().map(|a| a.call_handler(infos)).collect(),
+            callbacks: vec![],
+        }
+    }
+}
+
+struct ClientAccount {
+    key: compat::Pubkey,
+    is_signer: bool,
+    is_writable: bool,
+    lamports: u64,
+}
+
+impl ClientAccount {
+    fn info(&mut self) -> compat::AccountInfo<'_> {
+        let data: &mut [u8] = &mut [];
+        compat::AccountInfo {
+            key: &self.key,
+            is_signer: self.is_signer,
+            is_writable: self.is_writable,
+            lamports: Rc::new(RefCell::new(&mut self.lamports)),
+            data: Rc::new(RefCell::new(data)),
+            owner: &ACCOUNT_OWNER,
+            executable: false,
+            #[cfg(feature = "backward-compat")]
+            rent_epoch: 0,
+            #[cfg(not(feature = "backward-compat"))]
+            _unused: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ephem::{decode_intent_bundle, MAX_INTENT_BUNDLE_ACTIONS};
+
+    const PAYER: compat::Pubkey = compat::Pubkey::new_from_array([1; 32]);
+    const COUNTER: compat::Pubkey = compat::Pubkey::new_from_array([2; 32]);
+    const GAME: compat::Pubkey = compat::Pubkey::new_from_array([3; 32]);
+    const PROGRAM: compat::Pubkey = compat::Pubkey::new_from_array([4; 32]);
+    const AUTHORITY: compat::Pubkey = compat::Pubkey::new_from_array([5; 32]);
+    const VAULT: compat::Pubkey = compat::Pubkey::new_from_array([6; 32]);
+
+    fn action(escrow_authority: compat::Pubkey) -> ClientCallHandler {
+        ClientCallHandler {
+            args: ActionArgs::new(vec![1, 2, 3]),
+            compute_units: 100_000,
+            escrow_authority,
+            destination_program: PROGRAM,
+            accounts: vec![],
+        }
+    }
+
+    fn meta(ix: &compat::Instruction, key: compat::Pubkey) -> &compat::AccountMeta {
+        ix.accounts.iter().find(|meta| meta.pubkey == key).unwrap()
+    }
+
+    #[test]
+    fn test_client_intent_bundle() {
+        let ix = ClientIntentBundleBuilder::new(PAYER)
+            .commit(&[COUNTER, GAME])
+            .commit_and_undelegate_with_actions(&[GAME], [], [action(PAYER)])
+            .try_build()
+            .unwrap();
+        assert_eq!(ix.program_id, MAGIC_PROGRAM_ID);
+
+        let bundle = decode_intent_bundle(&ix).unwrap();
+        let payer = &bundle.accounts[0];
+        assert!(payer.pubkey == PAYER && payer.is_signer && payer.is_writable);
+        assert_eq!(bundle.accounts[1].pubkey, MAGIC_CONTEXT_ID);
+        assert!(bundle.accounts[2..]
+            .iter()
+            .all(|meta| meta.is_writable && !meta.is_signer));
+
+        // GAME is undelegated, so it's only committed by the commit and undelegate intent
+        let commit = bundle.commit.unwrap();
+        assert_eq!(commit.committed_accounts, vec![COUNTER]);
+        let cau = bundle.commit_and_undelegate.unwrap();
+        assert_eq!(cau.commit.committed_accounts, vec![GAME]);
+        assert_eq!(cau.undelegate_actions[0].escrow_authority, PAYER);
+        assert!(cau.undelegate_actions[0].callback.is_none());
+    }
+
+    #[test]
+    fn test_client_commit_finalize_with_actions() {
+        let ix = ClientIntentBundleBuilder::new(PAYER)
+            .commit_finalize_with_actions(&[COUNTER], [action(AUTHORITY)])
+            .commit_finalize_and_undelegate_with_actions(
+                &[GAME],
+                [action(AUTHORITY)],
+                [action(PAYER), action(AUTHORITY)],
+            )
+            .build();
+
+        let bundle = decode_intent_bundle(&ix).unwrap();
+        assert!(bundle.commit.is_none() && bundle.commit_and_undelegate.is_none());
+        let commit = bundle.commit_finalize.unwrap();
+        assert_eq!(commit.committed_accounts, vec![COUNTER]);
+        assert_eq!(commit.actions.len(), 1);
+        assert_eq!(commit.actions[0].escrow_authority, AUTHORITY);
+        assert_eq!(commit.actions[0].data, [1, 2, 3]);
+        let cau = bundle.commit_finalize_and_undelegate.unwrap();
+        assert_eq!(cau.commit.committed_accounts, vec![GAME]);
+        assert_eq!(cau.commit.actions.len(), 1);
+        assert_eq!(
+            cau.undelegate_actions
+                .iter()
+                .map(|action| action.escrow_authority)
+                .collect::<Vec<_>>(),
+            [PAYER, AUTHORITY]
+        );
+
+        // the escrow authorities sign the transaction, without being written to
+        let authority = meta(&ix, AUTHORITY);
+        assert!(authority.is_signer && !authority.is_writable);
+        let payer = meta(&ix, PAYER);
+        assert!(payer.is_signer && payer.is_writable);
+    }
+
+    #[test]
+    fn test_client_finalize_without_actions() {
+        let bundle = decode_intent_bundle(
+            &ClientIntentBundleBuilder::new(PAYER)
+                .commit_finalize(&[COUNTER])
+                .commit_finalize_and_undelegate(&[GAME])
+                .build(),
+        )
+        .unwrap();
+        let commit = bundle.commit_finalize.unwrap();
+        assert_eq!(commit.committed_accounts, vec![COUNTER]);
+        assert!(commit.actions.is_empty());
+        let cau = bundle.commit_finalize_and_undelegate.unwrap();
+        assert_eq!(cau.commit.committed_accounts, vec![GAME]);
+        assert!(cau.commit.actions.is_empty() && cau.undelegate_actions.is_empty());
+    }
+
+    #[test]
+    fn test_client_standalone_actions_with_fee_vault() {
+        let ix = ClientIntentBundleBuilder::new(PAYER)
+            .magic_fee_vault(VAULT)
+            .add_standalone_actions([action(AUTHORITY), action(PAYER)])
+            .build();
+
+        let bundle = decode_intent_bundle(&ix).unwrap();
+        let vault = &bundle.accounts[2];
+        assert!(vault.pubkey == VAULT && vault.is_writable && !vault.is_signer);
+        assert_eq!(
+            bundle
+                .standalone_actions
+                .iter()
+                .map(|action| action.escrow_authority)
+                .collect::<Vec<_>>(),
+            [AUTHORITY, PAYER]
+        );
+        assert!(meta(&ix, AUTHORITY).is_signer);
+    }
+
+    #[test]
+    fn test_client_too_many_actions() {
+        let actions = (0..=MAX_INTENT_BUNDLE_ACTIONS).map(|_| action(AUTHORITY));
+        assert_eq!(
+            ClientIntentBundleBuilder::new(PAYER)
+                .add_standalone_actions(actions)
+                .try_build()
+                .err(),
+            Some(EphemeralRollupsSdkError::TooManyActions)
+        );
+    }
+
+    #[test]
+    fn test_client_build_chunked() {
+        let mut committed: Vec<_> = (0..200).map(|_| compat::Pubkey::new_unique()).collect();
+        let chunks = ClientIntentBundleBuilder::new(PAYER)
+            .commit(&committed)
+            .add_standalone_actions([action(AUTHORITY)])
+            .build_chunked()
+            .unwrap();
+        assert!(chunks.len() > 1);
+
+        let mut chunked = vec![];
+        let mut actions = 0;
+        for ix in &chunks {
+            let bundle = decode_intent_bundle(ix).unwrap();
+            assert_eq!(bundle.accounts[0].pubkey, PAYER);
+            chunked.extend(bundle.commit.map_or(vec![], |c| c.committed_accounts));
+            actions += bundle.standalone_actions.len();
+        }
+        chunked.sort();
+        committed.sort();
+        assert_eq!(chunked, committed);
+        assert_eq!(actions, 1);
+    }
+}
@@ -0,0 +1,5 @@
+//! Off-chain instruction builders, for clients and services which submit
+//! transactions to the programs directly, rather than via CPI.
+
+pub mod dlp;
+pub mod intent_bundle;
@@ -25,6 +25,8 @@ mod backward_compat {
     pub use anchor_lang_compat as anchor_lang;
 
     pub use solana_account_info_compat::AccountInfo;
+    #[doc(hidden)]
+    pub use solana_program_compat::entrypoint as program_entrypoint;
     pub use solana_program_compat::instruction::{AccountMeta, Instruction};
     pub use solana_program_error_compat::{ProgramError, ProgramResult};
 }
@@ -37,6 +39,8 @@ pub(crate) mod latest {
 
     pub use account_info::AccountInfo;
     pub use solana_program::account_info;
+    #[doc(hidden)]
+    pub use solana_program::entrypoint as program_entrypoint;
     pub use solana_program::entrypoint_deprecated::ProgramResult;
     pub use solana_program::instruction::{AccountMeta, Instruction};
     pub use solana_program::program_error::ProgramError;
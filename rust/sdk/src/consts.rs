@@ -11,6 +11,10 @@ pub const MAGIC_PROGRAM_ID: compat::Pubkey =
 pub const MAGIC_CONTEXT_ID: compat::Pubkey =
     compat::Pubkey::new_from_array(MAGIC_CONTEXT_PUBKEY.to_bytes());
 
+/// The signer of the action callbacks, i.e. the PDA of the callback executor of the validator.
+pub const CALLBACK_SIGNER: compat::Pubkey =
+    compat::Pubkey::new_from_array(magicblock_magic_program_api::pda::CALLBACK_SIGNER.to_bytes());
+
 /// The ephemeral vault ID (collects rent for ephemeral accounts).
 pub const EPHEMERAL_VAULT_ID: compat::Pubkey =
     compat::Pubkey::new_from_array(EPHEMERAL_VAULT_PUBKEY.to_bytes());
@@ -1,20 +1,25 @@
+use crate::client::dlp::{delegate_instruction, delegate_with_actions_instruction};
+use crate::cu;
+use crate::delegate_args::DelegateAccounts as DelegateAccountKeys;
+use crate::error::EphemeralRollupsSdkError;
 use crate::modernize;
 use crate::types::DelegateAccountArgs;
-use crate::utils::{close_pda_with_system_transfer, create_pda, seeds_with_bump};
+use crate::utils::{
+    close_pda_with_system_transfer, create_pda, next_realloc_len, resize_rent_exempt,
+    seeds_with_bump,
+};
 use dlp_api::args::{DelegateArgs, DelegateWithActionsArgs, PostDelegationActions};
+use dlp_api::compact::{ClearText, ClearTextWithInsertable};
 use dlp_api::delegate_buffer_seeds_from_delegated_account;
 use dlp_api::discriminator::DlpDiscriminator;
-use solana_program::instruction::{AccountMeta, Instruction};
-use solana_program::program_error::ProgramError;
+use dlp_api::pda;
 
-use crate::compat::{
-    self,
-    borsh::{self, BorshSerialize},
-    AsModern, Compat, Modern,
-};
+use crate::compat::{self, borsh, AsModern, Compat, Modern};
 
 use solana_address::Address;
-use solana_program::{program::invoke_signed, program_memory::sol_memset};
+use solana_program::{
+    entrypoint::MAX_PERMITTED_DATA_INCREASE, program::invoke_signed, program_memory::sol_memset,
+};
 
 pub const DELEGATION_PROGRAM_ID: compat::Pubkey =
     compat::Pubkey::new_from_array(dlp_api::consts::DELEGATION_PROGRAM_ID.to_bytes());
@@ -30,6 +35,7 @@ pub struct DelegateAccounts<'a, 'info> {
     pub system_program: &'a compat::AccountInfo<'info>,
 }
 
+#[derive(Clone)]
 pub struct DelegateConfig {
     pub commit_frequency_ms: u32,
     pub validator: Option<compat::Pubkey>,
@@ -44,13 +50,44 @@ impl Default for DelegateConfig {
     }
 }
 
+/// Delegate the PDA derived from `pda_seeds` to the delegation program.
+///
+/// The PDA, buffer, delegation record and metadata addresses are verified up front,
+/// see [`delegate_account_unchecked`] to skip the checks. Accounts larger than
+/// `MAX_PERMITTED_DATA_INCREASE` (10 KiB) are rejected, as they couldn't be restored on
+/// undelegation, see [`undelegate_account`].
 #[allow(clippy::needless_lifetimes)]
 pub fn delegate_account<'a, 'info>(
     accounts: DelegateAccounts<'a, 'info>,
     pda_seeds: &[&[u8]],
     config: DelegateConfig,
 ) -> compat::ProgramResult {
-    delegate_account_inner(accounts, pda_seeds, config, false)
+    delegate_account_inner(
+        accounts,
+        pda_seeds,
+        DelegateBumps::default(),
+        config,
+        Delegation::Validator,
+        true,
+    )
+}
+
+/// Same as [`delegate_account`], but skips the address checks, saving the
+/// compute units spent on the delegation record and metadata derivation.
+#[allow(clippy::needless_lifetimes)]
+pub fn delegate_account_unchecked<'a, 'info>(
+    accounts: DelegateAccounts<'a, 'info>,
+    pda_seeds: &[&[u8]],
+    config: DelegateConfig,
+) -> compat::ProgramResult {
+    delegate_account_inner(
+        accounts,
+        pda_seeds,
+        DelegateBumps::default(),
+        config,
+        Delegation::Validator,
+        false,
+    )
 }
 
 #[allow(clippy::needless_lifetimes)]
@@ -59,136 +96,417 @@ pub fn delegate_account_with_any_validator<'a, 'info>(
     pda_seeds: &[&[u8]],
     config: DelegateConfig,
 ) -> compat::ProgramResult {
-    delegate_account_inner(accounts, pda_seeds, config, true)
+    delegate_account_inner(
+        accounts,
+        pda_seeds,
+        DelegateBumps::default(),
+        config,
+        Delegation::AnyValidator,
+        true,
+    )
 }
 
+/// Same as [`delegate_account_with_any_validator`], but skips the address checks.
 #[allow(clippy::needless_lifetimes)]
-fn delegate_account_inner<'a, 'info>(
+pub fn delegate_account_with_any_validator_unchecked<'a, 'info>(
     accounts: DelegateAccounts<'a, 'info>,
     pda_seeds: &[&[u8]],
     config: DelegateConfig,
-    any_validator: bool,
 ) -> compat::ProgramResult {
-    let buffer_seeds: &[&[u8]] = delegate_buffer_seeds_from_delegated_account!(accounts.pda.key);
+    delegate_account_inner(
+        accounts,
+        pda_seeds,
+        DelegateBumps::default(),
+        config,
+        Delegation::AnyValidator,
+        false,
+    )
+}
 
-    let (_, delegate_account_bump) =
-        Address::find_program_address(pda_seeds, accounts.owner_program.key.as_modern());
+/// Delegate the PDA derived from `pda_seeds`, with actions executed by the delegation program
+/// once the account is delegated. The addresses are verified up front as in [`delegate_account`].
+#[allow(clippy::needless_lifetimes)]
+pub fn delegate_account_with_actions<'a, 'info>(
+    accounts: DelegateAccounts<'a, 'info>,
+    pda_seeds: &[&[u8]],
+    config: DelegateConfig,
+    actions: PostDelegationActions,
+    action_signer_infos: &'a [&'a compat::AccountInfo<'info>],
+) -> compat::ProgramResult {
+    delegate_account_inner(
+        accounts,
+        pda_seeds,
+        DelegateBumps::default(),
+        config,
+        Delegation::WithActions {
+            actions,
+            action_signer_infos,
+        },
+        true,
+    )
+}
 
-    let (_, buffer_pda_bump) =
-        Address::find_program_address(buffer_seeds, accounts.owner_program.key.as_modern());
+/// Same as [`delegate_account_with_actions`], but skips the address checks.
+#[allow(clippy::needless_lifetimes)]
+pub fn delegate_account_with_actions_unchecked<'a, 'info>(
+    accounts: DelegateAccounts<'a, 'info>,
+    pda_seeds: &[&[u8]],
+    config: DelegateConfig,
+    actions: PostDelegationActions,
+    action_signer_infos: &'a [&'a compat::AccountInfo<'info>],
+) -> compat::ProgramResult {
+    delegate_account_inner(
+        accounts,
+        pda_seeds,
+        DelegateBumps::default(),
+        config,
+        Delegation::WithActions {
+            actions,
+            action_signer_infos,
+        },
+        false,
+    )
+}
 
-    // Pda signer seeds
-    let delegate_account_bump_slice: &[u8] = &[delegate_account_bump];
-    let pda_signer_seeds: &[&[&[u8]]] =
-        &[&*seeds_with_bump(pda_seeds, delegate_account_bump_slice)];
+/// Builder for the delegation of a PDA owned by the calling program, an alternative to
+/// the positional [`delegate_account`] family of functions.
+///
+/// When the bumps of the delegated account and its buffer are known, e.g. stored in the
+/// account, passing them via [`bump`](Self::bump) and [`buffer_bump`](Self::buffer_bump)
+/// saves the compute units spent on `find_program_address`.
+///
+/// ```ignore
+/// DelegateAccountCpiBuilder::new(accounts)
+///     .seeds(&[b"counter", user.key.as_ref()])
+///     .bump(counter.bump)
+///     .config(DelegateConfig {
+///         validator: Some(validator),
+///         ..Default::default()
+///     })
+///     .invoke()?;
+/// ```
+pub struct DelegateAccountCpiBuilder<'a, 'info> {
+    accounts: DelegateAccounts<'a, 'info>,
+    seeds: Option<&'a [&'a [u8]]>,
+    bumps: DelegateBumps,
+    config: DelegateConfig,
+    skip_address_checks: bool,
+}
 
-    // Buffer signer seeds
-    let buffer_bump_slice: &[u8] = &[buffer_pda_bump];
-    let buffer_signer_seeds: &[&[&[u8]]] = &[&*seeds_with_bump(buffer_seeds, buffer_bump_slice)];
+impl<'a, 'info> DelegateAccountCpiBuilder<'a, 'info> {
+    pub fn new(accounts: DelegateAccounts<'a, 'info>) -> Self {
+        Self {
+            accounts,
+            seeds: None,
+            bumps: DelegateBumps::default(),
+            config: DelegateConfig::default(),
+            skip_address_checks: false,
+        }
+    }
 
-    let data_len = accounts.pda.data_len();
+    /// Seeds of the delegated account, without the bump
+    pub fn seeds(mut self, seeds: &'a [&'a [u8]]) -> Self {
+        self.seeds = Some(seeds);
+        self
+    }
 
-    // Create the Buffer PDA
-    create_pda(
-        accounts.buffer,
-        accounts.owner_program.key,
-        data_len,
-        buffer_signer_seeds,
-        accounts.system_program,
-        accounts.payer,
-        false,
-    )?;
+    /// Known bump of the delegated account
+    pub fn bump(mut self, bump: u8) -> Self {
+        self.bumps.pda = Some(bump);
+        self
+    }
 
-    // Copy PDA -> buffer (RO pda, RW buffer)
-    {
-        let pda_ro = accounts.pda.try_borrow_data()?;
-        let mut buf = accounts.buffer.try_borrow_mut_data()?;
-        buf.copy_from_slice(&pda_ro);
+    /// Known bump of the delegation buffer
+    pub fn buffer_bump(mut self, bump: u8) -> Self {
+        self.bumps.buffer = Some(bump);
+        self
     }
 
-    // Zero PDA (single RW borrow)
-    {
-        let mut pda_mut = accounts.pda.try_borrow_mut_data()?;
-        #[allow(unused_unsafe)]
-        unsafe {
-            sol_memset(&mut pda_mut, 0, data_len)
-        };
+    pub fn config(mut self, config: DelegateConfig) -> Self {
+        self.config = config;
+        self
     }
 
-    // Assign the PDA to the delegation program if not already assigned
-    if accounts.pda.owner != accounts.system_program.key {
-        accounts.pda.assign(accounts.system_program.key);
+    /// By default the PDA, buffer, delegation record and metadata addresses are verified
+    /// before delegating, skipping the checks saves the compute units spent on derivations.
+    pub fn skip_address_checks(mut self) -> Self {
+        self.skip_address_checks = true;
+        self
     }
-    if accounts.pda.owner != accounts.delegation_program.key {
-        invoke_signed(
-            &solana_system_interface::instruction::assign(
-                accounts.pda.key.as_modern(),
-                accounts.delegation_program.key.as_modern(),
-            ),
-            &[accounts.pda.modern(), accounts.system_program.modern()],
-            pda_signer_seeds,
+
+    /// Estimate of the compute units spent by [`invoke`](Self::invoke), see [`crate::cu`]
+    pub fn estimated_compute_units(&self) -> u64 {
+        self.estimate(0, 0)
+    }
+
+    fn estimate(&self, action_accounts: usize, actions_len: usize) -> u64 {
+        let known_bumps = self.bumps.pda.is_some() as u64 + self.bumps.buffer.is_some() as u64;
+        cu::delegation(
+            self.seeds.unwrap_or_default(),
+            self.accounts.pda.data_len(),
+            known_bumps,
+            !self.skip_address_checks,
+            action_accounts,
+            actions_len,
         )
-        .compat()?;
     }
 
-    let seeds_vec: Vec<Vec<u8>> = pda_seeds.iter().map(|&slice| slice.to_vec()).collect();
+    /// Switches to the delegation with post-delegation actions, the `action_signer_infos`
+    /// must contain every account required to sign the actions.
+    pub fn with_actions(
+        self,
+        action_signer_infos: &'a [&'a compat::AccountInfo<'info>],
+    ) -> DelegateAccountWithActionsCpiBuilder<'a, 'info> {
+        DelegateAccountWithActionsCpiBuilder {
+            parent: self,
+            action_signer_infos,
+            actions: None,
+            instructions: Vec::new(),
+        }
+    }
 
-    let delegation_args = DelegateAccountArgs {
-        commit_frequency_ms: config.commit_frequency_ms,
-        seeds: seeds_vec,
-        validator: config.validator,
-    };
+    /// Delegate the account to the validator from config
+    pub fn invoke(self) -> compat::ProgramResult {
+        self.invoke_inner(Delegation::Validator)
+    }
 
-    if any_validator {
-        cpi_delegate_with_any_validator(
-            accounts.payer,
-            accounts.pda,
-            accounts.owner_program,
-            accounts.buffer,
-            accounts.delegation_record,
-            accounts.delegation_metadata,
-            accounts.system_program,
-            pda_signer_seeds,
-            delegation_args,
-        )?;
-    } else {
-        cpi_delegate(
-            accounts.payer,
-            accounts.pda,
-            accounts.owner_program,
-            accounts.buffer,
-            accounts.delegation_record,
-            accounts.delegation_metadata,
-            accounts.system_program,
-            pda_signer_seeds,
-            delegation_args,
-        )?;
+    /// Delegate the account, allowing any validator to pick it up
+    pub fn invoke_with_any_validator(self) -> compat::ProgramResult {
+        self.invoke_inner(Delegation::AnyValidator)
     }
 
-    close_pda_with_system_transfer(
-        accounts.buffer,
-        buffer_signer_seeds,
-        accounts.payer,
-        accounts.system_program,
-    )?;
+    fn invoke_inner(self, delegation: Delegation<'a, 'info>) -> compat::ProgramResult {
+        let seeds = self
+            .seeds
+            .ok_or(EphemeralRollupsSdkError::MissingDelegationSeeds)?;
+        delegate_account_inner(
+            self.accounts,
+            seeds,
+            self.bumps,
+            self.config,
+            delegation,
+            !self.skip_address_checks,
+        )
+    }
+}
+
+/// Delegation with post-delegation actions, see [`DelegateAccountCpiBuilder::with_actions`].
+pub struct DelegateAccountWithActionsCpiBuilder<'a, 'info> {
+    parent: DelegateAccountCpiBuilder<'a, 'info>,
+    action_signer_infos: &'a [&'a compat::AccountInfo<'info>],
+    actions: Option<PostDelegationActions>,
+    instructions: Vec<solana_program::instruction::Instruction>,
+}
+
+impl<'a, 'info> DelegateAccountWithActionsCpiBuilder<'a, 'info> {
+    /// Appends the instruction to be executed once the account is delegated
+    pub fn add_action(mut self, instruction: compat::Instruction) -> Self {
+        self.instructions.push(instruction.modern());
+        self
+    }
+
+    /// Prebuilt (possibly encrypted) actions, e.g. provided by an off-chain client, the
+    /// instructions added via [`add_action`](Self::add_action) are executed after them
+    pub fn actions(mut self, actions: PostDelegationActions) -> Self {
+        self.actions = Some(actions);
+        self
+    }
+
+    /// Estimate of the compute units spent by [`invoke`](Self::invoke), see [`crate::cu`]
+    pub fn estimated_compute_units(&self) -> u64 {
+        let prebuilt_len = self.actions.as_ref().map_or(0, |actions| {
+            borsh::to_vec(actions).map_or(0, |data| data.len())
+        });
+        let instructions_len =
+            borsh::to_vec(&self.instructions.clone().cleartext()).map_or(0, |data| data.len());
+        self.parent.estimate(
+            self.action_signer_infos.len(),
+            prebuilt_len + instructions_len,
+        )
+    }
+
+    pub fn invoke(self) -> compat::ProgramResult {
+        let actions = match self.actions {
+            Some(actions) if self.instructions.is_empty() => actions,
+            Some(actions) => self.instructions.cleartext_with_insertable(actions, 0),
+            None => self.instructions.cleartext(),
+        };
+        self.parent.invoke_inner(Delegation::WithActions {
+            actions,
+            action_signer_infos: self.action_signer_infos,
+        })
+    }
+}
+
+/// Program accounts shared by all of the items in [`delegate_accounts_batch`]
+pub struct DelegateBatchAccounts<'a, 'info> {
+    pub payer: &'a compat::AccountInfo<'info>,
+    pub owner_program: &'a compat::AccountInfo<'info>,
+    pub delegation_program: &'a compat::AccountInfo<'info>,
+    pub system_program: &'a compat::AccountInfo<'info>,
+}
+
+/// Account to delegate as part of [`delegate_accounts_batch`]
+pub struct DelegateItem<'a, 'info> {
+    pub pda: &'a compat::AccountInfo<'info>,
+    pub buffer: &'a compat::AccountInfo<'info>,
+    pub delegation_record: &'a compat::AccountInfo<'info>,
+    pub delegation_metadata: &'a compat::AccountInfo<'info>,
+    pub seeds: &'a [&'a [u8]],
+    pub config: DelegateConfig,
+}
+
+impl<'a, 'info> DelegateItem<'a, 'info> {
+    fn accounts(&self, shared: &DelegateBatchAccounts<'a, 'info>) -> DelegateAccounts<'a, 'info> {
+        DelegateAccounts {
+            payer: shared.payer,
+            pda: self.pda,
+            owner_program: shared.owner_program,
+            buffer: self.buffer,
+            delegation_record: self.delegation_record,
+            delegation_metadata: self.delegation_metadata,
+            delegation_program: shared.delegation_program,
+            system_program: shared.system_program,
+        }
+    }
+}
+
+/// Failure of [`delegate_accounts_batch`], with the result of each item, in order
+#[derive(Debug, PartialEq)]
+pub struct DelegateBatchError {
+    pub results: Vec<Result<(), compat::ProgramError>>,
+}
+
+impl DelegateBatchError {
+    /// Index and error of the items which failed
+    pub fn errors(&self) -> impl Iterator<Item = (usize, &compat::ProgramError)> {
+        self.results
+            .iter()
+            .enumerate()
+            .filter_map(|(index, result)| result.as_ref().err().map(|error| (index, error)))
+    }
+}
+
+impl From<DelegateBatchError> for compat::ProgramError {
+    fn from(value: DelegateBatchError) -> Self {
+        value
+            .results
+            .into_iter()
+            .find_map(Result::err)
+            .unwrap_or(compat::ProgramError::InvalidArgument)
+    }
+}
+
+/// Delegate multiple PDAs of the calling program at once, e.g. player, inventory and stats.
+///
+/// All of the items are validated before any account is mutated: duplicates are rejected and the
+/// addresses are verified as in [`delegate_account`]. The bumps derived during validation are
+/// reused for the delegation itself.
+///
+/// If any item is invalid, nothing is delegated and the error holds the result of every item.
+/// If the delegation of an item fails, the following items aren't delegated either, and the
+/// error holds the failure of that item along with the validation result of the others.
+#[allow(clippy::needless_lifetimes)]
+pub fn delegate_accounts_batch<'a, 'info>(
+    accounts: DelegateBatchAccounts<'a, 'info>,
+    items: &[DelegateItem<'a, 'info>],
+) -> Result<(), DelegateBatchError> {
+    let owner_program = accounts.owner_program.key.as_modern();
+
+    let validated: Vec<Result<DelegateBumps, EphemeralRollupsSdkError>> = items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            if items[..index]
+                .iter()
+                .any(|other| other.pda.key == item.pda.key)
+            {
+                return Err(EphemeralRollupsSdkError::DuplicateDelegatedAccount);
+            }
+
+            let buffer_seeds: &[&[u8]] =
+                delegate_buffer_seeds_from_delegated_account!(item.pda.key);
+            let (pda, pda_bump) = resolve_pda(item.seeds, owner_program, None, true);
+            let (buffer, buffer_bump) = resolve_pda(buffer_seeds, owner_program, None, true);
+            let item_accounts = item.accounts(&accounts);
+            verify_delegate_accounts(&item_accounts, pda.as_ref(), buffer.as_ref())?;
+            verify_restorable(item.pda)?;
+            is_buffer_prepared(&item_accounts)?;
+            Ok(DelegateBumps {
+                pda: Some(pda_bump),
+                buffer: Some(buffer_bump),
+            })
+        })
+        .collect();
+    let mut results: Vec<Result<(), compat::ProgramError>> = validated
+        .iter()
+        .map(|result| result.as_ref().map(|_| ()).map_err(|error| (*error).into()))
+        .collect();
+    if results.iter().any(Result::is_err) {
+        return Err(DelegateBatchError { results });
+    }
+
+    for (index, (item, bumps)) in items
+        .iter()
+        .zip(validated.into_iter().flatten())
+        .enumerate()
+    {
+        if let Err(error) = delegate_account_inner(
+            item.accounts(&accounts),
+            item.seeds,
+            bumps,
+            item.config.clone(),
+            Delegation::Validator,
+            false,
+        ) {
+            results[index] = Err(error);
+            return Err(DelegateBatchError { results });
+        }
+    }
     Ok(())
 }
 
+/// Delegation instruction to invoke, once the delegated account has been prepared
+enum Delegation<'a, 'info> {
+    Validator,
+    AnyValidator,
+    WithActions {
+        actions: PostDelegationActions,
+        action_signer_infos: &'a [&'a compat::AccountInfo<'info>],
+    },
+}
+
+/// Bumps of the delegated account and buffer PDAs, derived on-chain when not provided
+#[derive(Default, Clone, Copy)]
+struct DelegateBumps {
+    pda: Option<u8>,
+    buffer: Option<u8>,
+}
+
 #[allow(clippy::needless_lifetimes)]
-pub fn delegate_account_with_actions<'a, 'info>(
+fn delegate_account_inner<'a, 'info>(
     accounts: DelegateAccounts<'a, 'info>,
     pda_seeds: &[&[u8]],
+    bumps: DelegateBumps,
     config: DelegateConfig,
-    actions: PostDelegationActions,
-    action_signer_infos: &'a [&'a compat::AccountInfo<'info>],
+    delegation: Delegation<'a, 'info>,
+    verify: bool,
 ) -> compat::ProgramResult {
     let buffer_seeds: &[&[u8]] = delegate_buffer_seeds_from_delegated_account!(accounts.pda.key);
+    let owner_program = accounts.owner_program.key.as_modern();
 
-    let (_, delegate_account_bump) =
-        Address::find_program_address(pda_seeds, accounts.owner_program.key.as_modern());
+    let (delegate_account_pda, delegate_account_bump) =
+        resolve_pda(pda_seeds, owner_program, bumps.pda, verify);
+    let (buffer_pda, buffer_pda_bump) =
+        resolve_pda(buffer_seeds, owner_program, bumps.buffer, verify);
 
-    let (_, buffer_pda_bump) =
-        Address::find_program_address(buffer_seeds, accounts.owner_program.key.as_modern());
+    if verify {
+        verify_delegate_accounts(
+            &accounts,
+            delegate_account_pda.as_ref(),
+            buffer_pda.as_ref(),
+        )?;
+    }
 
     // Pda signer seeds
     let delegate_account_bump_slice: &[u8] = &[delegate_account_bump];
@@ -199,18 +517,21 @@ pub fn delegate_account_with_actions<'a, 'info>(
     let buffer_bump_slice: &[u8] = &[buffer_pda_bump];
     let buffer_signer_seeds: &[&[&[u8]]] = &[&*seeds_with_bump(buffer_seeds, buffer_bump_slice)];
 
+    verify_restorable(accounts.pda)?;
     let data_len = accounts.pda.data_len();
 
-    // Create the Buffer PDA
-    create_pda(
-        accounts.buffer,
-        accounts.owner_program.key,
-        data_len,
-        buffer_signer_seeds,
-        accounts.system_program,
-        accounts.payer,
-        false,
-    )?;
+    // Create the Buffer PDA, unless it has been prepared via `prepare_delegation_buffer`
+    if !is_buffer_prepared(&accounts)? {
+        create_pda(
+            accounts.buffer,
+            accounts.owner_program.key,
+            data_len,
+            buffer_signer_seeds,
+            accounts.system_program,
+            accounts.payer,
+            false,
+        )?;
+    }
 
     // Copy PDA -> buffer (RO pda, RW buffer)
     {
@@ -246,28 +567,56 @@ pub fn delegate_account_with_actions<'a, 'info>(
 
     let seeds_vec: Vec<Vec<u8>> = pda_seeds.iter().map(|&slice| slice.to_vec()).collect();
 
-    let delegate_args = DelegateArgs {
-        commit_frequency_ms: config.commit_frequency_ms,
-        seeds: seeds_vec,
-        validator: config.validator,
-    };
-    let args = DelegateWithActionsArgs {
-        delegate: delegate_args,
-        actions,
-    };
-
-    cpi_delegate_with_actions(
-        accounts.payer,
-        accounts.pda,
-        accounts.owner_program,
-        accounts.buffer,
-        accounts.delegation_record,
-        accounts.delegation_metadata,
-        accounts.system_program,
-        pda_signer_seeds,
-        args,
-        action_signer_infos,
-    )?;
+    match delegation {
+        Delegation::Validator | Delegation::AnyValidator => {
+            let delegation_args = DelegateAccountArgs {
+                commit_frequency_ms: config.commit_frequency_ms,
+                seeds: seeds_vec,
+                validator: config.validator,
+            };
+            let cpi = if matches!(delegation, Delegation::AnyValidator) {
+                cpi_delegate_with_any_validator
+            } else {
+                cpi_delegate
+            };
+            cpi(
+                accounts.payer,
+                accounts.pda,
+                accounts.owner_program,
+                accounts.buffer,
+                accounts.delegation_record,
+                accounts.delegation_metadata,
+                accounts.system_program,
+                pda_signer_seeds,
+                delegation_args,
+            )?;
+        }
+        Delegation::WithActions {
+            actions,
+            action_signer_infos,
+        } => {
+            let args = DelegateWithActionsArgs {
+                delegate: DelegateArgs {
+                    commit_frequency_ms: config.commit_frequency_ms,
+                    seeds: seeds_vec,
+                    validator: config.validator,
+                },
+                actions,
+            };
+            cpi_delegate_with_actions(
+                accounts.payer,
+                accounts.pda,
+                accounts.owner_program,
+                accounts.buffer,
+                accounts.delegation_record,
+                accounts.delegation_metadata,
+                accounts.system_program,
+                pda_signer_seeds,
+                args,
+                action_signer_infos,
+            )?;
+        }
+    }
 
     close_pda_with_system_transfer(
         accounts.buffer,
@@ -278,7 +627,197 @@ pub fn delegate_account_with_actions<'a, 'info>(
     Ok(())
 }
 
+/// Derive the escrow PDA of `escrow_authority` at `index`, signing the actions and callbacks
+/// paid from it, same as [`ephemeral_balance_pda`].
+///
+/// [`ephemeral_balance_pda`]: crate::ephemeral_balance::ephemeral_balance_pda
+pub fn escrow_pda(escrow_authority: &compat::Pubkey, index: u8) -> compat::Pubkey {
+    pda::ephemeral_balance_pda_from_payer(escrow_authority, index)
+}
+
+/// Verifies that `escrow` signs and is the escrow of `escrow_authority` at `escrow_index`,
+/// i.e. that the action or callback has been scheduled with the expected escrow authority.
+pub fn verify_escrow(
+    escrow: &compat::AccountInfo,
+    escrow_authority: &compat::AccountInfo,
+    escrow_index: u8,
+) -> Result<(), EphemeralRollupsSdkError> {
+    if !escrow.is_signer {
+        return Err(EphemeralRollupsSdkError::EscrowNotSigner);
+    }
+    if escrow.key != &escrow_pda(escrow_authority.key, escrow_index) {
+        return Err(EphemeralRollupsSdkError::InvalidEscrow);
+    }
+    Ok(())
+}
+
+/// Verifies that the action re-delegating `account` has been scheduled by its owner program,
+/// see [`MagicIntentBundleBuilder::migrate_to_validator`]. The escrow is signed by the
+/// delegation program only when the action has been authorized by the escrow authority, which
+/// has to be the migrated account itself, so only its owner program could have signed for it.
+///
+/// [`MagicIntentBundleBuilder::migrate_to_validator`]: crate::ephem::MagicIntentBundleBuilder::migrate_to_validator
+pub fn verify_migration_escrow(
+    account: &compat::Pubkey,
+    escrow_authority: &compat::AccountInfo,
+    escrow: &compat::AccountInfo,
+    escrow_index: u8,
+) -> Result<(), EphemeralRollupsSdkError> {
+    if escrow_authority.key != account {
+        return Err(EphemeralRollupsSdkError::InvalidEscrowAuthority);
+    }
+    verify_escrow(escrow, escrow_authority, escrow_index)
+}
+
+/// Whether the buffer has already been created by [`prepare_delegation_buffer`], in which case
+/// it must have been grown to the full size of the delegated account
+fn is_buffer_prepared(accounts: &DelegateAccounts) -> Result<bool, EphemeralRollupsSdkError> {
+    if accounts.buffer.owner != accounts.owner_program.key {
+        return Ok(false);
+    }
+    if accounts.buffer.data_len() != accounts.pda.data_len() {
+        return Err(EphemeralRollupsSdkError::IncompleteDelegationBuffer);
+    }
+    Ok(true)
+}
+
+/// Verifies that the delegated account can be re-created by the undelegation callback, a
+/// single instruction in which the runtime lets it grow by at most `MAX_PERMITTED_DATA_INCREASE`
+fn verify_restorable(pda: &compat::AccountInfo) -> Result<(), EphemeralRollupsSdkError> {
+    if pda.data_len() > MAX_PERMITTED_DATA_INCREASE {
+        return Err(EphemeralRollupsSdkError::UndelegatedAccountTooLarge);
+    }
+    Ok(())
+}
+
+/// Progress of the delegation buffer preparation, see [`prepare_delegation_buffer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DelegationBufferProgress {
+    /// Current length of the buffer
+    pub len: usize,
+    /// Length of the delegated account, which the buffer has to reach
+    pub data_len: usize,
+}
+
+impl DelegationBufferProgress {
+    /// Whether the buffer is ready and the account can be delegated
+    pub fn is_complete(&self) -> bool {
+        self.len == self.data_len
+    }
+}
+
+/// Prepare the delegation buffer ahead of the delegation, from instructions of its own.
+///
+/// Each call creates or grows the rent exempt buffer by at most `MAX_PERMITTED_DATA_INCREASE`
+/// (10 KiB) bytes, the growth permitted by the runtime within an instruction, until the returned
+/// progress is complete. The following delegation, e.g. via [`delegate_account`] or
+/// [`DelegateAccountCpiBuilder`], then reuses the prepared buffer, copies the account data into
+/// it and returns the buffer rent to the payer.
+///
+/// Accounts larger than `MAX_PERMITTED_DATA_INCREASE` are rejected with
+/// [`UndelegatedAccountTooLarge`](EphemeralRollupsSdkError::UndelegatedAccountTooLarge), here as
+/// by the delegation itself, since they couldn't be restored on undelegation, see
+/// [`undelegate_account`].
+///
+/// ```ignore
+/// let progress = prepare_delegation_buffer(pda, buffer, owner_program, payer, system_program)?;
+/// if progress.is_complete() {
+///     DelegateAccountCpiBuilder::new(accounts).seeds(seeds).invoke()?;
+/// }
+/// ```
+pub fn prepare_delegation_buffer<'a, 'info>(
+    pda: &'a compat::AccountInfo<'info>,
+    buffer: &'a compat::AccountInfo<'info>,
+    owner_program: &'a compat::AccountInfo<'info>,
+    payer: &'a compat::AccountInfo<'info>,
+    system_program: &'a compat::AccountInfo<'info>,
+) -> Result<DelegationBufferProgress, compat::ProgramError> {
+    verify_restorable(pda)?;
+    let data_len = pda.data_len();
+    let buffer_seeds: &[&[u8]] = delegate_buffer_seeds_from_delegated_account!(pda.key);
+    let (buffer_pda, buffer_bump) =
+        Address::find_program_address(buffer_seeds, owner_program.key.as_modern());
+    if buffer.key.as_modern() != &buffer_pda {
+        return Err(EphemeralRollupsSdkError::InvalidBuffer.into());
+    }
+
+    let len = if buffer.owner != owner_program.key {
+        let len = next_realloc_len(0, data_len);
+        let buffer_bump_slice: &[u8] = &[buffer_bump];
+        create_pda(
+            buffer,
+            owner_program.key,
+            len,
+            &[&*seeds_with_bump(buffer_seeds, buffer_bump_slice)],
+            system_program,
+            payer,
+            true,
+        )?;
+        len
+    } else {
+        let len = next_realloc_len(buffer.data_len(), data_len);
+        resize_rent_exempt(buffer, len, payer, system_program)?;
+        len
+    };
+    Ok(DelegationBufferProgress { len, data_len })
+}
+
+/// Returns the bump of the PDA, deriving it when not known, along with the PDA
+/// address when it's either derived anyway or required for verification
+fn resolve_pda(
+    seeds: &[&[u8]],
+    program_id: &Address,
+    bump: Option<u8>,
+    verify: bool,
+) -> (Option<Address>, u8) {
+    match bump {
+        None => {
+            let (pda, bump) = Address::find_program_address(seeds, program_id);
+            (Some(pda), bump)
+        }
+        Some(bump) if verify => {
+            let pda = Address::create_program_address(&seeds_with_bump(seeds, &[bump]), program_id);
+            (pda.ok(), bump)
+        }
+        Some(bump) => (None, bump),
+    }
+}
+
+/// Verifies that the accounts match the addresses derived from the delegated account,
+/// `pda` and `buffer` being the addresses already derived by the caller, if derivable
+fn verify_delegate_accounts(
+    accounts: &DelegateAccounts,
+    pda: Option<&Address>,
+    buffer: Option<&Address>,
+) -> Result<(), EphemeralRollupsSdkError> {
+    if Some(accounts.pda.key.as_modern()) != pda {
+        return Err(EphemeralRollupsSdkError::InvalidDelegatedAccount);
+    }
+    if Some(accounts.buffer.key.as_modern()) != buffer {
+        return Err(EphemeralRollupsSdkError::InvalidBuffer);
+    }
+    if accounts.delegation_record.key
+        != &pda::delegation_record_pda_from_delegated_account(accounts.pda.key)
+    {
+        return Err(EphemeralRollupsSdkError::InvalidDelegationRecord);
+    }
+    if accounts.delegation_metadata.key
+        != &pda::delegation_metadata_pda_from_delegated_account(accounts.pda.key)
+    {
+        return Err(EphemeralRollupsSdkError::InvalidDelegationMetadata);
+    }
+    Ok(())
+}
+
 /// Undelegate an account
+///
+/// The delegated account and the undelegation buffer addresses are verified up front,
+/// see [`undelegate_account_unchecked`] to skip the checks.
+///
+/// The account is re-created within the undelegation callback, a single instruction in which
+/// the runtime lets it grow by at most `MAX_PERMITTED_DATA_INCREASE` (10 KiB) bytes. Larger
+/// accounts can't be restored and fail with
+/// [`UndelegatedAccountTooLarge`](EphemeralRollupsSdkError::UndelegatedAccountTooLarge).
 pub fn undelegate_account<'a, 'info>(
     delegated_account: &'a compat::AccountInfo<'info>,
     owner_program: &compat::Pubkey,
@@ -286,19 +825,95 @@ pub fn undelegate_account<'a, 'info>(
     payer: &'a compat::AccountInfo<'info>,
     system_program: &'a compat::AccountInfo<'info>,
     account_signer_seeds: Vec<Vec<u8>>,
+) -> compat::ProgramResult {
+    undelegate_account_inner(
+        delegated_account,
+        owner_program,
+        buffer,
+        payer,
+        system_program,
+        &account_signer_seeds,
+        true,
+    )
+}
+
+/// Same as [`undelegate_account`], but skips the address checks. The buffer is still
+/// required to be a signer owned by the delegation program.
+pub fn undelegate_account_unchecked<'a, 'info>(
+    delegated_account: &'a compat::AccountInfo<'info>,
+    owner_program: &compat::Pubkey,
+    buffer: &'a compat::AccountInfo<'info>,
+    payer: &'a compat::AccountInfo<'info>,
+    system_program: &'a compat::AccountInfo<'info>,
+    account_signer_seeds: Vec<Vec<u8>>,
+) -> compat::ProgramResult {
+    undelegate_account_inner(
+        delegated_account,
+        owner_program,
+        buffer,
+        payer,
+        system_program,
+        &account_signer_seeds,
+        false,
+    )
+}
+
+/// Same as [`undelegate_account`], but invokes `on_undelegate` with the restored account and
+/// its seeds once the data has been copied back, e.g. to update the state derived from it.
+pub fn undelegate_account_with_hook<'a, 'info, F>(
+    delegated_account: &'a compat::AccountInfo<'info>,
+    owner_program: &compat::Pubkey,
+    buffer: &'a compat::AccountInfo<'info>,
+    payer: &'a compat::AccountInfo<'info>,
+    system_program: &'a compat::AccountInfo<'info>,
+    account_signer_seeds: Vec<Vec<u8>>,
+    on_undelegate: F,
+) -> compat::ProgramResult
+where
+    F: FnOnce(&'a compat::AccountInfo<'info>, &[Vec<u8>]) -> compat::ProgramResult,
+{
+    undelegate_account_inner(
+        delegated_account,
+        owner_program,
+        buffer,
+        payer,
+        system_program,
+        &account_signer_seeds,
+        true,
+    )?;
+    on_undelegate(delegated_account, &account_signer_seeds)
+}
+
+fn undelegate_account_inner<'a, 'info>(
+    delegated_account: &'a compat::AccountInfo<'info>,
+    owner_program: &compat::Pubkey,
+    buffer: &'a compat::AccountInfo<'info>,
+    payer: &'a compat::AccountInfo<'info>,
+    system_program: &'a compat::AccountInfo<'info>,
+    account_signer_seeds: &[Vec<u8>],
+    verify: bool,
 ) -> compat::ProgramResult {
     if !buffer.is_signer {
-        return Err(ProgramError::MissingRequiredSignature.compat());
+        return Err(EphemeralRollupsSdkError::BufferNotSigner.into());
     }
     if buffer.owner != &DELEGATION_PROGRAM_ID {
-        return Err(ProgramError::InvalidAccountOwner.compat());
+        return Err(EphemeralRollupsSdkError::InvalidBufferOwner.into());
     }
 
     let account_seeds: Vec<&[u8]> = account_signer_seeds.iter().map(|v| v.as_slice()).collect();
 
-    let (_, account_bump) =
+    let (account_pda, account_bump) =
         Address::find_program_address(account_seeds.as_ref(), owner_program.as_modern());
 
+    if verify {
+        if delegated_account.key.as_modern() != &account_pda {
+            return Err(EphemeralRollupsSdkError::InvalidDelegatedAccount.into());
+        }
+        if buffer.key != &pda::undelegate_buffer_pda_from_delegated_account(delegated_account.key) {
+            return Err(EphemeralRollupsSdkError::InvalidBuffer.into());
+        }
+    }
+
     // Account signer seeds
     let account_bump_slice: &[u8] = &[account_bump];
     let account_signer_seeds: &[&[&[u8]]] = &[&*seeds_with_bump(
@@ -306,11 +921,17 @@ pub fn undelegate_account<'a, 'info>(
         account_bump_slice,
     )];
 
-    // Re-create the original PDA
+    // Re-create the original PDA. The runtime bounds the growth of an account within an
+    // instruction to `MAX_PERMITTED_DATA_INCREASE`, and the callback is a single instruction
+    // of the delegation program, so larger accounts can't be restored
+    let data_len = buffer.data_len();
+    if data_len > MAX_PERMITTED_DATA_INCREASE {
+        return Err(EphemeralRollupsSdkError::UndelegatedAccountTooLarge.into());
+    }
     create_pda(
         delegated_account,
         owner_program,
-        buffer.data_len(),
+        data_len,
         account_signer_seeds,
         system_program,
         payer,
@@ -337,7 +958,7 @@ pub fn cpi_delegate<'a, 'info>(
     args: DelegateAccountArgs,
 ) -> compat::ProgramResult {
     cpi_delegate_with_discriminator(
-        0,
+        DlpDiscriminator::Delegate,
         payer,
         delegate_account,
         owner_program,
@@ -364,7 +985,7 @@ pub fn cpi_delegate_with_any_validator<'a, 'info>(
     args: DelegateAccountArgs,
 ) -> compat::ProgramResult {
     cpi_delegate_with_discriminator(
-        19,
+        DlpDiscriminator::DelegateWithAnyValidator,
         payer,
         delegate_account,
         owner_program,
@@ -379,7 +1000,7 @@ pub fn cpi_delegate_with_any_validator<'a, 'info>(
 
 #[allow(clippy::too_many_arguments)]
 fn cpi_delegate_with_discriminator<'a, 'info>(
-    discriminator: u64,
+    discriminator: DlpDiscriminator,
     payer: &'a compat::AccountInfo<'info>,
     delegate_account: &'a compat::AccountInfo<'info>,
     owner_program: &'a compat::AccountInfo<'info>,
@@ -390,6 +1011,19 @@ fn cpi_delegate_with_discriminator<'a, 'info>(
     signers_seeds: &[&[&[u8]]],
     args: DelegateAccountArgs,
 ) -> compat::ProgramResult {
+    let delegation_instruction = delegate_instruction(
+        discriminator,
+        payer.key,
+        &delegate_account_keys(
+            delegate_account,
+            owner_program,
+            buffer,
+            delegation_record,
+            delegation_metadata,
+            system_program,
+        ),
+        &args,
+    )?;
     modernize!(
         payer,
         delegate_account,
@@ -400,26 +1034,8 @@ fn cpi_delegate_with_discriminator<'a, 'info>(
         system_program
     );
 
-    let mut data: Vec<u8> = discriminator.to_le_bytes().to_vec();
-    args.serialize(&mut data)
-        .map_err(|_| compat::latest::ProgramError::BorshIoError.compat())?;
-
-    let delegation_instruction = Instruction {
-        program_id: crate::id().to_bytes().into(),
-        accounts: vec![
-            AccountMeta::new(payer.key.to_bytes().into(), true),
-            AccountMeta::new(delegate_account.key.to_bytes().into(), true),
-            AccountMeta::new_readonly(owner_program.key.to_bytes().into(), false),
-            AccountMeta::new(buffer.key.to_bytes().into(), false),
-            AccountMeta::new(delegation_record.key.to_bytes().into(), false),
-            AccountMeta::new(delegation_metadata.key.to_bytes().into(), false),
-            AccountMeta::new_readonly(system_program.key.to_bytes().into(), false),
-        ],
-        data,
-    };
-
     invoke_signed(
-        &delegation_instruction,
+        &delegation_instruction.modern(),
         &[
             payer.clone(),
             delegate_account.clone(),
@@ -448,6 +1064,18 @@ pub fn cpi_delegate_with_actions<'a, 'info>(
     args: DelegateWithActionsArgs,
     action_signer_infos: &'a [&'a compat::AccountInfo<'info>],
 ) -> compat::ProgramResult {
+    let delegation_instruction = delegate_with_actions_instruction(
+        payer.key,
+        &delegate_account_keys(
+            delegate_account,
+            owner_program,
+            buffer,
+            delegation_record,
+            delegation_metadata,
+            system_program,
+        ),
+        &args,
+    )?;
     modernize!(
         payer,
         delegate_account,
@@ -458,38 +1086,16 @@ pub fn cpi_delegate_with_actions<'a, 'info>(
         system_program
     );
 
-    let mut data = DlpDiscriminator::DelegateWithActions.to_vec();
-    let payload =
-        borsh::to_vec(&args).map_err(|_| ProgramError::InvalidInstructionData.compat())?;
-    data.extend_from_slice(&payload);
-
-    let mut accounts = vec![
-        AccountMeta::new(payer.key.to_bytes().into(), true),
-        AccountMeta::new(delegate_account.key.to_bytes().into(), true),
-        AccountMeta::new_readonly(owner_program.key.to_bytes().into(), false),
-        AccountMeta::new(buffer.key.to_bytes().into(), false),
-        AccountMeta::new(delegation_record.key.to_bytes().into(), false),
-        AccountMeta::new(delegation_metadata.key.to_bytes().into(), false),
-        AccountMeta::new_readonly(system_program.key.to_bytes().into(), false),
-    ];
-
     let mut signer_infos = Vec::new();
     for signer in &args.actions.signers {
         let info = action_signer_infos
             .iter()
             .find(|ai| *ai.key.as_array() == *signer)
-            .ok_or(ProgramError::NotEnoughAccountKeys.compat())?
+            .ok_or(EphemeralRollupsSdkError::MissingActionSigner)?
             .as_modern();
-        accounts.push(AccountMeta::new_readonly(*info.key, true));
         signer_infos.push((*info).clone());
     }
 
-    let delegation_instruction = Instruction {
-        program_id: crate::id().to_bytes().into(),
-        accounts,
-        data,
-    };
-
     let mut invoke_accounts = vec![
         payer.clone(),
         delegate_account.clone(),
@@ -501,5 +1107,604 @@ pub fn cpi_delegate_with_actions<'a, 'info>(
     ];
     invoke_accounts.extend(signer_infos);
 
-    invoke_signed(&delegation_instruction, &invoke_accounts, signers_seeds).compat()
+    invoke_signed(
+        &delegation_instruction.modern(),
+        &invoke_accounts,
+        signers_seeds,
+    )
+    .compat()
+}
+
+/// Collect the keys of the delegation accounts, as passed to the CPI
+fn delegate_account_keys(
+    delegate_account: &compat::AccountInfo,
+    owner_program: &compat::AccountInfo,
+    buffer: &compat::AccountInfo,
+    delegation_record: &compat::AccountInfo,
+    delegation_metadata: &compat::AccountInfo,
+    system_program: &compat::AccountInfo,
+) -> DelegateAccountKeys {
+    DelegateAccountKeys {
+        delegated_account: *delegate_account.key,
+        delegate_buffer: *buffer.key,
+        delegation_record: *delegation_record.key,
+        delegation_metadata: *delegation_metadata.key,
+        owner_program: *owner_program.key,
+        delegation_program: DELEGATION_PROGRAM_ID,
+        system_program: *system_program.key,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::ephem::tests::create_mock_account_info;
+
+    #[test]
+    fn test_verify_delegate_accounts() {
+        let owner_program = compat::Pubkey::new_unique();
+        let seeds: &[&[u8]] = &[b"counter"];
+        let (pda, _) = compat::Pubkey::find_program_address(seeds, &owner_program);
+        let (buffer, _) = compat::Pubkey::find_program_address(
+            delegate_buffer_seeds_from_delegated_account!(pda),
+            &owner_program,
+        );
+        let mut keys = [
+            pda,
+            buffer,
+            pda::delegation_record_pda_from_delegated_account(&pda),
+            pda::delegation_metadata_pda_from_delegated_account(&pda),
+            owner_program,
+        ];
+        let verify = |keys: &[compat::Pubkey; 5]| {
+            let mut lamports = [0u64; 5];
+            let mut data = [[0u8; 0]; 5];
+            let mut infos = keys
+                .iter()
+                .zip(lamports.iter_mut())
+                .zip(data.iter_mut())
+                .map(|((key, lamports), data)| {
+                    create_mock_account_info(key, lamports, data, key, false, true)
+                });
+            let [pda, buffer, record, metadata, program] =
+                std::array::from_fn(|_| infos.next().unwrap());
+            let accounts = DelegateAccounts {
+                payer: &program,
+                pda: &pda,
+                owner_program: &program,
+                buffer: &buffer,
+                delegation_record: &record,
+                delegation_metadata: &metadata,
+                delegation_program: &program,
+                system_program: &program,
+            };
+            let (pda, _) =
+                Address::find_program_address(seeds, accounts.owner_program.key.as_modern());
+            let (buffer, _) = Address::find_program_address(
+                delegate_buffer_seeds_from_delegated_account!(accounts.pda.key),
+                accounts.owner_program.key.as_modern(),
+            );
+            verify_delegate_accounts(&accounts, Some(&pda), Some(&buffer))
+        };
+        assert_eq!(verify(&keys), Ok(()));
+
+        let expected = [
+            EphemeralRollupsSdkError::InvalidDelegatedAccount,
+            EphemeralRollupsSdkError::InvalidBuffer,
+            EphemeralRollupsSdkError::InvalidDelegationRecord,
+            EphemeralRollupsSdkError::InvalidDelegationMetadata,
+        ];
+        for (i, err) in expected.into_iter().enumerate() {
+            let valid = keys[i];
+            keys[i] = compat::Pubkey::new_unique();
+            assert_eq!(verify(&keys), Err(err));
+            keys[i] = valid;
+        }
+    }
+
+    #[test]
+    fn test_resolve_pda_with_known_bump() {
+        let program_id = Address::new_unique();
+        let seeds: &[&[u8]] = &[b"counter"];
+        let (pda, bump) = Address::find_program_address(seeds, &program_id);
+
+        assert_eq!(
+            resolve_pda(seeds, &program_id, None, false),
+            (Some(pda), bump)
+        );
+        assert_eq!(
+            resolve_pda(seeds, &program_id, Some(bump), true),
+            (Some(pda), bump)
+        );
+        // the address is not needed, when the bump is known and checks are skipped
+        assert_eq!(
+            resolve_pda(seeds, &program_id, Some(bump), false),
+            (None, bump)
+        );
+        // wrong bump is caught by verification
+        let (wrong, _) = resolve_pda(seeds, &program_id, Some(bump.wrapping_sub(1)), true);
+        assert_ne!(wrong, Some(pda));
+    }
+
+    #[test]
+    fn test_builder_requires_seeds() {
+        let key = compat::Pubkey::new_unique();
+        let (mut lamports, mut data) = (0, []);
+        let info = create_mock_account_info(&key, &mut lamports, &mut data, &key, false, true);
+        let accounts = DelegateAccounts {
+            payer: &info,
+            pda: &info,
+            owner_program: &info,
+            buffer: &info,
+            delegation_record: &info,
+            delegation_metadata: &info,
+            delegation_program: &info,
+            system_program: &info,
+        };
+        assert_eq!(
+            DelegateAccountCpiBuilder::new(accounts).bump(255).invoke(),
+            Err(EphemeralRollupsSdkError::MissingDelegationSeeds.into())
+        );
+    }
+
+    #[test]
+    fn test_batch_is_validated_before_mutation() {
+        let owner_program = compat::Pubkey::new_unique();
+        let seeds: [&[&[u8]]; 2] = [&[b"player"], &[b"stats"]];
+        let mut keys: Vec<[compat::Pubkey; 4]> = seeds
+            .iter()
+            .map(|seeds| {
+                let (pda, _) = compat::Pubkey::find_program_address(seeds, &owner_program);
+                let (buffer, _) = compat::Pubkey::find_program_address(
+                    delegate_buffer_seeds_from_delegated_account!(pda),
+                    &owner_program,
+                );
+                [
+                    pda,
+                    buffer,
+                    pda::delegation_record_pda_from_delegated_account(&pda),
+                    pda::delegation_metadata_pda_from_delegated_account(&pda),
+                ]
+            })
+            .collect();
+        let batch = |keys: &[[compat::Pubkey; 4]], seeds: &[&[&[u8]]]| {
+            let (mut program_lamports, mut program_data) = (0, []);
+            let program = create_mock_account_info(
+                &owner_program,
+                &mut program_lamports,
+                &mut program_data,
+                &owner_program,
+                false,
+                true,
+            );
+            let mut lamports = vec![[1u64; 4]; keys.len()];
+            let mut data = vec![[[1u8; 8]; 4]; keys.len()];
+            let infos: Vec<Vec<_>> = keys
+                .iter()
+                .zip(lamports.iter_mut())
+                .zip(data.iter_mut())
+                .map(|((keys, lamports), data)| {
+                    keys.iter()
+                        .zip(lamports.iter_mut())
+                        .zip(data.iter_mut())
+                        .map(|((key, lamports), data)| {
+                            create_mock_account_info(key, lamports, data, key, false, true)
+                        })
+                        .collect()
+                })
+                .collect();
+            let items: Vec<_> = infos
+                .iter()
+                .zip(seeds)
+                .map(|(infos, seeds)| DelegateItem {
+                    pda: &infos[0],
+                    buffer: &infos[1],
+                    delegation_record: &infos[2],
+                    delegation_metadata: &infos[3],
+                    seeds,
+                    config: DelegateConfig::default(),
+                })
+                .collect();
+            let accounts = DelegateBatchAccounts {
+                payer: &program,
+                owner_program: &program,
+                delegation_program: &program,
+                system_program: &program,
+            };
+            let result = delegate_accounts_batch(accounts, &items);
+            // none of the accounts has been touched
+            assert!(infos
+                .iter()
+                .flatten()
+                .all(|info| info.lamports() == 1 && *info.data.borrow() == [1u8; 8]));
+            result
+        };
+
+        let duplicate = [keys[0], keys[1], keys[0]];
+        assert_eq!(
+            batch(&duplicate, &[seeds[0], seeds[1], seeds[0]]),
+            Err(DelegateBatchError {
+                results: vec![
+                    Ok(()),
+                    Ok(()),
+                    Err(EphemeralRollupsSdkError::DuplicateDelegatedAccount.into()),
+                ],
+            })
+        );
+
+        // every invalid item is reported, not only the first one
+        keys[0][3] = compat::Pubkey::new_unique();
+        keys[1][2] = compat::Pubkey::new_unique();
+        let error = batch(&keys, &seeds).unwrap_err();
+        assert_eq!(
+            error.results,
+            vec![
+                Err(EphemeralRollupsSdkError::InvalidDelegationMetadata.into()),
+                Err(EphemeralRollupsSdkError::InvalidDelegationRecord.into()),
+            ]
+        );
+        assert_eq!(
+            error.errors().map(|(index, _)| index).collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+        assert_eq!(
+            compat::ProgramError::from(error),
+            EphemeralRollupsSdkError::InvalidDelegationMetadata.into()
+        );
+    }
+
+    #[test]
+    fn test_realloc_steps_at_size_boundaries() {
+        const STEP: usize = solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE;
+        let steps = |data_len: usize| {
+            let mut lens = vec![next_realloc_len(0, data_len)];
+            while lens[lens.len() - 1] < data_len {
+                lens.push(next_realloc_len(lens[lens.len() - 1], data_len));
+            }
+            lens
+        };
+        assert_eq!(steps(0), vec![0]);
+        assert_eq!(steps(STEP - 1), vec![STEP - 1]);
+        assert_eq!(steps(STEP), vec![STEP]);
+        assert_eq!(steps(STEP + 1), vec![STEP, STEP + 1]);
+        assert_eq!(steps(2 * STEP), vec![STEP, 2 * STEP]);
+        assert_eq!(steps(100 * 1024).len(), 10);
+        // the shrunk account trims the buffer back in a single step
+        assert_eq!(next_realloc_len(3 * STEP, STEP + 1), STEP + 1);
+
+        let progress = |len| DelegationBufferProgress {
+            len,
+            data_len: STEP + 1,
+        };
+        assert!(!progress(STEP).is_complete());
+        assert!(progress(STEP + 1).is_complete());
+    }
+
+    /// System program emulated on top of the runtime serialization of `account`, so that
+    /// creating it goes through the bounds checked `AccountInfo::resize`
+    struct SystemProgramStubs {
+        account: Address,
+    }
+
+    impl solana_program::program_stubs::SyscallStubs for SystemProgramStubs {
+        fn sol_get_rent_sysvar(&self, _var_addr: *mut u8) -> u64 {
+            // keeps the default rent
+            solana_program::entrypoint::SUCCESS
+        }
+
+        fn sol_invoke_signed(
+            &self,
+            instruction: &solana_program::instruction::Instruction,
+            account_infos: &[solana_program::account_info::AccountInfo],
+            _signers_seeds: &[&[&[u8]]],
+        ) -> solana_program::entrypoint::ProgramResult {
+            let Some(account) = account_infos.iter().find(|info| info.key == &self.account) else {
+                return Ok(());
+            };
+            // CreateAccount { lamports, space, owner }
+            let (discriminant, args) = instruction.data.split_at(4);
+            assert_eq!(discriminant, [0; 4]);
+            let lamports = u64::from_le_bytes(args[..8].try_into().unwrap());
+            let space = u64::from_le_bytes(args[8..16].try_into().unwrap());
+            account.resize(space as usize)?;
+            account.assign(&Address::new_from_array(args[16..48].try_into().unwrap()));
+            **account.try_borrow_mut_lamports()? += lamports;
+            Ok(())
+        }
+    }
+
+    const SERIALIZED_KEY: usize = 8;
+    const SERIALIZED_OWNER: usize = 40;
+    const SERIALIZED_DATA: usize = 80;
+    const SERIALIZED_LEN: usize = (SERIALIZED_DATA + MAX_PERMITTED_DATA_INCREASE) / 8;
+
+    /// Empty system account laid out as serialized by the runtime for an invocation: the
+    /// original data length precedes the key, the data length precedes the data, and the
+    /// data is followed by `MAX_PERMITTED_DATA_INCREASE` bytes of spare room
+    #[allow(deprecated)]
+    fn serialized_account<'a>(
+        memory: &'a mut [u64; SERIALIZED_LEN],
+        key: &compat::Pubkey,
+        lamports: &'a mut u64,
+    ) -> compat::AccountInfo<'a> {
+        let bytes = memory.as_mut_ptr() as *mut u8;
+        unsafe {
+            bytes
+                .add(SERIALIZED_KEY)
+                .copy_from_nonoverlapping(key.as_ref().as_ptr(), 32);
+            compat::AccountInfo {
+                key: &*(bytes.add(SERIALIZED_KEY) as *const compat::Pubkey),
+                is_signer: false,
+                is_writable: true,
+                lamports: Rc::new(RefCell::new(lamports)),
+                data: Rc::new(RefCell::new(std::slice::from_raw_parts_mut(
+                    bytes.add(SERIALIZED_DATA),
+                    0,
+                ))),
+                owner: &*(bytes.add(SERIALIZED_OWNER) as *const compat::Pubkey),
+                executable: false,
+                #[cfg(feature = "backward-compat")]
+                rent_epoch: 0,
+                #[cfg(not(feature = "backward-compat"))]
+                _unused: 0,
+            }
+        }
+    }
+
+    #[test]
+    fn test_undelegated_account_restore_at_realloc_boundary() {
+        let owner_program = compat::Pubkey::new_unique();
+        let seeds = vec![b"counter".to_vec()];
+        let (pda, _) = compat::Pubkey::find_program_address(&[b"counter"], &owner_program);
+        let buffer_key = compat::Pubkey::new_unique();
+        let other_key = compat::Pubkey::new_unique();
+        let previous =
+            solana_program::program_stubs::set_syscall_stubs(Box::new(SystemProgramStubs {
+                account: *pda.as_modern(),
+            }));
+
+        let restore = |data_len: usize| {
+            let mut memory = [0u64; SERIALIZED_LEN];
+            let mut lamports = 0;
+            let account = serialized_account(&mut memory, &pda, &mut lamports);
+            let (mut buffer_lamports, mut buffer_data) = (1, vec![7u8; data_len]);
+            let buffer = compat::AccountInfo {
+                is_signer: true,
+                owner: &DELEGATION_PROGRAM_ID,
+                ..create_mock_account_info(
+                    &buffer_key,
+                    &mut buffer_lamports,
+                    &mut buffer_data,
+                    &buffer_key,
+                    false,
+                    true,
+                )
+            };
+            let (mut other_lamports, mut other_data) = (1, []);
+            let other = create_mock_account_info(
+                &other_key,
+                &mut other_lamports,
+                &mut other_data,
+                &other_key,
+                false,
+                true,
+            );
+            let result = undelegate_account_unchecked(
+                &account,
+                &owner_program,
+                &buffer,
+                &other,
+                &other,
+                seeds.clone(),
+            );
+            let restored = account.data.borrow().iter().all(|byte| *byte == 7)
+                && account.owner == &owner_program;
+            (result, account.data_len(), restored)
+        };
+
+        assert_eq!(
+            restore(MAX_PERMITTED_DATA_INCREASE),
+            (Ok(()), MAX_PERMITTED_DATA_INCREASE, true)
+        );
+        // the runtime can't grow the account any further within the callback instruction,
+        // which is rejected before creating anything
+        assert_eq!(
+            restore(MAX_PERMITTED_DATA_INCREASE + 1),
+            (
+                Err(EphemeralRollupsSdkError::UndelegatedAccountTooLarge.into()),
+                0,
+                false
+            )
+        );
+        let mut memory = [0u64; SERIALIZED_LEN];
+        let mut lamports = 0;
+        let account = serialized_account(&mut memory, &pda, &mut lamports);
+        assert_eq!(
+            account.modern().resize(MAX_PERMITTED_DATA_INCREASE + 1),
+            Err(solana_program::program_error::ProgramError::InvalidRealloc)
+        );
+
+        solana_program::program_stubs::set_syscall_stubs(previous);
+    }
+
+    #[test]
+    fn test_unrestorable_account_is_not_delegated() {
+        let owner_program = compat::Pubkey::new_unique();
+        let (pda_key, buffer_key) = (compat::Pubkey::new_unique(), compat::Pubkey::new_unique());
+        let (mut program_lamports, mut program_data) = (0, []);
+        let program = create_mock_account_info(
+            &owner_program,
+            &mut program_lamports,
+            &mut program_data,
+            &owner_program,
+            false,
+            true,
+        );
+        let (mut pda_lamports, mut pda_data) = (1, vec![1u8; MAX_PERMITTED_DATA_INCREASE + 1]);
+        let pda = create_mock_account_info(
+            &pda_key,
+            &mut pda_lamports,
+            &mut pda_data,
+            &owner_program,
+            false,
+            true,
+        );
+        let (mut buffer_lamports, mut buffer_data) = (0, []);
+        let buffer = create_mock_account_info(
+            &buffer_key,
+            &mut buffer_lamports,
+            &mut buffer_data,
+            &buffer_key,
+            false,
+            true,
+        );
+        let accounts = || DelegateAccounts {
+            payer: &program,
+            pda: &pda,
+            owner_program: &program,
+            buffer: &buffer,
+            delegation_record: &program,
+            delegation_metadata: &program,
+            delegation_program: &program,
+            system_program: &program,
+        };
+
+        // rejected before anything is created, as the undelegation couldn't restore it
+        let too_large = Err(EphemeralRollupsSdkError::UndelegatedAccountTooLarge.into());
+        assert_eq!(
+            prepare_delegation_buffer(&pda, &buffer, &program, &program, &program).map(|_| ()),
+            too_large
+        );
+        assert_eq!(
+            delegate_account_unchecked(accounts(), &[b"counter"], DelegateConfig::default()),
+            too_large
+        );
+        assert_eq!(
+            DelegateAccountCpiBuilder::new(accounts())
+                .seeds(&[b"counter"])
+                .bump(255)
+                .buffer_bump(255)
+                .skip_address_checks()
+                .invoke(),
+            too_large
+        );
+        assert_eq!(buffer.data_len(), 0);
+        assert!(pda.data.borrow().iter().all(|byte| *byte == 1));
+    }
+
+    #[test]
+    fn test_incomplete_prepared_buffer_is_rejected() {
+        let owner_program = compat::Pubkey::new_unique();
+        let (pda_key, buffer_key) = (compat::Pubkey::new_unique(), compat::Pubkey::new_unique());
+        let (mut program_lamports, mut program_data) = (0, []);
+        let program = create_mock_account_info(
+            &owner_program,
+            &mut program_lamports,
+            &mut program_data,
+            &owner_program,
+            false,
+            true,
+        );
+        let (mut pda_lamports, mut pda_data) = (1, [1u8; 16]);
+        let pda = create_mock_account_info(
+            &pda_key,
+            &mut pda_lamports,
+            &mut pda_data,
+            &pda_key,
+            false,
+            true,
+        );
+        let (mut buffer_lamports, mut buffer_data) = (1, [0u8; 8]);
+        let mut buffer = create_mock_account_info(
+            &buffer_key,
+            &mut buffer_lamports,
+            &mut buffer_data,
+            &buffer_key,
+            false,
+            true,
+        );
+        buffer.owner = &owner_program;
+
+        let accounts = DelegateAccounts {
+            payer: &program,
+            pda: &pda,
+            owner_program: &program,
+            buffer: &buffer,
+            delegation_record: &program,
+            delegation_metadata: &program,
+            delegation_program: &program,
+            system_program: &program,
+        };
+        assert_eq!(
+            DelegateAccountCpiBuilder::new(accounts)
+                .seeds(&[b"counter"])
+                .bump(255)
+                .buffer_bump(255)
+                .skip_address_checks()
+                .invoke(),
+            Err(EphemeralRollupsSdkError::IncompleteDelegationBuffer.into())
+        );
+        assert_eq!(*pda.data.borrow(), [1u8; 16]);
+    }
+
+    #[test]
+    fn test_verify_escrow() {
+        let authority_key = compat::Pubkey::new_unique();
+        let escrow_key = escrow_pda(&authority_key, 7);
+        let (mut lamports, mut data) = ([0u64; 2], [[0u8; 0]; 2]);
+        let [l0, l1] = &mut lamports;
+        let [d0, d1] = &mut data;
+        let authority =
+            create_mock_account_info(&authority_key, l0, d0, &authority_key, false, true);
+        let mut escrow = create_mock_account_info(&escrow_key, l1, d1, &escrow_key, false, true);
+
+        assert_eq!(
+            verify_escrow(&escrow, &authority, 7),
+            Err(EphemeralRollupsSdkError::EscrowNotSigner)
+        );
+        escrow.is_signer = true;
+        assert_eq!(verify_escrow(&escrow, &authority, 7), Ok(()));
+        assert_eq!(
+            verify_escrow(&escrow, &authority, 255),
+            Err(EphemeralRollupsSdkError::InvalidEscrow)
+        );
+        assert_eq!(
+            verify_escrow(&authority, &escrow, 7),
+            Err(EphemeralRollupsSdkError::EscrowNotSigner)
+        );
+    }
+
+    #[test]
+    fn test_verify_migration_escrow() {
+        let account = compat::Pubkey::new_unique();
+        let escrow_key = pda::ephemeral_balance_pda_from_payer(&account, 1);
+        let other = compat::Pubkey::new_unique();
+        let (mut lamports, mut data) = ([0u64; 3], [[0u8; 0]; 3]);
+        let [l0, l1, l2] = &mut lamports;
+        let [d0, d1, d2] = &mut data;
+        let authority = create_mock_account_info(&account, l0, d0, &account, false, true);
+        let mut escrow = create_mock_account_info(&escrow_key, l1, d1, &escrow_key, false, true);
+        let other = create_mock_account_info(&other, l2, d2, &other, false, true);
+
+        assert_eq!(
+            verify_migration_escrow(&account, &authority, &escrow, 1),
+            Err(EphemeralRollupsSdkError::EscrowNotSigner)
+        );
+        escrow.is_signer = true;
+        assert_eq!(
+            verify_migration_escrow(&account, &authority, &escrow, 1),
+            Ok(())
+        );
+        assert_eq!(
+            verify_migration_escrow(&account, &other, &escrow, 1),
+            Err(EphemeralRollupsSdkError::InvalidEscrowAuthority)
+        );
+        assert_eq!(
+            verify_migration_escrow(&account, &authority, &escrow, 2),
+            Err(EphemeralRollupsSdkError::InvalidEscrow)
+        );
+    }
 }
@@ -5,6 +5,9 @@ use solana_program::{
 };
 
 use crate::compat::{self, AsModern, Compat, Modern};
+use crate::consts::{MAGIC_CONTEXT_ID, MAGIC_PROGRAM_ID};
+use crate::ephem::{FoldableIntentBuilder, MagicIntentBundleBuilder};
+use crate::error::EphemeralRollupsSdkError;
 
 pub struct ScheduleCrankCpi<'a> {
     pub payer: &'a compat::AccountInfo<'a>,
@@ -96,22 +99,167 @@ impl<'a> CancelCrankCpi<'a> {
     }
 }
 
+/// Registers a crank committing `committed_accounts` every `execution_interval_millis`,
+/// without undelegating them.
+///
+/// The crank invokes `program_id` with `data`, whose handler commits the accounts with
+/// [`process_scheduled_commit`]. The crank instruction accounts are the commit payer, the
+/// magic context, the magic program and the committed accounts.
+pub struct ScheduledCommit<'a> {
+    pub payer: &'a compat::AccountInfo<'a>,
+    pub task_context: &'a compat::AccountInfo<'a>,
+    pub magic_program: &'a compat::AccountInfo<'a>,
+    pub magic_context: &'a compat::AccountInfo<'a>,
+    /// Payer of the commits, a PDA of `program_id` signed for by the handler
+    pub commit_payer: &'a compat::AccountInfo<'a>,
+    pub committed_accounts: &'a [compat::AccountInfo<'a>],
+    /// Program handling the crank instruction
+    pub program_id: compat::Pubkey,
+    /// Instruction data of the handler
+    pub data: Vec<u8>,
+    pub task_id: i64,
+    pub execution_interval_millis: i64,
+    pub iterations: i64,
+}
+
+impl<'a> ScheduledCommit<'a> {
+    /// Instruction executed by the crank.
+    pub fn crank_instruction(&self) -> compat::Instruction {
+        let mut accounts = Vec::with_capacity(3 + self.committed_accounts.len());
+        accounts.push(AccountMeta::new(*self.commit_payer.key.as_modern(), false));
+        accounts.push(AccountMeta::new(*self.magic_context.key.as_modern(), false));
+        accounts.push(AccountMeta::new_readonly(
+            *self.magic_program.key.as_modern(),
+            false,
+        ));
+        accounts.extend(self.committed_accounts.iter().map(|ai| AccountMeta {
+            pubkey: *ai.key.as_modern(),
+            is_signer: false,
+            is_writable: ai.is_writable,
+        }));
+
+        Instruction {
+            program_id: *self.program_id.as_modern(),
+            accounts,
+            data: self.data.clone(),
+        }
+        .compat()
+    }
+
+    pub fn instruction(&self) -> compat::Instruction {
+        let mut accounts = Vec::with_capacity(5 + self.committed_accounts.len());
+        accounts.push(AccountMeta::new(*self.payer.key.as_modern(), true));
+        accounts.extend(self.instruction_accounts().iter().map(|ai| AccountMeta {
+            pubkey: *ai.key.as_modern(),
+            is_signer: ai.is_signer,
+            is_writable: ai.is_writable,
+        }));
+
+        Instruction::new_with_bincode(
+            *self.magic_program.key.as_modern(),
+            &MagicBlockInstruction::ScheduleTask(ScheduleTaskArgs {
+                task_id: self.task_id,
+                execution_interval_millis: self.execution_interval_millis,
+                iterations: self.iterations,
+                instructions: vec![self.crank_instruction()],
+            }),
+            accounts,
+        )
+        .compat()
+    }
+
+    pub fn invoke(&self) -> compat::ProgramResult {
+        self.invoke_signed(&[])
+    }
+
+    pub fn invoke_signed(&self, signers_seeds: &[&[&[u8]]]) -> compat::ProgramResult {
+        let mut accounts = vec![self.payer.clone()];
+        accounts.extend(self.instruction_accounts());
+
+        invoke_signed(
+            &self.instruction().modern(),
+            &accounts.modern(),
+            signers_seeds,
+        )
+        .compat()
+    }
+
+    /// Cancels the crank registered with `task_id`.
+    pub fn cancel<'b>(
+        authority: &'b compat::AccountInfo<'b>,
+        task_context: &'b compat::AccountInfo<'b>,
+        magic_program: &'b compat::AccountInfo<'b>,
+        task_id: i64,
+    ) -> CancelCrankCpi<'b> {
+        CancelCrankCpi {
+            authority,
+            task_context,
+            magic_program,
+            crank_id: task_id,
+        }
+    }
+
+    /// Accounts of the `ScheduleTask` instruction following the payer.
+    fn instruction_accounts(&self) -> Vec<compat::AccountInfo<'a>> {
+        let mut accounts = Vec::with_capacity(4 + self.committed_accounts.len());
+        accounts.extend([
+            self.task_context.clone(),
+            self.commit_payer.clone(),
+            self.magic_context.clone(),
+            self.magic_program.clone(),
+        ]);
+        accounts.extend_from_slice(self.committed_accounts);
+        accounts
+    }
+}
+
+/// Commits the accounts of a crank registered with [`ScheduledCommit`], `accounts` being the
+/// accounts of the crank instruction and `commit_payer_seeds` the seeds of the commit payer.
+///
+/// As anyone can invoke the handler, the magic program and context must be
+/// [`MAGIC_PROGRAM_ID`] and [`MAGIC_CONTEXT_ID`], and the committed accounts must be
+/// `committed`, the accounts the crank has been registered with, in order. The program either
+/// stores them at registration or derives them, e.g. as PDAs of the commit payer.
+pub fn process_scheduled_commit<'info>(
+    accounts: &[compat::AccountInfo<'info>],
+    committed: &[compat::Pubkey],
+    commit_payer_seeds: &[&[u8]],
+) -> compat::ProgramResult {
+    let [commit_payer, magic_context, magic_program, committed_accounts @ ..] = accounts else {
+        return Err(compat::ProgramError::NotEnoughAccountKeys);
+    };
+    if committed_accounts.is_empty() {
+        return Err(compat::ProgramError::NotEnoughAccountKeys);
+    }
+    if magic_program.key != &MAGIC_PROGRAM_ID {
+        return Err(compat::ProgramError::IncorrectProgramId);
+    }
+    if magic_context.key != &MAGIC_CONTEXT_ID {
+        return Err(EphemeralRollupsSdkError::InvalidMagicContext.into());
+    }
+    if !committed_accounts
+        .iter()
+        .map(|account| account.key)
+        .eq(committed)
+    {
+        return Err(EphemeralRollupsSdkError::InvalidCommittedAccounts.into());
+    }
+
+    MagicIntentBundleBuilder::new(
+        commit_payer.clone(),
+        magic_context.clone(),
+        magic_program.clone(),
+    )
+    .commit(committed_accounts)
+    .build_and_invoke_signed(&[commit_payer_seeds])
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::compat::{AccountInfo, AccountMeta, Pubkey};
+    use crate::ephem::tests::create_mock_account_info;
     use magicblock_magic_program_api::args::ScheduleTaskArgs;
-    use solana_program::{account_info::AccountInfo, pubkey::Pubkey};
-
-    fn new_account_info<'a>(
-        key: &'a Pubkey,
-        is_signer: bool,
-        is_writable: bool,
-        lamports: &'a mut u64,
-        data: &'a mut [u8],
-        owner: &'a Pubkey,
-    ) -> AccountInfo<'a> {
-        AccountInfo::new(key, is_signer, is_writable, lamports, data, owner, false)
-    }
 
     #[test]
     fn schedule_instruction_marks_payer_writable_signer() {
@@ -126,29 +274,29 @@ mod tests {
         let mut program_data = [];
         let mut task_context_data = [];
 
-        let payer = new_account_info(
+        let payer = create_mock_account_info(
             &payer_key,
-            false,
-            false,
             &mut payer_lamports,
             &mut payer_data,
             &owner,
-        );
-        let magic_program = new_account_info(
-            &magic_program_key,
             false,
             false,
+        );
+        let magic_program = create_mock_account_info(
+            &magic_program_key,
             &mut program_lamports,
             &mut program_data,
             &owner,
+            false,
+            false,
         );
-        let task_context = new_account_info(
+        let task_context = create_mock_account_info(
             &task_context_key,
-            false,
-            true,
             &mut task_context_lamports,
             &mut task_context_data,
             &owner,
+            false,
+            true,
         );
         let instruction_accounts = [task_context];
 
@@ -185,29 +333,29 @@ mod tests {
         let mut task_context_data = [];
         let mut program_data = [];
 
-        let authority = new_account_info(
+        let authority = create_mock_account_info(
             &authority_key,
-            false,
-            false,
             &mut authority_lamports,
             &mut authority_data,
             &owner,
-        );
-        let task_context = new_account_info(
-            &task_context_key,
             false,
             false,
+        );
+        let task_context = create_mock_account_info(
+            &task_context_key,
             &mut task_context_lamports,
             &mut task_context_data,
             &owner,
-        );
-        let magic_program = new_account_info(
-            &magic_program_key,
             false,
             false,
+        );
+        let magic_program = create_mock_account_info(
+            &magic_program_key,
             &mut program_lamports,
             &mut program_data,
             &owner,
+            false,
+            false,
         );
 
         let instruction = CancelCrankCpi {
@@ -242,29 +390,29 @@ mod tests {
         let mut task_context_data = [];
         let mut program_data = [];
 
-        let authority = new_account_info(
+        let authority = create_mock_account_info(
             &authority_key,
-            false,
-            true,
             &mut authority_lamports,
             &mut authority_data,
             &owner,
-        );
-        let task_context = new_account_info(
-            &task_context_key,
             false,
             true,
+        );
+        let task_context = create_mock_account_info(
+            &task_context_key,
             &mut task_context_lamports,
             &mut task_context_data,
             &owner,
+            false,
+            true,
         );
-        let magic_program = new_account_info(
+        let magic_program = create_mock_account_info(
             &magic_program_key,
-            false,
-            false,
             &mut program_lamports,
             &mut program_data,
             &owner,
+            false,
+            false,
         );
 
         let instruction = CancelCrankCpi {
@@ -285,4 +433,169 @@ mod tests {
             AccountMeta::new(task_context_key, false)
         );
     }
+
+    #[test]
+    fn scheduled_commit_instruction_schedules_crank_committing_accounts() {
+        let keys: Vec<Pubkey> = (0..7).map(|_| Pubkey::new_unique()).collect();
+        let program_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mut lamports = [0u64; 7];
+        let mut data: [[u8; 0]; 7] = Default::default();
+        let mut infos: Vec<AccountInfo> = keys
+            .iter()
+            .zip(lamports.iter_mut())
+            .zip(data.iter_mut())
+            .map(|((key, lamports), data)| {
+                create_mock_account_info(key, lamports, data, &owner, false, true)
+            })
+            .collect();
+        infos[0].is_signer = true;
+        infos[2].is_writable = false;
+        let [payer, task_context, magic_program, magic_context, commit_payer, committed @ ..] =
+            infos.as_slice()
+        else {
+            unreachable!()
+        };
+
+        let scheduled_commit = ScheduledCommit {
+            payer,
+            task_context,
+            magic_program,
+            magic_context,
+            commit_payer,
+            committed_accounts: committed,
+            program_id,
+            data: vec![1, 2, 3],
+            task_id: 5,
+            execution_interval_millis: 1_000,
+            iterations: 10,
+        };
+        let instruction = scheduled_commit.instruction();
+
+        assert_eq!(instruction.program_id, keys[2]);
+        assert_eq!(
+            instruction
+                .accounts
+                .iter()
+                .map(|meta| meta.pubkey)
+                .collect::<Vec<_>>(),
+            [keys[0], keys[1], keys[4], keys[3], keys[2], keys[5], keys[6]]
+        );
+        assert_eq!(instruction.accounts[0], AccountMeta::new(keys[0], true));
+
+        let MagicBlockInstruction::ScheduleTask(args) =
+            bincode::deserialize(&instruction.data).unwrap()
+        else {
+            panic!("expected ScheduleTask");
+        };
+        assert_eq!(
+            (
+                args.task_id,
+                args.execution_interval_millis,
+                args.iterations
+            ),
+            (5, 1_000, 10)
+        );
+        assert_eq!(args.instructions, [scheduled_commit.crank_instruction()]);
+        let crank_instruction = &args.instructions[0];
+        assert_eq!(crank_instruction.program_id, program_id);
+        assert_eq!(crank_instruction.data, [1, 2, 3]);
+        assert_eq!(
+            crank_instruction.accounts,
+            [
+                AccountMeta::new(keys[4], false),
+                AccountMeta::new(keys[3], false),
+                AccountMeta::new_readonly(keys[2], false),
+                AccountMeta::new(keys[5], false),
+                AccountMeta::new(keys[6], false),
+            ]
+        );
+
+        let cancel = ScheduledCommit::cancel(payer, task_context, magic_program, 5).instruction();
+        assert_eq!(
+            bincode::deserialize::<MagicBlockInstruction>(&cancel.data).unwrap(),
+            MagicBlockInstruction::CancelTask { task_id: 5 }
+        );
+    }
+
+    #[test]
+    fn process_scheduled_commit_requires_committed_accounts() {
+        let keys: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let owner = Pubkey::new_unique();
+        let mut lamports = [0u64; 3];
+        let mut data: [[u8; 0]; 3] = Default::default();
+        let infos: Vec<AccountInfo> = keys
+            .iter()
+            .zip(lamports.iter_mut())
+            .zip(data.iter_mut())
+            .map(|((key, lamports), data)| {
+                create_mock_account_info(key, lamports, data, &owner, false, true)
+            })
+            .collect();
+
+        assert_eq!(
+            process_scheduled_commit(&infos, &[], &[]),
+            Err(compat::ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!(
+            process_scheduled_commit(&infos[..2], &[], &[]),
+            Err(compat::ProgramError::NotEnoughAccountKeys)
+        );
+    }
+
+    #[test]
+    fn process_scheduled_commit_checks_accounts() {
+        let committed = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let other = Pubkey::new_unique();
+        let keys = [
+            Pubkey::new_unique(),
+            MAGIC_CONTEXT_ID,
+            MAGIC_PROGRAM_ID,
+            committed[0],
+            committed[1],
+        ];
+        let owner = Pubkey::new_unique();
+        let mut lamports = [0u64; 5];
+        let mut data: [[u8; 0]; 5] = Default::default();
+        let infos: Vec<AccountInfo> = keys
+            .iter()
+            .zip(lamports.iter_mut())
+            .zip(data.iter_mut())
+            .map(|((key, lamports), data)| {
+                create_mock_account_info(key, lamports, data, &owner, false, true)
+            })
+            .collect();
+        let with_key = |i: usize| {
+            let mut infos = infos.clone();
+            infos[i].key = &other;
+            infos
+        };
+
+        assert_eq!(
+            process_scheduled_commit(&with_key(2), &committed, &[]),
+            Err(compat::ProgramError::IncorrectProgramId)
+        );
+        assert_eq!(
+            process_scheduled_commit(&with_key(1), &committed, &[]),
+            Err(EphemeralRollupsSdkError::InvalidMagicContext.into())
+        );
+        for infos in [with_key(3), with_key(4), infos[..4].to_vec()] {
+            assert_eq!(
+                process_scheduled_commit(&infos, &committed, &[]),
+                Err(EphemeralRollupsSdkError::InvalidCommittedAccounts.into())
+            );
+        }
+        let swapped = [
+            infos[0].clone(),
+            infos[1].clone(),
+            infos[2].clone(),
+            infos[4].clone(),
+            infos[3].clone(),
+        ];
+        assert_eq!(
+            process_scheduled_commit(&swapped, &committed, &[]),
+            Err(EphemeralRollupsSdkError::InvalidCommittedAccounts.into())
+        );
+        assert_eq!(process_scheduled_commit(&infos, &committed, &[]), Ok(()));
+    }
 }
@@ -0,0 +1,187 @@
+//! Deterministic, rough compute unit estimates of the SDK CPIs and intent bundles.
+//!
+//! The estimates add up the costs charged by the runtime for what the SDK does on-chain:
+//! `find_program_address` and `create_program_address` calls, the invocation and serialization
+//! of every CPI (including the nested ones, e.g. the system program invoked by the delegation
+//! program), and memory operations on the account data. The execution of the invoked programs
+//! is accounted with a fixed budget per instruction.
+//!
+//! The estimates are meant to size the compute unit limit of a transaction. They're rough:
+//! the per-program budgets below haven't been calibrated against the deployed programs, and
+//! `find_program_address` is accounted with the average number of attempts, while a given
+//! address can take many more. They're neither upper nor lower bounds of the consumed units,
+//! add a margin when sizing the limit. The builders expose them via
+//! `estimated_compute_units()`, e.g. [`MagicIntentBundleBuilder::estimated_compute_units`].
+//!
+//! [`MagicIntentBundleBuilder::estimated_compute_units`]: crate::ephem::MagicIntentBundleBuilder::estimated_compute_units
+
+use crate::compat;
+
+/// Units charged for every CPI
+pub const INVOKE_UNITS: u64 = 1_000;
+/// Units charged for a single `create_program_address` call
+pub const CREATE_PROGRAM_ADDRESS_UNITS: u64 = 1_500;
+/// Bumps tried on average by `find_program_address`, as every candidate lands off the curve
+/// with a probability of 1/2. An average rather than a bound: one address in 2^n takes more
+/// than n attempts
+pub const FIND_PROGRAM_ADDRESS_ATTEMPTS: u64 = 2;
+/// Serialized bytes of the instruction and account data per unit charged on CPI
+pub const CPI_BYTES_PER_UNIT: u64 = 250;
+/// Minimum units charged for a memory operation, e.g. `sol_memcpy`
+pub const MEM_OP_BASE_UNITS: u64 = 10;
+/// Rough units consumed by the system program instructions
+pub const SYSTEM_PROGRAM_UNITS: u64 = 150;
+
+/// Rough budget of the delegation program delegate instruction, excluding the derivations, CPIs
+/// and data copies accounted separately
+pub const DELEGATE_UNITS: u64 = 6_000;
+/// Rough budget of the magic program `ScheduleIntentBundle` instruction, excluding the per-account
+/// and per-action costs
+pub const SCHEDULE_INTENT_BUNDLE_UNITS: u64 = 5_000;
+/// Rough budget of the magic program `AddActionCallback` instruction
+pub const ADD_ACTION_CALLBACK_UNITS: u64 = 2_000;
+/// Rough units spent by the SDK and the magic program on every account referenced by the bundle,
+/// i.e. deduplication, indexing and the lookup of the delegation state
+pub const INTENT_ACCOUNT_UNITS: u64 = 700;
+/// Rough budget of a single eSPL token program instruction, including its own CPIs
+pub const ESPL_INSTRUCTION_UNITS: u64 = 40_000;
+
+/// Serialized size of an account meta in the CPI instruction
+const ACCOUNT_META_LEN: u64 = 34;
+/// Serialized size of the system program instructions invoked by the SDK
+const CREATE_ACCOUNT_DATA_LEN: u64 = 52;
+const ASSIGN_DATA_LEN: u64 = 36;
+const TRANSFER_DATA_LEN: u64 = 12;
+
+/// Estimate of `find_program_address`
+pub const fn find_program_address() -> u64 {
+    CREATE_PROGRAM_ADDRESS_UNITS * FIND_PROGRAM_ADDRESS_ATTEMPTS
+}
+
+/// Estimate of a memory operation over `len` bytes
+pub const fn mem_op(len: usize) -> u64 {
+    let units = len as u64 / CPI_BYTES_PER_UNIT;
+    if units > MEM_OP_BASE_UNITS {
+        units
+    } else {
+        MEM_OP_BASE_UNITS
+    }
+}
+
+/// Estimate of a CPI with the given number of accounts and instruction data length, excluding
+/// the execution of the invoked program
+pub const fn cpi(accounts: usize, data_len: usize) -> u64 {
+    INVOKE_UNITS + (accounts as u64 * ACCOUNT_META_LEN + data_len as u64) / CPI_BYTES_PER_UNIT
+}
+
+/// Estimate of the CPI of the instruction, see [`cpi`]
+pub fn instruction(instruction: &compat::Instruction) -> u64 {
+    cpi(instruction.accounts.len(), instruction.data.len())
+}
+
+/// Units charged for passing an account with `data_len` bytes of data through a CPI
+const fn account_data(data_len: usize) -> u64 {
+    data_len as u64 / CPI_BYTES_PER_UNIT
+}
+
+const fn system_cpi(accounts: usize, data_len: u64, account_data_len: usize) -> u64 {
+    cpi(accounts, data_len as usize) + account_data(account_data_len) + SYSTEM_PROGRAM_UNITS
+}
+
+/// Estimate of [`crate::cpi::delegate_account`] for an account with `data_len` bytes of data,
+/// which derives the delegated account and buffer bumps and verifies every address on-chain
+pub fn delegate_account(seeds: &[&[u8]], data_len: usize) -> u64 {
+    delegation(seeds, data_len, 0, true, 0, 0)
+}
+
+/// Estimate of a delegation, with `known_bumps` of the delegated account and buffer bumps
+/// provided by the caller and `action_accounts` / `actions_len` the signers and the encoded
+/// post delegation actions, if any
+pub(crate) fn delegation(
+    seeds: &[&[u8]],
+    data_len: usize,
+    known_bumps: u64,
+    verify: bool,
+    action_accounts: usize,
+    actions_len: usize,
+) -> u64 {
+    // delegated account and buffer bumps, then the delegation record and metadata addresses
+    let mut units = (2 - known_bumps) * find_program_address();
+    if verify {
+        units += known_bumps * CREATE_PROGRAM_ADDRESS_UNITS + 2 * find_program_address();
+    }
+
+    // buffer creation, copy of the data and assignment of the delegated account
+    units += system_cpi(2, CREATE_ACCOUNT_DATA_LEN, 0);
+    units += 2 * mem_op(data_len);
+    units += system_cpi(1, ASSIGN_DATA_LEN, data_len);
+
+    // delegate instruction: commit frequency, seeds and validator
+    let args_len = 8 + 4 + 4 + seeds.iter().map(|seed| 4 + seed.len()).sum::<usize>() + 33;
+    units += cpi(7 + action_accounts, args_len + actions_len) + 2 * account_data(data_len);
+    units += DELEGATE_UNITS
+        + CREATE_PROGRAM_ADDRESS_UNITS
+        + 2 * find_program_address()
+        + 2 * system_cpi(2, CREATE_ACCOUNT_DATA_LEN, 0)
+        + mem_op(data_len);
+
+    // buffer refund
+    units + system_cpi(3, TRANSFER_DATA_LEN, 0)
+}
+
+/// Estimate of an invocation of the `ScheduleIntentBundle` instruction with the given number
+/// of accounts and instruction data length
+pub const fn intent_bundle(accounts: usize, data_len: usize) -> u64 {
+    cpi(accounts, data_len) + SCHEDULE_INTENT_BUNDLE_UNITS + accounts as u64 * INTENT_ACCOUNT_UNITS
+}
+
+/// Estimate of an invocation of the `ScheduleIntentBundle` instruction, see [`intent_bundle`]
+pub fn schedule_intent_bundle(instruction: &compat::Instruction) -> u64 {
+    intent_bundle(instruction.accounts.len(), instruction.data.len())
+}
+
+/// Estimate of an invocation of the `AddActionCallback` instruction with the given number of
+/// accounts and instruction data length
+pub const fn action_callback(accounts: usize, data_len: usize) -> u64 {
+    cpi(accounts, data_len) + ADD_ACTION_CALLBACK_UNITS
+}
+
+/// Estimate of an invocation of the `AddActionCallback` instruction, see [`action_callback`]
+pub fn add_action_callback(instruction: &compat::Instruction) -> u64 {
+    action_callback(instruction.accounts.len(), instruction.data.len())
+}
+
+/// Estimate of an eSPL token program instruction
+pub fn espl_instruction(instruction: &compat::Instruction) -> u64 {
+    self::instruction(instruction) + ESPL_INSTRUCTION_UNITS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delegation_estimates() {
+        let seeds: &[&[u8]] = &[b"counter"];
+        // find_program_address for the PDA, buffer, record and metadata
+        let checked = delegate_account(seeds, 0);
+        assert_eq!(checked - delegation(seeds, 0, 0, false, 0, 0), 6_000);
+        // known bumps are verified with create_program_address
+        assert_eq!(checked - delegation(seeds, 0, 2, true, 0, 0), 3_000);
+
+        // larger accounts cost more to copy and to pass through the CPIs
+        assert!(delegate_account(seeds, 10_240) > checked);
+        assert_eq!(
+            delegate_account(seeds, 10_240) - checked,
+            3 * (mem_op(10_240) - MEM_OP_BASE_UNITS) + 3 * account_data(10_240)
+        );
+    }
+
+    #[test]
+    fn test_cpi_estimates() {
+        assert_eq!(cpi(0, 0), INVOKE_UNITS);
+        assert_eq!(cpi(10, 160), INVOKE_UNITS + 2);
+        assert_eq!(mem_op(0), MEM_OP_BASE_UNITS);
+        assert_eq!(mem_op(10_000), 40);
+    }
+}
@@ -0,0 +1,316 @@
+//! Introspection of the delegation state on the base layer.
+//!
+//! When an account is delegated, its owner becomes the delegation program, which keeps the
+//! delegation record (validator, original owner, slot, lamports) and the delegation metadata
+//! (seeds, rent payer) in PDAs derived from the delegated account. The readers below borrow
+//! the account data without deserializing it, after verifying the account owner and address.
+
+use std::cell::Ref;
+
+use dlp_api::pda;
+use dlp_api::state::discriminator::AccountDiscriminator;
+
+use crate::compat;
+use crate::cpi::DELEGATION_PROGRAM_ID;
+use crate::error::EphemeralRollupsSdkError;
+
+const DISCRIMINATOR_LEN: usize = AccountDiscriminator::SPACE;
+
+const RECORD_AUTHORITY_OFFSET: usize = DISCRIMINATOR_LEN;
+const RECORD_OWNER_OFFSET: usize = RECORD_AUTHORITY_OFFSET + 32;
+const RECORD_SLOT_OFFSET: usize = RECORD_OWNER_OFFSET + 32;
+const RECORD_LAMPORTS_OFFSET: usize = RECORD_SLOT_OFFSET + 8;
+const RECORD_COMMIT_FREQUENCY_OFFSET: usize = RECORD_LAMPORTS_OFFSET + 8;
+const RECORD_LEN: usize = RECORD_COMMIT_FREQUENCY_OFFSET + 8;
+
+const METADATA_NONCE_OFFSET: usize = DISCRIMINATOR_LEN;
+const METADATA_UNDELEGATABLE_OFFSET: usize = METADATA_NONCE_OFFSET + 8;
+const METADATA_SEEDS_OFFSET: usize = METADATA_UNDELEGATABLE_OFFSET + 1;
+
+/// Whether the account is currently delegated, i.e. owned by the delegation program
+pub fn is_delegated(account: &compat::AccountInfo) -> bool {
+    account.owner == &DELEGATION_PROGRAM_ID
+}
+
+/// Verifies that the account is owned by the delegation program and derived from the delegated
+/// account, then borrows its data
+fn borrow_delegation_account<'a>(
+    account: &'a compat::AccountInfo,
+    expected_key: &compat::Pubkey,
+    invalid_address: EphemeralRollupsSdkError,
+) -> Result<Ref<'a, [u8]>, compat::ProgramError> {
+    if account.key != expected_key {
+        return Err(invalid_address.into());
+    }
+    if account.owner != &DELEGATION_PROGRAM_ID {
+        return Err(EphemeralRollupsSdkError::InvalidDelegationAccountOwner.into());
+    }
+    Ok(Ref::map(account.try_borrow_data()?, |data| &**data))
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> compat::Pubkey {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&data[offset..offset + 32]);
+    compat::Pubkey::new_from_array(bytes)
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// Zero-copy reader of the delegation record of a delegated account
+pub struct DelegationRecordView<'a> {
+    data: Ref<'a, [u8]>,
+}
+
+impl<'a> DelegationRecordView<'a> {
+    /// Borrows the delegation record of `delegated_account`, verifying that it's owned by the
+    /// delegation program and that its address is the record PDA of the delegated account
+    pub fn from_account(
+        delegation_record: &'a compat::AccountInfo,
+        delegated_account: &compat::Pubkey,
+    ) -> Result<Self, compat::ProgramError> {
+        let data = borrow_delegation_account(
+            delegation_record,
+            &pda::delegation_record_pda_from_delegated_account(delegated_account),
+            EphemeralRollupsSdkError::InvalidDelegationRecord,
+        )?;
+        if data.len() < RECORD_LEN
+            || data[..DISCRIMINATOR_LEN] != AccountDiscriminator::DelegationRecord.to_bytes()
+        {
+            return Err(EphemeralRollupsSdkError::InvalidDelegationAccountData.into());
+        }
+        Ok(Self { data })
+    }
+
+    /// Validator the account is delegated to
+    pub fn authority(&self) -> compat::Pubkey {
+        read_pubkey(&self.data, RECORD_AUTHORITY_OFFSET)
+    }
+
+    /// Program which owned the account before the delegation
+    pub fn owner(&self) -> compat::Pubkey {
+        read_pubkey(&self.data, RECORD_OWNER_OFFSET)
+    }
+
+    /// Slot at which the account was delegated
+    pub fn delegation_slot(&self) -> u64 {
+        read_u64(&self.data, RECORD_SLOT_OFFSET)
+    }
+
+    /// Lamports of the account at the delegation or at the last finalized commit
+    pub fn lamports(&self) -> u64 {
+        read_u64(&self.data, RECORD_LAMPORTS_OFFSET)
+    }
+
+    pub fn commit_frequency_ms(&self) -> u64 {
+        read_u64(&self.data, RECORD_COMMIT_FREQUENCY_OFFSET)
+    }
+}
+
+/// Zero-copy reader of the delegation metadata of a delegated account
+pub struct DelegationMetadataView<'a> {
+    data: Ref<'a, [u8]>,
+    rent_payer_offset: usize,
+}
+
+impl<'a> DelegationMetadataView<'a> {
+    /// Borrows the delegation metadata of `delegated_account`, verifying that it's owned by the
+    /// delegation program and that its address is the metadata PDA of the delegated account
+    pub fn from_account(
+        delegation_metadata: &'a compat::AccountInfo,
+        delegated_account: &compat::Pubkey,
+    ) -> Result<Self, compat::ProgramError> {
+        let data = borrow_delegation_account(
+            delegation_metadata,
+            &pda::delegation_metadata_pda_from_delegated_account(delegated_account),
+            EphemeralRollupsSdkError::InvalidDelegationMetadata,
+        )?;
+        if data.get(..DISCRIMINATOR_LEN)
+            != Some(&AccountDiscriminator::DelegationMetadata.to_bytes()[..])
+        {
+            return Err(EphemeralRollupsSdkError::InvalidDelegationAccountData.into());
+        }
+        let rent_payer_offset = seeds_end(&data)
+            .filter(|offset| data.len() >= offset + 32)
+            .ok_or(EphemeralRollupsSdkError::InvalidDelegationAccountData)?;
+        Ok(Self {
+            data,
+            rent_payer_offset,
+        })
+    }
+
+    pub fn last_update_nonce(&self) -> u64 {
+        read_u64(&self.data, METADATA_NONCE_OFFSET)
+    }
+
+    /// Whether the account can be undelegated
+    pub fn is_undelegatable(&self) -> bool {
+        self.data[METADATA_UNDELEGATABLE_OFFSET] != 0
+    }
+
+    /// Seeds of the delegated account, without the bump
+    pub fn seeds(&self) -> SeedsIter<'_> {
+        SeedsIter {
+            data: &self.data[..self.rent_payer_offset],
+            offset: METADATA_SEEDS_OFFSET + 4,
+            remaining: read_u32(&self.data, METADATA_SEEDS_OFFSET).unwrap_or_default(),
+        }
+    }
+
+    /// Account which paid the rent of the delegation accounts, refunded on undelegation
+    pub fn rent_payer(&self) -> compat::Pubkey {
+        read_pubkey(&self.data, self.rent_payer_offset)
+    }
+}
+
+/// Walks the borsh encoded seeds, returns the offset right after them
+fn seeds_end(data: &[u8]) -> Option<usize> {
+    let count = read_u32(data, METADATA_SEEDS_OFFSET)?;
+    let mut offset = METADATA_SEEDS_OFFSET + 4;
+    for _ in 0..count {
+        let len = read_u32(data, offset)? as usize;
+        offset = offset.checked_add(4 + len)?;
+    }
+    (offset <= data.len()).then_some(offset)
+}
+
+/// Iterator over the seeds of the delegation metadata, see [`DelegationMetadataView::seeds`]
+pub struct SeedsIter<'a> {
+    data: &'a [u8],
+    offset: usize,
+    remaining: u32,
+}
+
+impl<'a> Iterator for SeedsIter<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        // the layout has been validated when the view was created
+        let len = read_u32(self.data, self.offset)? as usize;
+        let seed = &self.data[self.offset + 4..self.offset + 4 + len];
+        self.offset += 4 + len;
+        self.remaining -= 1;
+        Some(seed)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining as usize, Some(self.remaining as usize))
+    }
+}
+
+impl ExactSizeIterator for SeedsIter<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use dlp_api::state::{DelegationMetadata, DelegationRecord};
+
+    use super::*;
+    use crate::compat::borsh;
+    use crate::ephem::tests::create_mock_account_info;
+
+    #[test]
+    fn test_read_delegation_record() {
+        let delegated = compat::Pubkey::new_unique();
+        let record = DelegationRecord {
+            authority: compat::Pubkey::new_unique(),
+            owner: compat::Pubkey::new_unique(),
+            delegation_slot: 42,
+            lamports: 1_000_000,
+            commit_frequency_ms: 30_000,
+        };
+        let mut data = AccountDiscriminator::DelegationRecord.to_bytes().to_vec();
+        data.extend_from_slice(bytemuck::bytes_of(&record));
+        let key = pda::delegation_record_pda_from_delegated_account(&delegated);
+        let mut lamports = 0;
+        let info = create_mock_account_info(
+            &key,
+            &mut lamports,
+            &mut data,
+            &DELEGATION_PROGRAM_ID,
+            false,
+            false,
+        );
+
+        assert!(is_delegated(&info));
+        let view = DelegationRecordView::from_account(&info, &delegated).unwrap();
+        assert_eq!(view.authority(), record.authority);
+        assert_eq!(view.owner(), record.owner);
+        assert_eq!(view.delegation_slot(), 42);
+        assert_eq!(view.lamports(), 1_000_000);
+        assert_eq!(view.commit_frequency_ms(), 30_000);
+        drop(view);
+
+        // the record of another account is rejected
+        assert_eq!(
+            DelegationRecordView::from_account(&info, &compat::Pubkey::new_unique()).err(),
+            Some(EphemeralRollupsSdkError::InvalidDelegationRecord.into())
+        );
+    }
+
+    #[test]
+    fn test_read_delegation_metadata() {
+        let delegated = compat::Pubkey::new_unique();
+        let metadata = DelegationMetadata {
+            last_update_nonce: 7,
+            is_undelegatable: true,
+            seeds: vec![b"counter".to_vec(), vec![], vec![1; 32]],
+            rent_payer: compat::Pubkey::new_unique(),
+        };
+        let mut data = AccountDiscriminator::DelegationMetadata.to_bytes().to_vec();
+        data.extend(borsh::to_vec(&metadata).unwrap());
+        let key = pda::delegation_metadata_pda_from_delegated_account(&delegated);
+        let mut lamports = 0;
+
+        let owner = compat::Pubkey::new_unique();
+        let info = create_mock_account_info(&key, &mut lamports, &mut data, &owner, false, false);
+        assert!(!is_delegated(&info));
+        assert_eq!(
+            DelegationMetadataView::from_account(&info, &delegated).err(),
+            Some(EphemeralRollupsSdkError::InvalidDelegationAccountOwner.into())
+        );
+
+        let info = create_mock_account_info(
+            &key,
+            &mut lamports,
+            &mut data,
+            &DELEGATION_PROGRAM_ID,
+            false,
+            false,
+        );
+        let view = DelegationMetadataView::from_account(&info, &delegated).unwrap();
+        assert_eq!(view.last_update_nonce(), 7);
+        assert!(view.is_undelegatable());
+        assert_eq!(
+            view.seeds().map(<[u8]>::to_vec).collect::<Vec<_>>(),
+            metadata.seeds
+        );
+        assert_eq!(view.rent_payer(), metadata.rent_payer);
+        drop(view);
+
+        // truncated seeds are rejected
+        let len = data.len();
+        let info = create_mock_account_info(
+            &key,
+            &mut lamports,
+            &mut data[..len - 40],
+            &DELEGATION_PROGRAM_ID,
+            false,
+            false,
+        );
+        assert_eq!(
+            DelegationMetadataView::from_account(&info, &delegated).err(),
+            Some(EphemeralRollupsSdkError::InvalidDelegationAccountData.into())
+        );
+    }
+}
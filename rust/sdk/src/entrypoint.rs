@@ -0,0 +1,206 @@
+//! Undelegation callback support for native `solana_program` programs.
+//!
+//! Once the account is undelegated, the delegation program invokes the owner program with
+//! the [`EXTERNAL_UNDELEGATE_DISCRIMINATOR`] to restore the account from the undelegation buffer.
+//! Anchor programs get the handler from `#[ephemeral]`, native programs can either call
+//! [`handle_undelegation`] at the top of their processor or wrap it with [`ephemeral_entrypoint!`].
+
+use crate::compat::{self, borsh::BorshDeserialize, AsModern};
+use crate::consts::EXTERNAL_UNDELEGATE_DISCRIMINATOR;
+use crate::cpi::undelegate_account_with_hook;
+use crate::error::EphemeralRollupsSdkError;
+
+/// Handles the undelegation callback of the delegation program, returns `None` if the
+/// instruction is not the callback and must be processed by the program itself.
+///
+/// The accounts are expected in the order the delegation program passes them:
+/// `[delegated_account, buffer, payer, system_program]`. The delegated account and buffer
+/// addresses are verified against the seeds, see [`crate::cpi::undelegate_account`].
+///
+/// ```ignore
+/// pub fn process_instruction(
+///     program_id: &Pubkey,
+///     accounts: &[AccountInfo],
+///     instruction_data: &[u8],
+/// ) -> ProgramResult {
+///     if let Some(result) = handle_undelegation(program_id, accounts, instruction_data) {
+///         return result;
+///     }
+///     // the program instructions
+/// }
+/// ```
+pub fn handle_undelegation(
+    program_id: &compat::Pubkey,
+    accounts: &[compat::AccountInfo],
+    data: &[u8],
+) -> Option<compat::ProgramResult> {
+    handle_undelegation_with_hook(program_id, accounts, data, |_, _| Ok(()))
+}
+
+/// Same as [`handle_undelegation`], but invokes `on_undelegate` with the restored account and
+/// its seeds once the data has been copied back, e.g. to update the state derived from it.
+pub fn handle_undelegation_with_hook<'a, 'info, F>(
+    program_id: &compat::Pubkey,
+    accounts: &'a [compat::AccountInfo<'info>],
+    data: &[u8],
+    on_undelegate: F,
+) -> Option<compat::ProgramResult>
+where
+    F: FnOnce(&'a compat::AccountInfo<'info>, &[Vec<u8>]) -> compat::ProgramResult,
+{
+    let args = data.strip_prefix(&EXTERNAL_UNDELEGATE_DISCRIMINATOR)?;
+    Some(process_undelegation(
+        program_id,
+        accounts,
+        args,
+        on_undelegate,
+    ))
+}
+
+fn process_undelegation<'a, 'info, F>(
+    program_id: &compat::Pubkey,
+    accounts: &'a [compat::AccountInfo<'info>],
+    args: &[u8],
+    on_undelegate: F,
+) -> compat::ProgramResult
+where
+    F: FnOnce(&'a compat::AccountInfo<'info>, &[Vec<u8>]) -> compat::ProgramResult,
+{
+    let [delegated_account, buffer, payer, system_program, ..] = accounts else {
+        return Err(EphemeralRollupsSdkError::InvalidUndelegationAccounts.into());
+    };
+    if system_program.key.as_modern() != &solana_system_interface::program::id()
+        || !delegated_account.is_writable
+        || !payer.is_writable
+    {
+        return Err(EphemeralRollupsSdkError::InvalidUndelegationAccounts.into());
+    }
+    let account_seeds = Vec::<Vec<u8>>::try_from_slice(args)
+        .map_err(|_| EphemeralRollupsSdkError::InvalidUndelegationSeeds)?;
+
+    undelegate_account_with_hook(
+        delegated_account,
+        program_id,
+        buffer,
+        payer,
+        system_program,
+        account_seeds,
+        on_undelegate,
+    )
+}
+
+/// Declares the program entrypoint, which handles the undelegation callback via
+/// [`handle_undelegation`] and passes every other instruction to the given processor.
+/// The optional `on_undelegate` hook is passed to [`handle_undelegation_with_hook`].
+///
+/// The entrypoint is the one of the `solana_program` version matching the [`crate::compat`]
+/// types, so the program doesn't need to depend on `solana_program` itself.
+///
+/// ```ignore
+/// ephemeral_entrypoint!(process_instruction);
+/// // or
+/// ephemeral_entrypoint!(process_instruction, on_undelegate = on_undelegate);
+///
+/// pub fn process_instruction(
+///     program_id: &Pubkey,
+///     accounts: &[AccountInfo],
+///     instruction_data: &[u8],
+/// ) -> ProgramResult {
+///     // the program instructions
+/// }
+/// ```
+#[macro_export]
+macro_rules! ephemeral_entrypoint {
+    ($process_instruction:path) => {
+        $crate::ephemeral_entrypoint!($process_instruction, on_undelegate = |_, _| Ok(()));
+    };
+    ($process_instruction:path, on_undelegate = $on_undelegate:expr) => {
+        $crate::compat::program_entrypoint!(__ephemeral_rollups_process_instruction);
+
+        fn __ephemeral_rollups_process_instruction(
+            program_id: &$crate::compat::Pubkey,
+            accounts: &[$crate::compat::AccountInfo],
+            instruction_data: &[u8],
+        ) -> $crate::compat::ProgramResult {
+            if let Some(result) = $crate::entrypoint::handle_undelegation_with_hook(
+                program_id,
+                accounts,
+                instruction_data,
+                $on_undelegate,
+            ) {
+                return result;
+            }
+            $process_instruction(program_id, accounts, instruction_data)
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compat::borsh;
+    use crate::ephem::tests::create_mock_account_info;
+
+    #[test]
+    fn test_handle_undelegation_checks() {
+        let program_id = compat::Pubkey::new_unique();
+        let system_program_id =
+            compat::Pubkey::new_from_array(solana_system_interface::program::id().to_bytes());
+        let keys = [
+            compat::Pubkey::new_unique(),
+            compat::Pubkey::new_unique(),
+            compat::Pubkey::new_unique(),
+        ];
+        let mut lamports = [0u64; 4];
+        let mut data = [[0u8; 0]; 4];
+        let mut infos = keys
+            .iter()
+            .chain([&system_program_id])
+            .zip(lamports.iter_mut())
+            .zip(data.iter_mut())
+            .map(|((key, lamports), data)| {
+                create_mock_account_info(key, lamports, data, key, false, true)
+            });
+        let accounts: [_; 4] = std::array::from_fn(|_| infos.next().unwrap());
+
+        let seeds: Vec<Vec<u8>> = vec![b"counter".to_vec()];
+        let mut data = EXTERNAL_UNDELEGATE_DISCRIMINATOR.to_vec();
+        data.extend(borsh::to_vec(&seeds).unwrap());
+
+        // other instructions are passed through
+        assert_eq!(handle_undelegation(&program_id, &accounts, &[0; 8]), None);
+        assert_eq!(
+            handle_undelegation(&program_id, &accounts[..3], &data),
+            Some(Err(
+                EphemeralRollupsSdkError::InvalidUndelegationAccounts.into()
+            ))
+        );
+        let wrong_system_program = [
+            accounts[0].clone(),
+            accounts[1].clone(),
+            accounts[2].clone(),
+            accounts[0].clone(),
+        ];
+        assert_eq!(
+            handle_undelegation(&program_id, &wrong_system_program, &data),
+            Some(Err(
+                EphemeralRollupsSdkError::InvalidUndelegationAccounts.into()
+            ))
+        );
+        assert_eq!(
+            handle_undelegation(&program_id, &accounts, &EXTERNAL_UNDELEGATE_DISCRIMINATOR),
+            Some(Err(
+                EphemeralRollupsSdkError::InvalidUndelegationSeeds.into()
+            ))
+        );
+        // the buffer must have signed the callback, the hook isn't invoked on failure
+        assert_eq!(
+            handle_undelegation_with_hook(&program_id, &accounts, &data, |_, _| {
+                panic!("hook invoked before the account was restored")
+            }),
+            Some(Err(
+                crate::error::EphemeralRollupsSdkError::BufferNotSigner.into()
+            ))
+        );
+    }
+}
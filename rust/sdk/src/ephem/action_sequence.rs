@@ -0,0 +1,64 @@
+//! Ordered groups of standalone actions.
+//!
+//! The standalone actions of a bundle are executed in the order of their flat index, see
+//! `AddActionCallbackArgs::action_index`. An [`ActionSequence`] appends its actions to the
+//! bundle contiguously, so they are executed in the order of the `then_action` calls, and is
+//! always scheduled whole by a single `ScheduleIntentBundle` instruction, see
+//! [`build_chunked`]:
+//!
+//! ```ignore
+//! MagicIntentBundleBuilder::new(payer, magic_context, magic_program)
+//!     .add_action_sequence(
+//!         ActionSequence::new()
+//!             .then_action(pay_out)
+//!             .then_action(close_game),
+//!     )
+//!     .build_and_invoke()?;
+//! ```
+//!
+//! Only the order is guaranteed: no failure policy is encoded in the intent, so whether the
+//! following actions are executed once one fails is decided by the validator. An action
+//! depending on the outcome of a previous one has to check it itself, e.g. in a callback.
+//!
+//! [`build_chunked`]: crate::ephem::MagicIntentBundleBuilder::build_chunked
+
+use crate::ephem::{ActionCallback, CallHandler};
+
+/// Standalone actions executed in order, see the [module](self) docs.
+#[derive(Clone, Default)]
+pub struct ActionSequence<'info> {
+    pub(crate) actions: Vec<CallHandler<'info>>,
+    pub(crate) callbacks: Vec<Option<ActionCallback>>, // parallel to actions
+}
+
+impl<'info> ActionSequence<'info> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends an action executed after the previous ones.
+    pub fn then_action(mut self, action: CallHandler<'info>) -> Self {
+        self.actions.push(action);
+        self.callbacks.push(None);
+        self
+    }
+
+    /// Appends an action executed after the previous ones, with a callback.
+    pub fn then_action_with_callback(
+        mut self,
+        action: CallHandler<'info>,
+        callback: ActionCallback,
+    ) -> Self {
+        self.actions.push(action);
+        self.callbacks.push(Some(callback));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+}
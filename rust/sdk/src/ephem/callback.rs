@@ -0,0 +1,428 @@
+//! Helpers for the base-layer handlers of [`ActionCallback`]s.
+//!
+//! The callback instruction data is the callback discriminator followed by the
+//! [`MagicResponse`] of the action, whose data is the callback payload.
+//!
+//! The validator invokes the callback through its callback executor, which signs with
+//! [`CALLBACK_SIGNER`], see `CallbackInstruction::ExecuteCallback` and `pda::CALLBACK_SIGNER`
+//! in `magicblock-magic-program-api`. The other accounts of the callback are the ones set
+//! in [`ActionCallback::accounts`] by the scheduling program.
+//!
+//! [`ActionCallback`]: crate::ephem::ActionCallback
+//! [`ActionCallback::accounts`]: crate::ephem::ActionCallback::accounts
+
+use crate::compat;
+use crate::compat::borsh::BorshDeserialize;
+use crate::consts::CALLBACK_SIGNER;
+use crate::cpi;
+use crate::error::EphemeralRollupsSdkError;
+use magicblock_magic_program_api::response::MagicResponse;
+
+/// Length of the sequence prefixing the payload of callbacks guarded against replays,
+/// see [`ActionCallbackResponse::reject_replay`].
+pub const CALLBACK_SEQUENCE_LEN: usize = 8;
+
+/// Seed of the callback sequence account of an escrow authority, a PDA of the program
+/// handling the callbacks derived from `[CALLBACK_SEQUENCE_SEED, escrow_authority]`, see
+/// [`reject_callback_replay`].
+pub const CALLBACK_SEQUENCE_SEED: &[u8] = b"callback-sequence";
+
+/// Verifies that the callback has been signed by [`CALLBACK_SIGNER`], i.e. that it has been
+/// issued by the validator, and that `escrow` is derived from `escrow_authority` and
+/// `escrow_index`, i.e. that the callback is the one of an action paid from that escrow.
+pub fn verify_action_callback(
+    callback_signer: &compat::AccountInfo,
+    escrow_authority: &compat::AccountInfo,
+    escrow: &compat::AccountInfo,
+    escrow_index: u8,
+) -> Result<(), EphemeralRollupsSdkError> {
+    if !callback_signer.is_signer || callback_signer.key != &CALLBACK_SIGNER {
+        return Err(EphemeralRollupsSdkError::InvalidCallbackSigner);
+    }
+    if escrow.key != &cpi::escrow_pda(escrow_authority.key, escrow_index) {
+        return Err(EphemeralRollupsSdkError::InvalidEscrow);
+    }
+    Ok(())
+}
+
+/// Rejects the callback if its sequence has already been accepted by the callback sequence
+/// account, and records it otherwise, see [`CallbackSequence`].
+///
+/// The account must be owned by the program, for the runtime to let it write the sequence, and
+/// hold at least [`CallbackSequence::LEN`] bytes, zeroed when created.
+pub fn reject_callback_replay(
+    callback_sequence: &compat::AccountInfo,
+    response: &ActionCallbackResponse,
+) -> Result<(), EphemeralRollupsSdkError> {
+    let mut data = callback_sequence
+        .try_borrow_mut_data()
+        .map_err(|_| EphemeralRollupsSdkError::InvalidCallbackSequence)?;
+    let state: &mut [u8; CallbackSequence::LEN] = data
+        .get_mut(..CallbackSequence::LEN)
+        .and_then(|state| state.try_into().ok())
+        .ok_or(EphemeralRollupsSdkError::InvalidCallbackSequence)?;
+    let mut sequence = CallbackSequence::from_bytes(state);
+    response.reject_replay(&mut sequence)?;
+    *state = sequence.to_bytes();
+    Ok(())
+}
+
+/// Sequences of the callbacks accepted so far, as a sliding window: any sequence not accepted
+/// yet is accepted, whatever the order the callbacks are executed in, unless it is
+/// [`Self::WINDOW`] or more below the highest one accepted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CallbackSequence {
+    /// Highest sequence accepted
+    pub highest: u64,
+    /// Sequences accepted up to `highest`, bit `n` standing for `highest - n`
+    pub window: u64,
+}
+
+impl CallbackSequence {
+    /// Length of the encoded sequence, little-endian `highest` followed by `window`
+    pub const LEN: usize = 16;
+    /// Number of sequences up to the highest one tracked by the window
+    pub const WINDOW: u64 = u64::BITS as u64;
+
+    /// Records `sequence`, `false` if it has already been accepted or is out of the window.
+    pub fn accept(&mut self, sequence: u64) -> bool {
+        if sequence > self.highest {
+            let shift = sequence - self.highest;
+            let window = if shift < Self::WINDOW {
+                self.window << shift
+            } else {
+                0
+            };
+            self.window = window | 1;
+            self.highest = sequence;
+            return true;
+        }
+        let offset = self.highest - sequence;
+        if offset >= Self::WINDOW || self.window & (1 << offset) != 0 {
+            return false;
+        }
+        self.window |= 1 << offset;
+        true
+    }
+
+    pub fn from_bytes(bytes: &[u8; Self::LEN]) -> Self {
+        let (highest, window) = bytes.split_at(8);
+        Self {
+            highest: u64::from_le_bytes(highest.try_into().unwrap()),
+            window: u64::from_le_bytes(window.try_into().unwrap()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::LEN] {
+        let mut bytes = [0; Self::LEN];
+        bytes[..8].copy_from_slice(&self.highest.to_le_bytes());
+        bytes[8..].copy_from_slice(&self.window.to_le_bytes());
+        bytes
+    }
+}
+
+/// Outcome of an action, decoded from the [`MagicResponse`] passed to its callback.
+///
+/// With the `anchor` features it can be taken as the argument of an Anchor handler,
+/// see `#[action_callback]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActionCallbackResponse {
+    /// `Ok` if the action succeeded, the reason of the failure otherwise
+    pub result: Result<(), String>,
+    /// Payload of the callback, present even if the action failed
+    pub data: Vec<u8>,
+    /// Signature of the action transaction, if available
+    pub signature: Option<[u8; 64]>,
+}
+
+impl ActionCallbackResponse {
+    /// Decodes the response following the discriminator in the callback instruction data.
+    pub fn decode(data: &[u8]) -> Result<Self, EphemeralRollupsSdkError> {
+        let response: MagicResponse = bincode::deserialize(data)
+            .map_err(|_| EphemeralRollupsSdkError::InvalidMagicResponse)?;
+        let MagicResponse::V1(response) = response;
+
+        let signature = match response.receipt {
+            Some(receipt) => Some(
+                receipt
+                    .signature
+                    .as_ref()
+                    .try_into()
+                    .map_err(|_| EphemeralRollupsSdkError::InvalidMagicResponse)?,
+            ),
+            None => None,
+        };
+        Ok(Self {
+            result: if response.ok {
+                Ok(())
+            } else {
+                Err(response.error)
+            },
+            data: response.data,
+            signature,
+        })
+    }
+
+    /// Whether the action succeeded.
+    pub fn is_ok(&self) -> bool {
+        self.result.is_ok()
+    }
+
+    /// Deserializes the borsh-encoded payload.
+    pub fn payload<T: BorshDeserialize>(&self) -> Result<T, EphemeralRollupsSdkError> {
+        T::try_from_slice(&self.data).map_err(|_| EphemeralRollupsSdkError::InvalidMagicResponse)
+    }
+
+    /// Deserializes the borsh-encoded payload following the sequence, see
+    /// [`Self::reject_replay`].
+    pub fn sequenced_payload<T: BorshDeserialize>(&self) -> Result<T, EphemeralRollupsSdkError> {
+        let data = self
+            .data
+            .get(CALLBACK_SEQUENCE_LEN..)
+            .ok_or(EphemeralRollupsSdkError::InvalidMagicResponse)?;
+        T::try_from_slice(data).map_err(|_| EphemeralRollupsSdkError::InvalidMagicResponse)
+    }
+
+    /// Sequence prefixing the payload, as a little-endian `u64`.
+    pub fn sequence(&self) -> Option<u64> {
+        let sequence = self.data.get(..CALLBACK_SEQUENCE_LEN)?;
+        Some(u64::from_le_bytes(sequence.try_into().ok()?))
+    }
+
+    /// Rejects a callback that has already been processed. The payload of the callback
+    /// must start with a sequence unique to the callback, see [`ActionCallback::with_sequence`],
+    /// `sequence` being the ones accepted so far stored by the program, which are updated.
+    ///
+    /// [`ActionCallback::with_sequence`]: crate::ephem::ActionCallback::with_sequence
+    pub fn reject_replay(
+        &self,
+        sequence: &mut CallbackSequence,
+    ) -> Result<(), EphemeralRollupsSdkError> {
+        let callback_sequence = self
+            .sequence()
+            .ok_or(EphemeralRollupsSdkError::InvalidMagicResponse)?;
+        if !sequence.accept(callback_sequence) {
+            return Err(EphemeralRollupsSdkError::CallbackReplayed);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "anchor-support")]
+mod anchor_args {
+    use super::ActionCallbackResponse;
+    use crate::compat::anchor_lang::{AnchorDeserialize, AnchorSerialize};
+    use magicblock_magic_program_api::response::{MagicResponse, MagicResponseV1};
+    use std::io;
+
+    impl AnchorDeserialize for ActionCallbackResponse {
+        fn deserialize_reader<R: io::Read>(reader: &mut R) -> io::Result<Self> {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data)?;
+            Self::decode(&data).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+        }
+    }
+
+    impl AnchorSerialize for ActionCallbackResponse {
+        fn serialize<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+            let (ok, error) = match &self.result {
+                Ok(()) => (true, String::new()),
+                Err(error) => (false, error.clone()),
+            };
+            let response = MagicResponse::V1(MagicResponseV1 {
+                ok,
+                data: self.data.clone(),
+                error,
+                receipt: self.signature.map(|signature| {
+                    magicblock_magic_program_api::response::ActionReceipt {
+                        signature: signature.into(),
+                    }
+                }),
+            });
+            bincode::serialize_into(writer, &response)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use magicblock_magic_program_api::response::{ActionReceipt, MagicResponseV1};
+
+    use super::*;
+    use crate::ephem::tests::create_mock_account_info;
+    use crate::ephem::ActionCallback;
+
+    fn encode(ok: bool, data: Vec<u8>, error: &str, signature: Option<[u8; 64]>) -> Vec<u8> {
+        bincode::serialize(&MagicResponse::V1(MagicResponseV1 {
+            ok,
+            data,
+            error: error.to_string(),
+            receipt: signature.map(|signature| ActionReceipt {
+                signature: signature.into(),
+            }),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_verify_action_callback() {
+        let escrow_authority = compat::Pubkey::new_unique();
+        let escrow = cpi::escrow_pda(&escrow_authority, 3);
+        let other = compat::Pubkey::new_unique();
+        let (mut lamports, mut data) = ([0u64; 5], [[0u8; 0]; 5]);
+        let [l0, l1, l2, l3, l4] = &mut lamports;
+        let [d0, d1, d2, d3, d4] = &mut data;
+        let escrow_authority =
+            create_mock_account_info(&escrow_authority, l0, d0, &escrow_authority, false, false);
+        let escrow = create_mock_account_info(&escrow, l1, d1, &escrow, false, false);
+        let signer =
+            create_mock_account_info(&CALLBACK_SIGNER, l2, d2, &CALLBACK_SIGNER, true, false);
+        let non_signer =
+            create_mock_account_info(&CALLBACK_SIGNER, l3, d3, &CALLBACK_SIGNER, false, false);
+        let other_signer = create_mock_account_info(&other, l4, d4, &other, true, false);
+
+        assert_eq!(
+            verify_action_callback(&signer, &escrow_authority, &escrow, 3),
+            Ok(())
+        );
+        assert_eq!(
+            verify_action_callback(&non_signer, &escrow_authority, &escrow, 3),
+            Err(EphemeralRollupsSdkError::InvalidCallbackSigner)
+        );
+        assert_eq!(
+            verify_action_callback(&other_signer, &escrow_authority, &escrow, 3),
+            Err(EphemeralRollupsSdkError::InvalidCallbackSigner)
+        );
+        assert_eq!(
+            verify_action_callback(&signer, &escrow_authority, &escrow, 4),
+            Err(EphemeralRollupsSdkError::InvalidEscrow)
+        );
+    }
+
+    #[test]
+    fn test_decode_response() {
+        let response =
+            ActionCallbackResponse::decode(&encode(true, vec![1, 2, 3], "", Some([7; 64])))
+                .unwrap();
+        assert!(response.is_ok());
+        assert_eq!(response.data, vec![1, 2, 3]);
+        assert_eq!(response.signature, Some([7; 64]));
+
+        let response =
+            ActionCallbackResponse::decode(&encode(false, vec![], "TimeoutError", None)).unwrap();
+        assert_eq!(response.result, Err("TimeoutError".to_string()));
+        assert_eq!(response.signature, None);
+
+        assert_eq!(
+            ActionCallbackResponse::decode(&[1, 0, 0, 0]),
+            Err(EphemeralRollupsSdkError::InvalidMagicResponse)
+        );
+    }
+
+    fn sequenced_response(sequence: u64) -> ActionCallbackResponse {
+        let callback = ActionCallback {
+            destination_program: compat::Pubkey::new_unique(),
+            discriminator: vec![0; 8],
+            payload: 42u32.to_le_bytes().to_vec(),
+            compute_units: 10_000,
+            accounts: vec![],
+        }
+        .with_sequence(sequence);
+        ActionCallbackResponse::decode(&encode(true, callback.payload, "", None)).unwrap()
+    }
+
+    #[test]
+    fn test_reject_replay() {
+        let response = sequenced_response(5);
+        assert_eq!(response.sequence(), Some(5));
+        assert_eq!(response.sequenced_payload::<u32>(), Ok(42));
+
+        let mut sequence = CallbackSequence::default();
+        assert_eq!(response.reject_replay(&mut sequence), Ok(()));
+        assert_eq!(
+            response.reject_replay(&mut sequence),
+            Err(EphemeralRollupsSdkError::CallbackReplayed)
+        );
+
+        let unsequenced = ActionCallbackResponse::decode(&encode(true, vec![1], "", None)).unwrap();
+        assert_eq!(
+            unsequenced.reject_replay(&mut sequence),
+            Err(EphemeralRollupsSdkError::InvalidMagicResponse)
+        );
+    }
+
+    #[test]
+    fn test_reject_out_of_order_replay() {
+        let mut sequence = CallbackSequence::default();
+        for n in [3, 1, 7, 2, 0, 5] {
+            assert_eq!(
+                sequenced_response(n).reject_replay(&mut sequence),
+                Ok(()),
+                "{n}"
+            );
+        }
+        for n in [0, 1, 2, 3, 5, 7] {
+            assert_eq!(
+                sequenced_response(n).reject_replay(&mut sequence),
+                Err(EphemeralRollupsSdkError::CallbackReplayed),
+                "{n}"
+            );
+        }
+        assert_eq!(sequenced_response(4).reject_replay(&mut sequence), Ok(()));
+        assert_eq!(sequence.highest, 7);
+
+        // Sequences before the window can't be told apart from the accepted ones anymore
+        let highest = 7 + CallbackSequence::WINDOW;
+        assert_eq!(
+            sequenced_response(highest).reject_replay(&mut sequence),
+            Ok(())
+        );
+        assert_eq!(
+            sequenced_response(6).reject_replay(&mut sequence),
+            Err(EphemeralRollupsSdkError::CallbackReplayed)
+        );
+        assert_eq!(sequenced_response(8).reject_replay(&mut sequence), Ok(()));
+        assert_eq!(CallbackSequence::from_bytes(&sequence.to_bytes()), sequence);
+    }
+
+    #[test]
+    fn test_reject_callback_replay() {
+        let program = compat::Pubkey::new_unique();
+        let (mut lamports, mut data, mut short) = (0, [0u8; CallbackSequence::LEN + 4], [0u8; 8]);
+        let callback_sequence =
+            create_mock_account_info(&program, &mut lamports, &mut data, &program, false, false);
+
+        assert_eq!(
+            reject_callback_replay(&callback_sequence, &sequenced_response(2)),
+            Ok(())
+        );
+        assert_eq!(
+            reject_callback_replay(&callback_sequence, &sequenced_response(1)),
+            Ok(())
+        );
+        assert_eq!(
+            reject_callback_replay(&callback_sequence, &sequenced_response(2)),
+            Err(EphemeralRollupsSdkError::CallbackReplayed)
+        );
+        let state: [u8; CallbackSequence::LEN] = callback_sequence.data.borrow()
+            [..CallbackSequence::LEN]
+            .try_into()
+            .unwrap();
+        assert_eq!(
+            CallbackSequence::from_bytes(&state),
+            CallbackSequence {
+                highest: 2,
+                window: 0b11
+            }
+        );
+
+        let mut lamports = 0;
+        let too_small =
+            create_mock_account_info(&program, &mut lamports, &mut short, &program, false, false);
+        assert_eq!(
+            reject_callback_replay(&too_small, &sequenced_response(1)),
+            Err(EphemeralRollupsSdkError::InvalidCallbackSequence)
+        );
+    }
+}
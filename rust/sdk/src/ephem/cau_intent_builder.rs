@@ -10,6 +10,7 @@ use crate::ephem::{
 /// Created via [`MagicIntentBundleBuilder::commit_and_undelegate()`] or
 /// [`CommitIntentBuilder::commit_and_undelegate()`]. Owns the parent builder
 /// and returns it (or a sibling sub-builder) on every transition/terminal call.
+#[derive(Clone)]
 pub struct CommitAndUndelegateIntentBuilder<'info> {
     pub(in crate::ephem) parent: MagicIntentBundleBuilder<'info>,
     pub(in crate::ephem) accounts: Vec<compat::AccountInfo<'info>>,
@@ -9,6 +9,12 @@ use crate::ephem::{
 ///
 /// Created via [`MagicIntentBundleBuilder::commit()`]. Owns the parent builder
 /// and returns it (or a sibling sub-builder) on every transition/terminal call.
+///
+/// The intent only references the committed accounts, their state is read by the validator
+/// when it commits them. The encoding of the commit on the base layer, e.g. the byte-range
+/// diff of the delegation program `CommitDiff` instruction, which is signed by the validator,
+/// can't be chosen by the intent.
+#[derive(Clone)]
 pub struct CommitIntentBuilder<'info> {
     pub(in crate::ephem) parent: MagicIntentBundleBuilder<'info>,
     pub(in crate::ephem) accounts: Vec<compat::AccountInfo<'info>>,
@@ -0,0 +1,227 @@
+use crate::compat;
+use crate::ephem::IntentInstructions;
+use crate::error::EphemeralRollupsSdkError;
+use magicblock_magic_program_api::args::{
+    AddActionCallbackArgs, BaseActionArgs, CommitAndUndelegateArgs, CommitTypeArgs,
+    MagicIntentBundleArgs, ShortAccountMeta, UndelegateTypeArgs,
+};
+use magicblock_magic_program_api::instruction::MagicBlockInstruction;
+
+/// Content of a `ScheduleIntentBundle` instruction, with the account indices resolved back to
+/// the pubkeys of the instruction accounts, see [`decode_intent_bundle`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedIntentBundle {
+    /// Accounts of the instruction: payer, magic context, optional magic fee vault, then the
+    /// accounts referenced by the intents
+    pub accounts: Vec<compat::AccountMeta>,
+    pub commit: Option<DecodedCommit>,
+    pub commit_and_undelegate: Option<DecodedCommitAndUndelegate>,
+    pub commit_finalize: Option<DecodedCommit>,
+    pub commit_finalize_and_undelegate: Option<DecodedCommitAndUndelegate>,
+    pub standalone_actions: Vec<DecodedAction>,
+}
+
+/// Committed accounts along with the actions executed after the commit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedCommit {
+    pub committed_accounts: Vec<compat::Pubkey>,
+    pub actions: Vec<DecodedAction>,
+}
+
+/// Commit of the undelegated accounts along with the actions executed after the undelegation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedCommitAndUndelegate {
+    pub commit: DecodedCommit,
+    pub undelegate_actions: Vec<DecodedAction>,
+}
+
+/// Base layer action scheduled by the intent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedAction {
+    pub escrow_authority: compat::Pubkey,
+    pub escrow_index: u8,
+    pub destination_program: compat::Pubkey,
+    pub data: Vec<u8>,
+    pub compute_units: u32,
+    pub accounts: Vec<ShortAccountMeta>,
+    /// Callback attached via `AddActionCallback`, only resolved by [`decode_intent_instructions`]
+    pub callback: Option<DecodedActionCallback>,
+}
+
+/// Callback invoked after the action, see [`crate::ephem::ActionCallback`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedActionCallback {
+    pub destination_program: compat::Pubkey,
+    pub discriminator: Vec<u8>,
+    pub payload: Vec<u8>,
+    pub compute_units: u32,
+    pub accounts: Vec<ShortAccountMeta>,
+}
+
+impl DecodedIntentBundle {
+    /// Actions in the order of their flat index, as referenced by `AddActionCallback`:
+    /// commit, commit and undelegate, commit finalize, commit finalize and undelegate
+    /// actions, then the standalone ones.
+    pub fn actions(&self) -> Vec<&DecodedAction> {
+        let mut actions = Vec::new();
+        for (commit, cau) in [
+            (&self.commit, &self.commit_and_undelegate),
+            (&self.commit_finalize, &self.commit_finalize_and_undelegate),
+        ] {
+            actions.extend(commit.iter().flat_map(|c| &c.actions));
+            actions.extend(
+                cau.iter()
+                    .flat_map(|c| c.commit.actions.iter().chain(&c.undelegate_actions)),
+            );
+        }
+        actions.extend(&self.standalone_actions);
+        actions
+    }
+
+    fn actions_mut(&mut self) -> Vec<&mut DecodedAction> {
+        let Self {
+            commit,
+            commit_and_undelegate,
+            commit_finalize,
+            commit_finalize_and_undelegate,
+            standalone_actions,
+            ..
+        } = self;
+        let mut actions = Vec::new();
+        for (commit, cau) in [
+            (commit, commit_and_undelegate),
+            (commit_finalize, commit_finalize_and_undelegate),
+        ] {
+            actions.extend(commit.iter_mut().flat_map(|c| &mut c.actions));
+            actions.extend(
+                cau.iter_mut()
+                    .flat_map(|c| c.commit.actions.iter_mut().chain(&mut c.undelegate_actions)),
+            );
+        }
+        actions.extend(standalone_actions);
+        actions
+    }
+}
+
+/// Decodes the `ScheduleIntentBundle` instruction, e.g. built by
+/// [`MagicIntentBundleBuilder::build`](crate::ephem::MagicIntentBundleBuilder::build), to check
+/// what is actually scheduled. The callbacks are carried by separate instructions, see
+/// [`decode_intent_instructions`].
+pub fn decode_intent_bundle(
+    instruction: &compat::Instruction,
+) -> Result<DecodedIntentBundle, EphemeralRollupsSdkError> {
+    let args = match bincode::deserialize(&instruction.data) {
+        Ok(MagicBlockInstruction::ScheduleIntentBundle(args)) => args,
+        _ => return Err(EphemeralRollupsSdkError::InvalidIntentData),
+    };
+    let MagicIntentBundleArgs {
+        commit,
+        commit_and_undelegate,
+        commit_finalize,
+        commit_finalize_and_undelegate,
+        standalone_actions,
+    } = args;
+    let resolver = Resolver(&instruction.accounts);
+    Ok(DecodedIntentBundle {
+        accounts: instruction.accounts.clone(),
+        commit: commit.map(|c| resolver.commit(c)).transpose()?,
+        commit_and_undelegate: commit_and_undelegate
+            .map(|c| resolver.commit_and_undelegate(c))
+            .transpose()?,
+        commit_finalize: commit_finalize.map(|c| resolver.commit(c)).transpose()?,
+        commit_finalize_and_undelegate: commit_finalize_and_undelegate
+            .map(|c| resolver.commit_and_undelegate(c))
+            .transpose()?,
+        standalone_actions: resolver.actions(standalone_actions)?,
+    })
+}
+
+/// Decodes the instructions built by
+/// [`MagicIntentBundleBuilder::build`](crate::ephem::MagicIntentBundleBuilder::build), attaching
+/// the callbacks of the `AddActionCallback` instructions to their actions.
+pub fn decode_intent_instructions(
+    instructions: &IntentInstructions,
+) -> Result<DecodedIntentBundle, EphemeralRollupsSdkError> {
+    let mut bundle = decode_intent_bundle(&instructions.schedule_intent_ix.1)?;
+    let mut actions = bundle.actions_mut();
+    for (_, ix) in &instructions.add_callback_ixs {
+        let args: AddActionCallbackArgs = match bincode::deserialize(&ix.data) {
+            Ok(MagicBlockInstruction::AddActionCallback(args)) => args,
+            _ => return Err(EphemeralRollupsSdkError::InvalidIntentData),
+        };
+        let action = actions
+            .get_mut(args.action_index as usize)
+            .ok_or(EphemeralRollupsSdkError::InvalidIntentData)?;
+        action.callback = Some(DecodedActionCallback {
+            destination_program: args.destination_program.to_bytes().into(),
+            discriminator: args.discriminator,
+            payload: args.payload,
+            compute_units: args.compute_units,
+            accounts: args.accounts,
+        });
+    }
+    Ok(bundle)
+}
+
+/// Resolves the account indices of the intent args against the instruction accounts
+struct Resolver<'a>(&'a [compat::AccountMeta]);
+
+impl Resolver<'_> {
+    fn pubkey(&self, index: u8) -> Result<compat::Pubkey, EphemeralRollupsSdkError> {
+        self.0
+            .get(index as usize)
+            .map(|meta| meta.pubkey)
+            .ok_or(EphemeralRollupsSdkError::MissingIntentAccount)
+    }
+
+    fn actions(
+        &self,
+        actions: Vec<BaseActionArgs>,
+    ) -> Result<Vec<DecodedAction>, EphemeralRollupsSdkError> {
+        actions
+            .into_iter()
+            .map(|action| {
+                Ok(DecodedAction {
+                    escrow_authority: self.pubkey(action.escrow_authority)?,
+                    escrow_index: action.args.escrow_index,
+                    destination_program: action.destination_program.to_bytes().into(),
+                    data: action.args.data,
+                    compute_units: action.compute_units,
+                    accounts: action.accounts,
+                    callback: None,
+                })
+            })
+            .collect()
+    }
+
+    fn commit(&self, commit: CommitTypeArgs) -> Result<DecodedCommit, EphemeralRollupsSdkError> {
+        let (indices, actions) = match commit {
+            CommitTypeArgs::Standalone(indices) => (indices, vec![]),
+            CommitTypeArgs::WithBaseActions {
+                committed_accounts,
+                base_actions,
+            } => (committed_accounts, base_actions),
+        };
+        Ok(DecodedCommit {
+            committed_accounts: indices
+                .into_iter()
+                .map(|index| self.pubkey(index))
+                .collect::<Result<_, _>>()?,
+            actions: self.actions(actions)?,
+        })
+    }
+
+    fn commit_and_undelegate(
+        &self,
+        args: CommitAndUndelegateArgs,
+    ) -> Result<DecodedCommitAndUndelegate, EphemeralRollupsSdkError> {
+        let undelegate_actions = match args.undelegate_type {
+            UndelegateTypeArgs::Standalone => vec![],
+            UndelegateTypeArgs::WithBaseActions { base_actions } => self.actions(base_actions)?,
+        };
+        Ok(DecodedCommitAndUndelegate {
+            commit: self.commit(args.commit_type)?,
+            undelegate_actions,
+        })
+    }
+}
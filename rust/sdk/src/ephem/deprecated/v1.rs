@@ -2,6 +2,7 @@
 
 use crate::compat::{self, AsModern, Compat, Modern};
 use crate::ephem::deprecated::v1::utils::accounts_to_indices;
+use crate::error::EphemeralRollupsSdkError;
 use magicblock_magic_program_api::args::{
     ActionArgs, AddActionCallbackArgs, BaseActionArgs, CommitAndUndelegateArgs, CommitTypeArgs,
     MagicBaseIntentArgs, ShortAccountMeta, UndelegateTypeArgs,
@@ -11,7 +12,11 @@ use solana_program::instruction::{AccountMeta, Instruction};
 use solana_program::program::invoke;
 use std::collections::{HashMap, HashSet};
 
-const EXPECTED_KEY_MSG: &str = "Key expected to exist!";
+/// Encoded lengths of the bincode enum variant tags, vector length prefixes and short account
+/// metas of the magic program instructions
+pub(crate) const ENUM_TAG_LEN: usize = 4;
+pub(crate) const VEC_LEN_PREFIX: usize = 8;
+const SHORT_ACCOUNT_META_LEN: usize = 33;
 
 /// Instruction builder for magicprogram
 #[deprecated(since = "0.7.0", note = "Use `MagicIntentBundleBuilder` instead")]
@@ -31,8 +36,29 @@ impl<'info> MagicInstructionBuilder<'info> {
         self
     }
 
+    /// Validates the magic fee vault, if any, against the one of the ER `validator`, see
+    /// [`verify_magic_fee_vault`](crate::ephem::verify_magic_fee_vault).
+    pub fn validate_magic_fee_vault(
+        &self,
+        validator: &compat::Pubkey,
+    ) -> Result<(), EphemeralRollupsSdkError> {
+        self.magic_fee_vault.as_ref().map_or(Ok(()), |vault| {
+            crate::ephem::verify_magic_fee_vault(vault, validator)
+        })
+    }
+
     /// Build instruction for supplied an action and prepares accounts
+    ///
+    /// Panics if the action can't be encoded, see [`Self::try_build`]
     pub fn build(self) -> (Vec<compat::AccountInfo<'info>>, compat::Instruction) {
+        self.try_build().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Fallible variant of [`Self::build`]
+    pub fn try_build(
+        self,
+    ) -> Result<(Vec<compat::AccountInfo<'info>>, compat::Instruction), EphemeralRollupsSdkError>
+    {
         // set those to be first
         let mut all_accounts = vec![self.payer, self.magic_context];
         if let Some(vault) = self.magic_fee_vault {
@@ -41,10 +67,10 @@ impl<'info> MagicInstructionBuilder<'info> {
         // collect all accounts to be used in instruction
         self.magic_action.collect_accounts(&mut all_accounts);
         // filter duplicates & get indices map
-        let indices_map = utils::filter_duplicates_with_map(&mut all_accounts);
+        let indices_map = utils::filter_duplicates_with_map(&mut all_accounts)?;
 
         // construct args of ScheduleAction instruction
-        let args = self.magic_action.build_args(&indices_map);
+        let args = self.magic_action.build_args(&indices_map)?;
         // create accounts metas
         let accounts_meta = all_accounts
             .iter()
@@ -55,7 +81,7 @@ impl<'info> MagicInstructionBuilder<'info> {
             })
             .collect();
 
-        (
+        Ok((
             all_accounts,
             Instruction::new_with_bincode(
                 *self.magic_program.key.as_modern(),
@@ -63,12 +89,12 @@ impl<'info> MagicInstructionBuilder<'info> {
                 accounts_meta,
             )
             .compat(),
-        )
+        ))
     }
 
     /// Builds instruction for action & invokes magicprogram
     pub fn build_and_invoke(self) -> compat::ProgramResult {
-        let (accounts, ix) = self.build();
+        let (accounts, ix) = self.try_build()?;
         invoke(&ix.modern(), &accounts.modern()).compat()
     }
 }
@@ -100,25 +126,32 @@ impl<'info> MagicAction<'info> {
     }
 
     /// Creates argument for CPI
-    fn build_args(self, indices_map: &HashMap<compat::Pubkey, u8>) -> MagicBaseIntentArgs {
-        match self {
+    fn build_args(
+        self,
+        indices_map: &HashMap<compat::Pubkey, u8>,
+    ) -> Result<MagicBaseIntentArgs, EphemeralRollupsSdkError> {
+        let args = match self {
             MagicAction::BaseActions(call_handlers) => {
                 let call_handlers_args = call_handlers
                     .into_iter()
                     .map(|call_handler| call_handler.into_args(indices_map))
-                    .collect();
+                    .collect::<Result<_, _>>()?;
                 MagicBaseIntentArgs::BaseActions(call_handlers_args)
             }
-            MagicAction::Commit(value) => MagicBaseIntentArgs::Commit(value.into_args(indices_map)),
+            MagicAction::Commit(value) => {
+                MagicBaseIntentArgs::Commit(value.into_args(indices_map)?)
+            }
             MagicAction::CommitAndUndelegate(value) => {
-                MagicBaseIntentArgs::CommitAndUndelegate(value.into_args(indices_map))
+                MagicBaseIntentArgs::CommitAndUndelegate(value.into_args(indices_map)?)
             }
-        }
+        };
+        Ok(args)
     }
 }
 
 /// Type of commit , can be whether standalone or with some custom actions on Base layer post commit
 #[deprecated(since = "0.7.0", note = "Use `CommitIntentBuilder` instead")]
+#[derive(Clone)]
 pub enum CommitType<'info> {
     /// Regular commit without actions
     Standalone(Vec<compat::AccountInfo<'info>>), // accounts to commit
@@ -176,10 +209,13 @@ impl<'info> CommitType<'info> {
         }
     }
 
-    pub(crate) fn into_args(self, indices_map: &HashMap<compat::Pubkey, u8>) -> CommitTypeArgs {
-        match self {
+    pub(crate) fn into_args(
+        self,
+        indices_map: &HashMap<compat::Pubkey, u8>,
+    ) -> Result<CommitTypeArgs, EphemeralRollupsSdkError> {
+        let args = match self {
             Self::Standalone(accounts) => {
-                let accounts_indices = accounts_to_indices(accounts.as_slice(), indices_map);
+                let accounts_indices = accounts_to_indices(accounts.as_slice(), indices_map)?;
                 CommitTypeArgs::Standalone(accounts_indices)
             }
             Self::WithHandler {
@@ -188,19 +224,60 @@ impl<'info> CommitType<'info> {
                 ..
             } => {
                 let commited_accounts_indices =
-                    accounts_to_indices(commited_accounts.as_slice(), indices_map);
+                    accounts_to_indices(commited_accounts.as_slice(), indices_map)?;
                 let call_handlers_args = call_handlers
                     .into_iter()
                     .map(|call_handler| call_handler.into_args(indices_map))
-                    .collect();
+                    .collect::<Result<_, _>>()?;
                 CommitTypeArgs::WithBaseActions {
                     committed_accounts: commited_accounts_indices,
                     base_actions: call_handlers_args,
                 }
             }
+        };
+        Ok(args)
+    }
+
+    pub(crate) fn actions_len(&self) -> usize {
+        match self {
+            Self::Standalone(_) => 0,
+            Self::WithHandler { call_handlers, .. } => call_handlers.len(),
         }
     }
 
+    /// Length of the encoded [`CommitTypeArgs`]
+    pub(crate) fn encoded_len(&self) -> usize {
+        let accounts_len = ENUM_TAG_LEN + VEC_LEN_PREFIX + self.committed_accounts().len();
+        match self {
+            Self::Standalone(_) => accounts_len,
+            Self::WithHandler { call_handlers, .. } => {
+                accounts_len + CallHandler::encoded_list_len(call_handlers)
+            }
+        }
+    }
+
+    /// Length of the encoding saved by [`Self::merge`] of `other` into this intent
+    pub(crate) fn shared_len(&self, other: &Self) -> usize {
+        let with_handler = |value: &Self| usize::from(matches!(value, Self::WithHandler { .. }));
+        let merged_with_handler = usize::from(self.actions_len() + other.actions_len() > 0);
+        ENUM_TAG_LEN
+            + VEC_LEN_PREFIX
+            + (with_handler(self) + with_handler(other) - merged_with_handler) * VEC_LEN_PREFIX
+    }
+
+    /// Callbacks of the actions, in the order of the actions
+    pub(crate) fn callbacks(&self) -> impl Iterator<Item = &ActionCallback> {
+        let (call_handlers, callbacks) = match self {
+            Self::Standalone(_) => (&[][..], &[][..]),
+            Self::WithHandler {
+                call_handlers,
+                callbacks,
+                ..
+            } => (&call_handlers[..], &callbacks[..]),
+        };
+        callbacks.iter().take(call_handlers.len()).flatten()
+    }
+
     pub(crate) fn extract_callbacks(&mut self, idx: &mut u8, out: &mut Vec<(u8, ActionCallback)>) {
         if let Self::WithHandler {
             call_handlers,
@@ -262,6 +339,7 @@ impl<'info> CommitType<'info> {
     since = "0.7.0",
     note = "Use `CommitAndUndelegateIntentBuilder` instead"
 )]
+#[derive(Clone)]
 pub enum UndelegateType<'info> {
     Standalone,
     WithHandler {
@@ -280,21 +358,62 @@ impl<'info> UndelegateType<'info> {
         }
     }
 
-    fn into_args(self, indices_map: &HashMap<compat::Pubkey, u8>) -> UndelegateTypeArgs {
-        match self {
+    fn into_args(
+        self,
+        indices_map: &HashMap<compat::Pubkey, u8>,
+    ) -> Result<UndelegateTypeArgs, EphemeralRollupsSdkError> {
+        let args = match self {
             Self::Standalone => UndelegateTypeArgs::Standalone,
             Self::WithHandler { call_handlers, .. } => {
                 let call_handlers_args = call_handlers
                     .into_iter()
                     .map(|call_handler| call_handler.into_args(indices_map))
-                    .collect();
+                    .collect::<Result<_, _>>()?;
                 UndelegateTypeArgs::WithBaseActions {
                     base_actions: call_handlers_args,
                 }
             }
+        };
+        Ok(args)
+    }
+
+    pub(crate) fn actions_len(&self) -> usize {
+        match self {
+            Self::Standalone => 0,
+            Self::WithHandler { call_handlers, .. } => call_handlers.len(),
+        }
+    }
+
+    /// Length of the encoded [`UndelegateTypeArgs`]
+    pub(crate) fn encoded_len(&self) -> usize {
+        match self {
+            Self::Standalone => ENUM_TAG_LEN,
+            Self::WithHandler { call_handlers, .. } => {
+                ENUM_TAG_LEN + CallHandler::encoded_list_len(call_handlers)
+            }
+        }
+    }
+
+    /// Length of the encoding saved by [`Self::merge`] of `other` into this intent
+    pub(crate) fn shared_len(&self, other: &Self) -> usize {
+        match (self, other) {
+            (Self::WithHandler { .. }, Self::WithHandler { .. }) => ENUM_TAG_LEN + VEC_LEN_PREFIX,
+            _ => ENUM_TAG_LEN,
         }
     }
 
+    /// Callbacks of the actions, in the order of the actions
+    pub(crate) fn callbacks(&self) -> impl Iterator<Item = &ActionCallback> {
+        let (call_handlers, callbacks) = match self {
+            Self::Standalone => (&[][..], &[][..]),
+            Self::WithHandler {
+                call_handlers,
+                callbacks,
+            } => (&call_handlers[..], &callbacks[..]),
+        };
+        callbacks.iter().take(call_handlers.len()).flatten()
+    }
+
     pub(crate) fn extract_callbacks(&mut self, idx: &mut u8, out: &mut Vec<(u8, ActionCallback)>) {
         if let Self::WithHandler {
             call_handlers,
@@ -341,6 +460,7 @@ impl<'info> UndelegateType<'info> {
     since = "0.7.0",
     note = "Use `CommitAndUndelegateIntentBuilder` instead"
 )]
+#[derive(Clone)]
 pub struct CommitAndUndelegate<'info> {
     pub commit_type: CommitType<'info>,
     pub undelegate_type: UndelegateType<'info>,
@@ -358,19 +478,41 @@ impl<'info> CommitAndUndelegate<'info> {
     pub(crate) fn into_args(
         self,
         indices_map: &HashMap<compat::Pubkey, u8>,
-    ) -> CommitAndUndelegateArgs {
-        let commit_type_args = self.commit_type.into_args(indices_map);
-        let undelegate_type_args = self.undelegate_type.into_args(indices_map);
-        CommitAndUndelegateArgs {
+    ) -> Result<CommitAndUndelegateArgs, EphemeralRollupsSdkError> {
+        let commit_type_args = self.commit_type.into_args(indices_map)?;
+        let undelegate_type_args = self.undelegate_type.into_args(indices_map)?;
+        Ok(CommitAndUndelegateArgs {
             commit_type: commit_type_args,
             undelegate_type: undelegate_type_args,
-        }
+        })
     }
 
     pub(crate) fn dedup(&mut self) -> HashSet<compat::Pubkey> {
         self.commit_type.dedup()
     }
 
+    pub(crate) fn actions_len(&self) -> usize {
+        self.commit_type.actions_len() + self.undelegate_type.actions_len()
+    }
+
+    /// Length of the encoded [`CommitAndUndelegateArgs`]
+    pub(crate) fn encoded_len(&self) -> usize {
+        self.commit_type.encoded_len() + self.undelegate_type.encoded_len()
+    }
+
+    /// Length of the encoding saved by [`Self::merge`] of `other` into this intent
+    pub(crate) fn shared_len(&self, other: &Self) -> usize {
+        self.commit_type.shared_len(&other.commit_type)
+            + self.undelegate_type.shared_len(&other.undelegate_type)
+    }
+
+    /// Callbacks of the actions, in the order of the actions
+    pub(crate) fn callbacks(&self) -> impl Iterator<Item = &ActionCallback> {
+        self.commit_type
+            .callbacks()
+            .chain(self.undelegate_type.callbacks())
+    }
+
     pub(crate) fn extract_callbacks(&mut self, idx: &mut u8, out: &mut Vec<(u8, ActionCallback)>) {
         self.commit_type.extract_callbacks(idx, out);
         self.undelegate_type.extract_callbacks(idx, out);
@@ -387,6 +529,7 @@ impl<'info> CommitAndUndelegate<'info> {
 /// Attach via `add_*_action_with_callback` on the intent builders. The flat
 /// `action_index` required by `AddActionCallback` is computed automatically at
 /// invoke time — the callback travels with its action through merge/normalize.
+#[derive(Clone)]
 pub struct ActionCallback {
     pub destination_program: compat::Pubkey,
     pub discriminator: Vec<u8>,
@@ -396,6 +539,29 @@ pub struct ActionCallback {
 }
 
 impl ActionCallback {
+    /// Prefixes the payload with `sequence` as a little-endian `u64`, so that the handler
+    /// can reject replays, see [`ActionCallbackResponse::reject_replay`].
+    ///
+    /// [`ActionCallbackResponse::reject_replay`]: crate::ephem::ActionCallbackResponse::reject_replay
+    pub fn with_sequence(mut self, sequence: u64) -> Self {
+        self.payload.splice(0..0, sequence.to_le_bytes());
+        self
+    }
+
+    /// Length of the encoded `AddActionCallback` instruction
+    pub(crate) fn encoded_len(&self) -> usize {
+        ENUM_TAG_LEN
+            + 1
+            + 32
+            + VEC_LEN_PREFIX
+            + self.discriminator.len()
+            + VEC_LEN_PREFIX
+            + self.payload.len()
+            + 4
+            + VEC_LEN_PREFIX
+            + self.accounts.len() * SHORT_ACCOUNT_META_LEN
+    }
+
     pub(crate) fn into_args(self, action_index: u8) -> AddActionCallbackArgs {
         AddActionCallbackArgs {
             action_index,
@@ -408,6 +574,7 @@ impl ActionCallback {
     }
 }
 
+#[derive(Clone)]
 pub struct CallHandler<'info> {
     pub args: ActionArgs,
     pub compute_units: u32,
@@ -421,24 +588,44 @@ impl<'info> CallHandler<'info> {
         container.push(self.escrow_authority.clone());
     }
 
-    pub(crate) fn into_args(self, indices_map: &HashMap<compat::Pubkey, u8>) -> BaseActionArgs {
+    /// Length of the encoded [`BaseActionArgs`]
+    pub(crate) fn encoded_len(&self) -> usize {
+        // escrow index and data, compute units, escrow authority, destination program
+        1 + VEC_LEN_PREFIX
+            + self.args.data.len()
+            + 4
+            + 1
+            + 32
+            + VEC_LEN_PREFIX
+            + self.accounts.len() * SHORT_ACCOUNT_META_LEN
+    }
+
+    /// Length of the encoded `Vec<BaseActionArgs>`
+    pub(crate) fn encoded_list_len(call_handlers: &[Self]) -> usize {
+        VEC_LEN_PREFIX + call_handlers.iter().map(Self::encoded_len).sum::<usize>()
+    }
+
+    pub(crate) fn into_args(
+        self,
+        indices_map: &HashMap<compat::Pubkey, u8>,
+    ) -> Result<BaseActionArgs, EphemeralRollupsSdkError> {
         let escrow_authority_index = indices_map
             .get(self.escrow_authority.key)
-            .expect(EXPECTED_KEY_MSG);
+            .ok_or(EphemeralRollupsSdkError::MissingIntentAccount)?;
 
-        BaseActionArgs {
+        Ok(BaseActionArgs {
             args: self.args,
             compute_units: self.compute_units,
             destination_program: self.destination_program.to_bytes().into(),
             escrow_authority: *escrow_authority_index,
             accounts: self.accounts,
-        }
+        })
     }
 }
 
 pub(crate) mod utils {
-    use super::EXPECTED_KEY_MSG;
     use crate::compat;
+    use crate::error::EphemeralRollupsSdkError;
     use std::collections::hash_map::Entry;
     use std::collections::HashMap;
 
@@ -446,18 +633,24 @@ pub(crate) mod utils {
     pub fn accounts_to_indices(
         accounts: &[compat::AccountInfo],
         indices_map: &HashMap<compat::Pubkey, u8>,
-    ) -> Vec<u8> {
+    ) -> Result<Vec<u8>, EphemeralRollupsSdkError> {
         accounts
             .iter()
-            .map(|account| *indices_map.get(account.key).expect(EXPECTED_KEY_MSG))
+            .map(|account| {
+                indices_map
+                    .get(account.key)
+                    .copied()
+                    .ok_or(EphemeralRollupsSdkError::MissingIntentAccount)
+            })
             .collect()
     }
 
     /// Removes duplicates from array by pubkey
-    /// Returns a map of key to index in cleaned array
+    /// Returns a map of key to index in cleaned array, fails if
+    /// the unique accounts can't be indexed by `u8`
     pub fn filter_duplicates_with_map(
         container: &mut Vec<compat::AccountInfo>,
-    ) -> HashMap<compat::Pubkey, u8> {
+    ) -> Result<HashMap<compat::Pubkey, u8>, EphemeralRollupsSdkError> {
         let mut map = HashMap::new();
         container.retain(|el| match map.entry(*el.key) {
             Entry::Occupied(_) => false,
@@ -467,12 +660,15 @@ pub(crate) mod utils {
                 true
             }
         });
+        if container.len() > u8::MAX as usize + 1 {
+            return Err(EphemeralRollupsSdkError::TooManyAccounts);
+        }
         // update map with valid indices
         container.iter().enumerate().for_each(|(i, account)| {
-            *map.get_mut(account.key).unwrap() = i as u8;
+            map.insert(*account.key, i as u8);
         });
 
-        map
+        Ok(map)
     }
 }
 
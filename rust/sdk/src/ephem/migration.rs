@@ -0,0 +1,71 @@
+use crate::compat;
+use crate::cpi::DelegateConfig;
+use crate::ephem::{CallHandler, CommitAndUndelegateIntentBuilder, MagicIntentBundleBuilder};
+use magicblock_magic_program_api::args::{ActionArgs, ShortAccountMeta};
+
+/// Max length of the re-delegate action data, see [`redelegate_action_data`]
+pub const MAX_REDELEGATE_ACTION_DATA_LEN: usize = 8 + 4 + 1 + 32;
+
+/// Migration of the delegated account to another validator, see
+/// [`MagicIntentBundleBuilder::migrate_to_validator`].
+pub struct ValidatorMigration {
+    /// Program owning the migrated account, which implements the re-delegate instruction
+    pub owner_program: compat::Pubkey,
+    /// Discriminator of the re-delegate instruction
+    pub discriminator: [u8; 8],
+    /// Index of the escrow of the migrated account, which signs the re-delegate action
+    pub escrow_index: u8,
+    /// Accounts of the re-delegate instruction
+    pub accounts: Vec<ShortAccountMeta>,
+    /// Delegation config passed to the re-delegate instruction, with the target validator
+    pub config: DelegateConfig,
+    pub compute_units: u32,
+}
+
+/// Instruction data of the re-delegate action: the discriminator followed by the borsh
+/// encoded `commit_frequency_ms: u32` and `validator: Option<Pubkey>` arguments
+pub fn redelegate_action_data(discriminator: &[u8; 8], config: &DelegateConfig) -> Vec<u8> {
+    let mut data = Vec::with_capacity(MAX_REDELEGATE_ACTION_DATA_LEN);
+    data.extend_from_slice(discriminator);
+    data.extend_from_slice(&config.commit_frequency_ms.to_le_bytes());
+    match config.validator {
+        Some(validator) => {
+            data.push(1);
+            data.extend_from_slice(validator.as_ref());
+        }
+        None => data.push(0),
+    }
+    data
+}
+
+impl<'info> MagicIntentBundleBuilder<'info> {
+    /// Moves the delegated account to another validator in a single call: commits and
+    /// undelegates it, then re-delegates it via the post-undelegate action, which invokes the
+    /// re-delegate instruction of the owner program with the migration config.
+    ///
+    /// The migrated account is the escrow authority of the action, so the bundle must be
+    /// invoked with its seeds, e.g. via [`build_and_invoke_signed`]. On the base layer, the
+    /// re-delegate instruction must verify the escrow signer, as done by the
+    /// `redelegate_<field>` methods generated by `#[delegate]`.
+    ///
+    /// [`build_and_invoke_signed`]: MagicIntentBundleBuilder::build_and_invoke_signed
+    pub fn migrate_to_validator(
+        self,
+        account: &compat::AccountInfo<'info>,
+        migration: ValidatorMigration,
+    ) -> CommitAndUndelegateIntentBuilder<'info> {
+        let data = redelegate_action_data(&migration.discriminator, &migration.config);
+        let redelegate = CallHandler {
+            args: ActionArgs {
+                escrow_index: migration.escrow_index,
+                data,
+            },
+            compute_units: migration.compute_units,
+            escrow_authority: account.clone(),
+            destination_program: migration.owner_program,
+            accounts: migration.accounts,
+        };
+        self.commit_and_undelegate(std::slice::from_ref(account))
+            .add_post_undelegate_actions([redelegate])
+    }
+}
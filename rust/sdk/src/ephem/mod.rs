@@ -1,27 +1,46 @@
 #![allow(deprecated)]
 
 use crate::compat::{self, AsModern, Compat, Modern};
+use crate::cu;
 pub use crate::ephem::deprecated::v0::{
     commit_accounts, commit_and_undelegate_accounts, create_schedule_commit_ix,
 };
-use crate::ephem::deprecated::v1::utils;
+use crate::ephem::deprecated::v1::{utils, ENUM_TAG_LEN, VEC_LEN_PREFIX};
 pub use crate::ephem::deprecated::v1::{
     ActionCallback, CallHandler, CommitAndUndelegate, CommitType, MagicAction,
     MagicInstructionBuilder, UndelegateType,
 };
+use crate::error::EphemeralRollupsSdkError;
+use crate::pda;
+pub use action_sequence::ActionSequence;
+pub use callback::{
+    reject_callback_replay, verify_action_callback, ActionCallbackResponse, CallbackSequence,
+    CALLBACK_SEQUENCE_LEN, CALLBACK_SEQUENCE_SEED,
+};
 pub use cau_intent_builder::{CommitAndUndelegateIntentBuilder, FoldableCauIntentBuilder};
 pub use commit_intent_builder::{CommitIntentBuilder, FoldableCommitIntentBuilder};
+pub use decode::{
+    decode_intent_bundle, decode_intent_instructions, DecodedAction, DecodedActionCallback,
+    DecodedCommit, DecodedCommitAndUndelegate, DecodedIntentBundle,
+};
 use magicblock_magic_program_api::args::MagicIntentBundleArgs;
 use magicblock_magic_program_api::instruction::MagicBlockInstruction;
 pub use magicblock_magic_program_api::response::MagicResponse;
+pub use migration::{redelegate_action_data, ValidatorMigration};
+pub use schedule_commit::ScheduleCommitCpi;
 use solana_program::instruction::{AccountMeta, Instruction};
 use solana_program::program::{invoke, invoke_signed};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 pub mod action_builder;
+pub mod action_sequence;
+pub mod callback;
 pub mod cau_intent_builder;
 pub mod commit_intent_builder;
+pub mod decode;
 pub mod deprecated;
+pub mod migration;
+pub mod schedule_commit;
 
 /// Intent to be scheduled for execution on the base layer.
 ///
@@ -75,16 +94,92 @@ impl<'info> IntentInstructions<'info> {
             })
             .compat()
     }
+
+    /// Instructions in the order they have to be executed: `ScheduleIntentBundle`, then each
+    /// `AddActionCallback`.
+    pub fn into_instructions(self) -> Vec<compat::Instruction> {
+        std::iter::once(self.schedule_intent_ix.1)
+            .chain(self.add_callback_ixs.into_iter().map(|(_, ix)| ix))
+            .collect()
+    }
+
+    /// Estimate of the compute units spent by [`Self::invoke`], see [`crate::cu`]
+    pub fn estimated_compute_units(&self) -> u64 {
+        cu::schedule_intent_bundle(&self.schedule_intent_ix.1)
+            + self
+                .add_callback_ixs
+                .iter()
+                .map(|(_, ix)| cu::add_action_callback(ix))
+                .sum::<u64>()
+    }
+}
+
+/// Verifies the magic fee vault passed along with the intent against the one of the ER
+/// `validator`, derived via [`crate::pda::magic_fee_vault_pda_from_validator`].
+///
+/// The vault is required when the payer is delegated, since the magic program charges the
+/// fees of a delegated payer to the vault. That can't be told from the payer on the ER, where
+/// delegated accounts are owned by their program, so it's up to the caller to pass the vault,
+/// see [`MagicIntentBundleBuilder::payer_is_delegated`].
+pub fn verify_magic_fee_vault(
+    magic_fee_vault: &compat::AccountInfo,
+    validator: &compat::Pubkey,
+) -> Result<(), EphemeralRollupsSdkError> {
+    if magic_fee_vault.key != &pda::magic_fee_vault_pda_from_validator(validator) {
+        return Err(EphemeralRollupsSdkError::InvalidMagicFeeVault);
+    }
+    Ok(())
+}
+
+/// Maximum number of accounts of the `ScheduleIntentBundle` CPI, i.e. the account infos
+/// accepted by the runtime in a single CPI
+pub const MAX_INTENT_BUNDLE_ACCOUNTS: usize = 128;
+/// Maximum instruction data length of the `ScheduleIntentBundle` CPI
+pub const MAX_INTENT_BUNDLE_DATA_LEN: usize = 10 * 1024;
+/// Maximum number of actions of a bundle, shared with the pinocchio crate
+pub const MAX_INTENT_BUNDLE_ACTIONS: usize = 10;
+/// Encoded length of a `ScheduleIntentBundle` instruction without intents: the instruction
+/// tag, the tags of the four optional intents and the length of the standalone actions
+const EMPTY_INTENT_BUNDLE_LEN: usize = ENUM_TAG_LEN + 4 + VEC_LEN_PREFIX;
+
+/// Size of the `ScheduleIntentBundle` instruction against the CPI limits, see
+/// [`MagicIntentBundleBuilder::validate`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntentBundleReport {
+    /// Unique accounts of the instruction, at most [`MAX_INTENT_BUNDLE_ACCOUNTS`]
+    pub accounts: usize,
+    /// Serialized instruction data length, at most [`MAX_INTENT_BUNDLE_DATA_LEN`]
+    pub data_len: usize,
+    /// Actions of all the intents, at most [`MAX_INTENT_BUNDLE_ACTIONS`]
+    pub actions: usize,
+}
+
+impl IntentBundleReport {
+    /// Checks the report against the CPI limits
+    fn check(self) -> Result<Self, EphemeralRollupsSdkError> {
+        if self.accounts > MAX_INTENT_BUNDLE_ACCOUNTS {
+            Err(EphemeralRollupsSdkError::TooManyAccounts)
+        } else if self.data_len > MAX_INTENT_BUNDLE_DATA_LEN {
+            Err(EphemeralRollupsSdkError::IntentDataTooLarge)
+        } else if self.actions > MAX_INTENT_BUNDLE_ACTIONS {
+            Err(EphemeralRollupsSdkError::TooManyActions)
+        } else {
+            Ok(self)
+        }
+    }
 }
 
 /// Builds a single `MagicBlockInstruction::ScheduleIntentBundle` instruction by aggregating
 /// multiple independent intents (base actions, commits, commit+undelegate), normalizing them,
 /// and producing a deduplicated account list plus the corresponding CPI `Instruction`.
+#[derive(Clone)]
 pub struct MagicIntentBundleBuilder<'info> {
     payer: compat::AccountInfo<'info>,
     magic_context: compat::AccountInfo<'info>,
     magic_program: compat::AccountInfo<'info>,
     magic_fee_vault: Option<compat::AccountInfo<'info>>,
+    magic_fee_vault_validator: Option<compat::Pubkey>,
+    payer_is_delegated: bool,
     intent_bundle: MagicIntentBundle<'info>,
 }
 
@@ -99,17 +194,47 @@ impl<'info> MagicIntentBundleBuilder<'info> {
             magic_context,
             magic_program,
             magic_fee_vault: None,
+            magic_fee_vault_validator: None,
+            payer_is_delegated: false,
             intent_bundle: MagicIntentBundle::default(),
         }
     }
 
     /// Sets an optional magic fee vault account to be passed at index 2
-    /// (right after payer and magic_context). Required when the payer is delegated.
+    /// (right after payer and magic_context). Required when the payer is delegated,
+    /// the vault of the validator is derived via [`crate::pda::magic_fee_vault_pda_from_validator`].
     pub fn magic_fee_vault(mut self, vault: compat::AccountInfo<'info>) -> Self {
         self.magic_fee_vault = Some(vault);
         self
     }
 
+    /// Validates the magic fee vault, if any, against the one of the ER `validator` when
+    /// building, see [`verify_magic_fee_vault`].
+    pub fn validate_magic_fee_vault(mut self, validator: compat::Pubkey) -> Self {
+        self.magic_fee_vault_validator = Some(validator);
+        self
+    }
+
+    /// Marks the payer as delegated, so that building fails with
+    /// [`EphemeralRollupsSdkError::MissingMagicFeeVault`] without a magic fee vault, see
+    /// [`verify_magic_fee_vault`].
+    pub fn payer_is_delegated(mut self) -> Self {
+        self.payer_is_delegated = true;
+        self
+    }
+
+    /// Checks the magic fee vault per [`Self::payer_is_delegated`] and
+    /// [`Self::validate_magic_fee_vault`].
+    fn check_magic_fee_vault(&self) -> Result<(), EphemeralRollupsSdkError> {
+        match (&self.magic_fee_vault, self.magic_fee_vault_validator) {
+            (None, _) if self.payer_is_delegated => {
+                Err(EphemeralRollupsSdkError::MissingMagicFeeVault)
+            }
+            (Some(vault), Some(validator)) => verify_magic_fee_vault(vault, &validator),
+            _ => Ok(()),
+        }
+    }
+
     /// Starts building a Commit intent. Returns a [`CommitIntentBuilder`] that owns this parent.
     ///
     /// The returned builder lets you chain `.add_post_commit_actions()`, transition to other
@@ -143,6 +268,11 @@ impl<'info> MagicIntentBundleBuilder<'info> {
         }
     }
 
+    pub(crate) fn add_intent(mut self, intent: MagicIntent<'info>) -> Self {
+        self.intent_bundle.add_intent(intent);
+        self
+    }
+
     /// Adds standalone base-layer actions to be executed without any commit/undelegate semantics.
     pub fn add_standalone_actions(
         mut self,
@@ -172,10 +302,23 @@ impl<'info> MagicIntentBundleBuilder<'info> {
         action_builder::ActionBuilder::new(self, action, |mut parent, action, callback| {
             parent.intent_bundle.standalone_actions.push(action);
             parent.intent_bundle.standalone_callbacks.push(callback);
+            parent.intent_bundle.standalone_links.push(false);
             parent
         })
     }
 
+    /// Adds standalone actions executed in order, see [`ActionSequence`].
+    pub fn add_action_sequence(mut self, sequence: ActionSequence<'info>) -> Self {
+        let ActionSequence { actions, callbacks } = sequence;
+        let bundle = &mut self.intent_bundle;
+        bundle
+            .standalone_links
+            .extend((0..actions.len()).map(|i| i > 0));
+        bundle.standalone_actions.extend(actions);
+        bundle.standalone_callbacks.extend(callbacks);
+        self
+    }
+
     fn build_callback_ixs(
         &mut self,
     ) -> Vec<(Vec<compat::AccountInfo<'info>>, compat::Instruction)> {
@@ -217,7 +360,18 @@ impl<'info> MagicIntentBundleBuilder<'info> {
     }
 
     /// Builds [`IntentInstructions`] that schedules IntentBundle in magic program
-    pub fn build(mut self) -> IntentInstructions<'info> {
+    ///
+    /// Panics if the bundle can't be encoded, see [`Self::try_build`]
+    pub fn build(self) -> IntentInstructions<'info> {
+        self.try_build().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Fallible variant of [`Self::build`]
+    pub fn try_build(mut self) -> Result<IntentInstructions<'info>, EphemeralRollupsSdkError> {
+        self.check_magic_fee_vault()?;
+        if self.intent_bundle.actions_len() > MAX_INTENT_BUNDLE_ACTIONS {
+            return Err(EphemeralRollupsSdkError::TooManyActions);
+        }
         self.intent_bundle.normalize();
 
         // Build AddActionCallback instructions
@@ -229,8 +383,8 @@ impl<'info> MagicIntentBundleBuilder<'info> {
             all_accounts.push(vault);
         }
         self.intent_bundle.collect_accounts(&mut all_accounts);
-        let indices_map = utils::filter_duplicates_with_map(&mut all_accounts);
-        let args = self.intent_bundle.into_args(&indices_map);
+        let indices_map = utils::filter_duplicates_with_map(&mut all_accounts)?;
+        let args = self.intent_bundle.into_args(&indices_map)?;
         let metas = all_accounts
             .iter()
             .map(|ai| {
@@ -264,21 +418,186 @@ impl<'info> MagicIntentBundleBuilder<'info> {
         )
         .compat();
 
-        IntentInstructions {
+        Ok(IntentInstructions {
             schedule_intent_ix: (all_accounts, schedule_ix),
             add_callback_ixs,
-        }
+        })
     }
 
     /// Convenience wrapper: builds all instructions and invokes them in order.
     pub fn build_and_invoke(self) -> compat::ProgramResult {
-        self.build().invoke()
+        self.try_build()?.invoke()
+    }
+
+    /// Estimate of the compute units spent by [`Self::build_and_invoke`], see [`crate::cu`].
+    ///
+    /// Computed from the number of unique accounts and the encoded lengths of the bundle and
+    /// its callbacks, without building the instructions. Accounts committed more than once are
+    /// counted as many times in the instruction data, which only makes the estimate higher.
+    /// Fails if the bundle has too many actions or accounts to be encoded.
+    pub fn estimated_compute_units(&self) -> Result<u64, EphemeralRollupsSdkError> {
+        let bundle = &self.intent_bundle;
+        if bundle.actions_len() > MAX_INTENT_BUNDLE_ACTIONS {
+            return Err(EphemeralRollupsSdkError::TooManyActions);
+        }
+        let accounts = self.accounts_len(bundle);
+        if accounts > u8::MAX as usize + 1 {
+            return Err(EphemeralRollupsSdkError::TooManyAccounts);
+        }
+
+        let callback_accounts = 2 + usize::from(self.magic_fee_vault.is_some());
+        Ok(cu::intent_bundle(accounts, bundle.encoded_len())
+            + bundle
+                .callbacks()
+                .into_iter()
+                .map(|callback| cu::action_callback(callback_accounts, callback.encoded_len()))
+                .sum::<u64>())
+    }
+
+    /// Number of unique accounts of the `ScheduleIntentBundle` instruction of `bundle`
+    fn accounts_len(&self, bundle: &MagicIntentBundle<'info>) -> usize {
+        let mut accounts = vec![];
+        bundle.collect_accounts(&mut accounts);
+        [&self.payer, &self.magic_context]
+            .into_iter()
+            .chain(&self.magic_fee_vault)
+            .chain(&accounts)
+            .map(|account| account.key)
+            .collect::<HashSet<_>>()
+            .len()
     }
 
     /// Convenience wrapper: builds all instructions and invokes them signed with
     /// the provided PDA seeds.
     pub fn build_and_invoke_signed(self, signers_seeds: &[&[&[u8]]]) -> compat::ProgramResult {
-        self.build().invoke_signed(signers_seeds)
+        self.try_build()?.invoke_signed(signers_seeds)
+    }
+
+    /// Pre-flight check of the bundle against the CPI limits, returns the size of the
+    /// `ScheduleIntentBundle` instruction.
+    ///
+    /// Fails with [`EphemeralRollupsSdkError::TooManyAccounts`],
+    /// [`EphemeralRollupsSdkError::IntentDataTooLarge`] or
+    /// [`EphemeralRollupsSdkError::TooManyActions`] when the bundle has to be split,
+    /// see [`Self::build_chunked`].
+    pub fn validate(&self) -> Result<IntentBundleReport, EphemeralRollupsSdkError> {
+        self.check_magic_fee_vault()?;
+        let mut bundle = self.intent_bundle.clone();
+        bundle.normalize();
+        IntentBundleReport {
+            accounts: self.accounts_len(&bundle),
+            data_len: bundle.encoded_len(),
+            actions: bundle.actions_len(),
+        }
+        .check()
+    }
+
+    /// Builds the bundle as several [`IntentInstructions`], each of them passing [`Self::validate`].
+    ///
+    /// Commits without actions and standalone actions are split between the instructions,
+    /// while an account committed and undelegated is always committed and undelegated by the
+    /// same instruction, and an [`ActionSequence`] is scheduled whole. Intents with
+    /// post-commit or post-undelegate actions are kept whole, as their actions have to run
+    /// after all of their accounts are committed.
+    pub fn build_chunked(
+        mut self,
+    ) -> Result<Vec<IntentInstructions<'info>>, EphemeralRollupsSdkError> {
+        self.check_magic_fee_vault()?;
+        self.magic_fee_vault_validator = None;
+        let mut bundle = std::mem::take(&mut self.intent_bundle);
+        bundle.normalize();
+
+        let fixed_keys: HashSet<_> = [&self.payer, &self.magic_context]
+            .into_iter()
+            .chain(&self.magic_fee_vault)
+            .map(|account| *account.key)
+            .collect();
+        let mut chunks = vec![];
+        let mut chunk = Chunk::new(fixed_keys.clone());
+        for part in bundle.split() {
+            let size = match chunk.size_with(&part) {
+                // the part doesn't fit along with the current chunk, start a new one with it
+                Err(_) if !chunk.bundle.is_empty() => {
+                    let full = std::mem::replace(&mut chunk, Chunk::new(fixed_keys.clone()));
+                    chunks.push(full.bundle);
+                    chunk.size_with(&part)?
+                }
+                size => size?,
+            };
+            chunk.merge(part, size);
+        }
+        if chunks.is_empty() || !chunk.bundle.is_empty() {
+            chunks.push(chunk.bundle);
+        }
+
+        chunks
+            .into_iter()
+            .map(|intent_bundle| {
+                Self {
+                    intent_bundle,
+                    ..self.clone()
+                }
+                .try_build()
+            })
+            .collect()
+    }
+}
+
+/// Bundle filled by [`MagicIntentBundleBuilder::build_chunked`], along with the unique keys
+/// and the size of its instruction, accounted incrementally
+struct Chunk<'info> {
+    bundle: MagicIntentBundle<'info>,
+    keys: HashSet<compat::Pubkey>,
+    report: IntentBundleReport,
+}
+
+impl<'info> Chunk<'info> {
+    /// Empty chunk of an instruction passing the `fixed_keys`, i.e. the payer, the magic
+    /// context and the magic fee vault
+    fn new(fixed_keys: HashSet<compat::Pubkey>) -> Self {
+        Self {
+            bundle: MagicIntentBundle::default(),
+            report: IntentBundleReport {
+                accounts: fixed_keys.len(),
+                data_len: EMPTY_INTENT_BUNDLE_LEN,
+                actions: 0,
+            },
+            keys: fixed_keys,
+        }
+    }
+
+    /// Size of the chunk once the normalized `part` is merged into it, along with the keys
+    /// of the part it doesn't have yet. Fails if the chunk wouldn't pass the CPI limits
+    fn size_with(
+        &self,
+        part: &MagicIntentBundle<'info>,
+    ) -> Result<(IntentBundleReport, HashSet<compat::Pubkey>), EphemeralRollupsSdkError> {
+        let mut accounts = vec![];
+        part.collect_accounts(&mut accounts);
+        let new_keys: HashSet<_> = accounts
+            .iter()
+            .map(|account| *account.key)
+            .filter(|key| !self.keys.contains(key))
+            .collect();
+        let report = IntentBundleReport {
+            accounts: self.report.accounts + new_keys.len(),
+            data_len: self.report.data_len + part.encoded_len()
+                - EMPTY_INTENT_BUNDLE_LEN
+                - self.bundle.shared_len(part),
+            actions: self.report.actions + part.actions_len(),
+        };
+        Ok((report.check()?, new_keys))
+    }
+
+    /// Merges `part` into the chunk, with the size computed by [`Self::size_with`]
+    fn merge(
+        &mut self,
+        part: MagicIntentBundle<'info>,
+        (report, new_keys): (IntentBundleReport, HashSet<compat::Pubkey>),
+    ) {
+        self.report = report;
+        self.keys.extend(new_keys);
+        self.bundle.merge(part);
     }
 }
 
@@ -288,11 +607,15 @@ impl<'info> MagicIntentBundleBuilder<'info> {
 /// they will be undelegated
 ///
 /// Intents assumed to be independent and self-sufficient,
-/// hence order in which they were inserted doesn't matter
-#[derive(Default)]
+/// hence order in which they were inserted doesn't matter.
+/// Standalone actions are executed in order, see [`ActionSequence`]
+#[derive(Default, Clone)]
 struct MagicIntentBundle<'info> {
     standalone_actions: Vec<CallHandler<'info>>,
     standalone_callbacks: Vec<Option<ActionCallback>>, // parallel to standalone_actions
+    /// Whether the action has to be scheduled along with the previous one, i.e. belongs to
+    /// the same [`ActionSequence`]. Parallel to standalone_actions
+    standalone_links: Vec<bool>,
     commit_intent: Option<CommitType<'info>>,
     commit_and_undelegate_intent: Option<CommitAndUndelegate<'info>>,
     commit_finalize_intent: Option<CommitType<'info>>,
@@ -306,6 +629,8 @@ impl<'info> MagicIntentBundle<'info> {
             MagicIntent::StandaloneActions(value) => {
                 self.standalone_callbacks
                     .extend((0..value.len()).map(|_| None));
+                self.standalone_links
+                    .extend((0..value.len()).map(|_| false));
                 self.standalone_actions.extend(value);
             }
             MagicIntent::Commit(value) => {
@@ -369,29 +694,246 @@ impl<'info> MagicIntentBundle<'info> {
         out
     }
 
+    /// Length of the encoded `ScheduleIntentBundle` instruction
+    fn encoded_len(&self) -> usize {
+        let option_len = |len: Option<usize>| len.unwrap_or_default();
+        EMPTY_INTENT_BUNDLE_LEN
+            + option_len(self.commit_intent.as_ref().map(CommitType::encoded_len))
+            + option_len(
+                self.commit_and_undelegate_intent
+                    .as_ref()
+                    .map(CommitAndUndelegate::encoded_len),
+            )
+            + option_len(
+                self.commit_finalize_intent
+                    .as_ref()
+                    .map(CommitType::encoded_len),
+            )
+            + option_len(
+                self.commit_finalize_and_undelegate_intent
+                    .as_ref()
+                    .map(CommitAndUndelegate::encoded_len),
+            )
+            + self
+                .standalone_actions
+                .iter()
+                .map(CallHandler::encoded_len)
+                .sum::<usize>()
+    }
+
+    /// Length of the encoding saved by [`Self::merge`] of `other` into this bundle, on top of
+    /// the [`EMPTY_INTENT_BUNDLE_LEN`] of `other`
+    fn shared_len(&self, other: &Self) -> usize {
+        fn shared<T>(a: &Option<T>, b: &Option<T>, len: impl Fn(&T, &T) -> usize) -> usize {
+            match (a, b) {
+                (Some(a), Some(b)) => len(a, b),
+                _ => 0,
+            }
+        }
+        shared(
+            &self.commit_intent,
+            &other.commit_intent,
+            CommitType::shared_len,
+        ) + shared(
+            &self.commit_and_undelegate_intent,
+            &other.commit_and_undelegate_intent,
+            CommitAndUndelegate::shared_len,
+        ) + shared(
+            &self.commit_finalize_intent,
+            &other.commit_finalize_intent,
+            CommitType::shared_len,
+        ) + shared(
+            &self.commit_finalize_and_undelegate_intent,
+            &other.commit_finalize_and_undelegate_intent,
+            CommitAndUndelegate::shared_len,
+        )
+    }
+
+    /// Callbacks of all the actions of the bundle, in the order of [`Self::extract_callbacks`]
+    fn callbacks(&self) -> Vec<&ActionCallback> {
+        let mut callbacks = vec![];
+        if let Some(commit) = &self.commit_intent {
+            callbacks.extend(commit.callbacks());
+        }
+        if let Some(cau) = &self.commit_and_undelegate_intent {
+            callbacks.extend(cau.callbacks());
+        }
+        if let Some(commit_finalize) = &self.commit_finalize_intent {
+            callbacks.extend(commit_finalize.callbacks());
+        }
+        if let Some(cau_finalize) = &self.commit_finalize_and_undelegate_intent {
+            callbacks.extend(cau_finalize.callbacks());
+        }
+        callbacks.extend(
+            self.standalone_callbacks
+                .iter()
+                .take(self.standalone_actions.len())
+                .flatten(),
+        );
+        callbacks
+    }
+
+    fn is_empty(&self) -> bool {
+        self.standalone_actions.is_empty()
+            && self.commit_intent.is_none()
+            && self.commit_and_undelegate_intent.is_none()
+            && self.commit_finalize_intent.is_none()
+            && self.commit_finalize_and_undelegate_intent.is_none()
+    }
+
+    /// Number of actions of all the intents
+    fn actions_len(&self) -> usize {
+        self.standalone_actions.len()
+            + self
+                .commit_intent
+                .iter()
+                .chain(&self.commit_finalize_intent)
+                .map(CommitType::actions_len)
+                .sum::<usize>()
+            + self
+                .commit_and_undelegate_intent
+                .iter()
+                .chain(&self.commit_finalize_and_undelegate_intent)
+                .map(CommitAndUndelegate::actions_len)
+                .sum::<usize>()
+    }
+
+    /// Merges the intents and standalone actions of `other` into this bundle
+    fn merge(&mut self, other: Self) {
+        let Self {
+            standalone_actions,
+            standalone_callbacks,
+            standalone_links,
+            commit_intent,
+            commit_and_undelegate_intent,
+            commit_finalize_intent,
+            commit_finalize_and_undelegate_intent,
+        } = other;
+        self.standalone_actions.extend(standalone_actions);
+        self.standalone_callbacks.extend(standalone_callbacks);
+        self.standalone_links.extend(standalone_links);
+        let intents = [
+            commit_intent.map(MagicIntent::Commit),
+            commit_and_undelegate_intent.map(MagicIntent::CommitAndUndelegate),
+            commit_finalize_intent.map(MagicIntent::CommitFinalize),
+            commit_finalize_and_undelegate_intent.map(MagicIntent::CommitFinalizeAndUndelegate),
+        ];
+        intents
+            .into_iter()
+            .flatten()
+            .for_each(|intent| self.add_intent(intent));
+    }
+
+    /// Splits the normalized bundle into the smallest bundles that can be scheduled
+    /// independently: one per account of the intents without actions, one per intent with
+    /// actions and one per standalone action, or per [`ActionSequence`].
+    fn split(self) -> Vec<Self> {
+        let Self {
+            standalone_actions,
+            standalone_callbacks,
+            standalone_links,
+            commit_intent,
+            commit_and_undelegate_intent,
+            commit_finalize_intent,
+            commit_finalize_and_undelegate_intent,
+        } = self;
+        let mut intents = vec![];
+        for (commit, finalize) in [(commit_intent, false), (commit_finalize_intent, true)] {
+            let intent = |value| match finalize {
+                false => MagicIntent::Commit(value),
+                true => MagicIntent::CommitFinalize(value),
+            };
+            match commit {
+                Some(CommitType::Standalone(accounts)) => intents.extend(
+                    accounts
+                        .into_iter()
+                        .map(|account| intent(CommitType::Standalone(vec![account]))),
+                ),
+                Some(value) => intents.push(intent(value)),
+                None => {}
+            }
+        }
+        for (cau, finalize) in [
+            (commit_and_undelegate_intent, false),
+            (commit_finalize_and_undelegate_intent, true),
+        ] {
+            let intent = |value| match finalize {
+                false => MagicIntent::CommitAndUndelegate(value),
+                true => MagicIntent::CommitFinalizeAndUndelegate(value),
+            };
+            match cau {
+                Some(CommitAndUndelegate {
+                    commit_type: CommitType::Standalone(accounts),
+                    undelegate_type: UndelegateType::Standalone,
+                }) => intents.extend(accounts.into_iter().map(|account| {
+                    intent(CommitAndUndelegate {
+                        commit_type: CommitType::Standalone(vec![account]),
+                        undelegate_type: UndelegateType::Standalone,
+                    })
+                })),
+                Some(value) => intents.push(intent(value)),
+                None => {}
+            }
+        }
+
+        let mut parts: Vec<Self> = intents
+            .into_iter()
+            .map(|intent| {
+                let mut part = Self::default();
+                part.add_intent(intent);
+                part
+            })
+            .collect();
+        let mut part_start = parts.len();
+        for ((action, callback), linked) in standalone_actions
+            .into_iter()
+            .zip(standalone_callbacks)
+            .zip(standalone_links)
+        {
+            if !linked || part_start == parts.len() {
+                part_start = parts.len();
+                parts.push(Self::default());
+            }
+            let part = &mut parts[part_start];
+            part.standalone_actions.push(action);
+            part.standalone_callbacks.push(callback);
+            part.standalone_links.push(linked);
+        }
+        parts
+    }
+
     /// Consumes the bundle and encodes it into `MagicIntentBundleArgs` using a `compat::Pubkey -> u8` indices map.
-    fn into_args(self, indices_map: &HashMap<compat::Pubkey, u8>) -> MagicIntentBundleArgs {
-        MagicIntentBundleArgs {
-            commit: self.commit_intent.map(|c| c.into_args(indices_map)),
+    fn into_args(
+        self,
+        indices_map: &HashMap<compat::Pubkey, u8>,
+    ) -> Result<MagicIntentBundleArgs, EphemeralRollupsSdkError> {
+        Ok(MagicIntentBundleArgs {
+            commit: self
+                .commit_intent
+                .map(|c| c.into_args(indices_map))
+                .transpose()?,
 
             commit_and_undelegate: self
                 .commit_and_undelegate_intent
-                .map(|c| c.into_args(indices_map)),
+                .map(|c| c.into_args(indices_map))
+                .transpose()?,
 
             commit_finalize: self
                 .commit_finalize_intent
-                .map(|c| c.into_args(indices_map)),
+                .map(|c| c.into_args(indices_map))
+                .transpose()?,
 
             commit_finalize_and_undelegate: self
                 .commit_finalize_and_undelegate_intent
-                .map(|c| c.into_args(indices_map)),
+                .map(|c| c.into_args(indices_map))
+                .transpose()?,
 
             standalone_actions: self
                 .standalone_actions
                 .into_iter()
                 .map(|ch| ch.into_args(indices_map))
-                .collect::<Vec<_>>(),
-        }
+                .collect::<Result<Vec<_>, _>>()?,
+        })
     }
 
     /// Collects all accounts referenced by intents in this bundle.
@@ -505,10 +1047,21 @@ pub trait FoldableIntentBuilder<'info>: Sized {
         self.fold_builder().add_standalone_action(action)
     }
 
+    fn add_action_sequence(
+        self,
+        sequence: ActionSequence<'info>,
+    ) -> MagicIntentBundleBuilder<'info> {
+        self.fold_builder().add_action_sequence(sequence)
+    }
+
     fn build(self) -> IntentInstructions<'info> {
         self.fold_builder().build()
     }
 
+    fn try_build(self) -> Result<IntentInstructions<'info>, EphemeralRollupsSdkError> {
+        self.fold_builder().try_build()
+    }
+
     fn build_and_invoke(self) -> compat::ProgramResult {
         self.fold_builder().build_and_invoke()
     }
@@ -516,19 +1069,40 @@ pub trait FoldableIntentBuilder<'info>: Sized {
     fn build_and_invoke_signed(self, signers_seeds: &[&[&[u8]]]) -> compat::ProgramResult {
         self.fold_builder().build_and_invoke_signed(signers_seeds)
     }
+
+    /// See [`MagicIntentBundleBuilder::estimated_compute_units`]
+    fn estimated_compute_units(&self) -> Result<u64, EphemeralRollupsSdkError>
+    where
+        Self: Clone,
+    {
+        self.clone().fold_builder().estimated_compute_units()
+    }
+
+    /// See [`MagicIntentBundleBuilder::validate`]
+    fn validate(&self) -> Result<IntentBundleReport, EphemeralRollupsSdkError>
+    where
+        Self: Clone,
+    {
+        self.clone().fold_builder().validate()
+    }
+
+    /// See [`MagicIntentBundleBuilder::build_chunked`]
+    fn build_chunked(self) -> Result<Vec<IntentInstructions<'info>>, EphemeralRollupsSdkError> {
+        self.fold_builder().build_chunked()
+    }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use crate::compat;
-    use magicblock_magic_program_api::args::ActionArgs;
+    use magicblock_magic_program_api::args::{ActionArgs, ShortAccountMeta};
     use magicblock_magic_program_api::instruction::MagicBlockInstruction;
     use std::cell::RefCell;
     use std::rc::Rc;
 
-    /// Helper to create a mock compat::AccountInfo for testing
-    fn create_mock_account_info<'a>(
+    /// Helper to create a mock compat::AccountInfo for testing, shared by the tests of the crate
+    pub(crate) fn create_mock_account_info<'a>(
         key: &'a compat::Pubkey,
         lamports: &'a mut u64,
         data: &'a mut [u8],
@@ -1559,4 +2133,841 @@ mod tests {
         );
         assert_eq!(action_index(&add_callback_ixs[3].1), 3, "standalone action");
     }
+
+    #[test]
+    fn test_decode_intent_instructions() {
+        let owner = compat::Pubkey::new_unique();
+        let mut payer = TestAccount::new();
+        let mut magic_ctx = TestAccount::new();
+        let mut magic_prog = TestAccount::new();
+        let mut commit_acc = TestAccount::new();
+        let mut cau_acc = TestAccount::new();
+        let mut escrow_commit = TestAccount::new();
+        let mut escrow_undelegate = TestAccount::new();
+        let keys = [
+            payer.key,
+            magic_ctx.key,
+            commit_acc.key,
+            cau_acc.key,
+            escrow_commit.key,
+            escrow_undelegate.key,
+        ];
+
+        let (builder, _) = create_test_builder(&mut payer, &mut magic_ctx, &mut magic_prog, &owner);
+        let callback = ActionCallback {
+            destination_program: compat::Pubkey::new_unique(),
+            discriminator: vec![7u8; 8],
+            payload: vec![1, 2],
+            compute_units: 10_000,
+            accounts: vec![],
+        };
+
+        let instructions = builder
+            .commit(&[make_info(&mut commit_acc)])
+            .add_post_commit_action(create_test_call_handler(make_info(&mut escrow_commit)))
+            .commit_and_undelegate(&[make_info(&mut cau_acc)])
+            .add_post_undelegate_action(create_test_call_handler(make_info(&mut escrow_undelegate)))
+            .then(callback.clone())
+            .build();
+
+        let decoded = decode_intent_instructions(&instructions).unwrap();
+        // callbacks are only carried by the AddActionCallback instructions
+        let bundle = decode_intent_bundle(&instructions.schedule_intent_ix.1).unwrap();
+        assert!(bundle
+            .actions()
+            .iter()
+            .all(|action| action.callback.is_none()));
+        assert_eq!(decoded.accounts[0].pubkey, keys[0]);
+        assert_eq!(decoded.accounts[1].pubkey, keys[1]);
+
+        let commit = decoded.commit.as_ref().unwrap();
+        assert_eq!(commit.committed_accounts, vec![keys[2]]);
+        assert_eq!(commit.actions.len(), 1);
+        assert_eq!(commit.actions[0].escrow_authority, keys[4]);
+        assert_eq!(commit.actions[0].data, vec![1, 2, 3]);
+        assert!(commit.actions[0].callback.is_none());
+
+        let cau = decoded.commit_and_undelegate.as_ref().unwrap();
+        assert_eq!(cau.commit.committed_accounts, vec![keys[3]]);
+        assert!(cau.commit.actions.is_empty());
+        assert_eq!(cau.undelegate_actions[0].escrow_authority, keys[5]);
+        let decoded_callback = cau.undelegate_actions[0].callback.as_ref().unwrap();
+        assert_eq!(
+            decoded_callback.destination_program,
+            callback.destination_program
+        );
+        assert_eq!(decoded_callback.discriminator, callback.discriminator);
+        assert_eq!(decoded_callback.payload, callback.payload);
+        assert_eq!(decoded.actions().len(), 2);
+        assert!(decoded.standalone_actions.is_empty());
+
+        // the callback instruction isn't an intent bundle
+        assert_eq!(
+            decode_intent_bundle(&instructions.add_callback_ixs[0].1),
+            Err(EphemeralRollupsSdkError::InvalidIntentData)
+        );
+    }
+
+    #[test]
+    fn test_try_build_fails_on_too_many_accounts() {
+        let owner = compat::Pubkey::new_unique();
+        let mut payer = TestAccount::new();
+        let mut magic_ctx = TestAccount::new();
+        let mut magic_prog = TestAccount::new();
+        // payer + magic_ctx + 255 committed accounts exceed the u8 indices
+        let mut committed: Vec<TestAccount> = (0..255).map(|_| TestAccount::new()).collect();
+
+        let (builder, _) = create_test_builder(&mut payer, &mut magic_ctx, &mut magic_prog, &owner);
+        let infos: Vec<_> = committed.iter_mut().map(make_info).collect();
+
+        let result = builder.commit(&infos).try_build();
+        assert_eq!(
+            result.err(),
+            Some(EphemeralRollupsSdkError::TooManyAccounts)
+        );
+    }
+
+    #[test]
+    fn test_validate_reports_limits() {
+        let owner = compat::Pubkey::new_unique();
+        let mut payer = TestAccount::new();
+        let mut magic_ctx = TestAccount::new();
+        let mut magic_prog = TestAccount::new();
+        let mut committed = TestAccount::new();
+        let mut escrow = TestAccount::new();
+
+        let (builder, _) = create_test_builder(&mut payer, &mut magic_ctx, &mut magic_prog, &owner);
+        let builder = builder
+            .commit(&[make_info(&mut committed)])
+            .add_post_commit_action(create_test_call_handler(make_info(&mut escrow)))
+            .fold_builder();
+        let report = builder.validate().unwrap();
+        let ix = builder.build().schedule_intent_ix.1;
+        assert_eq!(
+            report,
+            IntentBundleReport {
+                accounts: 4,
+                data_len: ix.data.len(),
+                actions: 1,
+            }
+        );
+
+        // the callbacks can't index more actions
+        let mut payer = TestAccount::new();
+        let mut magic_ctx = TestAccount::new();
+        let mut magic_prog = TestAccount::new();
+        let mut escrow = TestAccount::new();
+        let (builder, _) = create_test_builder(&mut payer, &mut magic_ctx, &mut magic_prog, &owner);
+        let escrow = make_info(&mut escrow);
+        let builder = builder.add_standalone_actions(
+            (0..=MAX_INTENT_BUNDLE_ACTIONS).map(|_| create_test_call_handler(escrow.clone())),
+        );
+        assert_eq!(
+            builder.validate(),
+            Err(EphemeralRollupsSdkError::TooManyActions)
+        );
+        assert_eq!(
+            builder.try_build().err(),
+            Some(EphemeralRollupsSdkError::TooManyActions)
+        );
+
+        // a single action over the instruction data limit can't be split
+        let mut payer = TestAccount::new();
+        let mut magic_ctx = TestAccount::new();
+        let mut magic_prog = TestAccount::new();
+        let mut escrow = TestAccount::new();
+        let (builder, _) = create_test_builder(&mut payer, &mut magic_ctx, &mut magic_prog, &owner);
+        let mut handler = create_test_call_handler(make_info(&mut escrow));
+        handler.args = ActionArgs::new(vec![0; MAX_INTENT_BUNDLE_DATA_LEN]);
+        let builder = builder.add_standalone_actions([handler]);
+        assert_eq!(
+            builder.validate(),
+            Err(EphemeralRollupsSdkError::IntentDataTooLarge)
+        );
+        assert_eq!(
+            builder.build_chunked().err(),
+            Some(EphemeralRollupsSdkError::IntentDataTooLarge)
+        );
+    }
+
+    #[test]
+    fn test_build_chunked() {
+        let owner = compat::Pubkey::new_unique();
+        let mut payer = TestAccount::new();
+        let mut magic_ctx = TestAccount::new();
+        let mut magic_prog = TestAccount::new();
+        let mut committed: Vec<TestAccount> = (0..200).map(|_| TestAccount::new()).collect();
+        let mut undelegated: Vec<TestAccount> = (0..60).map(|_| TestAccount::new()).collect();
+        let mut escrow = TestAccount::new();
+        let committed_keys: Vec<_> = committed.iter().map(|acc| acc.key).collect();
+        let undelegated_keys: Vec<_> = undelegated.iter().map(|acc| acc.key).collect();
+
+        let (builder, _) = create_test_builder(&mut payer, &mut magic_ctx, &mut magic_prog, &owner);
+        let committed: Vec<_> = committed.iter_mut().map(make_info).collect();
+        let undelegated: Vec<_> = undelegated.iter_mut().map(make_info).collect();
+        let escrow = make_info(&mut escrow);
+        let callback = ActionCallback {
+            destination_program: compat::Pubkey::new_unique(),
+            discriminator: vec![0u8; 8],
+            payload: vec![],
+            compute_units: 10_000,
+            accounts: vec![],
+        };
+        let builder = builder
+            .commit(&committed)
+            .commit_and_undelegate(&undelegated)
+            .add_standalone_actions((0..3).map(|_| create_test_call_handler(escrow.clone())))
+            .add_standalone_action(create_test_call_handler(escrow.clone()))
+            .then(callback)
+            .fold_builder();
+        assert_eq!(
+            builder.validate(),
+            Err(EphemeralRollupsSdkError::TooManyAccounts)
+        );
+
+        let chunks = builder.build_chunked().unwrap();
+        assert!(chunks.len() > 1);
+        let mut commits = vec![];
+        let mut undelegations = vec![];
+        let mut actions = 0;
+        let mut callbacks = 0;
+        for chunk in &chunks {
+            let (_, ix) = &chunk.schedule_intent_ix;
+            assert!(ix.accounts.len() <= MAX_INTENT_BUNDLE_ACCOUNTS);
+            let decoded = decode_intent_instructions(chunk).unwrap();
+            if let Some(commit) = decoded.commit {
+                commits.extend(commit.committed_accounts);
+            }
+            if let Some(cau) = decoded.commit_and_undelegate {
+                undelegations.extend(cau.commit.committed_accounts);
+            }
+            actions += decoded.standalone_actions.len();
+            callbacks += decoded
+                .standalone_actions
+                .iter()
+                .filter(|action| action.callback.is_some())
+                .count();
+        }
+        assert_eq!(commits, committed_keys);
+        assert_eq!(undelegations, undelegated_keys);
+        assert_eq!((actions, callbacks), (4, 1));
+
+        // a bundle within the limits isn't split
+        let mut payer = TestAccount::new();
+        let mut magic_ctx = TestAccount::new();
+        let mut magic_prog = TestAccount::new();
+        let (builder, _) = create_test_builder(&mut payer, &mut magic_ctx, &mut magic_prog, &owner);
+        let chunks = builder.commit(&committed[..10]).build_chunked().unwrap();
+        assert_eq!(chunks.len(), 1);
+    }
+
+    #[test]
+    fn test_chunk_accounts_the_merged_size() {
+        let mut committed: Vec<TestAccount> = (0..6).map(|_| TestAccount::new()).collect();
+        let mut escrow = TestAccount::new();
+        let committed: Vec<_> = committed.iter_mut().map(make_info).collect();
+        let escrow = make_info(&mut escrow);
+        let actions = || vec![create_test_call_handler(escrow.clone())];
+        let commit = |accounts: std::ops::Range<usize>, with_actions: bool| match with_actions {
+            false => CommitType::Standalone(committed[accounts].to_vec()),
+            true => CommitType::WithHandler {
+                commited_accounts: committed[accounts].to_vec(),
+                call_handlers: actions(),
+                callbacks: vec![None],
+            },
+        };
+        let undelegate = |with_actions: bool| match with_actions {
+            false => UndelegateType::Standalone,
+            true => UndelegateType::WithHandler {
+                call_handlers: actions(),
+                callbacks: vec![None],
+            },
+        };
+        let part = |intent| {
+            let mut part = MagicIntentBundle::default();
+            part.add_intent(intent);
+            part
+        };
+
+        let mut chunk = Chunk::new(HashSet::from([compat::Pubkey::new_unique()]));
+        let parts = [
+            part(MagicIntent::Commit(commit(0..1, false))),
+            part(MagicIntent::Commit(commit(1..2, true))),
+            part(MagicIntent::Commit(commit(2..3, true))),
+            part(MagicIntent::Commit(commit(2..3, false))),
+            part(MagicIntent::CommitFinalizeAndUndelegate(
+                CommitAndUndelegate {
+                    commit_type: commit(3..4, false),
+                    undelegate_type: undelegate(false),
+                },
+            )),
+            part(MagicIntent::CommitFinalizeAndUndelegate(
+                CommitAndUndelegate {
+                    commit_type: commit(4..5, true),
+                    undelegate_type: undelegate(true),
+                },
+            )),
+            part(MagicIntent::CommitFinalizeAndUndelegate(
+                CommitAndUndelegate {
+                    commit_type: commit(5..6, false),
+                    undelegate_type: undelegate(true),
+                },
+            )),
+            part(MagicIntent::StandaloneActions(actions())),
+        ];
+        for part in parts {
+            let size = chunk.size_with(&part).unwrap();
+            chunk.merge(part, size);
+            let mut accounts = vec![];
+            chunk.bundle.collect_accounts(&mut accounts);
+            assert_eq!(chunk.report.data_len, chunk.bundle.encoded_len());
+            assert_eq!(chunk.report.actions, chunk.bundle.actions_len());
+            assert_eq!(
+                chunk.report.accounts,
+                1 + accounts
+                    .iter()
+                    .map(|account| account.key)
+                    .collect::<HashSet<_>>()
+                    .len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_action_sequence() {
+        let owner = compat::Pubkey::new_unique();
+        let mut payer = TestAccount::new();
+        let mut magic_ctx = TestAccount::new();
+        let mut magic_prog = TestAccount::new();
+        let mut committed: Vec<TestAccount> = (0..200).map(|_| TestAccount::new()).collect();
+        let mut escrow = TestAccount::new();
+        let committed: Vec<_> = committed.iter_mut().map(make_info).collect();
+        let escrow = make_info(&mut escrow);
+        let action = |tag: u8| {
+            let mut handler = create_test_call_handler(escrow.clone());
+            handler.args = ActionArgs::new(vec![tag]);
+            handler
+        };
+        let callback = ActionCallback {
+            destination_program: compat::Pubkey::new_unique(),
+            discriminator: vec![0u8; 8],
+            payload: vec![],
+            compute_units: 10_000,
+            accounts: vec![],
+        };
+        let tags = |actions: &[DecodedAction]| -> Vec<u8> {
+            actions.iter().map(|action| action.data[0]).collect()
+        };
+
+        // actions are executed in the order of the sequence, after the previous ones
+        let (builder, _) = create_test_builder(&mut payer, &mut magic_ctx, &mut magic_prog, &owner);
+        let instructions = builder
+            .add_standalone_actions([action(0)])
+            .add_action_sequence(
+                ActionSequence::new()
+                    .then_action(action(1))
+                    .then_action_with_callback(action(2), callback.clone())
+                    .then_action(action(3)),
+            )
+            .build();
+        let decoded = decode_intent_instructions(&instructions).unwrap();
+        assert_eq!(tags(&decoded.standalone_actions), [0, 1, 2, 3]);
+        assert_eq!(
+            decoded
+                .standalone_actions
+                .iter()
+                .map(|action| action.callback.is_some())
+                .collect::<Vec<_>>(),
+            [false, false, true, false]
+        );
+
+        // a sequence is encoded as the same actions in order, without any failure policy
+        let mut payer = TestAccount::new();
+        let mut magic_ctx = TestAccount::new();
+        let mut magic_prog = TestAccount::new();
+        let (builder, _) = create_test_builder(&mut payer, &mut magic_ctx, &mut magic_prog, &owner);
+        let sequence = |tags: std::ops::Range<u8>| {
+            tags.fold(ActionSequence::new(), |sequence, tag| {
+                sequence.then_action(action(tag))
+            })
+        };
+        let actions: Vec<_> = (0..4).map(action).collect();
+        let of_sequence = builder
+            .clone()
+            .add_action_sequence(
+                actions
+                    .iter()
+                    .fold(ActionSequence::new(), |sequence, action| {
+                        sequence.then_action(action.clone())
+                    }),
+            )
+            .build();
+        let of_actions = builder.clone().add_standalone_actions(actions).build();
+        assert_eq!(
+            of_sequence.schedule_intent_ix.1.data,
+            of_actions.schedule_intent_ix.1.data
+        );
+
+        // standalone actions are split between the instructions, a sequence is never split
+        let chunks = builder
+            .clone()
+            .commit(&committed)
+            .add_standalone_actions((0..4).map(action))
+            .add_action_sequence(sequence(4..9))
+            .build_chunked()
+            .unwrap();
+        assert!(chunks.len() > 1);
+        let chunk_tags: Vec<_> = chunks
+            .iter()
+            .map(|chunk| {
+                tags(
+                    &decode_intent_instructions(chunk)
+                        .unwrap()
+                        .standalone_actions,
+                )
+            })
+            .filter(|tags| !tags.is_empty())
+            .collect();
+        assert_eq!(chunk_tags.concat(), (0..9).collect::<Vec<_>>());
+        assert!(chunk_tags
+            .iter()
+            .any(|tags| tags.ends_with(&[4, 5, 6, 7, 8])));
+
+        let too_many = (0..=MAX_INTENT_BUNDLE_ACTIONS as u8).map(action);
+        let chunks = builder
+            .clone()
+            .add_standalone_actions(too_many.clone())
+            .build_chunked()
+            .unwrap();
+        assert!(chunks.len() > 1);
+        assert_eq!(
+            builder
+                .add_action_sequence(sequence(0..MAX_INTENT_BUNDLE_ACTIONS as u8 + 1))
+                .build_chunked()
+                .err(),
+            Some(EphemeralRollupsSdkError::TooManyActions)
+        );
+    }
+
+    #[test]
+    fn test_action_sequence_split_keeps_order_and_callbacks() {
+        let owner = compat::Pubkey::new_unique();
+        let mut payer = TestAccount::new();
+        let mut magic_ctx = TestAccount::new();
+        let mut magic_prog = TestAccount::new();
+        let mut committed: Vec<TestAccount> = (0..200).map(|_| TestAccount::new()).collect();
+        let mut escrow = TestAccount::new();
+        let committed: Vec<_> = committed.iter_mut().map(make_info).collect();
+        let escrow = make_info(&mut escrow);
+        let action = |tag: u8| {
+            let mut handler = create_test_call_handler(escrow.clone());
+            handler.args = ActionArgs::new(vec![tag]);
+            handler
+        };
+        // the payload of the callback tells the action it's attached to
+        let callback = |tag: u8| ActionCallback {
+            destination_program: compat::Pubkey::new_unique(),
+            discriminator: vec![0u8; 8],
+            payload: vec![tag],
+            compute_units: 10_000,
+            accounts: vec![],
+        };
+
+        let (builder, _) = create_test_builder(&mut payer, &mut magic_ctx, &mut magic_prog, &owner);
+        let builder = builder
+            .add_standalone_actions([action(0), action(1)])
+            .add_action_sequence(
+                ActionSequence::new()
+                    .then_action(action(2))
+                    .then_action_with_callback(action(3), callback(3))
+                    .then_action_with_callback(action(4), callback(4)),
+            )
+            .add_standalone_action(action(5))
+            .then(callback(5))
+            .fold_builder();
+
+        // the sequence is a single part, in order, along with the callbacks of its actions
+        let mut bundle = builder.intent_bundle.clone();
+        bundle.normalize();
+        let parts: Vec<_> = bundle
+            .split()
+            .into_iter()
+            .map(|mut part| {
+                let tags: Vec<u8> = part
+                    .standalone_actions
+                    .iter()
+                    .map(|action| action.args.data[0])
+                    .collect();
+                let callbacks: Vec<(u8, u8)> = part
+                    .extract_callbacks()
+                    .into_iter()
+                    .map(|(index, callback)| (index, callback.payload[0]))
+                    .collect();
+                (tags, callbacks)
+            })
+            .collect();
+        assert_eq!(
+            parts,
+            [
+                (vec![0], vec![]),
+                (vec![1], vec![]),
+                (vec![2, 3, 4], vec![(1, 3), (2, 4)]),
+                (vec![5], vec![(0, 5)]),
+            ]
+        );
+
+        // each built instruction attaches the callbacks to the same actions
+        let chunks = builder.commit(&committed).build_chunked().unwrap();
+        assert!(chunks.len() > 1);
+        let chunk_actions: Vec<Vec<(u8, Option<u8>)>> = chunks
+            .iter()
+            .map(|chunk| {
+                decode_intent_instructions(chunk)
+                    .unwrap()
+                    .standalone_actions
+                    .iter()
+                    .map(|action| {
+                        let callback = action.callback.as_ref();
+                        (action.data[0], callback.map(|callback| callback.payload[0]))
+                    })
+                    .collect()
+            })
+            .filter(|actions: &Vec<_>| !actions.is_empty())
+            .collect();
+        assert_eq!(
+            chunk_actions.concat(),
+            [
+                (0, None),
+                (1, None),
+                (2, None),
+                (3, Some(3)),
+                (4, Some(4)),
+                (5, Some(5)),
+            ]
+        );
+        assert!(chunk_actions.iter().any(|actions| actions
+            .windows(3)
+            .any(|window| window.iter().map(|(tag, _)| *tag).eq([2, 3, 4]))));
+    }
+
+    #[test]
+    fn test_estimated_compute_units() {
+        let owner = compat::Pubkey::new_unique();
+        let mut payer = TestAccount::new();
+        let mut magic_ctx = TestAccount::new();
+        let mut magic_prog = TestAccount::new();
+        let mut committed: Vec<TestAccount> = (0..3).map(|_| TestAccount::new()).collect();
+        let mut escrow = TestAccount::new();
+
+        let (builder, _) = create_test_builder(&mut payer, &mut magic_ctx, &mut magic_prog, &owner);
+        let infos: Vec<_> = committed.iter_mut().map(make_info).collect();
+
+        let one = builder.clone().commit(&infos[..1]);
+        let three = builder.clone().commit(&infos);
+        let one_units = one.estimated_compute_units().unwrap();
+        assert_eq!(
+            one_units,
+            one.clone().build().estimated_compute_units(),
+            "the estimate matches the built instructions"
+        );
+        // every committed account adds up
+        assert_eq!(
+            three.estimated_compute_units().unwrap() - one_units,
+            2 * cu::INTENT_ACCOUNT_UNITS
+        );
+
+        let callback = ActionCallback {
+            destination_program: compat::Pubkey::new_unique(),
+            discriminator: vec![0u8; 8],
+            payload: vec![],
+            compute_units: 10_000,
+            accounts: vec![],
+        };
+        let with_callback = one
+            .add_post_commit_action(create_test_call_handler(make_info(&mut escrow)))
+            .then(callback)
+            .fold_builder();
+        let with_callback_units = with_callback.estimated_compute_units().unwrap();
+        assert_eq!(
+            with_callback_units,
+            with_callback.build().estimated_compute_units()
+        );
+        assert!(
+            with_callback_units
+                > one_units + cu::INTENT_ACCOUNT_UNITS + cu::ADD_ACTION_CALLBACK_UNITS
+        );
+
+        // bundles which can't be encoded can't be estimated either
+        let mut too_many: Vec<TestAccount> = (0..255).map(|_| TestAccount::new()).collect();
+        let infos: Vec<_> = too_many.iter_mut().map(make_info).collect();
+        assert_eq!(
+            builder.commit(&infos).estimated_compute_units(),
+            Err(EphemeralRollupsSdkError::TooManyAccounts)
+        );
+    }
+
+    #[test]
+    fn test_estimate_matches_every_intent() {
+        let owner = compat::Pubkey::new_unique();
+        let mut payer = TestAccount::new();
+        let mut magic_ctx = TestAccount::new();
+        let mut magic_prog = TestAccount::new();
+        let mut vault = TestAccount::new();
+        let mut committed: Vec<TestAccount> = (0..4).map(|_| TestAccount::new()).collect();
+        let mut escrow = TestAccount::new();
+
+        let (builder, _) = create_test_builder(&mut payer, &mut magic_ctx, &mut magic_prog, &owner);
+        let escrow = make_info(&mut escrow);
+        let infos: Vec<_> = committed.iter_mut().map(make_info).collect();
+        let action = || CallHandler {
+            accounts: vec![ShortAccountMeta::default(); 3],
+            ..create_test_call_handler(escrow.clone())
+        };
+        let callback = || ActionCallback {
+            destination_program: compat::Pubkey::new_unique(),
+            discriminator: vec![1; 8],
+            payload: vec![2; 5],
+            compute_units: 10_000,
+            accounts: vec![ShortAccountMeta::default(); 2],
+        };
+
+        let builder = builder
+            .magic_fee_vault(make_info(&mut vault))
+            .commit(&infos[..1])
+            .add_post_commit_action(action())
+            .then(callback())
+            .commit_and_undelegate(&infos[1..2])
+            .add_post_undelegate_action(action())
+            .then(callback())
+            .fold_builder()
+            .add_intent(MagicIntent::CommitFinalize(CommitType::Standalone(
+                infos[2..3].to_vec(),
+            )))
+            .add_intent(MagicIntent::CommitFinalizeAndUndelegate(
+                CommitAndUndelegate {
+                    commit_type: CommitType::Standalone(infos[3..].to_vec()),
+                    undelegate_type: UndelegateType::Standalone,
+                },
+            ))
+            .add_standalone_action(action())
+            .then(callback())
+            .add_standalone_actions([action()]);
+
+        let units = builder.estimated_compute_units().unwrap();
+        let bundle_len = builder.intent_bundle.encoded_len();
+        let callback_lens: Vec<_> = builder
+            .intent_bundle
+            .callbacks()
+            .into_iter()
+            .map(ActionCallback::encoded_len)
+            .collect();
+        let instructions = builder.build();
+        assert_eq!(units, instructions.estimated_compute_units());
+        assert_eq!(bundle_len, instructions.schedule_intent_ix.1.data.len());
+        assert_eq!(
+            callback_lens,
+            instructions
+                .add_callback_ixs
+                .iter()
+                .map(|(_, ix)| ix.data.len())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_validate_magic_fee_vault() {
+        let validator = compat::Pubkey::new_unique();
+        let delegation_program = crate::cpi::DELEGATION_PROGRAM_ID;
+        let mut payer = TestAccount::new();
+        let mut magic_ctx = TestAccount::new();
+        let mut magic_prog = TestAccount::new();
+        let mut committed = TestAccount::new();
+        let mut vault = TestAccount::with_key(pda::magic_fee_vault_pda_from_validator(&validator));
+        let mut other_vault = TestAccount::new();
+
+        // the owner of the payer doesn't tell whether it's delegated on the ER
+        let (builder, _) = create_test_builder(
+            &mut payer,
+            &mut magic_ctx,
+            &mut magic_prog,
+            &delegation_program,
+        );
+        let builder = builder
+            .validate_magic_fee_vault(validator)
+            .commit(&[make_info(&mut committed)])
+            .fold_builder();
+
+        assert!(builder.clone().try_build().is_ok());
+        // unless the caller tells the payer is delegated
+        let delegated = builder.clone().payer_is_delegated();
+        assert_eq!(
+            delegated.clone().try_build().err(),
+            Some(EphemeralRollupsSdkError::MissingMagicFeeVault)
+        );
+        assert_eq!(
+            delegated.validate().err(),
+            Some(EphemeralRollupsSdkError::MissingMagicFeeVault)
+        );
+        assert_eq!(
+            delegated.clone().build_chunked().err(),
+            Some(EphemeralRollupsSdkError::MissingMagicFeeVault)
+        );
+        let vault_info = make_info(&mut vault);
+        assert!(delegated
+            .magic_fee_vault(vault_info.clone())
+            .try_build()
+            .is_ok());
+        assert_eq!(
+            builder
+                .clone()
+                .magic_fee_vault(make_info(&mut other_vault))
+                .try_build()
+                .err(),
+            Some(EphemeralRollupsSdkError::InvalidMagicFeeVault)
+        );
+        let ixs = builder.magic_fee_vault(vault_info).try_build().unwrap();
+        assert_eq!(
+            ixs.schedule_intent_ix.1.accounts[2].pubkey,
+            pda::magic_fee_vault_pda_from_validator(&validator)
+        );
+    }
+
+    #[test]
+    fn test_schedule_commit_cpi() {
+        let program_id = compat::Pubkey::new_unique();
+        let delegation_program = crate::cpi::DELEGATION_PROGRAM_ID;
+        let mut payer = TestAccount::new();
+        let mut magic_ctx = TestAccount::new();
+        let mut magic_prog = TestAccount::new();
+        let mut vault = TestAccount::new();
+        let mut committed = TestAccount::new();
+        // a delegated payer is owned by its program on the ER, like the committed accounts
+        let payer_info = create_mock_account_info(
+            &payer.key,
+            &mut payer.lamports,
+            &mut payer.data,
+            &program_id,
+            false,
+            true,
+        );
+        let ctx_info = make_info(&mut magic_ctx);
+        let prog_info = make_info(&mut magic_prog);
+        let vault_info = make_info(&mut vault);
+        let committed_key = committed.key;
+        let mut committed_info = create_mock_account_info(
+            &committed.key,
+            &mut committed.lamports,
+            &mut committed.data,
+            &program_id,
+            false,
+            false,
+        );
+
+        // neither the vault nor writable accounts are required to commit
+        let accounts = [committed_info.clone()];
+        let cpi =
+            ScheduleCommitCpi::new(&program_id, &payer_info, &ctx_info, &prog_info, &accounts);
+        assert_eq!(cpi.validate(), Ok(()));
+
+        // the accounts to undelegate must be writable
+        let cpi = cpi.magic_fee_vault(&vault_info).undelegate();
+        assert_eq!(
+            cpi.validate(),
+            Err(EphemeralRollupsSdkError::CommittedAccountNotWritable)
+        );
+        assert_eq!(
+            cpi.invoke(),
+            Err(EphemeralRollupsSdkError::CommittedAccountNotWritable.into())
+        );
+        committed_info.is_writable = true;
+        let accounts = [committed_info.clone()];
+        let cpi =
+            ScheduleCommitCpi::new(&program_id, &payer_info, &ctx_info, &prog_info, &accounts)
+                .magic_fee_vault(&vault_info)
+                .undelegate();
+        assert_eq!(cpi.validate(), Ok(()));
+
+        // the PDA payer signs via invoke_signed
+        let ix = cpi.instruction();
+        assert_eq!(
+            bincode::deserialize::<MagicBlockInstruction>(&ix.data).unwrap(),
+            MagicBlockInstruction::ScheduleCommitAndUndelegate
+        );
+        assert!(ix.accounts[0].is_signer && ix.accounts[0].is_writable);
+        assert_eq!(ix.accounts[2].pubkey, *vault_info.key);
+        assert_eq!(ix.accounts[3].pubkey, committed_key);
+        assert!(ix.accounts[3].is_writable);
+
+        // the committed accounts must be owned by the program invoking the magic program
+        let other_program = compat::Pubkey::new_unique();
+        for owner in [&delegation_program, &other_program] {
+            committed_info.owner = owner;
+            let accounts = [committed_info.clone()];
+            let cpi =
+                ScheduleCommitCpi::new(&program_id, &payer_info, &ctx_info, &prog_info, &accounts);
+            assert_eq!(
+                cpi.validate(),
+                Err(EphemeralRollupsSdkError::CommittedAccountNotDelegated)
+            );
+            assert_eq!(
+                cpi.invoke(),
+                Err(EphemeralRollupsSdkError::CommittedAccountNotDelegated.into())
+            );
+        }
+    }
+
+    #[test]
+    fn test_migrate_to_validator() {
+        use crate::compat::borsh;
+        use crate::cpi::DelegateConfig;
+        use magicblock_magic_program_api::args::UndelegateTypeArgs;
+
+        let owner = compat::Pubkey::new_unique();
+        let validator = compat::Pubkey::new_unique();
+        let mut payer = TestAccount::new();
+        let mut magic_ctx = TestAccount::new();
+        let mut magic_prog = TestAccount::new();
+        let mut account = TestAccount::new();
+
+        let (builder, _) = create_test_builder(&mut payer, &mut magic_ctx, &mut magic_prog, &owner);
+        let config = DelegateConfig {
+            commit_frequency_ms: 1_000,
+            validator: Some(validator),
+        };
+        let migration = ValidatorMigration {
+            owner_program: owner,
+            discriminator: [7; 8],
+            escrow_index: 3,
+            accounts: vec![],
+            config: config.clone(),
+            compute_units: 200_000,
+        };
+        let (accounts, ix) = builder
+            .migrate_to_validator(&make_info(&mut account), migration)
+            .build()
+            .schedule_intent_ix;
+
+        // payer + magic_ctx + migrated account, which is also the escrow authority
+        assert_eq!(accounts.len(), 3);
+        let MagicBlockInstruction::ScheduleIntentBundle(args) =
+            bincode::deserialize::<MagicBlockInstruction>(&ix.data).unwrap()
+        else {
+            panic!("expected ScheduleIntentBundle");
+        };
+        let cau = args.commit_and_undelegate.unwrap();
+        let UndelegateTypeArgs::WithBaseActions { base_actions } = cau.undelegate_type else {
+            panic!("expected post-undelegate action");
+        };
+        assert_eq!(base_actions.len(), 1);
+        let action = &base_actions[0];
+        assert_eq!(action.destination_program.to_bytes(), owner.to_bytes());
+        assert_eq!(action.escrow_authority, 2);
+        assert_eq!(action.args.escrow_index, 3);
+
+        // the arguments are encoded as `(commit_frequency_ms: u32, validator: Option<Pubkey>)`
+        let mut expected = vec![7; 8];
+        expected.extend(borsh::to_vec(&(1_000u32, Some(validator))).unwrap());
+        assert_eq!(action.args.data, expected);
+        assert_eq!(redelegate_action_data(&[7; 8], &config), expected);
+    }
 }
@@ -0,0 +1,113 @@
+use crate::compat::{self, Compat, Modern};
+use crate::ephem::create_schedule_commit_ix;
+use crate::error::EphemeralRollupsSdkError;
+use solana_program::program::invoke_signed;
+
+/// CPI to the `ScheduleCommit` (or `ScheduleCommitAndUndelegate`) instruction of the magic
+/// program, for the cases which don't need an intent bundle.
+///
+/// Unlike [`commit_accounts`](crate::ephem::commit_accounts), the committed accounts are
+/// validated before invoking, see [`Self::validate`], and the payer can be a PDA signing via
+/// [`Self::invoke_signed`].
+///
+/// ```ignore
+/// ScheduleCommitCpi::new(program_id, &payer, &magic_context, &magic_program, &[counter])
+///     .magic_fee_vault(&magic_fee_vault)
+///     .undelegate()
+///     .invoke_signed(&[payer_seeds])?;
+/// ```
+pub struct ScheduleCommitCpi<'a, 'info> {
+    program_id: &'a compat::Pubkey,
+    payer: &'a compat::AccountInfo<'info>,
+    magic_context: &'a compat::AccountInfo<'info>,
+    magic_program: &'a compat::AccountInfo<'info>,
+    accounts: &'a [compat::AccountInfo<'info>],
+    magic_fee_vault: Option<&'a compat::AccountInfo<'info>>,
+    undelegate: bool,
+}
+
+impl<'a, 'info> ScheduleCommitCpi<'a, 'info> {
+    /// `program_id` is the program invoking the magic program, i.e. the owner of the
+    /// committed accounts on the ER
+    pub fn new(
+        program_id: &'a compat::Pubkey,
+        payer: &'a compat::AccountInfo<'info>,
+        magic_context: &'a compat::AccountInfo<'info>,
+        magic_program: &'a compat::AccountInfo<'info>,
+        accounts: &'a [compat::AccountInfo<'info>],
+    ) -> Self {
+        Self {
+            program_id,
+            payer,
+            magic_context,
+            magic_program,
+            accounts,
+            magic_fee_vault: None,
+            undelegate: false,
+        }
+    }
+
+    /// Sets the magic fee vault, required when the payer is delegated, see
+    /// [`verify_magic_fee_vault`](crate::ephem::verify_magic_fee_vault)
+    pub fn magic_fee_vault(mut self, vault: &'a compat::AccountInfo<'info>) -> Self {
+        self.magic_fee_vault = Some(vault);
+        self
+    }
+
+    /// Undelegates the accounts once committed
+    pub fn undelegate(mut self) -> Self {
+        self.undelegate = true;
+        self
+    }
+
+    /// Checks that every committed account is owned by `program_id`, as the magic program
+    /// only commits the accounts of the program invoking it, and that it's writable when
+    /// undelegating, as the magic program rejects read-only accounts to undelegate.
+    ///
+    /// On the ER, a delegated account is owned by its program rather than by the delegation
+    /// program, and the delegation of the payer can't be told, so it's up to the caller to
+    /// pass the magic fee vault for a delegated payer.
+    pub fn validate(&self) -> Result<(), EphemeralRollupsSdkError> {
+        for account in self.accounts {
+            if account.owner != self.program_id {
+                return Err(EphemeralRollupsSdkError::CommittedAccountNotDelegated);
+            }
+            if self.undelegate && !account.is_writable {
+                return Err(EphemeralRollupsSdkError::CommittedAccountNotWritable);
+            }
+        }
+        Ok(())
+    }
+
+    /// Instruction of the CPI, with the payer as signer
+    pub fn instruction(&self) -> compat::Instruction {
+        let accounts: Vec<_> = self.accounts.iter().collect();
+        create_schedule_commit_ix(
+            self.payer,
+            &accounts,
+            self.magic_context,
+            self.magic_program,
+            self.magic_fee_vault,
+            self.undelegate,
+        )
+    }
+
+    pub fn invoke(self) -> compat::ProgramResult {
+        self.invoke_signed(&[])
+    }
+
+    /// Validates the accounts, see [`Self::validate`], then invokes the magic program signed
+    /// with the seeds of the PDA payer
+    pub fn invoke_signed(self, signers_seeds: &[&[&[u8]]]) -> compat::ProgramResult {
+        self.validate()?;
+        let mut account_infos = vec![self.payer.clone(), self.magic_context.clone()];
+        account_infos.extend(self.magic_fee_vault.cloned());
+        account_infos.extend_from_slice(self.accounts);
+        invoke_signed(
+            &self.instruction().modern(),
+            &account_infos.modern(),
+            signers_seeds,
+        )
+        .compat()
+    }
+}
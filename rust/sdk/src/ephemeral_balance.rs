@@ -0,0 +1,341 @@
+//! Instruction builders and CPI helpers for ephemeral balances (escrows).
+//!
+//! An ephemeral balance is a system owned PDA of the delegation program, derived from
+//! the escrow authority and an index, so that a single authority can hold several of
+//! them. Once topped up and delegated, the balance can be spent on the ephemeral
+//! rollup, e.g. to pay for transactions or to fund the escrowed base actions.
+//!
+//! # Signing Requirements
+//!
+//! - **Top up**: only the payer must sign, anyone can top up anyone's escrow.
+//! - **Delegate**: both the payer and the escrow authority must sign.
+//! - **Close**: the escrow authority must sign, and receives the remaining lamports.
+//!
+//! If the escrow authority is a PDA of the calling program, sign on its behalf via
+//! the `invoke_signed` variants of the CPI helpers.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use ephemeral_rollups_sdk::ephemeral_balance::{
+//!     DelegateEphemeralBalanceCpi, TopUpEphemeralBalanceCpi,
+//! };
+//!
+//! TopUpEphemeralBalanceCpi {
+//!     payer: &ctx.payer,
+//!     escrow_authority: &ctx.escrow_authority,
+//!     ephemeral_balance: &ctx.escrow,
+//!     system_program: &ctx.system_program,
+//!     amount: 1_000_000,
+//!     index: 0,
+//! }
+//! .invoke()?;
+//! ```
+
+use dlp_api::{
+    args::{DelegateArgs, DelegateEphemeralBalanceArgs, TopUpEphemeralBalanceArgs},
+    discriminator::DlpDiscriminator,
+    pda::{
+        delegate_buffer_pda_from_delegated_account_and_owner_program,
+        delegation_metadata_pda_from_delegated_account,
+        delegation_record_pda_from_delegated_account, ephemeral_balance_pda_from_payer,
+    },
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program::invoke_signed,
+};
+
+use crate::{
+    compat::{self, borsh, AsModern, Compat, Modern},
+    cpi::DelegateConfig,
+    modernize,
+};
+
+/// The system program ID, the owner of the ephemeral balance accounts.
+const SYSTEM_PROGRAM_ID: compat::Pubkey = compat::Pubkey::new_from_array([0; 32]);
+
+/// Derive the ephemeral balance (escrow) PDA of the given authority at the given index.
+pub fn ephemeral_balance_pda(escrow_authority: &compat::Pubkey, index: u8) -> compat::Pubkey {
+    ephemeral_balance_pda_from_payer(escrow_authority, index)
+}
+
+// ------------------------
+// Off-chain Instructions
+// ------------------------
+
+/// Build an instruction to top up the ephemeral balance of `escrow_authority` at `index`
+/// with `amount` lamports transferred from `payer`. The escrow is created if missing.
+pub fn top_up_ephemeral_balance(
+    payer: compat::Pubkey,
+    escrow_authority: compat::Pubkey,
+    amount: u64,
+    index: u8,
+) -> compat::Instruction {
+    let ephemeral_balance = ephemeral_balance_pda(&escrow_authority, index);
+    modernize!(payer, escrow_authority, ephemeral_balance);
+
+    let mut data = DlpDiscriminator::TopUpEphemeralBalance.to_vec();
+    data.extend(
+        borsh::to_vec(&TopUpEphemeralBalanceArgs { amount, index }).expect("args serialize"),
+    );
+
+    Instruction {
+        program_id: *crate::id().as_modern(),
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(*escrow_authority, false),
+            AccountMeta::new(*ephemeral_balance, false),
+            AccountMeta::new_readonly(*SYSTEM_PROGRAM_ID.as_modern(), false),
+        ],
+        data,
+    }
+    .compat()
+}
+
+/// Build an instruction to delegate the ephemeral balance of `escrow_authority` at `index`,
+/// so that it can be spent on the ephemeral rollup.
+pub fn delegate_ephemeral_balance(
+    payer: compat::Pubkey,
+    escrow_authority: compat::Pubkey,
+    index: u8,
+    config: DelegateConfig,
+) -> compat::Instruction {
+    let ephemeral_balance = ephemeral_balance_pda(&escrow_authority, index);
+    let buffer = delegate_buffer_pda_from_delegated_account_and_owner_program(
+        &ephemeral_balance,
+        &SYSTEM_PROGRAM_ID,
+    );
+    let delegation_record = delegation_record_pda_from_delegated_account(&ephemeral_balance);
+    let delegation_metadata = delegation_metadata_pda_from_delegated_account(&ephemeral_balance);
+    let delegation_program = crate::id();
+
+    let args = DelegateEphemeralBalanceArgs {
+        delegate_args: DelegateArgs {
+            commit_frequency_ms: config.commit_frequency_ms,
+            // the seeds are derived by the delegation program from authority and index
+            seeds: vec![],
+            validator: config.validator,
+        },
+        index,
+    };
+    let mut data = DlpDiscriminator::DelegateEphemeralBalance.to_vec();
+    data.extend(borsh::to_vec(&args).expect("args serialize"));
+
+    modernize!(
+        payer,
+        escrow_authority,
+        ephemeral_balance,
+        buffer,
+        delegation_record,
+        delegation_metadata,
+        delegation_program
+    );
+    Instruction {
+        program_id: *delegation_program,
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(*escrow_authority, true),
+            AccountMeta::new(*ephemeral_balance, false),
+            AccountMeta::new(*buffer, false),
+            AccountMeta::new(*delegation_record, false),
+            AccountMeta::new(*delegation_metadata, false),
+            AccountMeta::new_readonly(*SYSTEM_PROGRAM_ID.as_modern(), false),
+            AccountMeta::new_readonly(*delegation_program, false),
+        ],
+        data,
+    }
+    .compat()
+}
+
+/// Build an instruction to close the (undelegated) ephemeral balance of `escrow_authority`
+/// at `index`, returning its lamports to the authority.
+pub fn close_ephemeral_balance(escrow_authority: compat::Pubkey, index: u8) -> compat::Instruction {
+    let ephemeral_balance = ephemeral_balance_pda(&escrow_authority, index);
+    modernize!(escrow_authority, ephemeral_balance);
+
+    let mut data = DlpDiscriminator::CloseEphemeralBalance.to_vec();
+    data.push(index);
+
+    Instruction {
+        program_id: *crate::id().as_modern(),
+        accounts: vec![
+            AccountMeta::new(*escrow_authority, true),
+            AccountMeta::new(*ephemeral_balance, false),
+            AccountMeta::new_readonly(*SYSTEM_PROGRAM_ID.as_modern(), false),
+        ],
+        data,
+    }
+    .compat()
+}
+
+// ------------------------
+// CPI Helpers
+// ------------------------
+
+/// CPI to the delegation program to top up an ephemeral balance.
+pub struct TopUpEphemeralBalanceCpi<'a, 'info> {
+    pub payer: &'a compat::AccountInfo<'info>,
+    pub escrow_authority: &'a compat::AccountInfo<'info>,
+    pub ephemeral_balance: &'a compat::AccountInfo<'info>,
+    pub system_program: &'a compat::AccountInfo<'info>,
+    pub amount: u64,
+    pub index: u8,
+}
+
+impl<'a, 'info> TopUpEphemeralBalanceCpi<'a, 'info> {
+    pub fn instruction(&self) -> compat::Instruction {
+        top_up_ephemeral_balance(
+            *self.payer.key,
+            *self.escrow_authority.key,
+            self.amount,
+            self.index,
+        )
+    }
+
+    pub fn invoke(&self) -> compat::ProgramResult {
+        self.invoke_signed(&[])
+    }
+
+    pub fn invoke_signed(&self, signers_seeds: &[&[&[u8]]]) -> compat::ProgramResult {
+        let accounts = [
+            self.payer.modern(),
+            self.escrow_authority.modern(),
+            self.ephemeral_balance.modern(),
+            self.system_program.modern(),
+        ];
+        invoke_signed(&self.instruction().modern(), &accounts, signers_seeds).compat()
+    }
+}
+
+/// CPI to the delegation program to delegate an ephemeral balance.
+pub struct DelegateEphemeralBalanceCpi<'a, 'info> {
+    pub payer: &'a compat::AccountInfo<'info>,
+    pub escrow_authority: &'a compat::AccountInfo<'info>,
+    pub ephemeral_balance: &'a compat::AccountInfo<'info>,
+    pub buffer: &'a compat::AccountInfo<'info>,
+    pub delegation_record: &'a compat::AccountInfo<'info>,
+    pub delegation_metadata: &'a compat::AccountInfo<'info>,
+    pub system_program: &'a compat::AccountInfo<'info>,
+    pub delegation_program: &'a compat::AccountInfo<'info>,
+    pub index: u8,
+    pub config: DelegateConfig,
+}
+
+impl<'a, 'info> DelegateEphemeralBalanceCpi<'a, 'info> {
+    pub fn instruction(&self) -> compat::Instruction {
+        delegate_ephemeral_balance(
+            *self.payer.key,
+            *self.escrow_authority.key,
+            self.index,
+            self.config.clone(),
+        )
+    }
+
+    pub fn invoke(&self) -> compat::ProgramResult {
+        self.invoke_signed(&[])
+    }
+
+    pub fn invoke_signed(&self, signers_seeds: &[&[&[u8]]]) -> compat::ProgramResult {
+        let accounts = [
+            self.payer.modern(),
+            self.escrow_authority.modern(),
+            self.ephemeral_balance.modern(),
+            self.buffer.modern(),
+            self.delegation_record.modern(),
+            self.delegation_metadata.modern(),
+            self.system_program.modern(),
+            self.delegation_program.modern(),
+        ];
+        invoke_signed(&self.instruction().modern(), &accounts, signers_seeds).compat()
+    }
+}
+
+/// CPI to the delegation program to close an ephemeral balance.
+pub struct CloseEphemeralBalanceCpi<'a, 'info> {
+    pub escrow_authority: &'a compat::AccountInfo<'info>,
+    pub ephemeral_balance: &'a compat::AccountInfo<'info>,
+    pub system_program: &'a compat::AccountInfo<'info>,
+    pub index: u8,
+}
+
+impl<'a, 'info> CloseEphemeralBalanceCpi<'a, 'info> {
+    pub fn instruction(&self) -> compat::Instruction {
+        close_ephemeral_balance(*self.escrow_authority.key, self.index)
+    }
+
+    pub fn invoke(&self) -> compat::ProgramResult {
+        self.invoke_signed(&[])
+    }
+
+    pub fn invoke_signed(&self, signers_seeds: &[&[&[u8]]]) -> compat::ProgramResult {
+        let accounts = [
+            self.escrow_authority.modern(),
+            self.ephemeral_balance.modern(),
+            self.system_program.modern(),
+        ];
+        invoke_signed(&self.instruction().modern(), &accounts, signers_seeds).compat()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_top_up_ephemeral_balance() {
+        let payer = compat::Pubkey::new_from_array([1; 32]);
+        let authority = compat::Pubkey::new_from_array([2; 32]);
+        let ix = top_up_ephemeral_balance(payer, authority, 1_000, 3);
+
+        assert_eq!(ix.program_id, crate::id());
+        assert_eq!(ix.accounts.len(), 4);
+        assert_eq!(ix.accounts[2].pubkey, ephemeral_balance_pda(&authority, 3));
+        assert!(ix.accounts[0].is_signer && ix.accounts[2].is_writable);
+        // [9,0,0,0,0,0,0,0] discriminator, u64 amount, u8 index
+        let mut expected = vec![9, 0, 0, 0, 0, 0, 0, 0];
+        expected.extend_from_slice(&1_000u64.to_le_bytes());
+        expected.push(3);
+        assert_eq!(ix.data, expected);
+    }
+
+    #[test]
+    fn test_delegate_ephemeral_balance() {
+        let payer = compat::Pubkey::new_from_array([1; 32]);
+        let authority = compat::Pubkey::new_from_array([2; 32]);
+        let validator = compat::Pubkey::new_from_array([3; 32]);
+        let ix = delegate_ephemeral_balance(
+            payer,
+            authority,
+            1,
+            DelegateConfig {
+                commit_frequency_ms: 0,
+                validator: Some(validator),
+            },
+        );
+
+        let escrow = ephemeral_balance_pda(&authority, 1);
+        assert_eq!(ix.accounts.len(), 8);
+        assert!(ix.accounts[1].is_signer);
+        assert_eq!(ix.accounts[2].pubkey, escrow);
+        assert_eq!(
+            ix.accounts[4].pubkey,
+            delegation_record_pda_from_delegated_account(&escrow)
+        );
+        assert_eq!(ix.data[..8], [10, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(ix.data.last(), Some(&1));
+    }
+
+    #[test]
+    fn test_close_ephemeral_balance() {
+        let authority = compat::Pubkey::new_from_array([2; 32]);
+        let ix = close_ephemeral_balance(authority, 255);
+
+        assert_eq!(ix.accounts.len(), 3);
+        assert_eq!(
+            ix.accounts[1].pubkey,
+            ephemeral_balance_pda(&authority, 255)
+        );
+        assert_eq!(ix.data, vec![11, 0, 0, 0, 0, 0, 0, 0, 255]);
+    }
+}
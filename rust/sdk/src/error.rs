@@ -0,0 +1,322 @@
+//! Errors raised by the SDK itself, as opposed to the ones propagated from the invoked programs.
+//!
+//! Every variant is surfaced on-chain as `ProgramError::Custom(code)`, where `code` lies in the
+//! range reserved for the SDK: [`SDK_ERROR_CODE_START`]`..=`[`SDK_ERROR_CODE_END`]. The codes are
+//! stable, so a failed transaction can be mapped back to the precondition which broke via
+//! [`EphemeralRollupsSdkError::from_code`] or [`EphemeralRollupsSdkError::from_logs`].
+
+use std::fmt;
+
+use crate::compat;
+
+/// First custom error code reserved for the SDK errors
+pub const SDK_ERROR_CODE_START: u32 = 0xEB_01_00_00;
+/// Last custom error code reserved for the SDK errors
+pub const SDK_ERROR_CODE_END: u32 = 0xEB_01_FF_FF;
+
+/// Marker with which the runtime reports the custom error code in transaction logs
+const CUSTOM_ERROR_LOG_MARKER: &str = "custom program error: ";
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EphemeralRollupsSdkError {
+    /// The undelegation buffer hasn't signed the undelegation callback
+    BufferNotSigner = SDK_ERROR_CODE_START + 1,
+    /// The undelegation buffer isn't owned by the delegation program
+    InvalidBufferOwner,
+    /// An account listed as a signer of the post delegation actions wasn't provided
+    MissingActionSigner,
+    /// Adding lamports to the destination account overflowed
+    LamportsOverflow,
+    /// The intent references more unique accounts than can be indexed in a single instruction
+    TooManyAccounts,
+    /// An account referenced by the intent is missing from the instruction accounts
+    MissingIntentAccount,
+    /// The ephemeral ATA account data is malformed
+    InvalidEphemeralAtaData,
+    /// The global vault account data is malformed
+    InvalidGlobalVaultData,
+    /// The delegated account doesn't match the PDA derived from the provided seeds
+    InvalidDelegatedAccount,
+    /// The buffer doesn't match the PDA derived from the delegated account
+    InvalidBuffer,
+    /// The delegation record doesn't match the PDA derived from the delegated account
+    InvalidDelegationRecord,
+    /// The delegation metadata doesn't match the PDA derived from the delegated account
+    InvalidDelegationMetadata,
+    /// The seeds of the account to delegate haven't been provided
+    MissingDelegationSeeds,
+    /// The same account is listed more than once in the delegation batch
+    DuplicateDelegatedAccount,
+    /// The prepared delegation buffer doesn't match the size of the delegated account
+    IncompleteDelegationBuffer,
+    /// The escrow hasn't signed the action
+    EscrowNotSigner,
+    /// The escrow authority isn't the account expected to authorize the action
+    InvalidEscrowAuthority,
+    /// The escrow doesn't match the PDA derived from the escrow authority and index
+    InvalidEscrow,
+    /// The payer is delegated, but the magic fee vault hasn't been provided
+    MissingMagicFeeVault,
+    /// The magic fee vault doesn't match the PDA derived from the validator
+    InvalidMagicFeeVault,
+    /// The instruction isn't a valid intent instruction of the magic program
+    InvalidIntentData,
+    /// The intent bundle has more actions than `MAX_INTENT_BUNDLE_ACTIONS`
+    TooManyActions,
+    /// The intent bundle instruction data exceeds the CPI instruction data limit
+    IntentDataTooLarge,
+    /// The action callback data isn't a valid response of the magic program
+    InvalidMagicResponse,
+    /// The action callback has already been processed
+    CallbackReplayed,
+    /// An account to commit and undelegate hasn't been passed as writable
+    CommittedAccountNotWritable,
+    /// An account to commit isn't owned by the program committing it, as delegated accounts
+    /// are on the ER
+    CommittedAccountNotDelegated,
+    /// The delegation record or metadata isn't owned by the delegation program
+    InvalidDelegationAccountOwner,
+    /// The delegation record or metadata account data is malformed
+    InvalidDelegationAccountData,
+    /// The accounts of the undelegation callback are missing, not writable, or the system
+    /// program isn't the expected one
+    InvalidUndelegationAccounts,
+    /// The seeds passed to the undelegation callback can't be deserialized
+    InvalidUndelegationSeeds,
+    /// The instruction arguments can't be serialized
+    InvalidInstructionArgs,
+    /// The PDA to create is already allocated or assigned to another program
+    PdaAlreadyInitialized,
+    /// More seeds have been provided than a PDA can be derived from
+    TooManySeeds,
+    /// The delegated or undelegated account is larger than can be re-created within the
+    /// undelegation callback, i.e. than `MAX_PERMITTED_DATA_INCREASE` (10 KiB)
+    UndelegatedAccountTooLarge,
+    /// The action callback hasn't been signed by the callback signer of the validator
+    InvalidCallbackSigner,
+    /// The callback sequence account is too small or already borrowed
+    InvalidCallbackSequence,
+    /// The magic context isn't `MAGIC_CONTEXT_ID`
+    InvalidMagicContext,
+    /// The committed accounts of a scheduled commit aren't the ones it has been registered with
+    InvalidCommittedAccounts,
+}
+
+impl EphemeralRollupsSdkError {
+    const ALL: [Self; 39] = [
+        Self::BufferNotSigner,
+        Self::InvalidBufferOwner,
+        Self::MissingActionSigner,
+        Self::LamportsOverflow,
+        Self::TooManyAccounts,
+        Self::MissingIntentAccount,
+        Self::InvalidEphemeralAtaData,
+        Self::InvalidGlobalVaultData,
+        Self::InvalidDelegatedAccount,
+        Self::InvalidBuffer,
+        Self::InvalidDelegationRecord,
+        Self::InvalidDelegationMetadata,
+        Self::MissingDelegationSeeds,
+        Self::DuplicateDelegatedAccount,
+        Self::IncompleteDelegationBuffer,
+        Self::EscrowNotSigner,
+        Self::InvalidEscrowAuthority,
+        Self::InvalidEscrow,
+        Self::MissingMagicFeeVault,
+        Self::InvalidMagicFeeVault,
+        Self::InvalidIntentData,
+        Self::TooManyActions,
+        Self::IntentDataTooLarge,
+        Self::InvalidMagicResponse,
+        Self::CallbackReplayed,
+        Self::CommittedAccountNotWritable,
+        Self::CommittedAccountNotDelegated,
+        Self::InvalidDelegationAccountOwner,
+        Self::InvalidDelegationAccountData,
+        Self::InvalidUndelegationAccounts,
+        Self::InvalidUndelegationSeeds,
+        Self::InvalidInstructionArgs,
+        Self::PdaAlreadyInitialized,
+        Self::TooManySeeds,
+        Self::UndelegatedAccountTooLarge,
+        Self::InvalidCallbackSigner,
+        Self::InvalidCallbackSequence,
+        Self::InvalidMagicContext,
+        Self::InvalidCommittedAccounts,
+    ];
+
+    /// Custom program error code of the error
+    pub const fn code(self) -> u32 {
+        self as u32
+    }
+
+    /// Maps the custom program error code back to the error, `None` if the code
+    /// doesn't belong to the SDK
+    pub fn from_code(code: u32) -> Option<Self> {
+        Self::ALL.into_iter().find(|err| err.code() == code)
+    }
+
+    /// Extracts the SDK error from the program error, `None` if it didn't originate in the SDK
+    pub fn from_program_error(error: &compat::ProgramError) -> Option<Self> {
+        match error {
+            compat::ProgramError::Custom(code) => Self::from_code(*code),
+            _ => None,
+        }
+    }
+
+    /// Parses a single log line, as emitted by the runtime on failure, e.g.
+    /// `Program <id> failed: custom program error: 0xeb010001`
+    pub fn from_log(log: &str) -> Option<Self> {
+        let (_, code) = log.split_once(CUSTOM_ERROR_LOG_MARKER)?;
+        let code = code.trim();
+        let code = code
+            .strip_prefix("0x")
+            .or_else(|| code.strip_prefix("0X"))?;
+        Self::from_code(u32::from_str_radix(code, 16).ok()?)
+    }
+
+    /// Finds the SDK error among the transaction log messages, if any
+    pub fn from_logs<S: AsRef<str>>(logs: &[S]) -> Option<Self> {
+        logs.iter().find_map(|log| Self::from_log(log.as_ref()))
+    }
+}
+
+impl fmt::Display for EphemeralRollupsSdkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            Self::BufferNotSigner => "undelegation buffer must be a signer",
+            Self::InvalidBufferOwner => {
+                "undelegation buffer must be owned by the delegation program"
+            }
+            Self::MissingActionSigner => "post delegation action signer account is missing",
+            Self::LamportsOverflow => "lamports overflow",
+            Self::TooManyAccounts => "intent references more than 256 unique accounts",
+            Self::MissingIntentAccount => "account referenced by intent is missing",
+            Self::InvalidEphemeralAtaData => "invalid ephemeral ATA account data",
+            Self::InvalidGlobalVaultData => "invalid global vault account data",
+            Self::InvalidDelegatedAccount => {
+                "delegated account doesn't match the PDA derived from seeds"
+            }
+            Self::InvalidBuffer => "buffer doesn't match the PDA derived from delegated account",
+            Self::InvalidDelegationRecord => {
+                "delegation record doesn't match the PDA derived from delegated account"
+            }
+            Self::InvalidDelegationMetadata => {
+                "delegation metadata doesn't match the PDA derived from delegated account"
+            }
+            Self::MissingDelegationSeeds => "seeds of the delegated account are missing",
+            Self::DuplicateDelegatedAccount => "account is delegated more than once in the batch",
+            Self::IncompleteDelegationBuffer => {
+                "delegation buffer hasn't been fully prepared for the delegated account"
+            }
+            Self::EscrowNotSigner => "escrow must be a signer of the action",
+            Self::InvalidEscrowAuthority => "escrow authority isn't the expected account",
+            Self::InvalidEscrow => {
+                "escrow doesn't match the PDA derived from escrow authority and index"
+            }
+            Self::MissingMagicFeeVault => "magic fee vault is required for a delegated payer",
+            Self::InvalidMagicFeeVault => {
+                "magic fee vault doesn't match the PDA derived from validator"
+            }
+            Self::InvalidIntentData => "instruction isn't a valid intent of magic program",
+            Self::TooManyActions => "intent bundle has too many actions",
+            Self::IntentDataTooLarge => "intent bundle exceeds CPI instruction data limit",
+            Self::InvalidMagicResponse => "action callback data isn't a valid magic response",
+            Self::CallbackReplayed => "action callback has already been processed",
+            Self::CommittedAccountNotWritable => "committed account must be writable",
+            Self::CommittedAccountNotDelegated => {
+                "committed account must be owned by the committing program"
+            }
+            Self::InvalidDelegationAccountOwner => {
+                "delegation record or metadata must be owned by the delegation program"
+            }
+            Self::InvalidDelegationAccountData => "invalid delegation record or metadata data",
+            Self::InvalidUndelegationAccounts => "invalid undelegation callback accounts",
+            Self::InvalidUndelegationSeeds => "invalid undelegation callback seeds",
+            Self::InvalidInstructionArgs => "instruction arguments can't be serialized",
+            Self::PdaAlreadyInitialized => "PDA is already allocated or assigned",
+            Self::TooManySeeds => "PDA seeds exceed the maximum of 15 plus the bump",
+            Self::UndelegatedAccountTooLarge => {
+                "account exceeds the 10 KiB that can be restored on undelegation"
+            }
+            Self::InvalidCallbackSigner => "action callback must be signed by the callback signer",
+            Self::InvalidCallbackSequence => "callback sequence account can't hold the sequence",
+            Self::InvalidMagicContext => "magic context must be MAGIC_CONTEXT_ID",
+            Self::InvalidCommittedAccounts => "committed accounts don't match the scheduled commit",
+        };
+        write!(f, "{msg} (code {:#x})", self.code())
+    }
+}
+
+impl std::error::Error for EphemeralRollupsSdkError {}
+
+impl From<EphemeralRollupsSdkError> for compat::ProgramError {
+    fn from(error: EphemeralRollupsSdkError) -> Self {
+        compat::ProgramError::Custom(error.code())
+    }
+}
+
+impl TryFrom<u32> for EphemeralRollupsSdkError {
+    type Error = u32;
+
+    fn try_from(code: u32) -> Result<Self, Self::Error> {
+        Self::from_code(code).ok_or(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_are_stable_and_in_reserved_range() {
+        assert_eq!(
+            EphemeralRollupsSdkError::BufferNotSigner.code(),
+            0xEB_01_00_01
+        );
+        assert_eq!(
+            EphemeralRollupsSdkError::InvalidGlobalVaultData.code(),
+            0xEB_01_00_08
+        );
+        assert_eq!(
+            EphemeralRollupsSdkError::InvalidDelegationMetadata.code(),
+            0xEB_01_00_0C
+        );
+        for err in EphemeralRollupsSdkError::ALL {
+            assert!((SDK_ERROR_CODE_START..=SDK_ERROR_CODE_END).contains(&err.code()));
+            assert_eq!(EphemeralRollupsSdkError::from_code(err.code()), Some(err));
+            assert_eq!(
+                EphemeralRollupsSdkError::from_program_error(&err.into()),
+                Some(err)
+            );
+        }
+        assert_eq!(
+            EphemeralRollupsSdkError::from_code(SDK_ERROR_CODE_START),
+            None
+        );
+        assert_eq!(
+            EphemeralRollupsSdkError::try_from(0xEB_00_00_01),
+            Err(0xEB_00_00_01)
+        );
+    }
+
+    #[test]
+    fn test_decode_logs() {
+        let logs = [
+            "Program DELeGGvXpWV2fqJUhqcF5ZSYMS4JTLjteaAMARRSaeSh invoke [1]",
+            "Program DELeGGvXpWV2fqJUhqcF5ZSYMS4JTLjteaAMARRSaeSh consumed 4200 of 200000 compute units",
+            "Program DELeGGvXpWV2fqJUhqcF5ZSYMS4JTLjteaAMARRSaeSh failed: custom program error: 0xeb010002",
+        ];
+        assert_eq!(
+            EphemeralRollupsSdkError::from_logs(&logs),
+            Some(EphemeralRollupsSdkError::InvalidBufferOwner)
+        );
+        // errors of other programs are not confused with the SDK ones
+        assert_eq!(
+            EphemeralRollupsSdkError::from_log("Program failed: custom program error: 0x1"),
+            None
+        );
+        assert_eq!(EphemeralRollupsSdkError::from_logs(&logs[..2]), None);
+    }
+}
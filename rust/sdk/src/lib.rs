@@ -10,13 +10,19 @@ compile_error!("feature `anchor-modern` cannot be combined with `backward-compat
 
 #[cfg(feature = "anchor-support")]
 pub mod anchor;
+pub mod client;
 pub mod consts;
 pub mod cpi;
 pub mod crank;
+pub mod cu;
 pub mod delegate_args;
+pub mod delegation;
+pub mod entrypoint;
 pub mod ephem;
 #[cfg(any(feature = "anchor-support", feature = "modular-sdk",))]
 pub mod ephemeral_accounts;
+pub mod ephemeral_balance;
+pub mod error;
 pub mod types;
 pub mod utils;
 pub mod vrf;
@@ -35,7 +41,7 @@ pub use dlp_api::{
     commit_record_seeds_from_delegated_account, commit_state_seeds_from_delegated_account,
     delegate_buffer_seeds_from_delegated_account, delegation_metadata_seeds_from_delegated_account,
     delegation_record_seeds_from_delegated_account, ephemeral_balance_seeds_from_payer,
-    fees_vault_seeds, program_config_seeds_from_program_id,
+    fees_vault_seeds, magic_fee_vault_seeds_from_validator, program_config_seeds_from_program_id,
     undelegate_buffer_seeds_from_delegated_account, validator_fees_vault_seeds_from_validator,
 };
 pub use magicblock_magic_program_api::args::{
@@ -63,3 +63,63 @@ pub use delegate_ephemeral_ata_permission::*;
 pub use reset_ephemeral_ata_permission::*;
 #[cfg(feature = "access-control")]
 pub use undelegate_ephemeral_ata_permission::*;
+
+/// Implements `estimated_compute_units` on the builders, from the instruction they build,
+/// see [`crate::cu::espl_instruction`]
+macro_rules! impl_estimated_compute_units {
+    ($($builder:ty),* $(,)?) => {$(
+        impl $builder {
+            /// Estimate of the compute units spent by the instruction, see [`crate::cu`]
+            pub fn estimated_compute_units(&self) -> u64 {
+                crate::cu::espl_instruction(&self.instruction())
+            }
+        }
+    )*};
+    ($($builder:ty => $error:ty),* $(,)?) => {$(
+        impl $builder {
+            /// Estimate of the compute units spent by the instruction, see [`crate::cu`].
+            /// Fails if the instruction can't be built.
+            pub fn estimated_compute_units(&self) -> Result<u64, $error> {
+                Ok(crate::cu::espl_instruction(&self.instruction()?))
+            }
+        }
+    )*};
+}
+
+impl_estimated_compute_units!(
+    AllocateTransferQueueBuilder,
+    CloseEphemeralAtaBuilder,
+    DelegateEphemeralAtaBuilder,
+    DelegateShuttleEphemeralAtaBuilder,
+    DelegateTransferQueueBuilder,
+    DepositSplTokensBuilder,
+    EnsureTransferQueueCrankBuilder,
+    InitializeEphemeralAtaBuilder,
+    InitializeGlobalVaultBuilder,
+    InitializeRentPdaBuilder,
+    InitializeShuttleEphemeralAtaBuilder,
+    InitializeTransferQueueBuilder,
+    LamportsDelegatedTransferBuilder,
+    MergeShuttleIntoAtaBuilder,
+    ProcessPendingTransferQueueRefillBuilder,
+    SetupAndDelegateShuttleEphemeralAtaWithMergeBuilder,
+    UndelegateAndCloseShuttleEphemeralAtaBuilder,
+    UndelegateEphemeralAtaBuilder,
+    WithdrawSplTokensBuilder,
+    WithdrawThroughDelegatedShuttleWithMergeBuilder,
+);
+
+impl_estimated_compute_units!(
+    DepositAndDelegateShuttleEphemeralAtaWithMergeAndPrivateTransferBuilder =>
+        DepositAndDelegateShuttleEphemeralAtaWithMergeAndPrivateTransferBuilderError,
+    DepositAndQueueTransferBuilder => DepositAndQueueTransferBuilderError,
+    SchedulePrivateTransferBuilder => SchedulePrivateTransferBuilderError,
+);
+
+#[cfg(feature = "access-control")]
+impl_estimated_compute_units!(
+    CreateEphemeralAtaPermissionBuilder,
+    DelegateEphemeralAtaPermissionBuilder,
+    ResetEphemeralAtaPermissionBuilder,
+    UndelegateEphemeralAtaPermissionBuilder,
+);
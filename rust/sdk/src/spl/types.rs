@@ -3,6 +3,7 @@ use crate::{
     consts::{
         ASSOCIATED_TOKEN_PROGRAM_ID, ESPL_TOKEN_PROGRAM_ID, HYDRA_PROGRAM_ID, TOKEN_PROGRAM_ID,
     },
+    error::EphemeralRollupsSdkError,
 };
 
 /// Internal representation of a token account data.
@@ -28,23 +29,23 @@ impl EphemeralAta {
 
     pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, compat::ProgramError> {
         if bytes.len() != Self::LEN {
-            return Err(compat::ProgramError::InvalidAccountData);
+            return Err(EphemeralRollupsSdkError::InvalidEphemeralAtaData.into());
         }
         Ok(Self {
             owner: compat::Pubkey::new_from_array(
                 bytes[0..32]
                     .try_into()
-                    .map_err(|_| compat::ProgramError::InvalidAccountData)?,
+                    .map_err(|_| EphemeralRollupsSdkError::InvalidEphemeralAtaData)?,
             ),
             mint: compat::Pubkey::new_from_array(
                 bytes[32..64]
                     .try_into()
-                    .map_err(|_| compat::ProgramError::InvalidAccountData)?,
+                    .map_err(|_| EphemeralRollupsSdkError::InvalidEphemeralAtaData)?,
             ),
             amount: u64::from_le_bytes(
                 bytes[64..72]
                     .try_into()
-                    .map_err(|_| compat::ProgramError::InvalidAccountData)?,
+                    .map_err(|_| EphemeralRollupsSdkError::InvalidEphemeralAtaData)?,
             ),
         })
     }
@@ -66,13 +67,13 @@ impl GlobalVault {
 
     pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, compat::ProgramError> {
         if bytes.len() != Self::LEN {
-            return Err(compat::ProgramError::InvalidAccountData);
+            return Err(EphemeralRollupsSdkError::InvalidGlobalVaultData.into());
         }
         Ok(Self {
             mint: compat::Pubkey::new_from_array(
                 bytes[0..32]
                     .try_into()
-                    .map_err(|_| compat::ProgramError::InvalidAccountData)?,
+                    .map_err(|_| EphemeralRollupsSdkError::InvalidGlobalVaultData)?,
             ),
         })
     }
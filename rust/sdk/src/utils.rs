@@ -1,4 +1,5 @@
 use solana_program::{
+    entrypoint::MAX_PERMITTED_DATA_INCREASE,
     program::{invoke, invoke_signed},
     rent::Rent,
     sysvar::Sysvar,
@@ -6,6 +7,7 @@ use solana_program::{
 
 use crate::{
     compat::{self, AsModern, Compat},
+    error::EphemeralRollupsSdkError,
     modernize,
 };
 
@@ -47,6 +49,12 @@ pub fn create_pda<'a, 'info>(
         )
         .compat()?;
     } else {
+        // A prefunded account can only be allocated and assigned while it's a system account
+        if !target_account.data_is_empty()
+            || target_account.owner != &solana_system_interface::program::id()
+        {
+            return Err(EphemeralRollupsSdkError::PdaAlreadyInitialized.into());
+        }
         // Otherwise, if balance is nonzero:
         // 1) transfer sufficient lamports for rent exemption
         if rent_exempt {
@@ -96,6 +104,55 @@ pub fn create_pda<'a, 'info>(
     Ok(())
 }
 
+/// Borsh serializes the instruction arguments
+#[inline(always)]
+pub(crate) fn serialize_args<T: compat::borsh::BorshSerialize>(
+    args: &T,
+) -> Result<Vec<u8>, EphemeralRollupsSdkError> {
+    compat::borsh::to_vec(args).map_err(|_| EphemeralRollupsSdkError::InvalidInstructionArgs)
+}
+
+/// Length of the account after a single realloc step towards `target_len`, the runtime
+/// permits the account to grow by at most `MAX_PERMITTED_DATA_INCREASE` bytes at once
+#[inline(always)]
+pub fn next_realloc_len(current_len: usize, target_len: usize) -> usize {
+    target_len.min(current_len.saturating_add(MAX_PERMITTED_DATA_INCREASE))
+}
+
+/// Resize the account owned by the calling program, topping up its lamports from the payer
+/// to keep it rent exempt
+#[inline(always)]
+pub fn resize_rent_exempt<'a, 'info>(
+    target_account: &'a compat::AccountInfo<'info>,
+    new_len: usize,
+    payer: &'a compat::AccountInfo<'info>,
+    system_program: &'a compat::AccountInfo<'info>,
+) -> compat::ProgramResult {
+    modernize!(target_account, payer, system_program);
+
+    let rent = Rent::get().map_err(|err| err.compat())?;
+    let missing_lamports = rent
+        .minimum_balance(new_len)
+        .saturating_sub(target_account.lamports());
+    if missing_lamports > 0 {
+        invoke(
+            &solana_system_interface::instruction::transfer(
+                payer.key,
+                target_account.key,
+                missing_lamports,
+            ),
+            &[
+                payer.clone(),
+                target_account.clone(),
+                system_program.clone(),
+            ],
+        )
+        .compat()?;
+    }
+
+    target_account.resize(new_len).compat()
+}
+
 /// Close PDA
 #[inline(always)]
 pub fn close_pda<'a, 'info>(
@@ -108,7 +165,7 @@ pub fn close_pda<'a, 'info>(
     let dest_starting_lamports = destination.lamports();
     **destination.lamports.borrow_mut() = dest_starting_lamports
         .checked_add(target_account.lamports())
-        .unwrap();
+        .ok_or(EphemeralRollupsSdkError::LamportsOverflow)?;
     **target_account.lamports.borrow_mut() = 0;
 
     target_account.assign(&solana_system_interface::program::id());
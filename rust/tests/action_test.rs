@@ -0,0 +1,264 @@
+//! Checks the accounts added by `#[action]` and `#[action_callback]` and the verifications they
+//! enforce by running the `try_accounts` generated by Anchor for the instructions.
+
+#![allow(unexpected_cfgs, clippy::diverging_sub_expression)]
+
+use std::collections::BTreeSet;
+
+use ephemeral_rollups_sdk::anchor::{action, action_callback};
+use ephemeral_rollups_sdk::compat::anchor_lang;
+use ephemeral_rollups_sdk::compat::anchor_lang::prelude::*;
+use ephemeral_rollups_sdk::compat::anchor_lang::ToAccountMetas;
+use ephemeral_rollups_sdk::consts::CALLBACK_SIGNER;
+use ephemeral_rollups_sdk::cpi::escrow_pda;
+use ephemeral_rollups_sdk::ephem::{
+    ActionCallbackResponse, CallbackSequence, CALLBACK_SEQUENCE_SEED,
+};
+use ephemeral_rollups_sdk::error::EphemeralRollupsSdkError;
+
+declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS");
+
+#[program]
+pub mod counter {
+    use super::*;
+
+    pub fn increment_counter(_ctx: Context<IncrementCounter>) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn on_counter_incremented(
+        _ctx: Context<OnCounterIncremented>,
+        _response: ActionCallbackResponse,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[action(escrow_index = 1)]
+#[derive(Accounts)]
+pub struct IncrementCounter<'info> {
+    /// CHECK: read only
+    pub counter: UncheckedAccount<'info>,
+}
+
+#[action_callback(escrow_index = 2)]
+#[derive(Accounts)]
+pub struct OnCounterIncremented<'info> {
+    /// CHECK: read only
+    pub counter: UncheckedAccount<'info>,
+}
+
+struct TestAccount {
+    key: Pubkey,
+    is_signer: bool,
+    owner: Pubkey,
+    lamports: u64,
+    data: Vec<u8>,
+}
+
+impl TestAccount {
+    fn new(key: Pubkey) -> Self {
+        Self {
+            key,
+            is_signer: false,
+            owner: Pubkey::default(),
+            lamports: 0,
+            data: vec![],
+        }
+    }
+
+    fn info(&mut self) -> AccountInfo<'_> {
+        AccountInfo::new(
+            &self.key,
+            self.is_signer,
+            true,
+            &mut self.lamports,
+            &mut self.data,
+            &self.owner,
+            false,
+        )
+    }
+}
+
+struct Callback {
+    callback_signer: TestAccount,
+    counter: TestAccount,
+    escrow_auth: TestAccount,
+    escrow: TestAccount,
+    callback_sequence: TestAccount,
+}
+
+impl Callback {
+    fn new() -> Self {
+        let escrow_auth = Pubkey::new_unique();
+        let (sequence, _) = Pubkey::find_program_address(
+            &[CALLBACK_SEQUENCE_SEED, escrow_auth.as_ref()],
+            &crate::ID,
+        );
+        let mut callback_signer = TestAccount::new(CALLBACK_SIGNER);
+        callback_signer.is_signer = true;
+        let mut callback_sequence = TestAccount::new(sequence);
+        callback_sequence.owner = crate::ID;
+        callback_sequence.data = vec![0; CallbackSequence::LEN];
+        Self {
+            callback_signer,
+            counter: TestAccount::new(Pubkey::new_unique()),
+            escrow: TestAccount::new(escrow_pda(&escrow_auth, 2)),
+            escrow_auth: TestAccount::new(escrow_auth),
+            callback_sequence,
+        }
+    }
+
+    /// Runs the account verifications of the callback carrying `sequence`
+    fn try_accounts(&mut self, sequence: u64) -> Result<()> {
+        let response = ActionCallbackResponse {
+            result: Ok(()),
+            data: sequence.to_le_bytes().to_vec(),
+            signature: None,
+        };
+        let mut ix_data = vec![];
+        response.serialize(&mut ix_data).unwrap();
+
+        let infos = [
+            self.callback_signer.info(),
+            self.counter.info(),
+            self.escrow_auth.info(),
+            self.escrow.info(),
+            self.callback_sequence.info(),
+        ];
+        // `try_accounts` takes the accounts for the lifetime of the instruction
+        let infos: &[AccountInfo] = Box::leak(Box::new(infos));
+        let mut accounts = infos;
+        OnCounterIncremented::try_accounts(
+            &crate::ID,
+            &mut accounts,
+            &ix_data,
+            &mut OnCounterIncrementedBumps::default(),
+            &mut BTreeSet::new(),
+        )
+        .map(|_| ())
+    }
+}
+
+fn error_code(result: Result<()>) -> u32 {
+    match result.unwrap_err() {
+        Error::AnchorError(err) => err.error_code_number,
+        Error::ProgramError(err) => u64::from(err.program_error) as u32,
+    }
+}
+
+/// Runs the account verifications of the action paid from `escrow`
+fn try_action_accounts(escrow_auth: &mut TestAccount, escrow: &mut TestAccount) -> Result<()> {
+    let mut counter = TestAccount::new(Pubkey::new_unique());
+    let infos = [counter.info(), escrow_auth.info(), escrow.info()];
+    let infos: &[AccountInfo] = Box::leak(Box::new(infos));
+    let mut accounts = infos;
+    IncrementCounter::try_accounts(
+        &crate::ID,
+        &mut accounts,
+        &[],
+        &mut IncrementCounterBumps::default(),
+        &mut BTreeSet::new(),
+    )
+    .map(|_| ())
+}
+
+#[test]
+fn test_action_escrow_verified() {
+    let mut escrow_auth = TestAccount::new(Pubkey::new_unique());
+    let mut escrow = TestAccount::new(escrow_pda(&escrow_auth.key, 1));
+    escrow.is_signer = true;
+    assert!(try_action_accounts(&mut escrow_auth, &mut escrow).is_ok());
+
+    escrow.is_signer = false;
+    assert_eq!(
+        error_code(try_action_accounts(&mut escrow_auth, &mut escrow)),
+        u32::from(ErrorCode::ConstraintSigner)
+    );
+
+    let mut escrow = TestAccount::new(escrow_pda(&escrow_auth.key, 2));
+    escrow.is_signer = true;
+    assert_eq!(
+        error_code(try_action_accounts(&mut escrow_auth, &mut escrow)),
+        u32::from(ErrorCode::ConstraintSeeds)
+    );
+}
+
+#[test]
+fn test_accounts_layout() {
+    let keys: [Pubkey; 5] = std::array::from_fn(|_| Pubkey::new_unique());
+    let metas = accounts::OnCounterIncremented {
+        callback_signer: keys[0],
+        counter: keys[1],
+        escrow_auth: keys[2],
+        escrow: keys[3],
+        callback_sequence: keys[4],
+    }
+    .to_account_metas(None);
+
+    let layout: Vec<_> = metas
+        .iter()
+        .map(|meta| (meta.pubkey, meta.is_signer, meta.is_writable))
+        .collect();
+    assert_eq!(
+        layout,
+        vec![
+            (keys[0], true, false),
+            (keys[1], false, false),
+            (keys[2], false, false),
+            (keys[3], false, false),
+            (keys[4], false, true),
+        ]
+    );
+}
+
+#[test]
+fn test_callback_verified() {
+    let mut callback = Callback::new();
+    assert!(callback.try_accounts(3).is_ok());
+
+    let mut callback = Callback::new();
+    callback.callback_signer.is_signer = false;
+    assert_eq!(
+        error_code(callback.try_accounts(3)),
+        u32::from(ErrorCode::AccountNotSigner)
+    );
+
+    let mut callback = Callback::new();
+    callback.callback_signer.key = Pubkey::new_unique();
+    assert_eq!(
+        error_code(callback.try_accounts(3)),
+        u32::from(ErrorCode::ConstraintAddress)
+    );
+
+    let mut callback = Callback::new();
+    callback.escrow.key = escrow_pda(&callback.escrow_auth.key, 3);
+    assert_eq!(
+        error_code(callback.try_accounts(3)),
+        u32::from(ErrorCode::ConstraintSeeds)
+    );
+}
+
+#[test]
+fn test_out_of_order_callbacks() {
+    let mut callback = Callback::new();
+    for sequence in [2, 0, 1] {
+        assert!(callback.try_accounts(sequence).is_ok(), "{sequence}");
+    }
+    for sequence in [0, 1, 2] {
+        assert_eq!(
+            error_code(callback.try_accounts(sequence)),
+            EphemeralRollupsSdkError::CallbackReplayed.code(),
+            "{sequence}"
+        );
+    }
+    let state: [u8; CallbackSequence::LEN] =
+        callback.callback_sequence.data[..].try_into().unwrap();
+    assert_eq!(
+        CallbackSequence::from_bytes(&state),
+        CallbackSequence {
+            highest: 2,
+            window: 0b111
+        }
+    );
+}
@@ -0,0 +1,50 @@
+// Tests for the ephemeral_entrypoint! macro, expanded without a solana_program dependency
+
+#![allow(unexpected_cfgs)]
+
+use std::cell::Cell;
+
+use ephemeral_rollups_sdk::compat::{AccountInfo, ProgramError, ProgramResult, Pubkey};
+use ephemeral_rollups_sdk::consts::EXTERNAL_UNDELEGATE_DISCRIMINATOR;
+use ephemeral_rollups_sdk::ephemeral_entrypoint;
+use ephemeral_rollups_sdk::error::EphemeralRollupsSdkError;
+
+thread_local! {
+    static PROCESSED: Cell<bool> = const { Cell::new(false) };
+}
+
+ephemeral_entrypoint!(process_instruction);
+
+fn process_instruction(
+    _program_id: &Pubkey,
+    _accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    assert_eq!(instruction_data, [1, 2, 3]);
+    PROCESSED.with(|processed| processed.set(true));
+    Ok(())
+}
+
+#[test]
+fn test_program_instructions_reach_the_processor() {
+    assert_eq!(
+        __ephemeral_rollups_process_instruction(&Pubkey::new_unique(), &[], &[1, 2, 3]),
+        Ok(())
+    );
+    assert!(PROCESSED.with(Cell::get));
+}
+
+#[test]
+fn test_undelegation_callback_is_handled() {
+    assert_eq!(
+        __ephemeral_rollups_process_instruction(
+            &Pubkey::new_unique(),
+            &[],
+            &EXTERNAL_UNDELEGATE_DISCRIMINATOR,
+        ),
+        Err(ProgramError::from(
+            EphemeralRollupsSdkError::InvalidUndelegationAccounts
+        ))
+    );
+    assert!(!PROCESSED.with(Cell::get));
+}
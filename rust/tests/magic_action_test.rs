@@ -0,0 +1,85 @@
+//! Checks the `#[derive(MagicAction)]` call handler against the account metas and the
+//! instruction data generated by Anchor for the same instruction.
+
+#![allow(unexpected_cfgs, clippy::diverging_sub_expression)]
+
+use ephemeral_rollups_sdk::anchor::MagicAction;
+use ephemeral_rollups_sdk::compat::anchor_lang;
+use ephemeral_rollups_sdk::compat::anchor_lang::prelude::*;
+use ephemeral_rollups_sdk::compat::anchor_lang::solana_program::instruction::AccountMeta;
+use ephemeral_rollups_sdk::compat::anchor_lang::{InstructionData, ToAccountMetas};
+
+declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS");
+
+#[program]
+pub mod counter {
+    use super::*;
+
+    pub fn update_counter(_ctx: Context<UpdateCounter>, _value: u64) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[account]
+pub struct Counter {
+    pub count: u64,
+}
+
+#[derive(Accounts, MagicAction)]
+#[magic_action(args = u64)]
+pub struct UpdateCounter<'info> {
+    #[account(mut, seeds = [b"counter", authority.key().as_ref()], bump)]
+    pub counter: Account<'info, Counter>,
+    /// CHECK: read only
+    pub authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub record: Box<Account<'info, Counter>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[test]
+fn test_accounts_match_anchor() {
+    let keys: [Pubkey; 4] = std::array::from_fn(|_| Pubkey::new_unique());
+    let anchor_metas = accounts::UpdateCounter {
+        counter: keys[0],
+        authority: keys[1],
+        record: keys[2],
+        system_program: keys[3],
+    }
+    .to_account_metas(None);
+
+    let mut lamports = 0;
+    let mut data = [];
+    let escrow_key = Pubkey::new_unique();
+    let escrow = AccountInfo::new(
+        &escrow_key,
+        false,
+        false,
+        &mut lamports,
+        &mut data,
+        &escrow_key,
+        false,
+    );
+    let handler = UpdateCounterMagicAction {
+        counter: keys[0].to_bytes().into(),
+        authority: keys[1].to_bytes().into(),
+        record: keys[2].to_bytes().into(),
+        system_program: keys[3].to_bytes().into(),
+    }
+    .into_call_handler(7, escrow.clone(), 10_000);
+    let action_metas: Vec<_> = handler
+        .accounts
+        .iter()
+        .map(|meta| AccountMeta {
+            pubkey: meta.pubkey.to_bytes().into(),
+            is_signer: false,
+            is_writable: meta.is_writable,
+        })
+        .collect();
+
+    assert_eq!(action_metas, anchor_metas);
+    assert_eq!(
+        handler.args.data,
+        instruction::UpdateCounter { _value: 7 }.data()
+    );
+}